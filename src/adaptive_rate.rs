@@ -0,0 +1,166 @@
+// Adaptive send rate. If the avatar has a parameter that it sets back for every chunk it has
+// received (the shader profile's ack parameter), VRChat reports the changes to it over OSC, and we
+// can ramp the message rate up for as long as the acknowledgements keep pace, and back off when
+// they stall. Saves the user from having to guess a safe fixed rate.
+//
+// The listener also picks up the checksum results (see checksum.rs), as both come in on the same port.
+
+use rosc::{decoder, OscMessage, OscPacket, OscType};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Where VRChat sends its OSC output by default
+pub const DEFAULT_LISTEN_PORT: u16 = 9001;
+
+// Where we listen, which is somewhere else when we've told VRChat so over OSCQuery (see oscquery.rs)
+static LISTEN_PORT: AtomicU16 = AtomicU16::new(DEFAULT_LISTEN_PORT);
+
+pub fn listen_port() -> u16 {
+    LISTEN_PORT.load(Ordering::Relaxed)
+}
+
+pub fn set_listen_port(port: u16) {
+    LISTEN_PORT.store(port, Ordering::Relaxed);
+}
+
+// How many chunks the acknowledgements may trail behind and still count as keeping pace
+const ACK_WINDOW: usize = 2;
+// Trailing by more than this is a stall
+const STALL_LAG: usize = 6;
+// How long to wait for a stall to clear up before carrying on anyway
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+const RAMP_UP: f64 = 1.05;
+const BACK_OFF: f64 = 0.7;
+const MIN_RATE: f64 = 1.0;
+const MAX_RATE_FACTOR: f64 = 4.0; // Never go above this times the rate we started out with
+
+// Counts the OSC messages coming in for the ack address, and keeps the values coming in for the
+// result address
+pub struct AckListener {
+    acks: Arc<AtomicUsize>,
+    results: Arc<Mutex<VecDeque<i32>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+fn for_each_message(packet: &OscPacket, f: &mut impl FnMut(&OscMessage)) {
+    match packet {
+        OscPacket::Message(msg) => f(msg),
+        OscPacket::Bundle(bundle) => bundle.content.iter().for_each(|p| for_each_message(p, f)),
+    }
+}
+
+impl AckListener {
+    pub fn start(port: u16, address: Option<String>, result_address: Option<String>) -> Result<AckListener, Box<dyn Error>> {
+        let sock = UdpSocket::bind(("127.0.0.1", port))
+            .map_err(|err| format!("Couldn't listen for acknowledgements on port {port}: {err}"))?;
+        // So that we get to check the stop flag now and then
+        sock.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+        let acks = Arc::new(AtomicUsize::new(0));
+        let results: Arc<Mutex<VecDeque<i32>>> = Arc::new(Mutex::new(VecDeque::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let acks = Arc::clone(&acks);
+            let results = Arc::clone(&results);
+            let stop = Arc::clone(&stop);
+            move || {
+                info!("Listening for {address:?} and {result_address:?} on port {port}");
+                let mut buf = [0u8; rosc::decoder::MTU];
+                while !stop.load(Ordering::Relaxed) {
+                    let len = match sock.recv(&mut buf) {
+                        Ok(len) => len,
+                        Err(err) if matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+                        Err(err) => {
+                            warn!("Ack listener: {err}");
+                            break;
+                        },
+                    };
+                    match decoder::decode_udp(&buf[..len]) {
+                        Ok((_, packet)) => for_each_message(&packet, &mut |msg| {
+                            if Some(&msg.addr) == address.as_ref() {
+                                trace!("Ack");
+                                acks.fetch_add(1, Ordering::Relaxed);
+                            }
+                            if Some(&msg.addr) == result_address.as_ref() {
+                                if let Some(&OscType::Int(result)) = msg.args.first() {
+                                    debug!("Result {result}");
+                                    results.lock().unwrap_or_else(|err| err.into_inner()).push_back(result);
+                                }
+                            }
+                        }),
+                        Err(err) => debug!("Ack listener: couldn't decode packet: {err}"),
+                    }
+                }
+                debug!("Ack listener stopped");
+            }
+        });
+
+        Ok(AckListener { acks: acks, results: results, stop: stop, thread: Some(thread) })
+    }
+
+    pub fn acks(&self) -> usize {
+        self.acks.load(Ordering::Relaxed)
+    }
+
+    // The oldest result that hasn't been taken yet
+    pub fn take_result(&self) -> Option<i32> {
+        self.results.lock().unwrap_or_else(|err| err.into_inner()).pop_front()
+    }
+
+    // Throw away any results still queued up from before
+    pub fn clear_results(&self) {
+        self.results.lock().unwrap_or_else(|err| err.into_inner()).clear();
+    }
+}
+
+impl Drop for AckListener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                warn!("Ack listener thread panicked");
+            }
+        }
+    }
+}
+
+pub struct RateController {
+    rate: f64,
+    max_rate: f64,
+}
+
+impl RateController {
+    pub fn new(initial_rate: f64) -> Self {
+        RateController {
+            rate: initial_rate.max(MIN_RATE),
+            max_rate: (initial_rate*MAX_RATE_FACTOR).max(MIN_RATE),
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    pub fn is_stalled(sent: usize, acked: usize) -> bool {
+        sent.saturating_sub(acked) > STALL_LAG
+    }
+
+    // Call after every chunk, returns how long to sleep before the next one
+    pub fn update(&mut self, sent: usize, acked: usize) -> Duration {
+        let lag = sent.saturating_sub(acked);
+        if lag <= ACK_WINDOW {
+            self.rate = (self.rate*RAMP_UP).min(self.max_rate);
+        } else if lag > STALL_LAG {
+            self.rate = (self.rate*BACK_OFF).max(MIN_RATE);
+            debug!("Acks trailing by {lag}, backing off to {:.1} messages/s", self.rate);
+        }
+        Duration::from_secs_f64(1.0/self.rate)
+    }
+}
@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+// foo.png -> .foo.png.tmp, in the same directory so that the rename stays on the same filesystem
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+// Writes to a temporary file next to path, and only renames it into place once everything has been
+// written and synced to disk. That way an interrupted save can't leave a truncated file where a
+// previous good export used to be. All our exports should go through here.
+pub fn write_atomically<F>(path: &Path, write: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), Box<dyn Error>>,
+{
+    let tmp_path = temp_path(path);
+
+    let result = || -> Result<(), Box<dyn Error>> {
+        let file = File::create(&tmp_path)
+            .map_err(|err| format!("Couldn't create temporary file {tmp_path:?}: {err}"))?;
+        let mut bufw = BufWriter::new(file);
+
+        write(&mut bufw)?;
+
+        let file = bufw.into_inner()
+            .map_err(|err| format!("Couldn't flush {tmp_path:?}: {}", err.error()))?;
+        file.sync_all()
+            .map_err(|err| format!("Couldn't sync {tmp_path:?}: {err}"))?;
+        drop(file);
+
+        std::fs::rename(&tmp_path, path)
+            .map_err(|err| format!("Couldn't rename {tmp_path:?} to {path:?}: {err}"))?;
+
+        // Make sure the rename itself hits the disk as well. Not possible to open a directory like
+        // this on Windows, where it isn't needed anyway.
+        #[cfg(unix)]
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(dir).and_then(|dir| dir.sync_all())
+                .map_err(|err| format!("Couldn't sync directory {dir:?}: {err}"))?;
+        }
+
+        Ok(())
+    }();
+
+    if result.is_err() {
+        // Don't leave junk lying around. The file might not even exist, so ignore any errors.
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+
+    result
+}
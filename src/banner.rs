@@ -0,0 +1,214 @@
+// Renders a line or two of text into the letterbox padding that ResizeType::ToFit leaves us with,
+// so that the otherwise wasted area can be used for a caption or the filename.
+
+// Classic 5x7 font for ASCII 0x20-0x7e. 5 bytes per glyph, one byte per column, LSB is the top row.
+const FONT_5X7: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x08, 0x2a, 0x1c, 0x2a, 0x08], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3e, 0x41, 0x49, 0x49, 0x7a], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x3f, 0x40, 0x38, 0x40, 0x3f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // backslash
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x0c, 0x52, 0x52, 0x52, 0x3e], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x7f, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 7;
+const CHAR_ADVANCE: usize = GLYPH_WIDTH + 1;
+const LINE_ADVANCE: usize = GLYPH_HEIGHT + 1;
+
+pub fn glyph(c: char) -> &'static [u8; 5] {
+    match c {
+        ' '..='~' => &FONT_5X7[(c as usize) - 0x20],
+        _ => &FONT_5X7[('?' as usize) - 0x20], // We only do ASCII
+    }
+}
+
+// Pick the palette entry that stands out the most against the background entry
+pub fn contrasting_index(palette: &[quantizr::Color], bg_index: u8) -> u8 {
+    let luma = |c: &quantizr::Color| -> i32 {
+        (c.r as i32)*299 + (c.g as i32)*587 + (c.b as i32)*114
+    };
+
+    let Some(bg) = palette.get(bg_index as usize) else {
+        return 0;
+    };
+
+    palette.iter()
+        .enumerate()
+        .max_by_key(|(_, c)| (luma(c) - luma(bg)).abs())
+        .map_or(0, |(i, _)| i as u8)
+}
+
+// Splits text into lines of at most max_chars characters (breaking on whitespace where possible)
+pub fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        loop {
+            let line_len = line.chars().count();
+            let sep = if line_len == 0 { 0 } else { 1 };
+            if line_len + sep + word.len() <= max_chars {
+                if sep == 1 {
+                    line.push(' ');
+                }
+                line.extend(word.iter());
+                break;
+            } else if line_len == 0 {
+                // Word too long to ever fit, hard break it
+                let rest = word.split_off(max_chars);
+                lines.push(word.iter().collect());
+                word = rest;
+            } else {
+                lines.push(std::mem::take(&mut line));
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+// Draws text centered in the rows y0..y0+rows of an 8bpp indexed image. Lines that don't fit are
+// dropped. Returns false if not even a single line fits.
+pub fn draw_banner(
+    indexes: &mut [u8],
+    width: u32, height: u32,
+    y0: u32, rows: u32,
+    text: &str,
+    fg_index: u8,
+) -> bool {
+    let width = width as usize;
+    let height = height as usize;
+    let y0 = y0 as usize;
+    let rows = rows as usize;
+
+    assert!(width * height == indexes.len(), "width={width} * height={height} != indexes.len()={}", indexes.len());
+    assert!(y0 + rows <= height);
+
+    let max_chars = (width + 1) / CHAR_ADVANCE;
+    let max_lines = (rows + 1) / LINE_ADVANCE;
+    if max_chars == 0 || max_lines == 0 {
+        return false;
+    }
+
+    let mut lines = wrap(text, max_chars);
+    lines.truncate(max_lines);
+    if lines.is_empty() {
+        return false;
+    }
+
+    let text_height = lines.len() * LINE_ADVANCE - 1;
+    let top = y0 + (rows - text_height) / 2;
+
+    for (n, line) in lines.iter().enumerate() {
+        let line_width = line.chars().count() * CHAR_ADVANCE - 1;
+        let left = (width - line_width) / 2;
+        let line_top = top + n * LINE_ADVANCE;
+
+        for (i, c) in line.chars().enumerate() {
+            let glyph_left = left + i * CHAR_ADVANCE;
+            for (gx, column) in glyph(c).iter().enumerate() {
+                for gy in 0..GLYPH_HEIGHT {
+                    if (column >> gy) & 1 != 0 {
+                        indexes[(glyph_left + gx) + (line_top + gy) * width] = fg_index;
+                    }
+                }
+            }
+        }
+    }
+
+    true
+}
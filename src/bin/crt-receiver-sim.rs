@@ -0,0 +1,145 @@
+// Stands in for VRChat and the CRT shader: listens for OSC on UDP (port 9000 by default, so quit
+// VRChat first), runs the messages through the same receiver model as the preview in the progress
+// window, and shows the image as it gets built up. For testing sends end to end without VRChat.
+//
+//   crt-receiver-sim [--port 9000] [--size 128x128] [--zoom 4] [--prefix /avatar/parameters/PixelSendCRT]
+//                    [--bytes-per-send 24] [--bytes-per-int 1] [--protocol some-protocol.toml]
+
+use rust_image_fiddler::protocol_profile::ProtocolProfile;
+use rust_image_fiddler::receiver_sim::{ReceiverParams, ReceiverSim};
+
+use fltk::{prelude::*, app, frame::Frame, window::Window};
+use rosc::decoder;
+use std::error::Error;
+use std::net::UdpSocket;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(50);
+
+struct Args {
+    port: u16,
+    width: u32,
+    height: u32,
+    zoom: u32,
+    prefix: String,
+    bytes_per_send: usize,
+    bytes_per_int: usize,
+    protocol: ProtocolProfile,
+}
+
+impl Args {
+    fn parse() -> Result<Args, Box<dyn Error>> {
+        let mut args = Args {
+            port: 9000,
+            width: 128,
+            height: 128,
+            zoom: 4,
+            prefix: "/avatar/parameters/PixelSendCRT".to_string(),
+            bytes_per_send: 24,
+            bytes_per_int: 1,
+            protocol: ProtocolProfile::default(),
+        };
+
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            let mut value = || iter.next().ok_or(format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--port" => args.port = value()?.parse()?,
+                "--size" => {
+                    let size = value()?;
+                    let (w, h) = size.split_once('x').ok_or(format!("Size {size:?} should be WIDTHxHEIGHT"))?;
+                    (args.width, args.height) = (w.parse()?, h.parse()?);
+                },
+                "--zoom" => args.zoom = value()?.parse()?,
+                "--prefix" => args.prefix = value()?.trim_end_matches('/').to_string(),
+                "--bytes-per-send" => args.bytes_per_send = value()?.parse()?,
+                "--bytes-per-int" => args.bytes_per_int = value()?.parse()?,
+                "--protocol" => args.protocol = ProtocolProfile::load(Path::new(&value()?))?,
+                _ => return Err(format!("Unknown argument {arg:?}").into()),
+            }
+        }
+        if args.width == 0 || args.height == 0 || !(1..=36).contains(&args.bytes_per_send) {
+            return Err("Size and bytes per send (up to 36) can't be 0".into());
+        }
+        Ok(args)
+    }
+}
+
+// The PixelSendCRT parameters: V0-V9, then VA, VB, and so on
+fn receiver_params(args: &Args) -> ReceiverParams {
+    ReceiverParams {
+        clk_param: "CLK".to_string(),
+        reset_param: "Reset".to_string(),
+        checksum_param: Some("Checksum".to_string()),
+        data_params: (0..args.bytes_per_send as u32)
+            .map(|n| format!("V{}", char::from_digit(n, 36).unwrap_or('0').to_ascii_uppercase()))
+            .collect(),
+        bytes_per_int: args.bytes_per_int,
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse()?;
+    let sock = UdpSocket::bind(("127.0.0.1", args.port))
+        .map_err(|err| format!("Couldn't listen on port {}: {err} (is VRChat running?)", args.port))?;
+    println!("Listening on {} for {} ({}x{}, protocol {})",
+             sock.local_addr()?, args.prefix, args.width, args.height, args.protocol.name);
+
+    let app = app::App::default();
+    let mut win = Window::default()
+        .with_size((args.width*args.zoom) as i32, (args.height*args.zoom) as i32)
+        .with_label("CRT receiver sim");
+    let frame = Frame::default_fill();
+    win.end();
+    win.make_resizable(true);
+    win.show();
+
+    let mut sim = ReceiverSim::new(args.protocol.clone(), receiver_params(&args), args.width, args.height);
+    thread::spawn({
+        let mut frame = frame.clone();
+        let zoom = args.zoom;
+        let prefix = args.prefix.clone();
+        move || {
+            let mut buf = [0u8; decoder::MTU];
+            let mut last_redraw: Option<Instant> = None;
+            if let Err(err) = sock.set_read_timeout(Some(REDRAW_INTERVAL)) {
+                eprintln!("Couldn't set read timeout: {err}");
+            }
+            let mut changed = true;
+            loop {
+                match sock.recv(&mut buf) {
+                    Ok(len) => match decoder::decode_udp(&buf[..len]) {
+                        Ok((_, packet)) => {
+                            sim.packet(&packet, &prefix);
+                            changed = true;
+                        },
+                        Err(err) => eprintln!("Couldn't decode packet: {err}"),
+                    },
+                    Err(err) if matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => (),
+                    Err(err) => {
+                        eprintln!("Receiving failed: {err}");
+                        break;
+                    },
+                }
+
+                if changed && last_redraw.map_or(true, |t| t.elapsed() >= REDRAW_INTERVAL) {
+                    match sim.render(zoom) {
+                        Ok(image) => {
+                            frame.set_image(Some(image));
+                            frame.redraw();
+                            app::awake();
+                        },
+                        Err(err) => eprintln!("{err}"),
+                    }
+                    last_redraw = Some(Instant::now());
+                    changed = false;
+                }
+            }
+        }
+    });
+
+    app.run()?;
+    Ok(())
+}
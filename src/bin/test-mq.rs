@@ -58,10 +58,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     let _handle1 = thread::spawn({
         move || -> () {
             let mut clear_count: i32 = 0;
-            let mut run: bool = true;
 
-            while run {
-                let msg = rx.recv().unwrap();
+            for msg in rx {
                 match msg {
                     Message::Update(n) => {
                         println!("Processing update #{n}");
@@ -77,7 +75,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                     },
                     Message::Stop => {
                         println!("Got stop message. Stopping thread.");
-                        run = false;
+                        break;
                     },
                 }
             }
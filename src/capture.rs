@@ -0,0 +1,13 @@
+// Screen capture as an image source. See hotkeys.rs for capturing and sending it right away.
+
+use std::error::Error;
+
+// Grab whatever is on the primary monitor (or the first one, if none of them claims to be primary)
+pub fn capture_screen() -> Result<image::RgbaImage, Box<dyn Error>> {
+    let monitors = xcap::Monitor::all()?;
+    let monitor = monitors.iter()
+        .find(|m| m.is_primary())
+        .or_else(|| monitors.first())
+        .ok_or("No monitors found")?;
+    Ok(monitor.capture_image()?)
+}
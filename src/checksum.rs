@@ -0,0 +1,39 @@
+// Optional checksums of the pixel data, for shaders that support them (the shader profile has a
+// checksum parameter). Every so many chunks we raise the checksum parameter and send a chunk with
+// the block number and a Fletcher-16 of the chunks since the last checksum (all bytes of every
+// chunk, including the zero padding of a short last chunk) instead of pixel data:
+//
+//   [block number low, block number high, checksum low, checksum high, 0, ...]
+//
+// The avatar compares that against its own sum of what it got, and reports the outcome on the
+// checksum result parameter (if the profile has one): RESULT_OK, or RESULT_MISMATCH after which it
+// has gone back to where it was at the previous checksum, so that we can send the block again.
+
+use std::time::Duration;
+
+pub const DEFAULT_CHUNKS_PER_CHECKSUM: usize = 16;
+// How long to wait for the avatar to report back on a checksum before carrying on without knowing
+pub const RESULT_TIMEOUT: Duration = Duration::from_secs(2);
+// Give up on a block after it failed this many times
+pub const MAX_RETRIES: usize = 3;
+
+pub const RESULT_OK: i32 = 1;
+pub const RESULT_MISMATCH: i32 = 2;
+
+// Fletcher-16 over the chunks, each padded out to bytes_per_send like send_cmd does
+pub fn fletcher16<'a>(chunks: impl Iterator<Item = &'a [u8]>, bytes_per_send: usize) -> u16 {
+    let (mut sum1, mut sum2): (u16, u16) = (0, 0);
+    for chunk in chunks {
+        for n in 0..bytes_per_send {
+            let byte = chunk.get(n).copied().unwrap_or(0);
+            sum1 = (sum1 + (byte as u16)) % 255;
+            sum2 = (sum2 + sum1) % 255;
+        }
+    }
+    (sum2 << 8) | sum1
+}
+
+pub fn checksum_chunk(block: usize, checksum: u16) -> Vec<u8> {
+    let block = (block & 0xffff) as u16;
+    [block.to_le_bytes(), checksum.to_le_bytes()].concat()
+}
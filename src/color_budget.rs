@@ -0,0 +1,166 @@
+// Figures out whether spending more colors on an image would actually buy us anything, so that the
+// maxcolors vs transfer time tradeoff can be made with some actual data.
+
+use crate::ImageSettings;
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::iter::zip;
+
+// Below this relative improvement we consider more colors to be at the point of diminishing returns
+const WORTHWHILE_REDUCTION: f64 = 0.25;
+
+// Error (RMSE) we consider to look close enough for the suggestion. Gradients band visibly well
+// before that, so it gets tightened by up to half for images that are mostly gradients.
+const SUGGEST_MAX_RMSE: f64 = 12.0;
+// Neighbouring pixels closer than this (summed over RGBA), but not equal, count as a gradient
+const GRADIENT_STEP: i32 = 12;
+
+// Mean squared error per pixel (summed over the RGBA channels) between the source image and the
+// quantized one
+pub fn quantization_error(bytes: &[u8], indexes: &[u8], palette: &[quantizr::Color]) -> f64 {
+    assert!(bytes.len() == indexes.len() * 4);
+
+    if indexes.is_empty() {
+        return 0.0;
+    }
+
+    let sum: u64 = zip(bytes.chunks_exact(4), indexes)
+        .map(|(px, &index)| {
+            let c = &palette[index as usize];
+            let d = |a: u8, b: u8| { let d = (a as i64) - (b as i64); (d*d) as u64 };
+            d(px[0], c.r) + d(px[1], c.g) + d(px[2], c.b) + d(px[3], c.a)
+        })
+        .sum();
+
+    (sum as f64) / (indexes.len() as f64)
+}
+
+// Quantizes the image again (with the same settings) but more colors, and compares the error with what we got. Returns a
+// short human readable piece of advice.
+pub fn advise(
+    bytes: &[u8],
+    width: u32, height: u32,
+    settings: &ImageSettings,
+    error: f64,
+) -> Result<String, Box<dyn Error>> {
+    let maxcolors = settings.maxcolors;
+    let rmse = error.sqrt();
+
+    if error == 0.0 {
+        return Ok(format!("Lossless at {maxcolors} colors, no need for more"));
+    }
+
+    // Try doubling the amount of colors
+    let extra = maxcolors.min(256 - maxcolors);
+    if extra <= 0 {
+        return Ok(format!("Error (RMSE) {rmse:.1}, already using the maximum of 256 colors"));
+    }
+
+    let (indexes, palette) = crate::quantize_image(
+        bytes, width, height,
+        maxcolors + extra,
+        settings.dithering,
+        false,
+        &settings.quantizer_type,
+        &settings.quantizr,
+        &settings.dither_mask,
+        &settings.forced_colors,
+        None, // What more colors would do, locked palette or not
+        &settings.color_space,
+    )?;
+    let more_error = quantization_error(bytes, &indexes, &palette);
+    let reduction = if more_error < error { 1.0 - more_error/error } else { 0.0 };
+    let percent = (reduction * 100.0).round();
+
+    Ok(if reduction >= WORTHWHILE_REDUCTION {
+        format!("Error (RMSE) {rmse:.1}. +{extra} colors would reduce error by {percent}%")
+    } else {
+        format!("Error (RMSE) {rmse:.1}. Diminishing returns: +{extra} colors would only reduce error by {percent}%")
+    })
+}
+
+// A color count that should look good enough, picked from the counts where the bit depth goes up
+// (more colors than that don't cost anything extra to send)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Suggestion {
+    pub colors: i32,
+    pub bitdepth: u8,
+    pub rmse: f64,
+}
+
+impl Suggestion {
+    pub fn description(&self) -> String {
+        format!("Suggested: {} colors ({}bpp)", self.colors, self.bitdepth)
+    }
+}
+
+fn bitdepth_for(colors: usize) -> u8 {
+    match colors {
+        ..=2  => 1,
+        ..=4  => 2,
+        ..=16 => 4,
+        _     => 8,
+    }
+}
+
+// How much of the image is smooth gradients: the share of horizontally neighbouring pixels that
+// differ, but only by a little
+fn gradient_fraction(bytes: &[u8], width: u32) -> f64 {
+    let mut pairs: u64 = 0;
+    let mut gradient: u64 = 0;
+    for line in bytes.chunks_exact((width as usize) * 4) {
+        for (a, b) in zip(line.chunks_exact(4), line.chunks_exact(4).skip(1)) {
+            let diff: i32 = zip(a, b).map(|(&a, &b)| ((a as i32) - (b as i32)).abs()).sum();
+            pairs += 1;
+            if diff > 0 && diff < GRADIENT_STEP {
+                gradient += 1;
+            }
+        }
+    }
+    if pairs == 0 { 0.0 } else { (gradient as f64) / (pairs as f64) }
+}
+
+// Works out the fewest colors that keep the error under the threshold. Images that have few
+// enough colors to begin with get all of them.
+pub fn suggest(
+    bytes: &[u8],
+    width: u32, height: u32,
+    settings: &ImageSettings,
+) -> Result<Suggestion, Box<dyn Error>> {
+    let mut unique: HashSet<[u8; 4]> = HashSet::new();
+    for px in bytes.chunks_exact(4) {
+        unique.insert([px[0], px[1], px[2], px[3]]);
+        if unique.len() > 256 {
+            break;
+        }
+    }
+    if unique.len() <= 256 {
+        let colors = unique.len().max(2);
+        return Ok(Suggestion { colors: colors as i32, bitdepth: bitdepth_for(colors), rmse: 0.0 });
+    }
+
+    let max_rmse = SUGGEST_MAX_RMSE * (1.0 - 0.5*gradient_fraction(bytes, width));
+    let mut suggestion = Suggestion { colors: 256, bitdepth: 8, rmse: f64::INFINITY };
+    for colors in [2, 4, 16, 256] {
+        let (indexes, palette) = crate::quantize_image(
+            bytes, width, height,
+            colors,
+            settings.dithering,
+            false,
+            &settings.quantizer_type,
+            &settings.quantizr,
+            &settings.dither_mask,
+            &settings.forced_colors,
+            None,
+            &settings.color_space,
+        )?;
+        let rmse = quantization_error(bytes, &indexes, &palette).sqrt();
+        suggestion = Suggestion { colors: colors, bitdepth: bitdepth_for(colors as usize), rmse: rmse };
+        if rmse <= max_rmse {
+            break;
+        }
+    }
+    debug!("Color count suggestion {suggestion:?} (error threshold {max_rmse:.1})");
+    Ok(suggestion)
+}
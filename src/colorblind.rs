@@ -0,0 +1,71 @@
+// Color-blindness simulation for the preview, to check that the reduced palette still tells apart
+// what it needs to for color-blind viewers. Only the palette gets run through it on its way to the
+// screen, never what gets saved or sent.
+//
+// The matrices are the full-severity ones from Machado, Oliveira and Fernandes (2009), applied in
+// linear RGB.
+
+use crate::colorspace::{linear_to_srgb, srgb_to_linear};
+
+use std::sync::Mutex;
+use strum_macros::{VariantNames, EnumString};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, VariantNames, EnumString)]
+pub enum ColorBlindness {
+    #[default]
+    Off,
+    Protanopia,   // No red cones
+    Deuteranopia, // No green cones
+    Tritanopia,   // No blue cones
+}
+
+impl ColorBlindness {
+    fn matrix(&self) -> Option<[[f32; 3]; 3]> {
+        match self {
+            ColorBlindness::Off => None,
+            ColorBlindness::Protanopia => Some([
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ]),
+            ColorBlindness::Deuteranopia => Some([
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ]),
+            ColorBlindness::Tritanopia => Some([
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ]),
+        }
+    }
+
+    // The palette as it would look to someone with this kind of color blindness
+    pub fn simulate(&self, palette: &[quantizr::Color]) -> Vec<quantizr::Color> {
+        let Some(m) = self.matrix() else {
+            return palette.to_vec();
+        };
+        palette.iter()
+            .map(|c| {
+                let lin = [srgb_to_linear(c.r), srgb_to_linear(c.g), srgb_to_linear(c.b)];
+                let [r, g, b] = m.map(|row| linear_to_srgb(row[0]*lin[0] + row[1]*lin[1] + row[2]*lin[2]));
+                quantizr::Color { r: r, g: g, b: b, a: c.a }
+            })
+            .collect()
+    }
+}
+
+// What the preview choice says (like letterbox::LETTERBOX_STATE, it's only about drawing)
+static CURRENT: Mutex<ColorBlindness> = Mutex::new(ColorBlindness::Off);
+
+pub fn current() -> ColorBlindness {
+    CURRENT.lock().map_or(ColorBlindness::Off, |current| *current)
+}
+
+pub fn set_current(mode: ColorBlindness) {
+    match CURRENT.lock() {
+        Ok(mut current) => *current = mode,
+        Err(err) => warn!("Couldn't lock color blindness preview: {err}"),
+    }
+}
@@ -0,0 +1,105 @@
+// Color spaces to run the quantizer in. The quantizers only know about bytes, and treat the
+// distance between two colors as the distance between those bytes, which for sRGB doesn't match
+// what we see very well, especially in gradients at low color counts. So we convert the image into
+// another color space (still 8 bits per channel) before quantizing, and convert the palette back.
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{VariantNames, EnumString};
+
+#[derive(Debug, Clone, Default, PartialEq, VariantNames, EnumString, Serialize, Deserialize)]
+pub enum ColorSpace {
+    #[default]
+    SRGB,
+    #[serde(alias = "Linear")] // Projects saved with the old Linear option load as OKLab
+    OKLab,
+}
+
+// a and b of OKLab are roughly within ±0.4, scale them so that they use up most of a byte
+const OKLAB_AB_SCALE: f32 = 255.0;
+
+pub fn srgb_to_linear(c: u8) -> f32 {
+    let c = (c as f32) / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+pub fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    (c * 255.0).round() as u8
+}
+
+fn linear_to_oklab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let l = (0.4122214708*r + 0.5363325363*g + 0.0514459929*b).cbrt();
+    let m = (0.2119034982*r + 0.6806995451*g + 0.1073969566*b).cbrt();
+    let s = (0.0883024619*r + 0.2817188376*g + 0.6299787005*b).cbrt();
+    [
+        0.2104542553*l + 0.7936177850*m - 0.0040720468*s,
+        1.9779984951*l - 2.4285922050*m + 0.4505937099*s,
+        0.0259040371*l + 0.7827717662*m - 0.8086757660*s,
+    ]
+}
+
+fn oklab_to_linear([ll, a, b]: [f32; 3]) -> [f32; 3] {
+    let l = (ll + 0.3963377774*a + 0.2158037573*b).powi(3);
+    let m = (ll - 0.1055613458*a - 0.0638541728*b).powi(3);
+    let s = (ll - 0.0894841775*a - 1.2914855480*b).powi(3);
+    [
+        4.0767416621*l - 3.3077115913*m + 0.2309699292*s,
+        -1.2684380046*l + 2.6097574011*m - 0.3413193965*s,
+        -0.0041960863*l - 0.7034186147*m + 1.7076147010*s,
+    ]
+}
+
+fn to_byte(v: f32) -> u8 {
+    v.round().clamp(0.0, 255.0) as u8
+}
+
+impl ColorSpace {
+    // Convert an RGBA buffer into this color space. Alpha is left alone.
+    pub fn encode(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut out = bytes.to_vec();
+        if *self == ColorSpace::SRGB {
+            return out;
+        }
+
+        for px in out.chunks_exact_mut(4) {
+            let lin = [srgb_to_linear(px[0]), srgb_to_linear(px[1]), srgb_to_linear(px[2])];
+            let encoded = match self {
+                ColorSpace::SRGB => unreachable!(),
+                ColorSpace::OKLab => {
+                    let [l, a, b] = linear_to_oklab(lin);
+                    [to_byte(l * 255.0), to_byte(128.0 + a * OKLAB_AB_SCALE), to_byte(128.0 + b * OKLAB_AB_SCALE)]
+                },
+            };
+            px[..3].copy_from_slice(&encoded);
+        }
+        out
+    }
+
+    // Convert an sRGB palette into this color space, for remapping an encoded image with it
+    pub fn encode_palette(&self, palette: &[quantizr::Color]) -> Vec<quantizr::Color> {
+        let bytes: Vec<u8> = palette.iter().flat_map(|c| [c.r, c.g, c.b, c.a]).collect();
+        self.encode(&bytes)
+            .chunks_exact(4)
+            .map(|px| quantizr::Color { r: px[0], g: px[1], b: px[2], a: px[3] })
+            .collect()
+    }
+
+    // Convert a palette we got from quantizing an image in this color space back to sRGB
+    pub fn decode_palette(&self, palette: &[quantizr::Color]) -> Vec<quantizr::Color> {
+        palette.iter()
+            .map(|c| {
+                let lin = match self {
+                    ColorSpace::SRGB => return *c,
+                    ColorSpace::OKLab => oklab_to_linear([
+                        (c.r as f32) / 255.0,
+                        ((c.g as f32) - 128.0) / OKLAB_AB_SCALE,
+                        ((c.b as f32) - 128.0) / OKLAB_AB_SCALE,
+                    ]),
+                };
+                let [r, g, b] = lin.map(linear_to_srgb);
+                quantizr::Color { r: r, g: g, b: b, a: c.a }
+            })
+            .collect()
+    }
+}
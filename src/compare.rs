@@ -0,0 +1,87 @@
+// Before/after compare mode for the preview frame. The frame draws the processed image as usual,
+// and on top of that we draw the original image on the left side of a draggable divider.
+
+use fltk::{prelude::*, frame::Frame, enums::{Color, Event, LineStyle}, draw};
+use std::sync::Mutex;
+
+struct CompareState {
+    enabled: bool,
+    divider: f64, // Position of the divider as a fraction of the frame width
+    before: Option<fltk::image::RgbImage>,
+}
+
+static COMPARE_STATE: Mutex<CompareState> = Mutex::new(CompareState {
+    enabled: false,
+    divider: 0.5,
+    before: None,
+});
+
+// Hook up the custom drawing and the divider dragging to the preview frame
+pub fn attach(frame: &mut Frame) {
+    frame.draw(|f| {
+        draw_before(f);
+        // The letterbox markings go on top of whichever image ends up showing
+        crate::letterbox::draw(f);
+    });
+
+    frame.handle(|f, ev| {
+        match ev {
+            Event::Push | Event::Drag => {
+                let Ok(mut state) = COMPARE_STATE.lock() else {
+                    return false;
+                };
+                if !state.enabled || f.w() <= 0 {
+                    // Not comparing, so the editing tools get the mouse
+                    drop(state);
+                    return crate::edit::handle(f, ev);
+                }
+                state.divider = (((fltk::app::event_x() - f.x()) as f64)/(f.w() as f64)).clamp(0.0, 1.0);
+                f.redraw();
+                true
+            },
+            _ => crate::edit::handle(f, ev),
+        }
+    });
+}
+
+fn draw_before(f: &Frame) {
+    let Ok(mut state) = COMPARE_STATE.lock() else {
+        return;
+    };
+    let divider = state.divider;
+    if !state.enabled {
+        return;
+    }
+    let Some(before) = state.before.as_mut() else {
+        return;
+    };
+
+    let (w, h) = (before.w(), before.h());
+    let x = f.x() + (f.w() - w)/2;
+    let y = f.y() + (f.h() - h)/2;
+    let split_x = f.x() + ((f.w() as f64)*divider).round() as i32;
+
+    draw::push_clip(f.x(), f.y(), split_x - f.x(), f.h());
+    before.draw(x, y, w, h);
+    draw::pop_clip();
+
+    draw::set_draw_color(Color::Red);
+    draw::set_line_style(LineStyle::Solid, 2);
+    draw::draw_line(split_x, f.y(), split_x, f.y() + f.h() - 1);
+    draw::set_line_style(LineStyle::Solid, 0);
+}
+
+pub fn set_enabled(enabled: bool) {
+    match COMPARE_STATE.lock() {
+        Ok(mut state) => state.enabled = enabled,
+        Err(err) => warn!("Couldn't lock compare state: {err}"),
+    }
+}
+
+// The original image, at the same display size as what we will be comparing it to
+pub fn set_before(image: Option<fltk::image::RgbImage>) {
+    match COMPARE_STATE.lock() {
+        Ok(mut state) => state.before = image,
+        Err(err) => warn!("Couldn't lock compare state: {err}"),
+    }
+}
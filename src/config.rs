@@ -0,0 +1,124 @@
+// Machine-specific defaults (where to send, how fast, and so on) from a config.toml, so they don't
+// have to be set up again every time the program starts. Lives in %APPDATA%\OSCPixelSender on
+// Windows and $XDG_CONFIG_HOME/oscpixelsender (or ~/.config/oscpixelsender) elsewhere.
+
+use crate::atomic_write::write_atomically;
+use crate::prefetch;
+use crate::shader_profile;
+use crate::theme;
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub const DEFAULT_OSC_HOST: &'static str = "127.0.0.1";
+pub const DEFAULT_OSC_PORT: u16 = 9000;
+pub const DEFAULT_PALETTE_WIDTH: i32 = 50;
+pub const DEFAULT_CONTROL_WIDTH: i32 = 300;
+pub const DEFAULT_SEND_WARN_SECS: u64 = 300;
+pub const DEFAULT_MAX_SOURCE_MEGAPIXELS: f64 = 24.0;
+
+// Anything missing from the file just gets its default
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub osc_host: String,
+    pub osc_port: u16,
+    pub prefix: String,
+    pub msgs_per_second: f64,
+    pub send_warn_secs: u64, // Ask before sends estimated to take longer than this, 0 = never
+    pub scaler: String, // One of the ScalerType variants
+    pub scheme: String, // FLTK scheme: Base, Gtk, Gleam, Plastic or Oxy
+    pub theme: String,  // Colors: Light, Dark or HighContrast
+    pub ui_scale: f32,
+    pub font_size: i32,
+    pub language: String, // en, ja or de
+    pub max_source_megapixels: f64, // Bigger images get scaled down on load, 0 = never
+    pub confirm_send: bool, // Show the image and ask before sending it
+    pub playlist_prefetch: usize, // How many playlist images get loaded and processed ahead of time
+    // Layout, saved on exit
+    pub window: Option<[i32; 4]>, // x, y, w, h
+    pub palette_width: i32,
+    pub control_width: i32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            osc_host: DEFAULT_OSC_HOST.to_string(),
+            osc_port: DEFAULT_OSC_PORT,
+            prefix: shader_profile::DEFAULT_PREFIX.to_string(),
+            msgs_per_second: crate::OSC_SPEED_DEFAULT,
+            send_warn_secs: DEFAULT_SEND_WARN_SECS,
+            scaler: String::new(), // Leave the choice as it is
+            scheme: theme::DEFAULT_SCHEME.to_string(),
+            theme: theme::DEFAULT_THEME.to_string(),
+            ui_scale: theme::DEFAULT_UI_SCALE,
+            font_size: theme::DEFAULT_FONT_SIZE,
+            language: "en".to_string(),
+            max_source_megapixels: DEFAULT_MAX_SOURCE_MEGAPIXELS,
+            confirm_send: true,
+            playlist_prefetch: prefetch::DEFAULT_PREFETCH,
+            window: None, // Sized after the screen
+            palette_width: DEFAULT_PALETTE_WIDTH,
+            control_width: DEFAULT_CONTROL_WIDTH,
+        }
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        PathBuf::from(std::env::var_os("APPDATA")?).join("OSCPixelSender")
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?
+            .join("oscpixelsender")
+    };
+    Some(dir.join("config.toml"))
+}
+
+// Protocol profiles (see protocol_profile) go here
+pub fn protocols_dir() -> Option<PathBuf> {
+    Some(config_path()?.parent()?.join("protocols"))
+}
+
+impl Config {
+    // A missing file is fine and gives the defaults, a broken one is an error
+    pub fn load() -> Result<Config, Box<dyn Error>> {
+        let Some(path) = config_path() else {
+            warn!("Couldn't figure out where the config file should be, using defaults");
+            return Ok(Config::default());
+        };
+        if !path.exists() {
+            info!("No config file at {path:?}, using defaults");
+            return Ok(Config::default());
+        }
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Couldn't read {path:?}: {err}"))?;
+        let config: Config = toml::from_str(&contents)
+            .map_err(|err| format!("Couldn't parse {path:?}: {err}"))?;
+        info!("Loaded config from {path:?}");
+        Ok(config)
+    }
+
+    pub fn save(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let path = config_path().ok_or("Couldn't figure out where the config file should be")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|err| format!("Couldn't create {dir:?}: {err}"))?;
+        }
+
+        let contents = toml::to_string_pretty(self)?;
+        write_atomically(&path, |w| Ok(w.write_all(contents.as_bytes())?))?;
+        info!("Saved config to {path:?}");
+        Ok(path)
+    }
+
+    pub fn osc_target(&self) -> String {
+        format!("{}:{}", self.osc_host, self.osc_port)
+    }
+}
@@ -0,0 +1,80 @@
+// A rough idea of how the PixelSendCRT shader shows the image on the avatar: every pixel a cell
+// with a dark gap along its right and bottom edge, scanlines across each cell, and some bloom from
+// the neighbouring pixels bleeding into the dark parts. Only for the preview (like colorblind), it
+// gets drawn in place of the plain scaled up image and never goes anywhere else.
+
+use crate::filters;
+
+use std::sync::Mutex;
+
+const GAP_BRIGHTNESS: f32 = 0.35;     // What's left of the pixel in the gap between pixels
+const SCANLINE_DEPTH: f32 = 0.4;      // How much darker the top and bottom of a cell are than the middle
+const BLOOM_SIGMA: f32 = 0.8;         // In source pixels
+const BLOOM_AMOUNT: f32 = 0.3;
+
+static ENABLED: Mutex<bool> = Mutex::new(false);
+
+pub fn enabled() -> bool {
+    ENABLED.lock().map_or(false, |enabled| *enabled)
+}
+
+pub fn set_enabled(enabled: bool) {
+    match ENABLED.lock() {
+        Ok(mut e) => *e = enabled,
+        Err(err) => warn!("Couldn't lock CRT preview state: {err}"),
+    }
+}
+
+// Bilinear lookup in an RGBA buffer, x and y in pixels with the pixel centers at .5
+fn sample(bytes: &[u8], width: usize, height: usize, x: f32, y: f32, c: usize) -> f32 {
+    let x = (x - 0.5).clamp(0.0, (width - 1) as f32);
+    let y = (y - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let at = |x: usize, y: usize| bytes[(y*width + x)*4 + c] as f32;
+    let top = at(x0, y0)*(1.0 - fx) + at(x1, y0)*fx;
+    let bottom = at(x0, y1)*(1.0 - fx) + at(x1, y1)*fx;
+    top*(1.0 - fy) + bottom*fy
+}
+
+// Scales the RGBA buffer up by multiplier with the CRT look on it. Needs a multiplier of 2 or more
+// to have room for any of it, below that the pixels come out as they are.
+pub fn render(bytes: &[u8], width: u32, height: u32, multiplier: u32) -> Vec<u8> {
+    assert!((width * height * 4) as usize == bytes.len());
+    let m = multiplier.max(1) as usize;
+    let (w, h) = (width as usize, height as usize);
+    let (out_w, out_h) = (w*m, h*m);
+    let mut out = vec![0u8; out_w*out_h*4];
+    if w == 0 || h == 0 {
+        return out;
+    }
+
+    let mut bloom = bytes.to_vec();
+    filters::gaussian_blur(&mut bloom, width, height, BLOOM_SIGMA);
+
+    for oy in 0..out_h {
+        let (y, cy) = (oy/m, oy % m);
+        // 1 in the middle of the cell, 1 - SCANLINE_DEPTH at the edges
+        let scanline = if m >= 2 {
+            let d = ((cy as f32 + 0.5)/(m as f32) - 0.5)*2.0;
+            1.0 - SCANLINE_DEPTH*d*d
+        } else {
+            1.0
+        };
+        for ox in 0..out_w {
+            let (x, cx) = (ox/m, ox % m);
+            let gap = m >= 3 && (cx == m - 1 || cy == m - 1);
+            let mask = if gap { GAP_BRIGHTNESS } else { scanline };
+            let src = (y*w + x)*4;
+            let dst = (oy*out_w + ox)*4;
+            let (fx, fy) = ((ox as f32 + 0.5)/(m as f32), (oy as f32 + 0.5)/(m as f32));
+            for c in 0..3 {
+                let glow = if m >= 2 { BLOOM_AMOUNT*sample(&bloom, w, h, fx, fy, c) } else { 0.0 };
+                out[dst + c] = (bytes[src + c] as f32*mask + glow*(1.0 - mask)).round().clamp(0.0, 255.0) as u8;
+            }
+            out[dst + 3] = bytes[src + 3];
+        }
+    }
+    out
+}
@@ -0,0 +1,190 @@
+// Where to dither less or more than the dithering level says. Flat areas (UI, text, pixel art) look
+// cleaner without dither noise, while gradients band without it. Auto works that out from the
+// image: no dithering in flat areas, a little along hard edges, and the full level in smooth
+// gradients. Painted gets painted on the preview with the dither edit tools, in cells of a grid
+// over the source area, so it stays put when the scale changes.
+//
+// The quantizers only do one dithering level for the whole image, so with a mask the remapping is
+// ours (see quantizer::remap_masked).
+
+use fltk::{prelude::*, menu::Choice};
+use serde::{Deserialize, Serialize};
+use std::iter::zip;
+use std::sync::Mutex;
+use strum_macros::{VariantNames, EnumString};
+
+pub const GRID: usize = 64;
+const BRUSH_RADIUS: i64 = 2; // In cells
+// Difference to a neighbour (summed over RGB) that counts as a hard edge rather than a gradient
+const EDGE_STEP: i32 = 96;
+const EDGE_LEVEL: f32 = 0.25;
+const SMOOTH_RADIUS: usize = 2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, VariantNames, EnumString, Serialize, Deserialize)]
+pub enum DitherMaskMode {
+    #[default]
+    Off,
+    Auto,
+    Painted,
+}
+
+// What painting a cell does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Paint {
+    None,   // No dithering
+    Normal, // The dithering level
+    Full,   // Full dithering, whatever the level
+}
+
+impl Paint {
+    fn cell(&self) -> u8 {
+        match self {
+            Paint::None => 0,
+            Paint::Normal => 1,
+            Paint::Full => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DitherMask {
+    pub mode: DitherMaskMode,
+    pub painted: Vec<u8>, // GRID*GRID cells (see Paint::cell), empty until painted on
+}
+
+impl DitherMask {
+    // The dithering level for every pixel, None when the mask is off
+    pub fn levels(&self, bytes: &[u8], width: u32, height: u32, level: f32) -> Option<Vec<f32>> {
+        let (w, h) = (width as usize, height as usize);
+        match self.mode {
+            DitherMaskMode::Off => None,
+            DitherMaskMode::Auto => Some(auto_levels(bytes, w, h).into_iter().map(|l| l*level).collect()),
+            DitherMaskMode::Painted => Some(
+                (0..w*h)
+                    .map(|i| {
+                        let cell = (i/w)*GRID/h*GRID + (i % w)*GRID/w;
+                        match self.painted.get(cell).copied().unwrap_or(Paint::Normal.cell()) {
+                            0 => 0.0,
+                            1 => level,
+                            _ => 1.0,
+                        }
+                    })
+                    .collect()
+            ),
+        }
+    }
+}
+
+// 0 to 1 for every pixel, from how much it differs from its neighbours, smoothed out a little so
+// there are no hard seams between dithered and undithered areas
+fn auto_levels(bytes: &[u8], w: usize, h: usize) -> Vec<f32> {
+    let diff = |a: usize, b: usize| -> i32 {
+        zip(&bytes[a*4..a*4 + 3], &bytes[b*4..b*4 + 3])
+            .map(|(&a, &b)| ((a as i32) - (b as i32)).abs())
+            .sum()
+    };
+
+    let mut raw = vec![0f32; w*h];
+    for y in 0..h {
+        for x in 0..w {
+            let i = y*w + x;
+            let mut max_diff = 0;
+            if x + 1 < w { max_diff = max_diff.max(diff(i, i + 1)); }
+            if x > 0 { max_diff = max_diff.max(diff(i, i - 1)); }
+            if y + 1 < h { max_diff = max_diff.max(diff(i, i + w)); }
+            if y > 0 { max_diff = max_diff.max(diff(i, i - w)); }
+            raw[i] = match max_diff {
+                0 => 0.0,
+                d if d >= EDGE_STEP => EDGE_LEVEL,
+                _ => 1.0,
+            };
+        }
+    }
+
+    let mut levels = vec![0f32; w*h];
+    for y in 0..h {
+        for x in 0..w {
+            let (x0, x1) = (x.saturating_sub(SMOOTH_RADIUS), (x + SMOOTH_RADIUS).min(w - 1));
+            let (y0, y1) = (y.saturating_sub(SMOOTH_RADIUS), (y + SMOOTH_RADIUS).min(h - 1));
+            let sum: f32 = (y0..=y1).flat_map(|y| raw[y*w + x0..=y*w + x1].iter()).sum();
+            levels[y*w + x] = sum/(((x1 - x0 + 1)*(y1 - y0 + 1)) as f32);
+        }
+    }
+    levels
+}
+
+// What the widgets say, as far as get_image_settings is concerned (like pipeline::CURRENT)
+static CURRENT: Mutex<Option<DitherMask>> = Mutex::new(None);
+static ON_CHANGE: Mutex<Option<Box<dyn Fn() + Send>>> = Mutex::new(None);
+
+pub fn current() -> DitherMask {
+    CURRENT.lock().ok().and_then(|current| current.clone()).unwrap_or_default()
+}
+
+pub fn set_current(mask: DitherMask) {
+    match CURRENT.lock() {
+        Ok(mut current) => *current = Some(mask),
+        Err(err) => warn!("Couldn't lock dither mask: {err}"),
+    }
+}
+
+fn update(f: impl FnOnce(&mut DitherMask)) {
+    let mut mask = current();
+    f(&mut mask);
+    set_current(mask);
+}
+
+pub fn set_mode(mode: DitherMaskMode) {
+    update(|mask| mask.mode = mode);
+}
+
+pub fn clear() {
+    update(|mask| mask.painted.clear());
+}
+
+// Called after a stroke, to process the image again
+pub fn on_change(f: impl Fn() + Send + 'static) {
+    match ON_CHANGE.lock() {
+        Ok(mut on_change) => *on_change = Some(Box::new(f)),
+        Err(err) => warn!("Couldn't lock dither mask listener: {err}"),
+    }
+}
+
+pub fn changed() {
+    if let Ok(on_change) = ON_CHANGE.lock() {
+        if let Some(f) = on_change.as_ref() {
+            f();
+        }
+    }
+}
+
+// Paints a line between from and to (fractions of the source area). Switches the mask over to
+// the painted one, as that's what the user is looking at.
+pub fn paint(from: (f64, f64), to: (f64, f64), paint: Paint) {
+    update(|mask| {
+        if mask.painted.len() != GRID*GRID {
+            mask.painted = vec![Paint::Normal.cell(); GRID*GRID];
+        }
+        let cell = |(x, y): (f64, f64)| (
+            ((x*(GRID as f64)).floor() as i64).clamp(0, GRID as i64 - 1),
+            ((y*(GRID as f64)).floor() as i64).clamp(0, GRID as i64 - 1),
+        );
+        let ((x0, y0), (x1, y1)) = (cell(from), cell(to));
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).max(1);
+        for i in 0..=steps {
+            let x = x0 + (x1 - x0)*i/steps;
+            let y = y0 + (y1 - y0)*i/steps;
+            for cy in (y - BRUSH_RADIUS).max(0)..=(y + BRUSH_RADIUS).min(GRID as i64 - 1) {
+                for cx in (x - BRUSH_RADIUS).max(0)..=(x + BRUSH_RADIUS).min(GRID as i64 - 1) {
+                    mask.painted[(cy as usize)*GRID + (cx as usize)] = paint.cell();
+                }
+            }
+        }
+        mask.mode = DitherMaskMode::Painted;
+    });
+
+    if let Some(mut choice) = fltk::app::widget_from_id::<Choice>("dither_mask_choice") {
+        choice.set_value(choice.find_index(&format!("{:?}", DitherMaskMode::Painted)));
+    }
+}
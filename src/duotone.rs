@@ -0,0 +1,45 @@
+// Duotone mode: maps the brightness of every palette color onto a gradient between two colors,
+// for a monochrome monitor look (amber or green phosphor, sepia, ...). Only the palette changes,
+// so the indexes (and so the grayscale ramp the shader sees) stay the same.
+
+pub type Rgb = [u8; 3];
+
+// The presets offered in the duotone choice, dark color first
+pub const PRESETS: [(&'static str, Rgb, Rgb); 4] = [
+    ("Sepia", [40, 26, 13], [255, 236, 200]),
+    ("Amber", [20, 8, 0], [255, 176, 0]),
+    ("Green phosphor", [0, 16, 4], [51, 255, 102]),
+    ("Blue", [0, 8, 32], [160, 220, 255]),
+];
+
+pub const OFF: &'static str = "Off";
+pub const CUSTOM: &'static str = "Custom";
+
+// What goes into the choice, in order
+pub fn choices() -> Vec<&'static str> {
+    std::iter::once(OFF)
+        .chain(PRESETS.iter().map(|(name, _, _)| *name))
+        .chain(std::iter::once(CUSTOM))
+        .collect()
+}
+
+pub fn preset(name: &str) -> Option<(Rgb, Rgb)> {
+    PRESETS.iter().find(|(n, _, _)| *n == name).map(|&(_, dark, light)| (dark, light))
+}
+
+// The name of the preset with these colors, if there is one
+pub fn preset_name(dark: Rgb, light: Rgb) -> Option<&'static str> {
+    PRESETS.iter().find(|(_, d, l)| *d == dark && *l == light).map(|(name, _, _)| *name)
+}
+
+fn luma(c: &quantizr::Color) -> f32 {
+    (0.299*(c.r as f32) + 0.587*(c.g as f32) + 0.114*(c.b as f32))/255.0
+}
+
+pub fn apply(palette: &mut [quantizr::Color], dark: Rgb, light: Rgb) {
+    for c in palette.iter_mut() {
+        let t = luma(c);
+        let lerp = |i: usize| ((dark[i] as f32) + ((light[i] as f32) - (dark[i] as f32))*t).round().clamp(0.0, 255.0) as u8;
+        (c.r, c.g, c.b) = (lerp(0), lerp(1), lerp(2));
+    }
+}
@@ -0,0 +1,254 @@
+// Simple pixel editing on the processed image, for small fixes right before sending (a stray dither
+// pixel, something that needs blotting out). The tools work on the palette indexes directly, so
+// whatever gets drawn stays within the palette. The edits live in the BG thread's processed image,
+// so they get saved and sent like the rest of it, and thrown away when the image gets processed again.
+//
+// The dither tools paint the dither mask instead (see dither_mask), which is a setting and so does
+// survive processing the image again.
+
+use crate::BgMessage;
+use crate::dither_mask::{self, Paint};
+use crate::letterbox;
+use crate::mq;
+
+use fltk::{prelude::*, frame::Frame, enums::{Color, Event}};
+use std::sync::Mutex;
+use strum_macros::{VariantNames, EnumString};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, VariantNames, EnumString)]
+pub enum Tool {
+    #[default]
+    Off,
+    Pencil,
+    Fill,
+    Pick,
+    NoDither,
+    FullDither,
+    NormalDither,
+}
+
+impl Tool {
+    fn dither_paint(&self) -> Option<Paint> {
+        match self {
+            Tool::NoDither => Some(Paint::None),
+            Tool::FullDither => Some(Paint::Full),
+            Tool::NormalDither => Some(Paint::Normal),
+            _ => None,
+        }
+    }
+}
+
+// Positions are fractions of the image width/height, so the GUI side doesn't need to know the
+// size of the image or what it's been scaled up by for display
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Pencil { from: (f64, f64), to: (f64, f64), index: u8 },
+    Fill { at: (f64, f64), index: u8 },
+    Pick { at: (f64, f64) },
+    PickPalette(f64), // Fraction of the way down the palette strip
+}
+
+struct EditState {
+    tool: Tool,
+    index: u8,                  // The palette index being drawn with
+    last: Option<(f64, f64)>,   // Where the pencil was on the last event, while dragging
+    bg: Option<mq::MessageQueueSender<BgMessage>>,
+}
+
+static EDIT_STATE: Mutex<EditState> = Mutex::new(EditState {
+    tool: Tool::Off,
+    index: 0,
+    last: None,
+    bg: None,
+});
+
+pub fn set_tool(tool: Tool) {
+    match EDIT_STATE.lock() {
+        Ok(mut state) => state.tool = tool,
+        Err(err) => warn!("Couldn't lock edit state: {err}"),
+    }
+}
+
+pub fn set_index(index: u8) {
+    match EDIT_STATE.lock() {
+        Ok(mut state) => state.index = index,
+        Err(err) => warn!("Couldn't lock edit state: {err}"),
+    }
+}
+
+pub fn index() -> u8 {
+    EDIT_STATE.lock().map(|state| state.index).unwrap_or(0)
+}
+
+// Picking from the palette strip. The preview frame already has its handler set up by compare.rs,
+// which passes on to handle() below, so here we only need somewhere to send the edits.
+pub fn attach(palette_frame: &mut Frame, bg: &mq::MessageQueueSender<BgMessage>) {
+    match EDIT_STATE.lock() {
+        Ok(mut state) => state.bg = Some(bg.clone()),
+        Err(err) => warn!("Couldn't lock edit state: {err}"),
+    }
+
+    palette_frame.handle(|f, ev| {
+        if ev != Event::Push || f.h() <= 0 {
+            return false;
+        }
+        let Ok(state) = EDIT_STATE.lock() else {
+            return false;
+        };
+        if state.tool == Tool::Off {
+            return false;
+        }
+        let y = (((fltk::app::event_y() - f.y()) as f64)/(f.h() as f64)).clamp(0.0, 1.0);
+        send(&state, Edit::PickPalette(y));
+        true
+    });
+}
+
+fn send(state: &EditState, edit: Edit) {
+    let Some(ref bg) = state.bg else {
+        return;
+    };
+    if let Err(err) = bg.send(BgMessage::EditImage(edit)) {
+        warn!("Couldn't send edit to BG thread: {err}");
+    }
+}
+
+// Mouse events on the preview frame
+pub fn handle(f: &Frame, ev: Event) -> bool {
+    if !matches!(ev, Event::Push | Event::Drag | Event::Released) {
+        return false;
+    }
+    let Ok(mut state) = EDIT_STATE.lock() else {
+        return false;
+    };
+    if state.tool == Tool::Off {
+        return false;
+    }
+    if ev == Event::Released {
+        state.last = None;
+        // The image only gets processed again once the stroke is done
+        if state.tool.dither_paint().is_some() {
+            drop(state);
+            dither_mask::changed();
+        }
+        return true;
+    }
+    let Some(image) = f.image() else {
+        return false;
+    };
+
+    // The frame draws its image centered
+    let (w, h) = (image.w(), image.h());
+    if w <= 0 || h <= 0 {
+        return false;
+    }
+    let x = f.x() + (f.w() - w)/2;
+    let y = f.y() + (f.h() - h)/2;
+    let at = (
+        (((fltk::app::event_x() - x) as f64)/(w as f64)).clamp(0.0, 1.0),
+        (((fltk::app::event_y() - y) as f64)/(h as f64)).clamp(0.0, 1.0),
+    );
+
+    if let Some(paint) = state.tool.dither_paint() {
+        let from = state.last.unwrap_or(at);
+        state.last = Some(at);
+        dither_mask::paint(to_content(from), to_content(at), paint);
+        return true;
+    }
+
+    let edit = match (state.tool, ev) {
+        (Tool::Off, _) => return false,
+        (Tool::Pencil, _) => {
+            let from = state.last.unwrap_or(at);
+            state.last = Some(at);
+            Edit::Pencil { from: from, to: at, index: state.index }
+        },
+        (Tool::Fill, Event::Push) => Edit::Fill { at: at, index: state.index },
+        (Tool::Pick, Event::Push) => Edit::Pick { at: at },
+        _ => return true, // Only the pencil cares about dragging
+    };
+    send(&state, edit);
+    true
+}
+
+// From a fraction of the whole image to a fraction of the part that isn't letterbox padding
+fn to_content((x, y): (f64, f64)) -> (f64, f64) {
+    match letterbox::content() {
+        Some([cx, cy, cw, ch]) if cw > 0.0 && ch > 0.0 => (((x - cx)/cw).clamp(0.0, 1.0), ((y - cy)/ch).clamp(0.0, 1.0)),
+        _ => (x, y),
+    }
+}
+
+fn to_pixel((x, y): (f64, f64), width: u32, height: u32) -> (u32, u32) {
+    (
+        ((x*(width as f64)).floor() as u32).min(width - 1),
+        ((y*(height as f64)).floor() as u32).min(height - 1),
+    )
+}
+
+fn flood_fill(indexes: &mut [u8], width: u32, height: u32, (x, y): (u32, u32), index: u8) {
+    let (width, height) = (width as usize, height as usize);
+    let (x, y) = (x as usize, y as usize);
+    let target = indexes[y*width + x];
+    if target == index {
+        return;
+    }
+
+    let mut stack: Vec<(usize, usize)> = vec![(x, y)];
+    while let Some((x, y)) = stack.pop() {
+        if indexes[y*width + x] != target {
+            continue;
+        }
+        indexes[y*width + x] = index;
+        if x > 0 { stack.push((x - 1, y)); }
+        if x + 1 < width { stack.push((x + 1, y)); }
+        if y > 0 { stack.push((x, y - 1)); }
+        if y + 1 < height { stack.push((x, y + 1)); }
+    }
+}
+
+// Does the edit on the index buffer. Returns the index that got picked up for the pick edits, None
+// for the ones that changed the image.
+pub fn apply(edit: &Edit, indexes: &mut [u8], width: u32, height: u32, palette_len: usize) -> Option<u8> {
+    if width == 0 || height == 0 || palette_len == 0 || indexes.len() != (width*height) as usize {
+        return None;
+    }
+    // The palette might have shrunk since the color was picked
+    let clamp = |index: u8| index.min((palette_len - 1) as u8);
+
+    match *edit {
+        Edit::Pencil { from, to, index } => {
+            let ((x0, y0), (x1, y1)) = (to_pixel(from, width, height), to_pixel(to, width, height));
+            // A line between where the mouse was and where it is, so fast strokes don't leave gaps
+            let steps = x0.abs_diff(x1).max(y0.abs_diff(y1)).max(1);
+            for i in 0..=steps {
+                let t = (i as f64)/(steps as f64);
+                let x = ((x0 as f64) + ((x1 as f64) - (x0 as f64))*t).round() as usize;
+                let y = ((y0 as f64) + ((y1 as f64) - (y0 as f64))*t).round() as usize;
+                indexes[y*(width as usize) + x] = clamp(index);
+            }
+            None
+        },
+        Edit::Fill { at, index } => {
+            flood_fill(indexes, width, height, to_pixel(at, width, height), clamp(index));
+            None
+        },
+        Edit::Pick { at } => {
+            let (x, y) = to_pixel(at, width, height);
+            Some(indexes[(y*width + x) as usize])
+        },
+        Edit::PickPalette(y) => Some(clamp(((y*(palette_len as f64)).floor()).min(255.0) as u8)),
+    }
+}
+
+// Shows the color being drawn with in the main window
+pub fn show_color(palette: &[quantizr::Color]) -> Result<(), String> {
+    let mut frame: Frame = fltk::app::widget_from_id("edit_color_frame").ok_or("widget_from_id fail")?;
+    let color = palette.get(index() as usize)
+        .map(|c| Color::from_rgb(c.r, c.g, c.b))
+        .unwrap_or(Color::Background);
+    frame.set_color(color);
+    frame.set_label_color(Color::contrast(Color::Black, color));
+    frame.redraw();
+    Ok(())
+}
@@ -0,0 +1,162 @@
+// Error types for the background thread. Besides the message, the main thing these carry is
+// whether the user can do something about it (a file that won't decode, nothing loaded yet) or if
+// it's our fault (a widget that isn't there, a channel that went away), so the error dialog can
+// tell the two apart.
+
+use crate::i18n::tr;
+
+use fltk::dialog;
+use std::error::Error;
+use std::num::TryFromIntError;
+use std::path::PathBuf;
+
+pub trait AppError: Error {
+    // Whether this is a bug in the program rather than something the user did
+    fn is_bug(&self) -> bool;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessError {
+    #[error("No image loaded")]
+    NoImage,
+    #[error("Couldn't open image {path:?}")]
+    Open { path: PathBuf, source: std::io::Error },
+    #[error("Failed to decode image {path:?}")]
+    Decode { path: PathBuf, source: image::ImageError },
+    #[error("Couldn't read project {path:?}: {message}")]
+    Project { path: PathBuf, message: String },
+    #[error("Screen capture failed: {0}")]
+    Capture(String),
+    #[error("Couldn't get video frame: {0}")]
+    Video(String),
+    #[error("Scaling failed: {0}")]
+    Scale(String),
+    #[error("Quantization failed: {0}")]
+    Quantize(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError for ProcessError {
+    fn is_bug(&self) -> bool {
+        match self {
+            ProcessError::NoImage | ProcessError::Open { .. } | ProcessError::Decode { .. } | ProcessError::Project { .. } | ProcessError::Capture(_) | ProcessError::Video(_) => false,
+            ProcessError::Scale(_) | ProcessError::Quantize(_) | ProcessError::Internal(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SendError {
+    #[error("Nothing to send, the image hasn't been processed yet")]
+    NotReady,
+    #[error("Nothing has been sent yet, so there are no send settings to reuse")]
+    NothingSentYet,
+    #[error("Another send is in progress ({0}), wait for it to finish or cancel it")]
+    Busy(&'static str),
+    #[error("Sending failed: {0}")]
+    Send(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError for SendError {
+    fn is_bug(&self) -> bool {
+        match self {
+            // Mostly bad addresses and network trouble
+            SendError::NotReady | SendError::NothingSentYet | SendError::Busy(_) | SendError::Send(_) => false,
+            SendError::Internal(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SaveError {
+    #[error("Nothing to save, the image hasn't been processed yet")]
+    NothingToSave,
+    #[error("Trying to save an image with zero width or height")]
+    ZeroSize(#[from] TryFromIntError),
+    #[error("Couldn't save image to {path:?}: {message}")]
+    Write { path: PathBuf, message: String },
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError for SaveError {
+    fn is_bug(&self) -> bool {
+        match self {
+            SaveError::NothingToSave | SaveError::Write { .. } => false,
+            SaveError::ZeroSize(_) | SaveError::Internal(_) => true,
+        }
+    }
+}
+
+// Most of the helpers (widget lookups, sending to the main thread) still give us strings, and
+// those failing means something is broken on our end
+macro_rules! internal_from_string {
+    ($($t:ty),*) => {
+        $(
+            impl From<String> for $t {
+                fn from(s: String) -> Self {
+                    Self::Internal(s)
+                }
+            }
+
+            impl From<&str> for $t {
+                fn from(s: &str) -> Self {
+                    Self::Internal(s.to_string())
+                }
+            }
+        )*
+    }
+}
+
+internal_from_string!(ProcessError, SendError, SaveError);
+
+// Everything the error dialog needs, so it can be put together in the BG thread and shown from
+// the main thread
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    pub context: String,
+    pub message: String,
+    pub causes: Vec<String>,
+    pub bug: bool,
+}
+
+impl ErrorReport {
+    pub fn new<E: AppError>(context: &str, err: &E) -> Self {
+        let mut causes: Vec<String> = Vec::new();
+        let mut source = err.source();
+        while let Some(s) = source {
+            causes.push(s.to_string());
+            source = s.source();
+        }
+
+        ErrorReport {
+            context: context.to_string(),
+            message: err.to_string(),
+            causes: causes,
+            bug: err.is_bug(),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        let mut s = format!("{} {}:\n{}", self.context, tr("failed"), self.message);
+        for cause in &self.causes {
+            s += &format!("\n    {}: {cause}", tr("caused by"));
+        }
+        s
+    }
+
+    // Should only be called from the main thread
+    pub fn show(&self) {
+        if self.bug {
+            let msg = format!("{}\n\n{}", self.description(), tr("This is a bug in the program, not something you did wrong."));
+            if dialog::choice2_default(&msg, tr("Close"), tr("Copy details"), "") == Some(1) {
+                fltk::app::copy(&self.description());
+            }
+        } else {
+            dialog::alert_default(&self.description());
+        }
+    }
+}
@@ -0,0 +1,180 @@
+// Image filters for the RGBA buffer on its way to the quantizer (before or after scaling).
+
+use image::{imageops, RgbaImage};
+use serde::{Deserialize, Serialize};
+use strum_macros::{VariantNames, EnumString};
+
+// Gaussian blur radius for the unsharp mask. At CRT resolutions the mushiness is only about a
+// pixel wide, so a small one is what we want.
+const SHARPEN_SIGMA: f32 = 1.0;
+
+// Gaussian blur of the whole RGBA buffer (including alpha)
+pub fn gaussian_blur(bytes: &mut [u8], width: u32, height: u32, sigma: f32) {
+    assert!((width * height * 4) as usize == bytes.len());
+    if sigma <= 0.0 {
+        return;
+    }
+
+    let Some(image) = RgbaImage::from_raw(width, height, bytes.to_vec()) else {
+        return;
+    };
+    bytes.copy_from_slice(imageops::blur(&image, sigma).as_raw());
+}
+
+// Unsharp mask: adds back amount times the difference between the image and a blurred copy of
+// it. Alpha is left alone.
+pub fn unsharp_mask(bytes: &mut [u8], width: u32, height: u32, amount: f32) {
+    assert!((width * height * 4) as usize == bytes.len());
+    if amount <= 0.0 {
+        return;
+    }
+
+    let Some(image) = RgbaImage::from_raw(width, height, bytes.to_vec()) else {
+        return;
+    };
+    let blurred = imageops::blur(&image, SHARPEN_SIGMA);
+
+    for (px, blurred_px) in bytes.chunks_exact_mut(4).zip(blurred.as_raw().chunks_exact(4)) {
+        for c in 0..3 {
+            let orig = px[c] as f32;
+            let sharpened = orig + amount*(orig - blurred_px[c] as f32);
+            px[c] = sharpened.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+// Darkens the edges (Sobel on the luma) by up to amount, like an outline, so faces and text still
+// read after shrinking to a hundred pixels or so. Meant for after scaling, so the outlines are a
+// pixel wide in the output. Alpha is left alone.
+pub fn edge_darken(bytes: &mut [u8], width: u32, height: u32, amount: f32) {
+    assert!((width * height * 4) as usize == bytes.len());
+    if amount <= 0.0 || width < 3 || height < 3 {
+        return;
+    }
+
+    let (w, h) = (width as usize, height as usize);
+    let luma: Vec<f32> = bytes.chunks_exact(4)
+        .map(|px| 0.299*px[0] as f32 + 0.587*px[1] as f32 + 0.114*px[2] as f32)
+        .collect();
+    let at = |x: usize, y: usize| luma[y*w + x];
+
+    for y in 0..h {
+        for x in 0..w {
+            // Clamped to the edges of the image
+            let (xl, xr) = (x.saturating_sub(1), (x + 1).min(w - 1));
+            let (yu, yd) = (y.saturating_sub(1), (y + 1).min(h - 1));
+            let gx = at(xr, yu) + 2.0*at(xr, y) + at(xr, yd) - at(xl, yu) - 2.0*at(xl, y) - at(xl, yd);
+            let gy = at(xl, yd) + 2.0*at(x, yd) + at(xr, yd) - at(xl, yu) - 2.0*at(x, yu) - at(xr, yu);
+            // 4*255 is as strong as an edge gets
+            let edge = ((gx*gx + gy*gy).sqrt()/(4.0*255.0)).min(1.0);
+            let factor = 1.0 - amount*edge;
+            let px = &mut bytes[(y*w + x)*4..(y*w + x)*4 + 3];
+            for c in px.iter_mut() {
+                *c = (*c as f32*factor).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+// Cuts every color channel down to levels evenly spaced values (alpha is left alone). 256 or more
+// levels does nothing.
+pub fn posterize(bytes: &mut [u8], levels: u32) {
+    if levels >= 256 || levels < 2 {
+        return;
+    }
+    let steps = (levels - 1) as f32;
+    let lut: Vec<u8> = (0..=255u8)
+        .map(|v| (((v as f32)*steps/255.0).round()*255.0/steps).round() as u8)
+        .collect();
+    for px in bytes.chunks_exact_mut(4) {
+        for c in 0..3 {
+            px[c] = lut[px[c] as usize];
+        }
+    }
+}
+
+// Black and white point plus a simple curve through three points (what the quarter, half and
+// three-quarter inputs come out as), for lifting the shadows and midtones that the CRT-style
+// shaders crush. Done as one lookup table on the color channels, alpha is left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Levels {
+    pub black: u8, // Inputs at or below this come out black
+    pub white: u8, // and at or above this white
+    pub curve: [u8; 3], // Outputs for 64, 128 and 192 after the black and white points
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Levels { black: 0, white: 255, curve: CURVE_INPUTS }
+    }
+}
+
+const CURVE_INPUTS: [u8; 3] = [64, 128, 192];
+
+impl Levels {
+    pub fn is_identity(&self) -> bool {
+        *self == Levels::default()
+    }
+
+    fn lut(&self) -> Vec<u8> {
+        let black = self.black as f32;
+        let white = (self.white as f32).max(black + 1.0);
+        let xs = [0.0, CURVE_INPUTS[0] as f32, CURVE_INPUTS[1] as f32, CURVE_INPUTS[2] as f32, 255.0];
+        let ys = [0.0, self.curve[0] as f32, self.curve[1] as f32, self.curve[2] as f32, 255.0];
+        (0..=255u8)
+            .map(|v| {
+                let x = ((v as f32 - black)*255.0/(white - black)).clamp(0.0, 255.0);
+                // Piecewise linear between the curve points
+                let i = xs.windows(2).position(|w| x <= w[1]).unwrap_or(3);
+                let t = (x - xs[i])/(xs[i + 1] - xs[i]);
+                (ys[i] + t*(ys[i + 1] - ys[i])).round().clamp(0.0, 255.0) as u8
+            })
+            .collect()
+    }
+}
+
+pub fn levels(bytes: &mut [u8], levels: &Levels) {
+    if levels.is_identity() {
+        return;
+    }
+    let lut = levels.lut();
+    for px in bytes.chunks_exact_mut(4) {
+        for c in 0..3 {
+            px[c] = lut[px[c] as usize];
+        }
+    }
+}
+
+// Which color channel to keep, for seeing what a shader gets out of each channel on its own
+#[derive(Debug, Clone, Copy, Default, PartialEq, VariantNames, EnumString, Serialize, Deserialize)]
+pub enum Channel {
+    #[default]
+    All,
+    Red,
+    Green,
+    Blue,
+}
+
+// Invert, swap red and blue (for shaders that read the palette as BGR), and then zero all the
+// channels but one. Alpha is left alone.
+pub fn channel_ops(bytes: &mut [u8], invert: bool, swap_rb: bool, isolate: Channel) {
+    if !invert && !swap_rb && isolate == Channel::All {
+        return;
+    }
+    for px in bytes.chunks_exact_mut(4) {
+        if invert {
+            for c in 0..3 {
+                px[c] = 255 - px[c];
+            }
+        }
+        if swap_rb {
+            px.swap(0, 2);
+        }
+        match isolate {
+            Channel::All => (),
+            Channel::Red => { px[1] = 0; px[2] = 0; },
+            Channel::Green => { px[0] = 0; px[2] = 0; },
+            Channel::Blue => { px[0] = 0; px[1] = 0; },
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// Asking the shader what it can do before sending, for shaders that support it (the shader profile
+// has a query parameter). We raise the query parameter, and the avatar answers by setting the
+// capability parameters below (and sets them back to 0 when the query parameter goes down again, so
+// that the next answer counts as a change too), which VRChat then reports back to us over OSC:
+//
+//   CapVersion   protocol version, 1 and up
+//   CapWidth     the resolution of the screen, in units of 8 pixels (ints only go up to 255)
+//   CapHeight
+//   CapBitDepth  the largest bit depth it takes (1, 2, 4 or 8)
+//   CapRLE       1 if it does RLE compression, 0 if not
+//
+// Sends then get checked against the answer, so a mismatch gives an error instead of garbage.
+
+use crate::shader_profile::ShaderProfile;
+use crate::transport::Connection;
+
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+// How long to wait for the avatar to answer
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+const CAP_PARAMS: [&str; 5] = ["CapVersion", "CapWidth", "CapHeight", "CapBitDepth", "CapRLE"];
+const CAP_SIZE_UNIT: u32 = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    pub version: i32,
+    pub width: u32,
+    pub height: u32,
+    pub max_bitdepth: u8,
+    pub rle: bool,
+}
+
+impl Capabilities {
+    pub fn description(&self) -> String {
+        format!("Protocol version {}, {}x{}, up to {}bpp, RLE {}",
+                self.version, self.width, self.height, self.max_bitdepth,
+                if self.rle { "supported" } else { "not supported" })
+    }
+
+    // Whether the shader can show a send with these settings
+    pub fn check(&self, width: u32, height: u32, bitdepth: u8, rle: bool) -> Result<(), String> {
+        if width > self.width || height > self.height {
+            return Err(format!("The image is {width}x{height}, but the shader is only {}x{}", self.width, self.height));
+        }
+        if bitdepth > self.max_bitdepth {
+            return Err(format!("Sending at {bitdepth}bpp, but the shader only goes up to {}bpp", self.max_bitdepth));
+        }
+        if rle && !self.rle {
+            return Err("Sending RLE compressed, but the shader doesn't support RLE".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn collect_values(packet: &OscPacket, values: &mut HashMap<String, i32>) {
+    match packet {
+        OscPacket::Message(msg) => {
+            if let Some(&OscType::Int(value)) = msg.args.first() {
+                values.insert(msg.addr.clone(), value);
+            }
+        },
+        OscPacket::Bundle(bundle) => bundle.content.iter().for_each(|p| collect_values(p, values)),
+    }
+}
+
+fn send_bool(conn: &Connection, addr: String, b: bool) -> Result<(), Box<dyn Error>> {
+    let msg_buf = encoder::encode(&OscPacket::Message(OscMessage {
+        addr: addr,
+        args: vec![OscType::Bool(b)],
+    }))?;
+    conn.send(&msg_buf)?;
+    Ok(())
+}
+
+// Asks the shader, listening for the answer on listen_port. None if the profile has no query
+// parameter.
+pub fn query(
+    conn: &Connection,
+    listen_port: u16,
+    profile: &ShaderProfile,
+) -> Result<Option<Capabilities>, Box<dyn Error>> {
+    let Some(query_param) = profile.query_param.as_ref() else {
+        return Ok(None);
+    };
+
+    let listen_sock = UdpSocket::bind(("127.0.0.1", listen_port))
+        .map_err(|err| format!("Couldn't listen for the shader's answer on port {listen_port}: {err}"))?;
+    listen_sock.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+    send_bool(conn, profile.address(query_param), true)?;
+
+    let addresses: Vec<String> = CAP_PARAMS.iter().map(|p| profile.address(p)).collect();
+    let mut values: HashMap<String, i32> = HashMap::new();
+    let mut buf = [0u8; decoder::MTU];
+    let start = Instant::now();
+    while start.elapsed() < QUERY_TIMEOUT && !addresses.iter().all(|a| values.contains_key(a)) {
+        let len = match listen_sock.recv(&mut buf) {
+            Ok(len) => len,
+            Err(err) if matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        match decoder::decode_udp(&buf[..len]) {
+            Ok((_, packet)) => collect_values(&packet, &mut values),
+            Err(err) => debug!("Handshake: couldn't decode packet: {err}"),
+        }
+    }
+
+    send_bool(conn, profile.address(query_param), false)?;
+
+    let value = |param: &str| -> Result<i32, String> {
+        values.get(&profile.address(param)).copied()
+            .ok_or(format!("The shader didn't answer the query (no {param} within {:?})", QUERY_TIMEOUT))
+    };
+    let capabilities = Capabilities {
+        version: value("CapVersion")?,
+        width: (value("CapWidth")?.max(0) as u32)*CAP_SIZE_UNIT,
+        height: (value("CapHeight")?.max(0) as u32)*CAP_SIZE_UNIT,
+        max_bitdepth: value("CapBitDepth")?.clamp(0, 8) as u8,
+        rle: value("CapRLE")? != 0,
+    };
+    if capabilities.version < 1 {
+        return Err(format!("The shader answered with protocol version {}", capabilities.version).into());
+    }
+    info!("Shader capabilities: {capabilities:?}");
+    Ok(Some(capabilities))
+}
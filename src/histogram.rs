@@ -0,0 +1,87 @@
+use fltk::enums::ColorDepth;
+use std::error::Error;
+use std::iter::zip;
+
+// Per-channel and luminance histograms of an image
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    pub luma: [u32; 256],
+    pub r: [u32; 256],
+    pub g: [u32; 256],
+    pub b: [u32; 256],
+}
+
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    (((r as u32)*299 + (g as u32)*587 + (b as u32)*114) / 1000) as u8
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram { luma: [0; 256], r: [0; 256], g: [0; 256], b: [0; 256] }
+    }
+
+    fn add(&mut self, r: u8, g: u8, b: u8, count: u32) {
+        self.r[r as usize] += count;
+        self.g[g as usize] += count;
+        self.b[b as usize] += count;
+        self.luma[luma(r, g, b) as usize] += count;
+    }
+
+    // From an RGBA buffer
+    pub fn from_rgba(bytes: &[u8]) -> Self {
+        let mut hist = Self::new();
+        for px in bytes.chunks_exact(4) {
+            hist.add(px[0], px[1], px[2], 1);
+        }
+        hist
+    }
+
+    // From a quantized image. We only need to count the indexes, and then look up the colors once.
+    pub fn from_indexed(indexes: &[u8], palette: &[quantizr::Color]) -> Self {
+        let mut counts: [u32; 256] = [0; 256];
+        for &index in indexes {
+            counts[index as usize] += 1;
+        }
+
+        let mut hist = Self::new();
+        for (&count, c) in zip(&counts, palette) {
+            hist.add(c.r, c.g, c.b, count);
+        }
+        hist
+    }
+
+    // Renders the histogram as a 256 pixels wide image. The channels are drawn additively on top of
+    // each other, with the luminance as a white line on top.
+    pub fn to_fltk_rgbimage(&self, height: usize) -> Result<fltk::image::RgbImage, Box<dyn Error>> {
+        const WIDTH: usize = 256;
+        assert!(height > 0);
+
+        let max: u64 = [&self.r, &self.g, &self.b, &self.luma].iter()
+            .flat_map(|channel| channel.iter())
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1) as u64;
+        let bar_height = |count: u32| -> usize {
+            ((count as u64) * ((height - 1) as u64) / max) as usize
+        };
+
+        let mut fb: Vec<u8> = vec![0u8; WIDTH * height * 4];
+        for px in fb.chunks_exact_mut(4) {
+            px[3] = 255;
+        }
+
+        for x in 0..WIDTH {
+            for (c, channel) in [&self.r, &self.g, &self.b].iter().enumerate() {
+                for y in 0..bar_height(channel[x]) {
+                    fb[(x + (height - 1 - y) * WIDTH) * 4 + c] = 200;
+                }
+            }
+
+            let y = bar_height(self.luma[x]);
+            fb[(x + (height - 1 - y) * WIDTH) * 4..][..3].copy_from_slice(&[255, 255, 255]);
+        }
+
+        Ok(fltk::image::RgbImage::new(&fb, WIDTH as i32, height as i32, ColorDepth::Rgba8)?)
+    }
+}
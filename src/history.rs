@@ -0,0 +1,48 @@
+// Simple undo/redo history. Keeps track of the current state, and what came before and after it.
+
+const MAX_HISTORY: usize = 100;
+
+#[derive(Debug)]
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    current: Option<T>,
+}
+
+impl<T: Clone + PartialEq> History<T> {
+    pub const fn new() -> Self {
+        History { undo: Vec::new(), redo: Vec::new(), current: None }
+    }
+
+    // Record a new state. Does nothing if it's the same as the current one (which e.g. is what
+    // happens when we restore a state through undo/redo and the new state gets sent back to us).
+    pub fn push(&mut self, state: T) {
+        if self.current.as_ref() == Some(&state) {
+            return;
+        }
+
+        if let Some(current) = self.current.replace(state) {
+            self.undo.push(current);
+            if self.undo.len() > MAX_HISTORY {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self) -> Option<T> {
+        let state = self.undo.pop()?;
+        if let Some(current) = self.current.replace(state.clone()) {
+            self.redo.push(current);
+        }
+        Some(state)
+    }
+
+    pub fn redo(&mut self) -> Option<T> {
+        let state = self.redo.pop()?;
+        if let Some(current) = self.current.replace(state.clone()) {
+            self.undo.push(current);
+        }
+        Some(state)
+    }
+}
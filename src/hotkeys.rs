@@ -0,0 +1,97 @@
+// System-wide hotkeys, so things can be done without having to switch from VRChat to our window.
+// Needs to live on the main thread, as that is where the OS delivers the hotkey events (at least
+// on Windows).
+
+use crate::AppMessage;
+
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState, hotkey::HotKey};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{mpsc, Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotkeyAction {
+    CaptureAndSend, // Capture the screen and send it right away
+    Resend,         // Send the last sent image again, with the same send options
+}
+
+impl HotkeyAction {
+    fn message(&self) -> AppMessage {
+        match self {
+            HotkeyAction::CaptureAndSend => AppMessage::CaptureAndSend,
+            HotkeyAction::Resend => AppMessage::Resend,
+        }
+    }
+}
+
+pub struct Hotkeys {
+    manager: GlobalHotKeyManager,
+    // Shared with the event handler, so it knows what to do with each hotkey id
+    registered: Arc<Mutex<HashMap<u32, (HotKey, HotkeyAction)>>>,
+}
+
+impl Hotkeys {
+    pub fn new(appmsg: &mpsc::Sender<AppMessage>) -> Result<Self, Box<dyn Error>> {
+        let manager = GlobalHotKeyManager::new()?;
+        let registered: Arc<Mutex<HashMap<u32, (HotKey, HotkeyAction)>>> = Arc::new(Mutex::new(HashMap::new()));
+
+        let appmsg = appmsg.clone();
+        GlobalHotKeyEvent::set_event_handler(Some({
+            let registered = Arc::clone(&registered);
+            move |ev: GlobalHotKeyEvent| {
+                if ev.state != HotKeyState::Pressed {
+                    return;
+                }
+                let action = match registered.lock() {
+                    Ok(registered) => registered.get(&ev.id).map(|(_, action)| *action),
+                    Err(err) => {
+                        warn!("Couldn't lock hotkeys: {err}");
+                        return;
+                    },
+                };
+                let Some(action) = action else {
+                    return;
+                };
+                if let Err(err) = appmsg.send(action.message()) {
+                    warn!("Couldn't send {action:?}: {err}");
+                }
+                fltk::app::awake();
+            }
+        }));
+
+        Ok(Hotkeys { manager: manager, registered: registered })
+    }
+
+    // Replace the hotkey for action with the one described by spec (e.g. "ctrl+shift+F9"). An
+    // empty spec just unregisters the current one.
+    pub fn set(&mut self, action: HotkeyAction, spec: &str) -> Result<(), Box<dyn Error>> {
+        let mut registered = self.registered.lock()
+            .map_err(|err| format!("Couldn't lock hotkeys: {err}"))?;
+
+        let current: Vec<u32> = registered.iter()
+            .filter(|(_, (_, a))| *a == action)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in current {
+            if let Some((hotkey, _)) = registered.remove(&id) {
+                self.manager.unregister(hotkey)?;
+            }
+        }
+
+        let spec = spec.trim();
+        if spec.is_empty() {
+            return Ok(());
+        }
+
+        let hotkey: HotKey = spec.parse()
+            .map_err(|err| format!("Couldn't parse hotkey {spec:?}: {err}"))?;
+        if registered.contains_key(&hotkey.id()) {
+            return Err(format!("Hotkey {spec:?} is already in use").into());
+        }
+        self.manager.register(hotkey)?;
+        registered.insert(hotkey.id(), (hotkey, action));
+        info!("Registered {action:?} hotkey {spec:?}");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,572 @@
+// Translations of the UI. The English text doubles as the key, so anything missing from a table
+// just shows up in English. Widgets labeled through labeled() get relabeled when the language is
+// switched at runtime, everything else (alerts etc.) picks up the language the next time it's shown.
+
+use fltk::prelude::*;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    English,
+    Japanese,
+    German,
+}
+
+pub const LANGUAGES: [Language; 3] = [Language::English, Language::Japanese, Language::German];
+
+impl Language {
+    // In the language itself, for the language choice
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Japanese => "日本語",
+            Language::German => "Deutsch",
+        }
+    }
+
+    // For the config file
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Japanese => "ja",
+            Language::German => "de",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Language> {
+        LANGUAGES.iter().copied().find(|l| l.code() == code)
+    }
+
+    fn table(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::English => &[],
+            Language::Japanese => JA,
+            Language::German => DE,
+        }
+    }
+}
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0); // Index into LANGUAGES
+
+pub fn language() -> Language {
+    LANGUAGES.get(CURRENT.load(Ordering::Relaxed)).copied().unwrap_or(Language::English)
+}
+
+pub fn tr(text: &'static str) -> &'static str {
+    language().table().iter()
+        .find(|(en, _)| *en == text)
+        .map_or(text, |(_, translated)| *translated)
+}
+
+thread_local! {
+    // Widgets to relabel on language change, with their English labels. Only the main thread
+    // creates widgets, so this only needs to be per thread.
+    static LABELED: RefCell<Vec<(fltk::widget::Widget, &'static str)>> = RefCell::new(Vec::new());
+}
+
+// Sets the (translated) label and remembers the widget for when the language changes
+pub fn labeled<W: WidgetExt>(mut widget: W, text: &'static str) -> W {
+    widget.set_label(tr(text));
+    LABELED.with(|labeled| labeled.borrow_mut().push((widget.as_base_widget(), text)));
+    widget
+}
+
+// Should only be called from the main thread
+pub fn set_language(lang: Language) {
+    let index = LANGUAGES.iter().position(|l| *l == lang).unwrap_or(0);
+    CURRENT.store(index, Ordering::Relaxed);
+    info!("Language set to {lang:?}");
+
+    LABELED.with(|labeled| {
+        let mut labeled = labeled.borrow_mut();
+        labeled.retain(|(widget, _)| !widget.was_deleted());
+        for (widget, text) in labeled.iter_mut() {
+            widget.set_label(tr(text));
+            widget.redraw();
+        }
+    });
+    if let Some(mut win) = fltk::app::first_window() {
+        win.redraw();
+    }
+}
+
+const JA: &[(&str, &str)] = &[
+    ("Open", "開く"),
+    ("Save", "保存"),
+    ("Open project", "プロジェクトを開く"),
+    ("Save project", "プロジェクトを保存"),
+    ("Advanced quantization", "詳細な減色設定"),
+    ("Build palette from % of pixels", "パレット作成に使うピクセル (%)"),
+    ("Apply", "適用"),
+    ("K-means refinement passes (0 = off)", "K-means 補正の回数 (0 = オフ)"),
+    ("Dither mask:", "ディザマスク:"),
+    ("Clear painted dither mask", "描いたディザマスクを消去"),
+    ("Forced colors:", "必須の色:"),
+    ("Lock palette", "パレットを固定"),
+    ("Color blindness preview:", "色覚シミュレーション:"),
+    ("Show performance", "パフォーマンスを表示"),
+    ("Performance", "パフォーマンス"),
+    ("Reload at full resolution", "フル解像度で再読み込み"),
+    ("Loading image...", "画像を読み込み中..."),
+    ("Decoding video frame...", "動画フレームをデコード中..."),
+    ("Processing...", "処理中..."),
+    ("Scaling...", "拡大縮小中..."),
+    ("Quantizing...", "減色中..."),
+    ("Checking the color budget...", "色数を確認中..."),
+    ("Suggesting a color count...", "色数の提案を計算中..."),
+    ("Making palette...", "パレットを作成中..."),
+    ("Use palette from image...", "画像からパレットを使用..."),
+    ("Add...", "追加..."),
+    ("Clear", "クリア"),
+    ("Compare settings", "設定を比較"),
+    ("Pipeline...", "処理の順番..."),
+    ("Show histograms", "ヒストグラムを表示"),
+    ("Compare before/after", "変換前後を比較"),
+    ("Sharpen", "シャープ"),
+    ("Blur before downscaling", "縮小前にぼかす"),
+    ("Alpha threshold", "アルファしきい値"),
+    ("Transparent pixels:", "透明ピクセル:"),
+    ("Key out palette index", "パレット番号を透過"),
+    ("Adapt speed to acknowledgements", "受信確認に合わせて速度を調整"),
+    ("Duotone:", "ダブルトーン:"),
+    ("Posterize levels (256 = off)", "ポスタリゼーション階調 (256 = オフ)"),
+    ("Black point", "黒点"),
+    ("White point", "白点"),
+    ("Curve: shadows", "カーブ: シャドウ"),
+    ("Curve: midtones", "カーブ: 中間調"),
+    ("Curve: highlights", "カーブ: ハイライト"),
+    ("Invert colors", "色を反転"),
+    ("Darken edges", "エッジを暗く"),
+    ("CRT look preview", "CRT風プレビュー"),
+    ("Swap red and blue (RGB/BGR)", "赤と青を入れ替え (RGB/BGR)"),
+    ("Only channel:", "チャンネルのみ:"),
+    ("Dark", "暗"),
+    ("Light", "明"),
+    ("Mark letterbox padding", "余白を表示"),
+    ("View 1:1", "等倍表示"),
+    ("Edit tool:", "編集ツール:"),
+    ("Edit color", "編集色"),
+    ("Disable quantization", "減色しない"),
+    ("Grayscale the image\nbefore converting", "変換前に画像を\nグレースケール化"),
+    ("Output the palette\nindexes as grayscale", "パレット番号を\nグレースケールで出力"),
+    ("Sort palette", "パレットを並べ替え"),
+    ("Quantizer:", "減色方式:"),
+    ("Quantize in color space:", "減色する色空間:"),
+    ("Flatten alpha onto:", "透明部分の背景:"),
+    ("Max Colors", "最大色数"),
+    ("Dithering Level", "ディザリング"),
+    ("Enable scaling", "拡大縮小する"),
+    ("Scale (NxN)", "サイズ (NxN)"),
+    ("Scaling fit:", "合わせ方:"),
+    ("Scaler algorithm:", "拡大縮小アルゴリズム:"),
+    ("Display scale multiplier:", "表示倍率:"),
+    ("Caption in letterbox", "余白にキャプション"),
+    ("Caption (empty = filename)", "キャプション (空 = ファイル名)"),
+    ("Send OSC", "OSC送信"),
+    ("OSC updates/second", "OSC更新/秒"),
+    ("Use RLE compression", "RLE圧縮を使う"),
+    ("Confirm before sending", "送信前に確認"),
+    ("OSC Pixel format", "OSCピクセル形式"),
+    ("OSC target (host:port, tcp://host:port for TCP)", "OSC送信先 (ホスト:ポート、TCPはtcp://ホスト:ポート)"),
+    ("Shader profile...", "シェーダープロファイル..."),
+    ("Playlist...", "プレイリスト..."),
+    ("Stream...", "ストリーミング..."),
+    ("Video...", "動画..."),
+    ("Text...", "テキスト..."),
+    ("Query shader", "シェーダーに問い合わせ"),
+    ("Pick the smaller of raw and RLE", "無圧縮とRLEの小さい方を使う"),
+    ("Warn when a send takes longer than (s, 0 = never)", "送信がこれより長いと警告 (秒、0 = しない)"),
+    ("Burst size (chunks, 0 = even pacing)", "バースト長 (チャンク、0 = 均等)"),
+    ("Wait after each burst (ms)", "バースト後の待ち時間 (ms)"),
+    ("Retries on network errors", "ネットワークエラー時の再試行回数"),
+    ("Bandwidth budget (KB/s, empty = use updates/second)", "帯域幅の上限 (KB/s、空 = 更新回数/秒を使う)"),
+    ("Ready to send", "送信可能"),
+    ("Preparing to send...", "送信の準備中..."),
+    ("Sending...", "送信中..."),
+    ("Paused on network errors", "ネットワークエラーで一時停止中"),
+    ("Cancelling...", "キャンセル中..."),
+    ("Send history...", "送信履歴..."),
+    ("Show log...", "ログを表示..."),
+    ("Save as defaults", "デフォルトとして保存"),
+    ("Capture+send hotkey (e.g. ctrl+shift+F9)", "キャプチャ+送信キー (例 ctrl+shift+F9)"),
+    ("Resend hotkey (e.g. ctrl+alt+P)", "再送信キー (例 ctrl+alt+P)"),
+    ("HTTP remote control port (empty = off)", "HTTPリモート操作ポート (空 = オフ)"),
+    ("WebSocket bridge port (empty = off)", "WebSocketポート (空 = オフ)"),
+    ("OSCQuery return port (empty = off)", "OSCQuery受信ポート (空 = オフ)"),
+    ("Language:", "言語:"),
+    ("Source histogram", "元画像のヒストグラム"),
+    ("Output histogram", "出力のヒストグラム"),
+    ("Saved image as", "画像を保存しました:"),
+    ("Saved project as", "プロジェクトを保存しました:"),
+    ("This image was saved with its processing settings. Restore them?", "この画像には保存時の処理設定が含まれています。復元しますか？"),
+    ("No", "いいえ"),
+    ("Restore", "復元"),
+    ("Saved defaults to", "デフォルトを保存しました:"),
+    ("Couldn't save defaults", "デフォルトを保存できませんでした"),
+    ("failed", "失敗"),
+    ("caused by", "原因"),
+    ("This is a bug in the program, not something you did wrong.", "これはプログラムのバグです。操作の誤りではありません。"),
+    ("Close", "閉じる"),
+    ("Copy details", "詳細をコピー"),
+    ("Couldn't update the transfer estimate", "転送見積もりを更新できませんでした"),
+    ("Couldn't send message to BG thread", "バックグラウンドスレッドにメッセージを送れませんでした"),
+    ("Couldn't restore image after a crash", "クラッシュ後に画像を復元できませんでした"),
+    ("Forced colors", "固定色"),
+    ("Couldn't lock settings history", "設定履歴をロックできませんでした"),
+    ("Couldn't restore settings", "設定を復元できませんでした"),
+    ("Open button failed", "「開く」に失敗しました"),
+    ("Save button failed", "「保存」に失敗しました"),
+    ("Compare settings button failed", "設定の比較に失敗しました"),
+    ("Couldn't apply the suggestion", "提案を適用できませんでした"),
+    ("Shader capabilities", "シェーダーの機能"),
+    ("Couldn't query the shader", "シェーダーに問い合わせできませんでした"),
+    ("Palette from image button failed", "画像からのパレット取得に失敗しました"),
+    ("Open project button failed", "プロジェクトを開けませんでした"),
+    ("Save project button failed", "プロジェクトを保存できませんでした"),
+    ("Send OSC button error", "OSC送信エラー"),
+    ("Couldn't start HTTP server on port", "HTTPサーバーを起動できませんでした。ポート"),
+    ("Bad port", "不正なポート"),
+    ("Couldn't start WebSocket bridge on port", "WebSocketブリッジを起動できませんでした。ポート"),
+    ("Couldn't start OSCQuery service for port", "OSCQueryサービスを起動できませんでした。ポート"),
+    ("Couldn't set capture-and-send hotkey", "キャプチャ送信キーを設定できませんでした"),
+    ("Couldn't set resend hotkey", "再送信キーを設定できませんでした"),
+    ("Couldn't restore the project settings", "プロジェクトの設定を復元できませんでした"),
+    ("No palette to lock yet, load an image first", "ロックするパレットがまだありません。先に画像を読み込んでください"),
+    ("Use {} colors", "{}色を使用"),
+    ("{} should be in the pipeline once", "{}はパイプラインに1回だけ必要です"),
+    ("Quantize can't be switched off here, use \"Disable quantization\"", "ここでは減色をオフにできません。「減色しない」を使ってください"),
+    ("Padding has to come after scaling and quantizing", "余白はスケーリングと減色の後でなければなりません"),
+    ("Playlist: couldn't send", "プレイリスト: 送信できませんでした"),
+    ("Playlist: skipping", "プレイリスト: スキップ"),
+    ("Couldn't lock playlist", "プレイリストをロックできませんでした"),
+    ("Couldn't read the image settings", "画像設定を読み取れませんでした"),
+    ("Couldn't show playlist item", "プレイリストの項目を表示できませんでした"),
+    ("Couldn't start the playlist", "プレイリストを開始できませんでした"),
+    ("send_osc background process failed", "送信処理に失敗しました"),
+    ("send_osc background process failed while sending delete window command", "ウィンドウ削除コマンドの送信中に送信処理が失敗しました"),
+    ("No PixelSendCRT-style parameters found in", "PixelSendCRT形式のパラメーターが見つかりません:"),
+    ("Stream stopped", "ストリームが停止しました"),
+    ("Couldn't start streaming", "ストリーミングを開始できませんでした"),
+    ("Couldn't show the text", "テキストを表示できませんでした"),
+    ("Couldn't send the text", "テキストを送信できませんでした"),
+    ("Couldn't open", "開けませんでした:"),
+    ("Couldn't stream the clip", "クリップをストリーミングできませんでした"),
+    ("Ack parameter (for adaptive rate)", "ACKパラメーター (適応レート用)"),
+    ("Add images...", "画像を追加..."),
+    ("Background", "背景色"),
+    ("Cancel", "キャンセル"),
+    ("Cancelled, cleaning up", "キャンセルしました。後処理中"),
+    ("Chunks per checksum", "チェックサムごとのチャンク数"),
+    ("Clip ends here", "ここでクリップ終了"),
+    ("Clip frames/second", "クリップのフレーム/秒"),
+    ("Clip starts here", "ここからクリップ開始"),
+    ("Clip:", "クリップ:"),
+    ("Confirm send", "送信の確認"),
+    ("Copy to clipboard", "クリップボードにコピー"),
+    ("Detected profiles", "検出したプロファイル"),
+    ("Don't ask again", "今後確認しない"),
+    ("Down", "下へ"),
+    ("Font:", "フォント:"),
+    ("Frame {}: no change ({} skipped)", "フレーム{}: 変化なし ({}枚スキップ)"),
+    ("Frame {}: sending {}/{} rows", "フレーム{}: {}/{}行を送信中"),
+    ("Frame {}: sending {}/{} rows and the palette", "フレーム{}: {}/{}行とパレットを送信中"),
+    ("Grayscale", "グレースケール"),
+    ("Grid ({}x and up)", "グリッド ({}倍以上)"),
+    ("Images to prepare ahead:", "先に準備する画像数:"),
+    ("Is it running, with OSC enabled in the action menu? If the target is right, the whole send would go nowhere.", "起動していて、アクションメニューでOSCが有効になっていますか? 送信先が正しければ、送信内容はすべて届きません。"),
+    ("Keep the first frame's palette (smaller updates)", "最初のフレームのパレットを使い続ける (更新が小さくなる)"),
+    ("Log", "ログ"),
+    ("Long send", "長い送信"),
+    ("Loop, seconds per image:", "ループ、1枚あたりの秒数:"),
+    ("Making a palette from {} frames...", "{}フレームからパレットを作成中..."),
+    ("NDI source (empty = first found)", "NDIソース (空 = 最初に見つかったもの)"),
+    ("Network error", "ネットワークエラー"),
+    ("No processed image", "処理済みの画像がありません"),
+    ("Nothing listening", "受信側がありません"),
+    ("OK", "OK"),
+    ("On/off", "オン/オフ"),
+    ("One palette made from all the frames", "全フレームから1つのパレットを作る"),
+    ("One palette made from all the frames (GIFs)", "全フレームから1つのパレットを作る (GIF)"),
+    ("Open video...", "動画を開く..."),
+    ("Pack bytes into ints (experimental)", "バイトをintにまとめる (実験的)"),
+    ("Pad", "余白"),
+    ("Pick GIF...", "GIFを選ぶ..."),
+    ("Playlist", "プレイリスト"),
+    ("Preamble step delays in ms\n(0 = same as pixel chunks)", "前処理ステップの待ち時間 (ms)\n(0 = ピクセルチャンクと同じ)"),
+    ("Processing pipeline", "処理パイプライン"),
+    ("Protocol", "プロトコル"),
+    ("Quantize", "減色"),
+    ("Remove", "削除"),
+    ("Rendered at the size set in the main window", "メインウィンドウで設定したサイズで描画されます"),
+    ("Reset", "リセット"),
+    ("Retry", "再試行"),
+    ("Retry, or cancel the send?", "再試行しますか、それとも送信をキャンセルしますか?"),
+    ("Scale", "拡大縮小"),
+    ("Scan avatar OSC configs", "アバターのOSC設定をスキャン"),
+    ("Screen region x,y,w,h (empty = all)", "画面の範囲 x,y,w,h (空 = 全体)"),
+    ("Send", "送信"),
+    ("Send anyway", "それでも送信"),
+    ("Send history", "送信履歴"),
+    ("Send playlist", "プレイリストを送信"),
+    ("Sending OSC", "OSC送信中"),
+    ("Shader profile", "シェーダープロファイル"),
+    ("Show", "表示"),
+    ("Source:", "ソース:"),
+    ("Start streaming", "ストリーミング開始"),
+    ("Stop", "停止"),
+    ("Stopped after {} frames", "{}フレームで停止しました"),
+    ("Stream", "ストリーミング"),
+    ("Stream clip", "クリップをストリーミング"),
+    ("Text", "テキスト"),
+    ("Text color", "文字色"),
+    ("This is what will be sent. Are you sure?", "これが送信されます。よろしいですか?"),
+    ("This send will take about {}, which is more than {}.", "この送信には約{}かかり、{}を超えます。"),
+    ("Time (s)", "時間 (秒)"),
+    ("To make it faster:", "速くするには:"),
+    ("Up", "上へ"),
+    ("Use current settings", "現在の設定を使う"),
+    ("VRChat doesn't appear to be listening on {}.", "VRChatは{}で受信していないようです。"),
+    ("Video", "動画"),
+    ("ffmpeg and ffprobe need to be installed", "ffmpegとffprobeのインストールが必要です"),
+];
+
+const DE: &[(&str, &str)] = &[
+    ("Open", "Öffnen"),
+    ("Save", "Speichern"),
+    ("Open project", "Projekt öffnen"),
+    ("Save project", "Projekt speichern"),
+    ("Advanced quantization", "Erweiterte Quantisierung"),
+    ("Build palette from % of pixels", "Palette aus % der Pixel erstellen"),
+    ("Apply", "Anwenden"),
+    ("K-means refinement passes (0 = off)", "K-Means-Verfeinerungsdurchläufe (0 = aus)"),
+    ("Dither mask:", "Dithering-Maske:"),
+    ("Clear painted dither mask", "Gemalte Dithering-Maske löschen"),
+    ("Forced colors:", "Feste Farben:"),
+    ("Lock palette", "Palette sperren"),
+    ("Color blindness preview:", "Farbenblindheit-Vorschau:"),
+    ("Show performance", "Leistung anzeigen"),
+    ("Performance", "Leistung"),
+    ("Reload at full resolution", "In voller Auflösung neu laden"),
+    ("Loading image...", "Bild wird geladen..."),
+    ("Decoding video frame...", "Videobild wird dekodiert..."),
+    ("Processing...", "Verarbeitung..."),
+    ("Scaling...", "Skalierung..."),
+    ("Quantizing...", "Farbreduktion..."),
+    ("Checking the color budget...", "Farbbudget wird geprüft..."),
+    ("Suggesting a color count...", "Farbanzahl wird ermittelt..."),
+    ("Making palette...", "Palette wird erstellt..."),
+    ("Use palette from image...", "Palette aus Bild verwenden..."),
+    ("Add...", "Hinzufügen..."),
+    ("Clear", "Leeren"),
+    ("Compare settings", "Einstellungen vergleichen"),
+    ("Pipeline...", "Verarbeitungsreihenfolge..."),
+    ("Show histograms", "Histogramme anzeigen"),
+    ("Compare before/after", "Vorher/nachher vergleichen"),
+    ("Sharpen", "Schärfen"),
+    ("Blur before downscaling", "Vor dem Verkleinern weichzeichnen"),
+    ("Alpha threshold", "Alpha-Schwelle"),
+    ("Transparent pixels:", "Transparente Pixel:"),
+    ("Key out palette index", "Paletteneintrag ausstanzen"),
+    ("Adapt speed to acknowledgements", "Geschwindigkeit an Bestätigungen anpassen"),
+    ("Duotone:", "Duplex:"),
+    ("Posterize levels (256 = off)", "Tontrennung Stufen (256 = aus)"),
+    ("Black point", "Schwarzpunkt"),
+    ("White point", "Weißpunkt"),
+    ("Curve: shadows", "Kurve: Tiefen"),
+    ("Curve: midtones", "Kurve: Mitteltöne"),
+    ("Curve: highlights", "Kurve: Lichter"),
+    ("Invert colors", "Farben invertieren"),
+    ("Darken edges", "Kanten abdunkeln"),
+    ("CRT look preview", "CRT-Vorschau"),
+    ("Swap red and blue (RGB/BGR)", "Rot und Blau tauschen (RGB/BGR)"),
+    ("Only channel:", "Nur Kanal:"),
+    ("Dark", "Dunkel"),
+    ("Light", "Hell"),
+    ("Mark letterbox padding", "Letterbox-Ränder markieren"),
+    ("View 1:1", "1:1-Ansicht"),
+    ("Edit tool:", "Bearbeitungswerkzeug:"),
+    ("Edit color", "Bearbeitungsfarbe"),
+    ("Disable quantization", "Farbreduktion aus"),
+    ("Grayscale the image\nbefore converting", "Bild vor dem Umwandeln\nin Graustufen"),
+    ("Output the palette\nindexes as grayscale", "Palettenindizes als\nGraustufen ausgeben"),
+    ("Sort palette", "Palette sortieren"),
+    ("Quantizer:", "Farbreduktion:"),
+    ("Quantize in color space:", "Farbraum für Reduktion:"),
+    ("Flatten alpha onto:", "Transparenz füllen mit:"),
+    ("Max Colors", "Max. Farben"),
+    ("Dithering Level", "Dithering-Stärke"),
+    ("Enable scaling", "Skalieren"),
+    ("Scale (NxN)", "Größe (NxN)"),
+    ("Scaling fit:", "Einpassen:"),
+    ("Scaler algorithm:", "Skalierungsverfahren:"),
+    ("Display scale multiplier:", "Anzeigevergrößerung:"),
+    ("Caption in letterbox", "Beschriftung im Rand"),
+    ("Caption (empty = filename)", "Beschriftung (leer = Dateiname)"),
+    ("Send OSC", "Per OSC senden"),
+    ("OSC updates/second", "OSC-Updates/Sekunde"),
+    ("Use RLE compression", "RLE-Kompression"),
+    ("Confirm before sending", "Vor dem Senden bestätigen"),
+    ("OSC Pixel format", "OSC-Pixelformat"),
+    ("OSC target (host:port, tcp://host:port for TCP)", "OSC-Ziel (Host:Port, tcp://Host:Port für TCP)"),
+    ("Shader profile...", "Shader-Profil..."),
+    ("Playlist...", "Wiedergabeliste..."),
+    ("Stream...", "Streamen..."),
+    ("Video...", "Video..."),
+    ("Text...", "Text..."),
+    ("Query shader", "Shader abfragen"),
+    ("Pick the smaller of raw and RLE", "Kleineres von Roh und RLE wählen"),
+    ("Warn when a send takes longer than (s, 0 = never)", "Warnen, wenn Senden länger dauert als (s, 0 = nie)"),
+    ("Burst size (chunks, 0 = even pacing)", "Burstgröße (Chunks, 0 = gleichmäßig)"),
+    ("Wait after each burst (ms)", "Wartezeit nach jedem Burst (ms)"),
+    ("Retries on network errors", "Wiederholungen bei Netzwerkfehlern"),
+    ("Bandwidth budget (KB/s, empty = use updates/second)", "Bandbreitenbudget (KB/s, leer = Updates/Sekunde)"),
+    ("Ready to send", "Bereit zum Senden"),
+    ("Preparing to send...", "Senden wird vorbereitet..."),
+    ("Sending...", "Sende..."),
+    ("Paused on network errors", "Wegen Netzwerkfehlern pausiert"),
+    ("Cancelling...", "Wird abgebrochen..."),
+    ("Send history...", "Sendeverlauf..."),
+    ("Show log...", "Log anzeigen..."),
+    ("Save as defaults", "Als Standard speichern"),
+    ("Capture+send hotkey (e.g. ctrl+shift+F9)", "Aufnehmen+Senden-Taste (z.B. ctrl+shift+F9)"),
+    ("Resend hotkey (e.g. ctrl+alt+P)", "Erneut-senden-Taste (z.B. ctrl+alt+P)"),
+    ("HTTP remote control port (empty = off)", "HTTP-Fernsteuerungsport (leer = aus)"),
+    ("WebSocket bridge port (empty = off)", "WebSocket-Port (leer = aus)"),
+    ("OSCQuery return port (empty = off)", "OSCQuery-Rückkanalport (leer = aus)"),
+    ("Language:", "Sprache:"),
+    ("Source histogram", "Histogramm Quelle"),
+    ("Output histogram", "Histogramm Ausgabe"),
+    ("Saved image as", "Bild gespeichert als"),
+    ("Saved project as", "Projekt gespeichert als"),
+    ("This image was saved with its processing settings. Restore them?", "Dieses Bild wurde mit seinen Verarbeitungseinstellungen gespeichert. Wiederherstellen?"),
+    ("No", "Nein"),
+    ("Restore", "Wiederherstellen"),
+    ("Saved defaults to", "Standardeinstellungen gespeichert in"),
+    ("Couldn't save defaults", "Standardeinstellungen konnten nicht gespeichert werden"),
+    ("failed", "fehlgeschlagen"),
+    ("caused by", "verursacht durch"),
+    ("This is a bug in the program, not something you did wrong.", "Das ist ein Fehler im Programm, nicht in der Bedienung."),
+    ("Close", "Schließen"),
+    ("Copy details", "Details kopieren"),
+    ("Couldn't update the transfer estimate", "Übertragungsschätzung konnte nicht aktualisiert werden"),
+    ("Couldn't send message to BG thread", "Nachricht an den Hintergrund-Thread konnte nicht gesendet werden"),
+    ("Couldn't restore image after a crash", "Bild konnte nach einem Absturz nicht wiederhergestellt werden"),
+    ("Forced colors", "Feste Farben"),
+    ("Couldn't lock settings history", "Einstellungsverlauf konnte nicht gesperrt werden"),
+    ("Couldn't restore settings", "Einstellungen konnten nicht wiederhergestellt werden"),
+    ("Open button failed", "Öffnen fehlgeschlagen"),
+    ("Save button failed", "Speichern fehlgeschlagen"),
+    ("Compare settings button failed", "Einstellungsvergleich fehlgeschlagen"),
+    ("Couldn't apply the suggestion", "Vorschlag konnte nicht übernommen werden"),
+    ("Shader capabilities", "Shader-Fähigkeiten"),
+    ("Couldn't query the shader", "Shader konnte nicht abgefragt werden"),
+    ("Palette from image button failed", "Palette aus Bild fehlgeschlagen"),
+    ("Open project button failed", "Projekt öffnen fehlgeschlagen"),
+    ("Save project button failed", "Projekt speichern fehlgeschlagen"),
+    ("Send OSC button error", "Fehler beim OSC-Senden"),
+    ("Couldn't start HTTP server on port", "HTTP-Server konnte nicht gestartet werden, Port"),
+    ("Bad port", "Ungültiger Port"),
+    ("Couldn't start WebSocket bridge on port", "WebSocket-Brücke konnte nicht gestartet werden, Port"),
+    ("Couldn't start OSCQuery service for port", "OSCQuery-Dienst konnte nicht gestartet werden, Port"),
+    ("Couldn't set capture-and-send hotkey", "Aufnehmen-und-senden-Taste konnte nicht gesetzt werden"),
+    ("Couldn't set resend hotkey", "Erneut-senden-Taste konnte nicht gesetzt werden"),
+    ("Couldn't restore the project settings", "Projekteinstellungen konnten nicht wiederhergestellt werden"),
+    ("No palette to lock yet, load an image first", "Noch keine Palette zum Sperren, zuerst ein Bild laden"),
+    ("Use {} colors", "{} Farben verwenden"),
+    ("{} should be in the pipeline once", "{} muss genau einmal in der Pipeline sein"),
+    ("Quantize can't be switched off here, use \"Disable quantization\"", "Farbreduktion lässt sich hier nicht abschalten, \"Farbreduktion aus\" verwenden"),
+    ("Padding has to come after scaling and quantizing", "Auffüllen muss nach Skalieren und Quantisieren kommen"),
+    ("Playlist: couldn't send", "Wiedergabeliste: konnte nicht senden"),
+    ("Playlist: skipping", "Wiedergabeliste: überspringe"),
+    ("Couldn't lock playlist", "Wiedergabeliste konnte nicht gesperrt werden"),
+    ("Couldn't read the image settings", "Bildeinstellungen konnten nicht gelesen werden"),
+    ("Couldn't show playlist item", "Eintrag der Wiedergabeliste konnte nicht angezeigt werden"),
+    ("Couldn't start the playlist", "Wiedergabeliste konnte nicht gestartet werden"),
+    ("send_osc background process failed", "Sendevorgang fehlgeschlagen"),
+    ("send_osc background process failed while sending delete window command", "Sendevorgang beim Schließen des Fensters fehlgeschlagen"),
+    ("No PixelSendCRT-style parameters found in", "Keine PixelSendCRT-Parameter gefunden in"),
+    ("Stream stopped", "Stream angehalten"),
+    ("Couldn't start streaming", "Streaming konnte nicht gestartet werden"),
+    ("Couldn't show the text", "Text konnte nicht angezeigt werden"),
+    ("Couldn't send the text", "Text konnte nicht gesendet werden"),
+    ("Couldn't open", "Konnte nicht öffnen:"),
+    ("Couldn't stream the clip", "Clip konnte nicht gestreamt werden"),
+    ("Ack parameter (for adaptive rate)", "Ack-Parameter (für adaptive Rate)"),
+    ("Add images...", "Bilder hinzufügen..."),
+    ("Background", "Hintergrund"),
+    ("Cancel", "Abbrechen"),
+    ("Cancelled, cleaning up", "Abgebrochen, räume auf"),
+    ("Chunks per checksum", "Chunks pro Prüfsumme"),
+    ("Clip ends here", "Clip endet hier"),
+    ("Clip frames/second", "Clip-Bilder/Sekunde"),
+    ("Clip starts here", "Clip beginnt hier"),
+    ("Clip:", "Clip:"),
+    ("Confirm send", "Senden bestätigen"),
+    ("Copy to clipboard", "In die Zwischenablage kopieren"),
+    ("Detected profiles", "Gefundene Profile"),
+    ("Don't ask again", "Nicht mehr fragen"),
+    ("Down", "Runter"),
+    ("Font:", "Schriftart:"),
+    ("Frame {}: no change ({} skipped)", "Bild {}: keine Änderung ({} übersprungen)"),
+    ("Frame {}: sending {}/{} rows", "Bild {}: sende {}/{} Zeilen"),
+    ("Frame {}: sending {}/{} rows and the palette", "Bild {}: sende {}/{} Zeilen und die Palette"),
+    ("Grayscale", "Graustufen"),
+    ("Grid ({}x and up)", "Raster (ab {}x)"),
+    ("Images to prepare ahead:", "Im Voraus vorbereitete Bilder:"),
+    ("Is it running, with OSC enabled in the action menu? If the target is right, the whole send would go nowhere.", "Läuft es, mit OSC im Aktionsmenü aktiviert? Wenn das Ziel stimmt, ginge der ganze Sendevorgang ins Leere."),
+    ("Keep the first frame's palette (smaller updates)", "Palette des ersten Bildes behalten (kleinere Updates)"),
+    ("Log", "Protokoll"),
+    ("Long send", "Langer Sendevorgang"),
+    ("Loop, seconds per image:", "Wiederholen, Sekunden pro Bild:"),
+    ("Making a palette from {} frames...", "Erstelle eine Palette aus {} Bildern..."),
+    ("NDI source (empty = first found)", "NDI-Quelle (leer = erste gefundene)"),
+    ("Network error", "Netzwerkfehler"),
+    ("No processed image", "Kein verarbeitetes Bild"),
+    ("Nothing listening", "Niemand empfängt"),
+    ("OK", "OK"),
+    ("On/off", "An/aus"),
+    ("One palette made from all the frames", "Eine Palette aus allen Bildern"),
+    ("One palette made from all the frames (GIFs)", "Eine Palette aus allen Bildern (GIFs)"),
+    ("Open video...", "Video öffnen..."),
+    ("Pack bytes into ints (experimental)", "Bytes in Ints packen (experimentell)"),
+    ("Pad", "Auffüllen"),
+    ("Pick GIF...", "GIF auswählen..."),
+    ("Playlist", "Wiedergabeliste"),
+    ("Preamble step delays in ms\n(0 = same as pixel chunks)", "Verzögerung der Vorlaufschritte in ms\n(0 = wie Pixel-Chunks)"),
+    ("Processing pipeline", "Verarbeitungs-Pipeline"),
+    ("Protocol", "Protokoll"),
+    ("Quantize", "Quantisieren"),
+    ("Remove", "Entfernen"),
+    ("Rendered at the size set in the main window", "Wird in der im Hauptfenster eingestellten Größe gerendert"),
+    ("Reset", "Zurücksetzen"),
+    ("Retry", "Wiederholen"),
+    ("Retry, or cancel the send?", "Erneut versuchen oder den Sendevorgang abbrechen?"),
+    ("Scale", "Skalieren"),
+    ("Scan avatar OSC configs", "OSC-Konfigurationen der Avatare durchsuchen"),
+    ("Screen region x,y,w,h (empty = all)", "Bildschirmbereich x,y,w,h (leer = alles)"),
+    ("Send", "Senden"),
+    ("Send anyway", "Trotzdem senden"),
+    ("Send history", "Sendeverlauf"),
+    ("Send playlist", "Wiedergabeliste senden"),
+    ("Sending OSC", "Sende OSC"),
+    ("Shader profile", "Shader-Profil"),
+    ("Show", "Anzeigen"),
+    ("Source:", "Quelle:"),
+    ("Start streaming", "Streaming starten"),
+    ("Stop", "Stopp"),
+    ("Stopped after {} frames", "Nach {} Bildern angehalten"),
+    ("Stream", "Streamen"),
+    ("Stream clip", "Clip streamen"),
+    ("Text", "Text"),
+    ("Text color", "Textfarbe"),
+    ("This is what will be sent. Are you sure?", "Das wird gesendet. Sicher?"),
+    ("This send will take about {}, which is more than {}.", "Dieser Sendevorgang dauert etwa {}, also länger als {}."),
+    ("Time (s)", "Zeit (s)"),
+    ("To make it faster:", "Schneller geht es mit:"),
+    ("Up", "Hoch"),
+    ("Use current settings", "Aktuelle Einstellungen verwenden"),
+    ("VRChat doesn't appear to be listening on {}.", "VRChat scheint auf {} nicht zu empfangen."),
+    ("Video", "Video"),
+    ("ffmpeg and ffprobe need to be installed", "ffmpeg und ffprobe müssen installiert sein"),
+];
@@ -0,0 +1,91 @@
+// Marks the padding ResizeType::ToFit adds in the preview, so it's obvious which pixels are
+// filler and which came from the source. The pad bars get hatched and the source area outlined.
+// This only affects what gets drawn on screen, never what gets saved or sent.
+
+use fltk::{prelude::*, frame::Frame, enums::{Color, LineStyle}, draw};
+use std::sync::Mutex;
+
+const HATCH_SPACING: i32 = 8;
+const HATCH_COLOR: Color = Color::from_rgb(255, 0, 255);
+
+struct LetterboxState {
+    enabled: bool,
+    content: Option<[f64; 4]>, // x, y, w, h of the source area as fractions of the whole image
+}
+
+static LETTERBOX_STATE: Mutex<LetterboxState> = Mutex::new(LetterboxState {
+    enabled: true,
+    content: None,
+});
+
+pub fn set_enabled(enabled: bool) {
+    match LETTERBOX_STATE.lock() {
+        Ok(mut state) => state.enabled = enabled,
+        Err(err) => warn!("Couldn't lock letterbox state: {err}"),
+    }
+}
+
+// content is the x, y, w, h of the unpadded part in image pixels. None when there is no padding.
+pub fn set_content(content: Option<(u32, u32, u32, u32)>, width: u32, height: u32) {
+    let content = content
+        .filter(|_| width > 0 && height > 0)
+        .map(|(x, y, w, h)| {
+            let (width, height) = (width as f64, height as f64);
+            [(x as f64)/width, (y as f64)/height, (w as f64)/width, (h as f64)/height]
+        });
+    match LETTERBOX_STATE.lock() {
+        Ok(mut state) => state.content = content,
+        Err(err) => warn!("Couldn't lock letterbox state: {err}"),
+    }
+}
+
+// The x, y, w, h of the unpadded part as fractions of the whole image, None when there is no padding
+pub fn content() -> Option<[f64; 4]> {
+    LETTERBOX_STATE.lock().ok().and_then(|state| state.content)
+}
+
+fn hatch(x: i32, y: i32, w: i32, h: i32) {
+    if w <= 0 || h <= 0 {
+        return;
+    }
+    draw::push_clip(x, y, w, h);
+    let mut offset = 0;
+    while offset < w + h {
+        draw::draw_line(x + offset, y, x + offset - h, y + h);
+        offset += HATCH_SPACING;
+    }
+    draw::pop_clip();
+}
+
+// Called from the preview frame's draw callback, after the image itself has been drawn
+pub fn draw(f: &Frame) {
+    let Ok(state) = LETTERBOX_STATE.lock() else {
+        return;
+    };
+    let (true, Some([cx, cy, cw, ch])) = (state.enabled, state.content) else {
+        return;
+    };
+    let Some(image) = f.image() else {
+        return;
+    };
+
+    // The frame draws its image centered
+    let (w, h) = (image.w(), image.h());
+    let x = f.x() + (f.w() - w)/2;
+    let y = f.y() + (f.h() - h)/2;
+    let content_x = x + ((w as f64)*cx).round() as i32;
+    let content_y = y + ((h as f64)*cy).round() as i32;
+    let content_w = ((w as f64)*cw).round() as i32;
+    let content_h = ((h as f64)*ch).round() as i32;
+
+    draw::set_draw_color(HATCH_COLOR);
+    draw::set_line_style(LineStyle::Solid, 1);
+    hatch(x, y, w, content_y - y);                                                   // Top
+    hatch(x, content_y + content_h, w, (y + h) - (content_y + content_h));           // Bottom
+    hatch(x, content_y, content_x - x, content_h);                                   // Left
+    hatch(content_x + content_w, content_y, (x + w) - (content_x + content_w), content_h); // Right
+
+    draw::set_line_style(LineStyle::Dash, 1);
+    draw::draw_rect(content_x, content_y, content_w, content_h);
+    draw::set_line_style(LineStyle::Solid, 0);
+}
@@ -1 +1,6 @@
+#[macro_use]
+extern crate log;
+
 pub mod mq;
+pub mod protocol_profile;
+pub mod receiver_sim;
@@ -0,0 +1,190 @@
+// Logging that goes both to the console and to an in-app log window, since on Windows there
+// usually isn't a console to look at.
+
+use crate::i18n;
+
+use fltk::{prelude::*, window::Window, group::Flex, button::Button, menu, text, app};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::Mutex;
+
+const MAX_LINES: usize = 5000;
+const REFRESH_INTERVAL: f64 = 0.5;
+
+struct LogState {
+    lines: VecDeque<(Level, String)>,
+    generation: u64, // Bumped for every new line, so the window knows when to refresh
+}
+
+static LOG_STATE: Mutex<LogState> = Mutex::new(LogState {
+    lines: VecDeque::new(),
+    generation: 0,
+});
+
+struct PanelLogger;
+
+static LOGGER: PanelLogger = PanelLogger;
+
+impl Log for PanelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!("[{}] {}", record.level(), record.args());
+        if record.level() <= Level::Warn {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
+
+        // Can't log anything about failing to lock here, for obvious reasons
+        if let Ok(mut state) = LOG_STATE.lock() {
+            state.lines.push_back((record.level(), line));
+            if state.lines.len() > MAX_LINES {
+                state.lines.pop_front();
+            }
+            state.generation += 1;
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+const DEFAULT_VERBOSITY: LevelFilter = LevelFilter::Info;
+
+// The starting verbosity can be set with RUST_LOG (just the level, e.g. RUST_LOG=trace), and changed
+// later on from the log window
+pub fn init() -> Result<(), log::SetLoggerError> {
+    log::set_logger(&LOGGER)?;
+    let verbosity = std::env::var("RUST_LOG").ok()
+        .and_then(|s| s.trim().parse::<LevelFilter>().ok())
+        .unwrap_or(DEFAULT_VERBOSITY);
+    log::set_max_level(verbosity);
+    Ok(())
+}
+
+// The lines at or above the given level, joined up for display
+fn filtered_text(max_level: Level) -> (String, u64) {
+    match LOG_STATE.lock() {
+        Ok(state) => {
+            let lines: Vec<&str> = state.lines.iter()
+                .filter(|(level, _)| *level <= max_level)
+                .map(|(_, line)| line.as_str())
+                .collect();
+            (lines.join("\n"), state.generation)
+        },
+        Err(_) => (String::new(), 0),
+    }
+}
+
+const LEVELS: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+const VERBOSITIES: [LevelFilter; 6] = [
+    LevelFilter::Off, LevelFilter::Error, LevelFilter::Warn, LevelFilter::Info, LevelFilter::Debug, LevelFilter::Trace,
+];
+
+// Should only be called from the main thread
+pub fn show_log_window() {
+    let mut win = i18n::labeled(Window::default().with_size(800, 500), "Log");
+    win.make_resizable(true);
+
+    let mut col = Flex::default_fill().column();
+    col.set_margin(10);
+
+    let mut display = text::TextDisplay::default_fill();
+    display.set_buffer(text::TextBuffer::default());
+    display.set_text_font(fltk::enums::Font::Courier);
+
+    let mut row = Flex::default_fill().row();
+    let mut level_choice = menu::Choice::default();
+    level_choice.add_choice(&LEVELS.map(|l| l.to_string()).join("|"));
+    level_choice.set_value(2); // Info
+    level_choice.set_tooltip("Which of the collected lines to show");
+    // What gets logged at all. Lines below this level are never collected, so turning it up only
+    // affects what comes after.
+    let mut verbosity_choice = menu::Choice::default();
+    verbosity_choice.add_choice(&VERBOSITIES.map(|l| l.to_string()).join("|"));
+    verbosity_choice.set_value(
+        VERBOSITIES.iter().position(|&l| l == log::max_level()).map_or(3, |i| i as i32)
+    );
+    verbosity_choice.set_tooltip("Verbosity: which messages get logged at all");
+    let mut copy_btn = i18n::labeled(Button::default(), "Copy to clipboard");
+    row.end();
+    col.fixed(&row, 30);
+
+    col.end();
+    win.end();
+    win.show();
+
+    let level = |c: &menu::Choice| -> Level {
+        usize::try_from(c.value()).ok().and_then(|i| LEVELS.get(i).copied()).unwrap_or(Level::Info)
+    };
+
+    let refresh = {
+        let display = display.clone();
+        let level_choice = level_choice.clone();
+        move || -> u64 {
+            let mut display = display.clone();
+            let (text, generation) = filtered_text(level(&level_choice));
+            if let Some(mut buf) = display.buffer() {
+                buf.set_text(&text);
+                display.set_insert_position(buf.length());
+                display.show_insert_position();
+            }
+            generation
+        }
+    };
+
+    let shown_generation = Rc::new(Cell::new(refresh()));
+    let closed = Rc::new(Cell::new(false));
+
+    level_choice.set_callback({
+        let shown_generation = Rc::clone(&shown_generation);
+        let refresh = refresh.clone();
+        move |_| shown_generation.set(refresh())
+    });
+
+    verbosity_choice.set_callback(|c| {
+        if let Some(&verbosity) = usize::try_from(c.value()).ok().and_then(|i| VERBOSITIES.get(i)) {
+            log::set_max_level(verbosity);
+            info!("Log verbosity set to {verbosity}");
+        }
+    });
+
+    copy_btn.set_callback({
+        let level_choice = level_choice.clone();
+        move |_| {
+            let (text, _) = filtered_text(level(&level_choice));
+            app::copy(&text);
+        }
+    });
+
+    win.set_callback({
+        let closed = Rc::clone(&closed);
+        move |win| {
+            closed.set(true);
+            app::delete_widget(win.clone());
+        }
+    });
+
+    // Log lines come in from all threads, so poll for new ones rather than pushing them to the widget
+    app::add_timeout3(REFRESH_INTERVAL, move |handle| {
+        if closed.get() {
+            return;
+        }
+        let generation = match LOG_STATE.lock() {
+            Ok(state) => state.generation,
+            Err(_) => return,
+        };
+        if generation != shown_generation.get() {
+            shown_generation.set(refresh());
+        }
+        app::repeat_timeout3(REFRESH_INTERVAL, handle);
+    });
+}
@@ -1,14 +1,73 @@
+#[macro_use]
+extern crate log;
+
 pub mod mq;
 mod send_osc;
+mod pack_cli;
+mod send_job;
+mod adaptive_rate;
+mod checksum;
+mod handshake;
+mod transport;
+mod receiver_sim;
+mod pixel_protocol;
 mod save_png;
+mod atomic_write;
+mod shader_profile;
+mod protocol_profile;
+mod osc_config;
+mod banner;
+mod history;
+mod histogram;
+mod compare;
+mod letterbox;
+mod color_budget;
+mod quantizer;
+mod prefetch;
+mod playlist;
+mod stream;
+mod video;
+mod ndi;
+mod text;
+mod edit;
+mod pipeline;
+mod dither_mask;
+mod project;
+mod pixel_view;
+mod colorspace;
+mod filters;
+mod transparency;
+mod duotone;
+mod palette;
+mod progress;
+mod metrics;
+mod colorblind;
+mod crt;
+mod capture;
+mod hotkeys;
+mod remote;
+mod ws_bridge;
+mod oscquery;
+mod config;
+mod i18n;
+mod theme;
+mod splitter;
+mod send_stats;
+mod log_panel;
+mod error;
 #[macro_use]
 mod utility;
 
-use utility::{print_err, alert, error_alert};
+use utility::{print_err, alert, error_alert, report_error};
+use error::{ProcessError, SaveError};
+use quantizer::QuantizerType;
+use colorspace::ColorSpace;
+use transparency::TransparentMode;
 
 use fltk::{app, frame::Frame, enums::*, prelude::*, window::Window, group::*, button::*, valuator::*, dialog, input::*, menu};
 use std::error::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::net::ToSocketAddrs;
 use std::iter::zip;
 use rayon::prelude::*;
 use std::thread;
@@ -18,6 +77,11 @@ use image::{self, imageops};
 use std::sync::mpsc;
 use std::default::Default;
 use std::cmp::min;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
 use strum::*;
 use strum_macros::*;
 
@@ -39,7 +103,8 @@ macro_rules! time_it {
         $(
             $tt
         )+
-        println!("{}: {:?}", $context, timer.elapsed());
+        debug!("{}: {:?}", $context, timer.elapsed());
+        crate::metrics::record($context, timer.elapsed());
     }
 }
 
@@ -52,46 +117,114 @@ pub enum AppMessage {
     // TODO alt: Just have a generic "RunOnMain" message taking a closure.
     CreateWindow(i32, i32, String, Box<dyn FnOnce(&mut Window) -> Result<(), Box<dyn Error>> + Send + Sync>),
     DeleteWindow(Window),
+    Error(error::ErrorReport),
+    CaptureAndSend, // From the global hotkey
+    Resend,         // Also from a global hotkey
+    RemoteSend,     // From the HTTP API
+    ApplyProject(project::Project), // Set the widgets from an opened project
+    Progress(Option<&'static str>), // What the BG thread is busy with, None when done (see progress)
+}
+
+// All the settings for processing an image
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageSettings {
+    no_quantize: bool,
+    grayscale: bool,
+    grayscale_output: bool,
+    reorder_palette: bool,
+    quantizer_type: QuantizerType,
+    #[serde(default)]
+    quantizr: quantizer::QuantizrOptions,
+    #[serde(default)]
+    dither_mask: dither_mask::DitherMask,
+    #[serde(default)]
+    forced_colors: Vec<palette::Rgb>,
+    #[serde(default)]
+    locked_palette: Option<Vec<palette::Rgba>>,
+    color_space: ColorSpace,
+    flatten: Flatten,
+    alpha_threshold: u8,
+    transparent_mode: TransparentMode,
+    maxcolors: i32,
+    dithering: f32,
+    scaling: bool,
+    scale: u32,
+    multiplier: u8,
+    resize_type: ResizeType,
+    scaler_type: ScalerType,
+    pre_blur: bool,
+    sharpen: f32,
+    #[serde(default)]
+    edge_darken: f32,
+    posterize: u32, // Levels per channel, 256 is off
+    #[serde(default)]
+    levels: filters::Levels,
+    #[serde(default)]
+    invert: bool,
+    #[serde(default)]
+    swap_rb: bool,
+    #[serde(default)]
+    isolate_channel: filters::Channel,
+    duotone: Option<(duotone::Rgb, duotone::Rgb)>, // Dark and light color
+    banner: bool,
+    banner_text: String,
+    pipeline: pipeline::Pipeline,
+}
+
+impl ImageSettings {
+    // For quantizing a palette on its own (reference images, animations), which leaves room for the
+    // transparent entry whether the image has transparent pixels or not
+    fn palette_colors(&self) -> i32 {
+        if self.transparent_mode == TransparentMode::PaletteIndex { self.maxcolors - 1 } else { self.maxcolors }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, IntoStaticStr)]
 pub enum BgMessage{
     LoadImage(PathBuf),
+    LoadVideoFrame(PathBuf, f64), // The frame at that many seconds in
+    RenderText(text::TextSettings),
+    EditImage(edit::Edit), // Pixel editing on the processed image
     SaveImage(PathBuf),
-    UpdateImage{
-        no_quantize: bool,
-        grayscale: bool,
-        grayscale_output: bool,
-        reorder_palette: bool,
-        maxcolors: i32,
-        dithering: f32,
-        scaling: bool,
-        scale: u32,
-        multiplier: u8,
-        resize_type: ResizeType,
-        scaler_type: ScalerType,
-    },
+    SaveProject(PathBuf, ImageSettings, project::SendSettings), // Along with the source image, which only the BG thread has
+    OpenProject(PathBuf),
+    LoadFullResolution, // The source again, without scaling it down to the megapixel cap
+    PaletteFromImage(PathBuf, ImageSettings), // Quantize another image and lock its palette
+    UpdateImage(ImageSettings),
+    PreviewImage(ImageSettings), // Quick low resolution UpdateImage, while dragging sliders
+    CompareSettings(ImageSettings),
+    CaptureScreen,
     ClearImage,
-    SendOSC(send_osc::SendOSCOpts),
+    SendOSC(send_osc::SendOSCOpts, Option<SendSnapshot>), // None = the current image
+    ResendOSC, // Send the last image sent again, with the same options
+    EstimateTransfer(send_osc::SendOSCOpts), // Update the transfer estimate for new send settings
+    ColorBudget(ImageSettings), // Advice and color count suggestion for the image UpdateImage just finished
     Quit,
 }
 
 impl BgMessage {
     fn is_update(&self) -> bool {
         match self {
-            BgMessage::UpdateImage{..} => true,
+            BgMessage::UpdateImage(..) => true,
+            BgMessage::PreviewImage(..) => true,
             _ => false
         }
     }
 }
 
+// The last color count suggestion, for the Apply button
+static SUGGESTION: Mutex<Option<color_budget::Suggestion>> = Mutex::new(None);
+
+// Undo/redo history of the UpdateImage settings. Gets pushed to by send_updateimage.
+static SETTINGS_HISTORY: Mutex<history::History<ImageSettings>> = Mutex::new(history::History::new());
+
 fn get_file(dialogtype: dialog::FileDialogType) -> Option<PathBuf> {
     let mut nfc = dialog::NativeFileChooser::new(dialogtype);
 
     match nfc.try_show() {
         Err(err) => {
             let msg = format!("Failed to show NativeFileChooser: {err:?}");
-            eprintln!("{}", msg);
+            error!("{}", msg);
             dialog::alert_default(&msg);
             None
         },
@@ -110,18 +243,22 @@ fn get_file(dialogtype: dialog::FileDialogType) -> Option<PathBuf> {
     }
 }
 
-#[derive(Debug, Clone, Default, PartialEq, VariantNames, EnumString)]
+#[derive(Debug, Clone, Default, PartialEq, VariantNames, EnumString, Serialize, Deserialize)]
 pub enum ScalerType {
     #[default]
     XZBilinear,
+    XZBilinearLinear, // Interpolates in linear light
     ImageCrateNearest,
     ImageCrateTriangle,
     ImageCrateCatmullRom,
     ImageCrateGaussian,
     ImageCrateLanczos3,
+    FastImageResize,
+    AreaAverage,
+    IntegerNearest,
 }
 
-#[derive(Debug, Clone, Default, PartialEq, VariantNames, EnumString)]
+#[derive(Debug, Clone, Default, PartialEq, VariantNames, EnumString, Serialize, Deserialize)]
 pub enum ResizeType {
     #[default]
     ToFill,
@@ -129,17 +266,91 @@ pub enum ResizeType {
     ToFit,
 }
 
+// What to composite images with an alpha channel onto before processing them
+#[derive(Debug, Clone, Default, PartialEq, VariantNames, EnumString, Serialize, Deserialize)]
+pub enum Flatten {
+    #[default]
+    None,
+    Black,
+    White,
+    Gray,
+    Checker,
+}
+
+impl ScalerType {
+    // Whether the scaler only looks at a few source pixels per output pixel no matter how big the
+    // downscale is, and so aliases (moiré etc.) without a blur first
+    fn needs_pre_blur(&self) -> bool {
+        match self {
+            ScalerType::XZBilinear | ScalerType::XZBilinearLinear | ScalerType::ImageCrateNearest => true,
+            _ => false,
+        }
+    }
+}
+
+// How much to blur before downscaling from width x height to nwidth x nheight with the given
+// resize type, so that the blur about matches the area each output pixel covers. 0 for upscales.
+fn pre_blur_sigma(width: u32, height: u32, nwidth: u32, nheight: u32, resize: ResizeType) -> f32 {
+    let (_, _, from_width, from_height, nwidth, nheight) = resize_geometry(width as usize, height as usize, nwidth as usize, nheight as usize, resize);
+    let ratio = (from_width/(nwidth as f32)).max(from_height/(nheight as f32));
+    if ratio <= 1.0 {
+        0.0
+    } else {
+        ratio/2.0
+    }
+}
+
+// Which part of the source (x and y offset, width and height) gets scaled to what size
+// (nwidth x nheight, which ToFit shrinks to keep the aspect ratio) for the hand-rolled scalers
+fn resize_geometry(width: usize, height: usize,
+                   nwidth: usize, nheight: usize,
+                   resize: ResizeType
+) -> (f32, f32, f32, f32, usize, usize) {
+    type F = f32;
+
+    match resize {
+        ResizeType::ToFill => {
+            // Scale so that the whole nwidth x nheight area gets covered, and crop whatever
+            // sticks out evenly from both sides (same as resize_to_fill in the image crate)
+            let cover_scale: F = ((nwidth as F)/(width as F)).max((nheight as F)/(height as F));
+            let from_width: F = ((nwidth as F)/cover_scale).min(width as F);
+            let from_height: F = ((nheight as F)/cover_scale).min(height as F);
+            (((width as F) - from_width)/2.0, ((height as F) - from_height)/2.0,
+             from_width, from_height,
+             nwidth, nheight)
+        }
+        ResizeType::Stretch => (0.0, 0.0, width as F, height as F, nwidth, nheight),
+        ResizeType::ToFit => {
+            if width > height {
+                // Wider than tall
+                let aspect_ratio: F = (width as F)/(height as F);
+                (0.0, 0.0,
+                 width as F, height as F,
+                 nwidth, ((nheight as F)/aspect_ratio).round() as usize)
+            } else {
+                // Taller than wide (or square)
+                let aspect_ratio: F = (height as F)/(width as F);
+                (0.0, 0.0,
+                 width as F, height as F,
+                 ((nwidth as F)/aspect_ratio).round() as usize, nheight)
+            }
+        },
+    }
+}
+
 // Home-cooked bilinear scaling
-// TODO: Gamma-correct version? (convert into linear color-space before scaling, then convert back)
 // This is actually not all that good for scaling down, but it
 // actually often ends up looking kind of retro in a good way, and
 // sometimes sligthly better than just nearest neighbour.
-// In line with that maybe a gamme-correct version wouldn't be looking quite as retro either?
+// With linear set the color channels get converted into linear light before interpolating and back
+// afterwards. Interpolating the sRGB bytes directly makes edges between bright and dark darker than
+// they should be.
 // TODO: halfpel (or even smaller?) movements to allow tweaking the resulting pixelation to achieve pleasing results with mouths and the likes?
 fn scale_image_bilinear(src: &[u8],
                         width: u32, height: u32,
                         nwidth: u32, nheight: u32,
-                        resize: ResizeType
+                        resize: ResizeType,
+                        linear: bool,
 ) -> Result<(Vec<u8>, u32, u32), Box<dyn Error>> {
     type F = f32;
 
@@ -147,44 +358,34 @@ fn scale_image_bilinear(src: &[u8],
     let height = height as usize;
     let nwidth = nwidth as usize;
     let nheight = nheight as usize;
-    println!("{}: width={width}, height={height}, nwidth={nwidth}, nheight={nheight}", function!());
+    debug!("{}: width={width}, height={height}, nwidth={nwidth}, nheight={nheight}, linear={linear}", function!());
 
     assert!(src.len() == width * height * 4); // RGBA format assumed
 
-    let (src_x_offset, src_y_offset, from_width, from_height, nwidth, nheight): (F, F, usize, usize, usize, usize) = match resize {
-        ResizeType::ToFill => {
-            if width > height { // Wider than all
-                (((width - height) as F)/2.0, 0.0,
-                 height, height,
-                 nwidth, nheight)
-            } else { // Taller than wide (or square)
-                (0.0, ((height - width) as F)/2.0,
-                 width, width,
-                 nwidth, nheight)
-            }
+    let (src_x_offset, src_y_offset, from_width, from_height, nwidth, nheight) = resize_geometry(width, height, nwidth, nheight, resize);
+
+    // Linear light, but still in the 0..255 range so that the rest doesn't need to care
+    let to_linear: Vec<F> = (0..=255u8).map(|c| colorspace::srgb_to_linear(c)*255.0).collect();
+    let decode = |px: [u8; 4]| -> [F; 4] {
+        if linear {
+            [to_linear[px[0] as usize], to_linear[px[1] as usize], to_linear[px[2] as usize], px[3] as F]
+        } else {
+            px.map(|x| x as F)
+        }
+    };
+    let encode = |px: [F; 4]| -> [u8; 4] {
+        if linear {
+            let [r, g, b, a] = px;
+            [colorspace::linear_to_srgb(r/255.0), colorspace::linear_to_srgb(g/255.0), colorspace::linear_to_srgb(b/255.0), a as u8]
+        } else {
+            px.map(|x| x as u8)
         }
-        ResizeType::Stretch => (0.0, 0.0, width, height, nwidth, nheight),
-        ResizeType::ToFit => {
-            if width > height {
-                // Wider than tall
-                let aspect_ratio: F = (width as F)/(height as F);
-                (0.0, 0.0,
-                 width, height,
-                 nwidth, ((nheight as F)/aspect_ratio).round() as usize)
-            } else {
-                // Taller than wide (or square)
-                let aspect_ratio: F = (height as F)/(width as F);
-                (0.0, 0.0,
-                 width, height,
-                 ((nwidth as F)/aspect_ratio).round() as usize, nheight)
-            }
-        },
     };
 
-    println!("{}: src_x_offset={src_x_offset:.2}, src_y_offset={src_y_offset:.2} from_width={from_width}, from_height={from_height}, nwidth={nwidth}, nheight={nheight}", function!());
+    debug!("{}: src_x_offset={src_x_offset:.2}, src_y_offset={src_y_offset:.2} from_width={from_width}, from_height={from_height}, nwidth={nwidth}, nheight={nheight}", function!());
 
-    let x_scale: F = (from_width as F)/(nwidth as F);
-    let y_scale: F = (from_height as F)/(nheight as F);
+    let x_scale: F = from_width/(nwidth as F);
+    let y_scale: F = from_height/(nheight as F);
 
     let mut buffer: Vec<u8> = vec![0u8; nwidth * nheight * 4];
     // Parallelized using rayon
@@ -215,10 +416,10 @@ fn scale_image_bilinear(src: &[u8],
         let iur: Px = src[idx_src_ur..idx_src_ur+4].try_into().expect("ur: Slices should be 4 long by definition");
         let idl: Px = src[idx_src_dl..idx_src_dl+4].try_into().expect("dl: Slices should be 4 long by definition");
         let idr: Px = src[idx_src_dr..idx_src_dr+4].try_into().expect("dr: Slices should be 4 long by definition");
-        let ul: FPx = iul.map(|x| x as F);
-        let ur: FPx = iur.map(|x| x as F);
-        let dl: FPx = idl.map(|x| x as F);
-        let dr: FPx = idr.map(|x| x as F);
+        let ul: FPx = decode(iul);
+        let ur: FPx = decode(iur);
+        let dl: FPx = decode(idl);
+        let dr: FPx = decode(idr);
 
         // interpolate along x
         let diff_x: F = src_ur.0 - src_x;
@@ -249,13 +450,115 @@ fn scale_image_bilinear(src: &[u8],
             interp_u[3]*diff_y + interp_d[3]*(1.0 - diff_y),
         ];
 
-        let result: Px = result.map(|x| x as u8);
+        let result: Px = encode(result);
         pixel.copy_from_slice(&result);
     });
 
     Ok((buffer, nwidth.try_into()?, nheight.try_into()?))
 }
 
+// Box filter: every output pixel is the average of the source pixels it covers (weighted by how
+// much of each one is covered). Meant for big downscales, like shrinking a photo to 128x128, where
+// bilinear only ever looks at a few sparse source pixels and throws away most of the detail.
+fn scale_image_area(src: &[u8],
+                    width: u32, height: u32,
+                    nwidth: u32, nheight: u32,
+                    resize: ResizeType
+) -> Result<(Vec<u8>, u32, u32), Box<dyn Error>> {
+    type F = f32;
+
+    let width = width as usize;
+    let height = height as usize;
+    let nwidth = nwidth as usize;
+    let nheight = nheight as usize;
+    debug!("{}: width={width}, height={height}, nwidth={nwidth}, nheight={nheight}", function!());
+
+    assert!(src.len() == width * height * 4); // RGBA format assumed
+
+    let (src_x_offset, src_y_offset, from_width, from_height, nwidth, nheight) = resize_geometry(width, height, nwidth, nheight, resize);
+
+    let x_scale: F = from_width/(nwidth as F);
+    let y_scale: F = from_height/(nheight as F);
+
+    // Source pixels covered by the span [start, start + len), along with how much of each is covered
+    fn coverage(start: F, len: F, size: usize) -> impl Iterator<Item = (usize, F)> {
+        let end = start + len;
+        let first = (start.floor() as usize).min(size - 1);
+        let last = ((end.ceil() as usize).max(first + 1)).min(size);
+        (first..last).map(move |i| {
+            let weight = end.min((i + 1) as F) - start.max(i as F);
+            (i, weight.max(0.0))
+        })
+    }
+
+    let mut buffer: Vec<u8> = vec![0u8; nwidth * nheight * 4];
+    // Parallelized using rayon, one output row at a time
+    buffer.par_chunks_exact_mut(nwidth * 4).enumerate().for_each(|(dst_y, row)| {
+        let src_y = src_y_offset + (dst_y as F)*y_scale;
+        for (dst_x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let src_x = src_x_offset + (dst_x as F)*x_scale;
+
+            let mut sum: [F; 4] = [0.0; 4];
+            let mut total_weight: F = 0.0;
+            for (y, weight_y) in coverage(src_y, y_scale, height) {
+                for (x, weight_x) in coverage(src_x, x_scale, width) {
+                    let weight = weight_x*weight_y;
+                    let idx = (x + width*y)*4;
+                    for (s, &v) in zip(&mut sum, &src[idx..idx + 4]) {
+                        *s += (v as F)*weight;
+                    }
+                    total_weight += weight;
+                }
+            }
+
+            if total_weight > 0.0 {
+                let result: [u8; 4] = sum.map(|x| (x/total_weight).round().clamp(0.0, 255.0) as u8);
+                pixel.copy_from_slice(&result);
+            }
+        }
+    });
+
+    Ok((buffer, nwidth.try_into()?, nheight.try_into()?))
+}
+
+// Pixel-perfect scaling for pixel art: only ever scales by a whole factor, up by repeating pixels or
+// down by picking every n:th one, using the largest factor that fits. Always keeps the aspect ratio
+// whatever the resize type is, and whatever is left over of the target gets padded later.
+fn scale_image_integer(src: &[u8],
+                       width: u32, height: u32,
+                       nwidth: u32, nheight: u32,
+) -> Result<(Vec<u8>, u32, u32), Box<dyn Error>> {
+    let width = width as usize;
+    let height = height as usize;
+    let nwidth = nwidth as usize;
+    let nheight = nheight as usize;
+
+    assert!(src.len() == width * height * 4); // RGBA format assumed
+
+    // src pixel = (dst pixel * num + offset) / den
+    let (num, den, offset, owidth, oheight) = if width <= nwidth && height <= nheight {
+        let factor = (nwidth / width).min(nheight / height);
+        (1, factor, 0, width * factor, height * factor)
+    } else {
+        let factor = width.div_ceil(nwidth).max(height.div_ceil(nheight));
+        // Sample from the middle of each factor x factor block
+        (factor, 1, factor / 2, (width / factor).max(1), (height / factor).max(1))
+    };
+    debug!("{}: width={width}, height={height} -> {owidth}x{oheight} ({num}/{den})", function!());
+
+    let mut buffer: Vec<u8> = vec![0u8; owidth * oheight * 4];
+    buffer.par_chunks_exact_mut(owidth * 4).enumerate().for_each(|(dst_y, row)| {
+        let src_y = ((dst_y * num + offset) / den).min(height - 1);
+        for (dst_x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let src_x = ((dst_x * num + offset) / den).min(width - 1);
+            let idx = (src_x + width*src_y)*4;
+            pixel.copy_from_slice(&src[idx..idx + 4]);
+        }
+    });
+
+    Ok((buffer, owidth.try_into()?, oheight.try_into()?))
+}
+
 // Image scaling using scaling from the image crate
 fn scale_image_imagecrate(
     bytes: Vec<u8>,
@@ -278,6 +581,41 @@ fn scale_image_imagecrate(
     Ok((newimg.into_raw(), w, h))
 }
 
+// SIMD-accelerated scaling using fast_image_resize. Bilinear like XZBilinear, but a lot faster for
+// large sources since it's vectorized (and done as a proper convolution rather than sampling).
+fn scale_image_fast(
+    bytes: Vec<u8>,
+    width: u32, height: u32,
+    nwidth: u32, nheight: u32,
+    resize: ResizeType,
+) -> Result<(Vec<u8>, u32, u32), Box<dyn Error>> {
+    use fast_image_resize as fr;
+
+    assert!(bytes.len() == (width * height * 4) as usize); // RGBA format assumed
+
+    let mut options = fr::ResizeOptions::new()
+        .resize_alg(fr::ResizeAlg::Convolution(fr::FilterType::Bilinear));
+    let (nwidth, nheight) = match resize {
+        ResizeType::ToFill => {
+            // Crops the middle part out to match the aspect ratio of the destination
+            options = options.fit_into_destination(Some((0.5, 0.5)));
+            (nwidth, nheight)
+        },
+        ResizeType::Stretch => (nwidth, nheight),
+        ResizeType::ToFit => {
+            let ratio = ((nwidth as f64)/(width as f64)).min((nheight as f64)/(height as f64));
+            ((((width as f64)*ratio).round() as u32).clamp(1, nwidth),
+             (((height as f64)*ratio).round() as u32).clamp(1, nheight))
+        },
+    };
+
+    let src = fr::images::Image::from_vec_u8(width, height, bytes, fr::PixelType::U8x4)?;
+    let mut dst = fr::images::Image::new(nwidth, nheight, fr::PixelType::U8x4);
+    fr::Resizer::new().resize(&src, &mut dst, &options)?;
+
+    Ok((dst.into_vec(), nwidth, nheight))
+}
+
 fn scale_image(
     bytes: Vec<u8>,
     width: u32, height: u32,
@@ -286,12 +624,16 @@ fn scale_image(
     scaler_type: ScalerType,
 ) -> Result<(Vec<u8>, u32, u32), Box<dyn Error>> {
     match scaler_type {
-        ScalerType::XZBilinear           => scale_image_bilinear(&bytes, width, height, nwidth, nheight, resize),
+        ScalerType::XZBilinear           => scale_image_bilinear(&bytes, width, height, nwidth, nheight, resize, false),
+        ScalerType::XZBilinearLinear     => scale_image_bilinear(&bytes, width, height, nwidth, nheight, resize, true),
         ScalerType::ImageCrateNearest    => scale_image_imagecrate(bytes, width, height, nwidth, nheight, resize, imageops::FilterType::Nearest),
         ScalerType::ImageCrateTriangle   => scale_image_imagecrate(bytes, width, height, nwidth, nheight, resize, imageops::FilterType::Triangle),
         ScalerType::ImageCrateCatmullRom => scale_image_imagecrate(bytes, width, height, nwidth, nheight, resize, imageops::FilterType::CatmullRom),
         ScalerType::ImageCrateGaussian   => scale_image_imagecrate(bytes, width, height, nwidth, nheight, resize, imageops::FilterType::Gaussian),
         ScalerType::ImageCrateLanczos3   => scale_image_imagecrate(bytes, width, height, nwidth, nheight, resize, imageops::FilterType::Lanczos3),
+        ScalerType::FastImageResize      => scale_image_fast(bytes, width, height, nwidth, nheight, resize),
+        ScalerType::AreaAverage          => scale_image_area(&bytes, width, height, nwidth, nheight, resize),
+        ScalerType::IntegerNearest       => scale_image_integer(&bytes, width, height, nwidth, nheight),
     }
 }
 
@@ -313,6 +655,38 @@ fn rgbaimage_to_bytes(image: &image::RgbaImage, grayscale: bool) -> (Vec<u8>, u3
     (newimg.into_raw(), w, h)
 }
 
+// Composite an RGBA buffer onto a background so that the quantizer sees the colors as they were
+// intended to look, instead of whatever junk is hiding in the (semi-)transparent pixels.
+fn flatten_alpha(bytes: &mut [u8], width: u32, height: u32, flatten: &Flatten) {
+    assert!((width * height * 4) as usize == bytes.len());
+
+    let solid = |c: u8| move |_x: u32, _y: u32| -> [u8; 3] { [c, c, c] };
+    let background: Box<dyn Fn(u32, u32) -> [u8; 3]> = match flatten {
+        Flatten::None => return,
+        Flatten::Black => Box::new(solid(0)),
+        Flatten::White => Box::new(solid(255)),
+        Flatten::Gray => Box::new(solid(128)),
+        Flatten::Checker => {
+            // Aim for a 16x16 grid of squares no matter what the source resolution is
+            let size = (width.max(height) / 16).max(1);
+            Box::new(move |x, y| if ((x / size) + (y / size)) % 2 == 0 { [204, 204, 204] } else { [153, 153, 153] })
+        },
+    };
+
+    for (i, px) in bytes.chunks_exact_mut(4).enumerate() {
+        let a = px[3] as u32;
+        if a == 255 {
+            continue;
+        }
+        let (x, y) = ((i as u32) % width, (i as u32) / width);
+        let bg = background(x, y);
+        for c in 0..3 {
+            px[c] = (((px[c] as u32) * a + (bg[c] as u32) * (255 - a) + 127) / 255) as u8;
+        }
+        px[3] = 255;
+    }
+}
+
 #[allow(dead_code)]
 fn sharedimage_to_bytes(image : &fltk::image::SharedImage, grayscale : bool) -> Result<(Vec<u8>, u32, u32), Box<dyn Error>> {
     // let bytes : Vec<u8> = image.to_rgb_image()?.convert(ColorDepth::L8)?.convert(ColorDepth::Rgba8)?.to_rgb_data();
@@ -323,7 +697,7 @@ fn sharedimage_to_bytes(image : &fltk::image::SharedImage, grayscale : bool) ->
     }
 
     let bytes: Vec<u8> = rgbimage.convert(ColorDepth::Rgba8)?.to_rgb_data();
-    println!("bytes.len(): {}", bytes.len());
+    debug!("bytes.len(): {}", bytes.len());
     let width: u32 = rgbimage.data_w().try_into()?;
     let height: u32 = rgbimage.data_h().try_into()?;
 
@@ -334,25 +708,27 @@ fn sharedimage_to_bytes(image : &fltk::image::SharedImage, grayscale : bool) ->
 // grayscale by reordering the pallette, which means that the indexes
 // should be able to be used without the palette as a sort-of
 // grayscale image
-fn reorder_palette_by_brightness(indexes : &[u8], palette : &quantizr::Palette) -> (Vec<u8>, Vec<quantizr::Color>)
+fn reorder_palette_by_brightness(indexes : &[u8], palette : &[quantizr::Color]) -> (Vec<u8>, Vec<quantizr::Color>)
 {
-    let mut permutation : Vec<usize> = (0..(palette.count as usize)).collect();
+    let mut permutation : Vec<usize> = (0..palette.len()).collect();
     permutation.sort_by_key(|&i| {
-        let c = palette.entries[i];
+        let c = palette[i];
         let (r,g,b) = (c.r as i32, c.g as i32, c.b as i32);
         r + g + b
     });
 
     let new_palette : Vec<quantizr::Color> =
         permutation.iter()
-        .map(|&i| palette.entries[i])
+        .map(|&i| palette[i])
         .collect();
 
-    // Trying out fancy rayon parallel iterators
-    // TODO: use a HashMap? or just an array that gets the reverse mapping
-    let new_indexes : Vec<u8> = indexes.par_iter().map(
-        |ic| permutation.iter().position(|&r| r == *ic as usize).unwrap_or_default() as u8
-    ).collect();
+    // Reverse mapping from old index to new index, so remapping is a single lookup per pixel
+    let mut lut : [u8; 256] = [0; 256];
+    for (new_index, &old_index) in permutation.iter().enumerate() {
+        lut[old_index] = new_index as u8;
+    }
+
+    let new_indexes : Vec<u8> = indexes.par_iter().map(|&ic| lut[ic as usize]).collect();
 
     (new_indexes, new_palette)
 }
@@ -362,33 +738,79 @@ fn quantize_image(bytes : &[u8],
                   width : u32, height : u32,
                   max_colors : i32,
                   dithering_level : f32,
-                  reorder_palette : bool) -> Result<(Vec<u8>, Vec<quantizr::Color>), Box<dyn Error>> {
+                  reorder_palette : bool,
+                  quantizer_type : &QuantizerType,
+                  quantizr_options : &quantizer::QuantizrOptions,
+                  dither_mask : &dither_mask::DitherMask,
+                  forced_colors : &[palette::Rgb],
+                  locked_palette : Option<&[palette::Rgba]>,
+                  color_space : &ColorSpace) -> Result<(Vec<u8>, Vec<quantizr::Color>), Box<dyn Error>> {
 
     // Need to make sure that input buffer is matching width and
     // height params for an RGBA buffer (4 bytes per pixel)
     assert!((width * height * 4) as usize == bytes.len());
 
-    let qimage = quantizr::Image::new(bytes, width as usize, height as usize)?;
-    let mut qopts = quantizr::Options::default();
-    qopts.set_max_colors(max_colors)?;
-
-    let mut result = quantizr::QuantizeResult::quantize(&qimage, &qopts);
-    result.set_dithering_level(dithering_level)?;
-
-    let mut indexes = vec![0u8; (width*height) as usize];
-    result.remap_image(&qimage, indexes.as_mut_slice())?;
+    // When the colors already fit there's nothing to quantize, and going through the quantizer
+    // anyway would only shift them around a bit. The forced colors have to fit in as well, used or not.
+    let exact = quantizer::exact_palette(bytes, max_colors).and_then(|(indexes, mut palette)| {
+        palette.extend(palette::missing(forced_colors, &palette));
+        (palette.len() <= max_colors as usize).then_some((indexes, palette))
+    });
+    let (indexes, palette) = match (locked_palette, exact) {
+        // Locked, so no new palette, just remapping to the old one. Still no more than max_colors of
+        // it, or there'd be no room for the transparent entry (max_colors is one less then).
+        (Some(locked), _) => {
+            let mut palette = palette::from_rgba(locked);
+            if palette.len() > max_colors as usize {
+                warn!("Only using the first {max_colors} of the {} colors in the locked palette", palette.len());
+                palette.truncate(max_colors as usize);
+            }
+            let encoded = color_space.encode(bytes);
+            let levels = dither_mask.levels(bytes, width, height, dithering_level)
+                .unwrap_or_else(|| vec![dithering_level; (width*height) as usize]);
+            (quantizer::remap_masked(&encoded, width, height, &color_space.encode_palette(&palette), &levels), palette)
+        },
+        (None, Some(exact)) => {
+            debug!("Lossless: {} colors, no quantizing needed", exact.1.len());
+            exact
+        },
+        (None, None) => {
+            let encoded = color_space.encode(bytes);
+            // Entries reserved for the forced colors, though the quantizer always gets at least a couple
+            let reserved = forced_colors.len().min((max_colors as usize).saturating_sub(2)) as i32;
+            let q = quantizer_type.quantizer(quantizr_options);
+            let (indexes, palette) = if (width as u64)*(height as u64) >= quantizer::TILED_MIN_PIXELS {
+                quantizer::quantize_tiled(q.as_ref(), &encoded, width, height, max_colors - reserved, dithering_level)?
+            } else {
+                q.quantize(&encoded, width, height, max_colors - reserved, dithering_level)?
+            };
+            let levels = dither_mask.levels(bytes, width, height, dithering_level);
+            if forced_colors.is_empty() {
+                // Remapped again by us with the mask, the quantizers only do one dithering level for everything
+                let indexes = match levels {
+                    Some(levels) => quantizer::remap_masked(&encoded, width, height, &palette, &levels),
+                    None => indexes,
+                };
+                (indexes, color_space.decode_palette(&palette))
+            } else {
+                // The quantizer's indexes don't know about the forced colors, so the remapping is ours
+                let palette = palette::with_forced(forced_colors, &color_space.decode_palette(&palette), max_colors as usize);
+                let encoded_palette = color_space.encode_palette(&palette);
+                let levels = levels.unwrap_or_else(|| vec![dithering_level; (width*height) as usize]);
+                (quantizer::remap_masked(&encoded, width, height, &encoded_palette, &levels), palette)
+            }
+        },
+    };
     assert!((width * height) as usize == indexes.len());
 
-    let palette = result.get_palette();
-
     let result: (Vec<u8>, Vec<quantizr::Color>) = if reorder_palette {
         time_it!(
             "reorder_palette_by_brightness",
-            let result = reorder_palette_by_brightness(&indexes, palette);
+            let result = reorder_palette_by_brightness(&indexes, &palette);
         );
         result
     } else {
-        (indexes, palette.entries[0..(palette.count as usize)].to_vec())
+        (indexes, palette)
     };
 
     Ok(result)
@@ -404,7 +826,7 @@ fn find_pad_value(bytes: &[u8],
     let width: usize = width as usize;
     let height: usize = height as usize;
 
-    println!("{}: bytes.len()={} width={width}, height={height}", function!(), bytes.len());
+    debug!("{}: bytes.len()={} width={width}, height={height}", function!(), bytes.len());
 
     assert!(width != 0);
     assert!(height != 0);
@@ -455,7 +877,7 @@ fn pad_image(bytes: Vec<u8>,
     let nwidth: usize = nwidth as usize;
     let nheight: usize = nheight as usize;
 
-    println!("{}: bytes.len()={} width={width}, height={height}, nwidth={nwidth}, nheight={nheight}", function!(), bytes.len());
+    debug!("{}: bytes.len()={} width={width}, height={height}, nwidth={nwidth}, nheight={nheight}", function!(), bytes.len());
 
     assert!(width * height == bytes.len(), "width={width} * height={height} != bytes.len()={}", bytes.len()); // 8 bpp indexed image input
     assert!(nwidth >= width);
@@ -509,33 +931,45 @@ fn rgbaimage_to_fltk_rgbimage(image: &image::RgbaImage) -> Result<fltk::image::R
 }
 
 // Turn the quantized thing back into RGB for display
-fn quantized_image_to_fltk_rgbimage(
+fn quantized_image_to_rgba(
     indexes: &[u8],
     palette: &[quantizr::Color],
     width: u32,
     height: u32,
     grayscale_output: bool
-) -> Result<fltk::image::RgbImage, Box<dyn Error>> {
+) -> Vec<u8> {
     assert!((width * height) as usize == indexes.len());
 
+    // Parallelized using rayon
     let mut fb: Vec<u8> = vec![0u8; indexes.len() * 4];
     if !grayscale_output {
-        for (&index, pixel) in zip(indexes, fb.chunks_exact_mut(4)) {
+        indexes.par_iter().zip(fb.par_chunks_exact_mut(4)).for_each(|(&index, pixel)| {
             let c : quantizr::Color = palette[index as usize];
             pixel.copy_from_slice(&[c.r, c.g, c.b, c.a]);
-        }
+        });
     } else {
-        for (&index, pixel) in zip(indexes, fb.chunks_exact_mut(4)) {
-            let max: f64 = (palette.len() - 1) as f64;
+        let max: f64 = (palette.len() - 1) as f64;
+        indexes.par_iter().zip(fb.par_chunks_exact_mut(4)).for_each(|(&index, pixel)| {
             let index: u8 = (index as f64*(255.0/max)).round() as u8;
             pixel.copy_from_slice(&[index, index, index, 255]);
-        }
+        });
     }
+    fb
+}
 
+fn quantized_image_to_fltk_rgbimage(
+    indexes: &[u8],
+    palette: &[quantizr::Color],
+    width: u32,
+    height: u32,
+    grayscale_output: bool
+) -> Result<fltk::image::RgbImage, Box<dyn Error>> {
+    let fb = quantized_image_to_rgba(indexes, palette, width, height, grayscale_output);
     Ok(fltk::image::RgbImage::new(&fb, width as i32, height as i32, ColorDepth::Rgba8)?)
 }
 
 fn palette_to_fltk_rgbimage(palette: &[quantizr::Color], grayscale_output: bool) -> Result<fltk::image::RgbImage, Box<dyn Error>> {
+    let palette = &colorblind::current().simulate(palette);
     let mut fb: Vec<u8> = vec![0u8; palette.len() * 4];
     let width: i32 = 1;
     let height: i32 = palette.len().try_into()?;
@@ -556,6 +990,64 @@ fn palette_to_fltk_rgbimage(palette: &[quantizr::Color], grayscale_output: bool)
     Ok(fltk::image::RgbImage::new(&fb, width, height, ColorDepth::Rgba8)?)
 }
 
+fn set_histogram_frame(id: &str, hist: Option<&histogram::Histogram>) -> Result<(), String> {
+    let mut frame: Frame = app::widget_from_id(id).ok_or("widget_from_id fail")?;
+    match hist {
+        Some(hist) => {
+            let image = hist.to_fltk_rgbimage(128)
+                .map_err(|err| format!("Couldn't generate histogram RgbImage: {err:?}"))?;
+            frame.set_image_scaled(Some(image));
+        },
+        None => frame.set_image(None::<fltk::image::RgbImage>),
+    }
+    frame.changed();
+    frame.redraw();
+    Ok(())
+}
+
+fn set_info_text(text: &str) -> Result<(), String> {
+    let mut info_frame: Frame = app::widget_from_id("info_frame").ok_or("widget_from_id fail")?;
+    info_frame.set_label(text);
+    info_frame.redraw();
+    Ok(())
+}
+
+fn set_suggestion(suggestion: Option<color_budget::Suggestion>) -> Result<(), String> {
+    let mut suggestion_frame: Frame = app::widget_from_id("suggestion_frame").ok_or("widget_from_id fail")?;
+    let mut suggestion_apply_btn: Button = app::widget_from_id("suggestion_apply_btn").ok_or("widget_from_id fail")?;
+    match &suggestion {
+        Some(suggestion) => {
+            suggestion_frame.set_label(&suggestion.description());
+            suggestion_frame.set_tooltip(&format!("Error (RMSE) {:.1} at that many colors", suggestion.rmse));
+            suggestion_apply_btn.activate();
+        },
+        None => {
+            suggestion_frame.set_label("");
+            suggestion_apply_btn.deactivate();
+        },
+    }
+    suggestion_frame.redraw();
+    *SUGGESTION.lock().map_err(|err| format!("Couldn't lock suggestion: {err}"))? = suggestion;
+    Ok(())
+}
+
+fn set_transfer_estimate(img: Option<&ProcessedImage>, options: Option<&send_osc::SendOSCOpts>) -> Result<(), String> {
+    let mut frame: Frame = app::widget_from_id("transfer_estimate_frame").ok_or("widget_from_id fail")?;
+    let text = match (img, options) {
+        (Some(img), Some(options)) => {
+            match send_osc::estimate_transfer(&img.indexes, &img.palette, img.width, options) {
+                Ok(estimate) => estimate.description(),
+                Err(err) => format!("Can't estimate transfer: {err}"),
+            }
+        },
+        _ => String::new(),
+    };
+    frame.set_label(&text);
+    frame.redraw();
+    fltk::app::awake();
+    Ok(())
+}
+
 fn enable_save_and_send_osc_button(active: bool) -> Result<(), String> {
     let mut savebtn: Button = app::widget_from_id("savebtn").ok_or("widget_from_id fail")?;
     let mut send_osc_btn: Button = app::widget_from_id("send_osc_btn").ok_or("widget_from_id fail")?;
@@ -570,365 +1062,1584 @@ fn enable_save_and_send_osc_button(active: bool) -> Result<(), String> {
     Ok(())
 }
 
-fn start_background_process(appmsg_sender: &mpsc::Sender<AppMessage>) -> (thread::JoinHandle<()>, mq::MessageQueueSender<BgMessage>) {
-    let (sender, receiver) = mq::mq::<BgMessage>();
+// Called from whichever thread changed the state
+fn show_send_state(state: send_job::JobState) {
+    if let Some(mut frame) = app::widget_from_id::<Frame>("send_state_frame") {
+        frame.set_label(i18n::tr(state.description()));
+        frame.redraw();
+        fltk::app::awake();
+    }
+}
 
-    let appmsg = appmsg_sender.clone();
-    let sender_return = sender.clone();
+#[allow(dead_code)]
+#[derive(Clone)]
+struct ProcessedImage {
+    indexes: Vec<u8>,
+    palette: Vec<quantizr::Color>,
+    width: u32,
+    height: u32,
+    maxcolors: i32,
+    grayscale_output: bool,
+    display_multiplier: u8,
+    content: Option<(u32, u32, u32, u32)>, // x, y, w, h of the part that isn't padding, if padded
+    settings: ImageSettings, // What it was made with, goes in the saved PNG
+}
 
-    let joinhandle: thread::JoinHandle<()> = thread::spawn(move || -> () {
-        #[allow(dead_code)]
-        struct ProcessedImage {
-            indexes: Vec<u8>,
-            palette: Vec<quantizr::Color>,
-            width: u32,
-            height: u32,
-            maxcolors: i32,
-            grayscale_output: bool,
+impl ProcessedImage {
+    // Turn it back into RGB, one pixel per pixel
+    fn to_fltk_rgbimage_1to1(&self) -> Result<fltk::image::RgbImage, Box<dyn Error>> {
+        quantized_image_to_fltk_rgbimage(
+            &self.indexes, &colorblind::current().simulate(&self.palette),
+            self.width, self.height,
+            self.grayscale_output,
+        )
+    }
+
+    // Turn it back into RGB for display, scaled up by the display multiplier
+    fn to_fltk_rgbimage(&self) -> Result<fltk::image::RgbImage, Box<dyn Error>> {
+        if crt::enabled() && self.display_multiplier >= 2 {
+            let fb = quantized_image_to_rgba(
+                &self.indexes, &colorblind::current().simulate(&self.palette),
+                self.width, self.height,
+                self.grayscale_output,
+            );
+            let multiplier = self.display_multiplier as u32;
+            let crt = crt::render(&fb, self.width, self.height, multiplier);
+            return Ok(fltk::image::RgbImage::new(&crt, (self.width*multiplier) as i32, (self.height*multiplier) as i32, ColorDepth::Rgba8)?);
         }
+        let mut rgbimage = self.to_fltk_rgbimage_1to1()?;
+        rgbimage.scale((self.width as i32) * (self.display_multiplier as i32),
+                       (self.height as i32) * (self.display_multiplier as i32),
+                       true, true); // Display pixelly image larger
+        Ok(rgbimage)
+    }
+}
 
-        let mut rgbaimage: Option<image::RgbaImage> = None;
-        let mut processed_image: Option<ProcessedImage> = None;
+// The image a send is about, taken when the send gets asked for. The confirmation and the warnings
+// come back with it, so what goes out is what the user saw, whatever got processed in the meantime.
+#[derive(Clone)]
+pub struct SendSnapshot(Arc<ProcessedImage>);
 
-        loop {
-            let recvres = receiver.recv();
-            let Ok(msg) = recvres else {
-                let s = format!("Error receiving from mq::MessageQueueReceiver: {}", recvres.unwrap_err());
-                error_alert(&appmsg, s);
-                continue;
-            };
+impl SendSnapshot {
+    fn new(img: &ProcessedImage) -> Self {
+        SendSnapshot(Arc::new(img.clone()))
+    }
+}
 
-            match msg {
-                BgMessage::Quit => {
-                    break;
-                },
-                BgMessage::LoadImage(path) => {
-                    match || -> Result<(), String> {
-                        let image = image::ImageReader::open(&path)
-                            .map_err(|err| format!("Couldn't open image {path:?}: {err}"))?
-                            .with_guessed_format()
-                            .map_err(|err| format!("Error when guessing format: {err}"))?
-                            .decode()
-                            .map_err(|err| format!("Failed to decode image {path:?}: {err}"))?;
-
-                        rgbaimage = Some(image.to_rgba8());
-                        println!("Loaded image {path:?}");
-
-                        let pathstr = path.to_string_lossy();
-                        {
-                            let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
-                            frame.set_label(&pathstr);
-                            frame.changed();
-                            frame.redraw();
-                        }
+impl PartialEq for SendSnapshot {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
 
-                        appmsg.send(AppMessage::SetTitle(pathstr.to_string())).
-                            map_err(|err| format!("Send error: {err}"))?;
-                        fltk::app::awake();
+impl std::fmt::Debug for SendSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SendSnapshot({}x{}, {} colors)", self.0.width, self.0.height, self.0.palette.len())
+    }
+}
 
-                        send_updateimage(&appmsg, &sender);
+// The settings that the scaled (but not yet quantized) image depends on
+#[derive(Debug, Clone, PartialEq)]
+struct ScaleKey {
+    grayscale: bool,
+    flatten: Flatten,
+    alpha_threshold: u8,
+    transparent_mode: TransparentMode,
+    scaling: bool,
+    scale: u32,
+    resize_type: ResizeType,
+    scaler_type: ScalerType,
+    pre_blur: bool,
+    sharpen: f32,
+    edge_darken: f32,
+    posterize: u32,
+    levels: filters::Levels,
+    invert: bool,
+    swap_rb: bool,
+    isolate_channel: filters::Channel,
+}
 
-                        println!("Finished LoadImage for {path:?}");
-                        Ok(())
-                    }() {
-                        Ok(()) => (),
-                        Err(errmsg) => {
-                            error_alert(&appmsg, format!("LoadImage fail:\n{errmsg}"));
-                            print_err(sender.send(BgMessage::ClearImage));
-                        }
-                    };
-                },
-                BgMessage::SaveImage(path) => {
-                    match || -> Result<(), String> {
-                        let path = path.with_extension("png");
+// The settings that the quantized (but not yet padded) image depends on
+#[derive(Debug, Clone, PartialEq)]
+struct QuantizeKey {
+    scale: ScaleKey,
+    maxcolors: i32,
+    dithering: f32,
+    reorder_palette: bool,
+    quantizer_type: QuantizerType,
+    quantizr: quantizer::QuantizrOptions,
+    dither_mask: dither_mask::DitherMask,
+    forced_colors: Vec<palette::Rgb>,
+    locked_palette: Option<Vec<palette::Rgba>>,
+    color_space: ColorSpace,
+}
 
-                        let img = processed_image.as_ref()
-                            .ok_or("No indexes or palette data")?;
+impl ScaleKey {
+    fn new(settings: &ImageSettings) -> Self {
+        ScaleKey {
+            // These happen after quantizing instead when the pipeline says so (see process_image)
+            grayscale: settings.grayscale && settings.pipeline.before_quantize(pipeline::Stage::Grayscale),
+            flatten: settings.flatten.clone(),
+            alpha_threshold: settings.alpha_threshold,
+            transparent_mode: settings.transparent_mode.clone(),
+            scaling: settings.scaling && settings.pipeline.before_quantize(pipeline::Stage::Scale),
+            scale: settings.scale,
+            resize_type: settings.resize_type.clone(),
+            scaler_type: settings.scaler_type.clone(),
+            pre_blur: settings.pre_blur,
+            sharpen: settings.sharpen,
+            edge_darken: settings.edge_darken,
+            posterize: settings.posterize,
+            levels: settings.levels,
+            invert: settings.invert,
+            swap_rb: settings.swap_rb,
+            isolate_channel: settings.isolate_channel,
+        }
+    }
+}
 
-                        let w = img.width.try_into().map_err(|err| format!("Trying to save zero width image: {err}"))?;
-                        let h = img.height.try_into().map_err(|err| format!("Trying to save zero height image: {err}"))?;
+impl QuantizeKey {
+    fn new(settings: &ImageSettings) -> Self {
+        QuantizeKey {
+            scale: ScaleKey::new(settings),
+            maxcolors: settings.maxcolors,
+            dithering: settings.dithering,
+            reorder_palette: settings.reorder_palette,
+            quantizer_type: settings.quantizer_type.clone(),
+            quantizr: settings.quantizr,
+            dither_mask: settings.dither_mask.clone(),
+            forced_colors: settings.forced_colors.clone(),
+            locked_palette: settings.locked_palette.clone(),
+            color_space: settings.color_space.clone(),
+        }
+    }
+}
 
-                        save_png::save_png(
-                            &path, w, h, &img.indexes, &img.palette,
-                            match img.grayscale_output {
-                                true  => save_png::ColorType::Grayscale,
-                                false => save_png::ColorType::Indexed,
-                            },
-                        ).map_err(|err| format!("Couldn't save image to {path:?}: {err}"))?;
+struct ScaledStage {
+    key: ScaleKey,
+    bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+    transparent: Option<Vec<bool>>, // Pixels to map according to the TransparentMode
+}
 
-                        alert(&appmsg, format!("Saved image as {path:?}"));
-                        Ok(())
-                    }() {
-                        Ok(()) => (),
-                        Err(errmsg) => error_alert(&appmsg, format!("SaveImage error:\n{errmsg}")),
-                    };
-                },
-                BgMessage::ClearImage => {
-                    match || -> Result<(), String> {
-                        let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
-                        let mut palette_frame: Frame = app::widget_from_id("palette_frame").ok_or("widget_from_id fail")?;
+struct QuantizedStage {
+    key: QuantizeKey,
+    indexes: Vec<u8>,
+    palette: Vec<quantizr::Color>,
+    error: f64,
+    advice: Option<String>,
+}
 
-                        processed_image = None;
+// Intermediate results of process_image, so that changing e.g. only the display multiplier doesn't
+// mean scaling and quantizing all over again. Needs to be cleared when the source image changes.
+#[derive(Default)]
+struct PipelineCache {
+    scaled: Option<ScaledStage>,
+    quantized: Option<QuantizedStage>,
+    suggestion: Option<(QuantizeKey, color_budget::Suggestion)>,
+}
 
-                        rgbaimage = None;
+impl PipelineCache {
+    fn scaled(&mut self, image: &image::RgbaImage, settings: &ImageSettings) -> Result<&ScaledStage, ProcessError> {
+        let key = ScaleKey::new(settings);
+        if self.scaled.as_ref().is_some_and(|s| s.key == key) {
+            debug!("Using cached scaled image");
+        } else {
+            progress::stage("Scaling...");
+            let mut bytes: Vec<u8>;
+            let mut width: u32;
+            let mut height: u32;
+
+            time_it!(
+                "rgbaimage_to_bytes",
+                (bytes, width, height) = rgbaimage_to_bytes(&image, key.grayscale);
+            );
+
+            // With a mask the transparent pixels get picked out after scaling instead, since the
+            // mask is for the output pixels
+            if !key.transparent_mode.uses_mask() {
+                transparency::threshold_alpha(&mut bytes, key.alpha_threshold);
+                time_it!(
+                    "flatten_alpha",
+                    flatten_alpha(&mut bytes, width, height, &key.flatten);
+                );
+            }
 
-                        frame.set_image(None::<fltk::image::RgbImage>);
-                        frame.set_label("Clear");
-                        frame.changed();
+            if key.scaling {
+                if key.pre_blur && key.scaler_type.needs_pre_blur() {
+                    let sigma = pre_blur_sigma(width, height, key.scale, key.scale, key.resize_type.clone());
+                    debug!("pre-blur sigma={sigma:.2}");
+                    time_it!(
+                        "pre_blur",
+                        filters::gaussian_blur(&mut bytes, width, height, sigma);
+                    );
+                }
 
-                        palette_frame.set_image(None::<fltk::image::RgbImage>);
-                        palette_frame.changed();
+                time_it!(
+                    "scale_image",
+                    (bytes, width, height) = scale_image(bytes, width, height, key.scale, key.scale, key.resize_type.clone(), key.scaler_type.clone())
+                        .map_err(|err| ProcessError::Scale(format!("{err:?}")))?;
+                );
+
+                if key.sharpen > 0.0 {
+                    time_it!(
+                        "unsharp_mask",
+                        filters::unsharp_mask(&mut bytes, width, height, key.sharpen);
+                    );
+                }
+            }
 
-                        enable_save_and_send_osc_button(false)?;
+            if key.edge_darken > 0.0 {
+                time_it!(
+                    "edge_darken",
+                    filters::edge_darken(&mut bytes, width, height, key.edge_darken);
+                );
+            }
 
-                        appmsg.send(AppMessage::SetTitle("Clear".to_string()))
-                            .map_err(|err| format!("Send error: {err}"))?;
-                        fltk::app::awake();
+            time_it!(
+                "levels",
+                filters::levels(&mut bytes, &key.levels);
+            );
+
+            time_it!(
+                "channel_ops",
+                filters::channel_ops(&mut bytes, key.invert, key.swap_rb, key.isolate_channel);
+            );
+
+            time_it!(
+                "posterize",
+                filters::posterize(&mut bytes, key.posterize);
+            );
+
+            let transparent = if key.transparent_mode.uses_mask() {
+                let mask = transparency::take_transparent(&mut bytes, key.alpha_threshold);
+                // Still need to do something about the partly transparent pixels that are left
+                flatten_alpha(&mut bytes, width, height, &key.flatten);
+                mask
+            } else {
+                None
+            };
 
-                        Ok(())
-                    }() {
-                        Ok(()) => (),
-                        Err(errmsg) => error_alert(&appmsg, format!("ClearImage fail:\n{errmsg}")),
-                    };
-                },
-                BgMessage::UpdateImage{
-                    no_quantize,
-                    grayscale,
-                    grayscale_output,
-                    reorder_palette,
-                    maxcolors,
-                    dithering,
-                    scaling,
-                    scale,
-                    multiplier,
-                    resize_type,
-                    scaler_type,
-                } => {
-                    match || -> Result<(), String> {
-                        enable_save_and_send_osc_button(false)?;
-
-                        let Some(ref image) = rgbaimage else {
-                            eprintln!("No image loaded");
-                            return Ok(());
-                        };
+            self.scaled = Some(ScaledStage { key: key, bytes: bytes, width: width, height: height, transparent: transparent });
+            self.quantized = None;
+        }
 
-                        let now = std::time::Instant::now();
+        self.scaled.as_ref().ok_or("Scaled image missing from cache".into())
+    }
 
-                        if !no_quantize {
-                            let mut bytes: Vec<u8>;
-                            let mut width: u32;
-                            let mut height: u32;
+    fn quantized(&mut self, image: &image::RgbaImage, settings: &ImageSettings) -> Result<(&ScaledStage, &QuantizedStage), ProcessError> {
+        let key = QuantizeKey::new(settings);
+        self.scaled(image, settings)?;
+        let Some(scaled) = &self.scaled else {
+            return Err("Scaled image missing from cache".into());
+        };
 
-                            time_it!(
-                                "rgbaimage_to_bytes",
-                                (bytes, width, height) = rgbaimage_to_bytes(&image, grayscale);
-                            );
+        if self.quantized.as_ref().is_some_and(|q| q.key == key) {
+            debug!("Using cached quantized image");
+        } else {
+            progress::stage("Quantizing...");
+            // Leave room for the transparent entry
+            let palette_index_mask = scaled.transparent.as_ref()
+                .filter(|_| key.scale.transparent_mode == TransparentMode::PaletteIndex);
+            let maxcolors = if palette_index_mask.is_some() { key.maxcolors - 1 } else { key.maxcolors };
+
+            time_it!(
+                "quantize_image",
+                let (mut indexes, mut palette) = quantize_image(
+                    &scaled.bytes, scaled.width, scaled.height,
+                    maxcolors,
+                    key.dithering,
+                    key.reorder_palette,
+                    &key.quantizer_type,
+                    &key.quantizr,
+                    &key.dither_mask,
+                    &key.forced_colors,
+                    key.locked_palette.as_deref(),
+                    &key.color_space,
+                ).map_err(|err| ProcessError::Quantize(err.to_string()))?;
+            );
+
+            let error = color_budget::quantization_error(&scaled.bytes, &indexes, &palette);
+            palette::set_last(&palette);
+
+            if let Some(mask) = palette_index_mask {
+                transparency::apply_mask(&mut indexes, mask, palette.len() as u8);
+                palette.push(quantizr::Color { r: 0, g: 0, b: 0, a: 0 });
+            }
+            self.quantized = Some(QuantizedStage { key: key, indexes: indexes, palette: palette, error: error, advice: None });
+        }
 
-                            if scaling {
-                                time_it!(
-                                    "scale_image",
-                                    (bytes, width, height) = scale_image(bytes, width, height, scale, scale, resize_type, scaler_type)
-                                        .map_err(|err| format!("scale_image failed: {err:?}"))?;
-                                );
-                            }
+        match (&self.scaled, &self.quantized) {
+            (Some(scaled), Some(quantized)) => Ok((scaled, quantized)),
+            _ => Err("Quantized image missing from cache".into()),
+        }
+    }
 
-                            time_it!(
-                                "quantize_image",
-                                let (mut indexes, palette) = quantize_image(
-                                    &bytes, width, height,
-                                    maxcolors,
-                                    dithering,
-                                    reorder_palette,
-                                ).map_err(|err| format!("Quantization failed: {err:?}"))?;
-                            );
+    // Color budget advice for the currently cached quantized image
+    fn advice(&mut self, settings: &ImageSettings) -> Result<String, String> {
+        let (Some(scaled), Some(quantized)) = (&self.scaled, &mut self.quantized) else {
+            return Err("No quantized image".to_string());
+        };
 
-                            if scaling {
-                                // Pad if needed (needed when ResizeType::ToFit was used)
+        if quantized.advice.is_none() {
+            progress::stage("Checking the color budget...");
+            time_it!(
+                "color budget advice",
+                let advice = color_budget::advise(
+                    &scaled.bytes, scaled.width, scaled.height,
+                    settings, quantized.error,
+                ).map_err(|err| format!("Couldn't estimate color budget: {err}"))?;
+            );
+            quantized.advice = Some(advice);
+        }
 
-                                // While it would at first glance seem to make sense to handle padding directly in
-                                // scale_image that would essentially force black into the palette of all images, and
-                                // since the padding color isn't that important it's best to just do it after
-                                // quantization. For now just picking whatever color 0 is, but we could eventually try
-                                // to implement some fuzzy logic for picking the padding color.
+        quantized.advice.clone().ok_or("Advice missing from cache".to_string())
+    }
 
-                                time_it!(
-                                    "find_pad_value",
-                                    let pad_value: u8 = find_pad_value(&indexes, width, height);
-                                );
+    // Color count suggestion for the currently cached scaled image
+    fn suggestion(&mut self, settings: &ImageSettings) -> Result<color_budget::Suggestion, String> {
+        let Some(scaled) = &self.scaled else {
+            return Err("No scaled image".to_string());
+        };
 
-                                println!("pad_value={pad_value}");
+        // Doesn't depend on these, so it doesn't need redoing while the max colors get tweaked
+        let key = QuantizeKey { maxcolors: 0, reorder_palette: false, ..QuantizeKey::new(settings) };
+        if let Some((_, suggestion)) = self.suggestion.as_ref().filter(|(k, _)| *k == key) {
+            return Ok(*suggestion);
+        }
 
-                                time_it!(
-                                    "pad_image",
-                                    (indexes, width, height) = pad_image(indexes, pad_value, width, height, scale, scale);
-                                );
+        progress::stage("Suggesting a color count...");
+        time_it!(
+            "color count suggestion",
+            let suggestion = color_budget::suggest(&scaled.bytes, scaled.width, scaled.height, settings)
+                .map_err(|err| format!("Couldn't suggest a color count: {err}"))?;
+        );
+        self.suggestion = Some((key, suggestion));
+        Ok(suggestion)
+    }
+}
+
+// Runs the image through the whole scale/quantize/pad pipeline, reusing whatever it can from the
+// cache. Also returns the unquantized (but scaled) image at display size, for comparing against.
+fn process_image(
+    image: &image::RgbaImage,
+    image_path: Option<&Path>,
+    settings: &ImageSettings,
+    cache: &mut PipelineCache,
+) -> Result<(ProcessedImage, fltk::image::RgbImage), ProcessError> {
+    let ImageSettings {
+        grayscale_output,
+        maxcolors,
+        scaling,
+        scale,
+        multiplier,
+        ref resize_type,
+        banner,
+        ref banner_text,
+        ..
+    } = *settings;
+    let scaling = scaling && settings.pipeline.enabled(pipeline::Stage::Scale);
+
+    let (scaled, quantized) = cache.quantized(image, settings)?;
+    let mut indexes = quantized.indexes.clone();
+    let mut palette = quantized.palette.clone();
+    if let Some((dark, light)) = settings.duotone {
+        duotone::apply(&mut palette, dark, light);
+    }
+    let mut width = scaled.width;
+    let mut height = scaled.height;
+    let mut content = None;
+
+    if let (TransparentMode::PadColor, Some(mask)) = (&settings.transparent_mode, &scaled.transparent) {
+        let pad_value = find_pad_value(&indexes, width, height);
+        transparency::apply_mask(&mut indexes, mask, pad_value);
+    }
+
+    // Whatever the pipeline has happening on the quantized image
+    for stage in settings.pipeline.after_quantize() {
+        match stage {
+            pipeline::Stage::Grayscale if settings.grayscale => {
+                use image::Pixel;
+                for color in palette.iter_mut() {
+                    let gray = image::Rgb([color.r, color.g, color.b]).to_luma().0[0];
+                    (color.r, color.g, color.b) = (gray, gray, gray);
+                }
+            },
+            // Nearest neighbour, so that no new colors turn up. The indexes go through the scaler
+            // as the red channel.
+            pipeline::Stage::Scale if scaling => {
+                let bytes: Vec<u8> = indexes.iter().flat_map(|&i| [i, 0, 0, 255]).collect();
+                time_it!(
+                    "scale_image (quantized)",
+                    let (scaled_bytes, scaled_width, scaled_height) = scale_image(bytes, width, height, scale, scale, resize_type.clone(), ScalerType::ImageCrateNearest)
+                        .map_err(|err| ProcessError::Scale(format!("{err:?}")))?;
+                );
+                indexes = scaled_bytes.chunks_exact(4).map(|rgba| rgba[0]).collect();
+                (width, height) = (scaled_width, scaled_height);
+            },
+            _ => (),
+        }
+    }
+
+    // Only ToFit leaves us with padding to put the banner in
+    let letterboxed = *resize_type == ResizeType::ToFit;
+
+    // Keep the unquantized image around to compare against in the preview
+    let mut before_rgbimage = fltk::image::RgbImage::new(&scaled.bytes, scaled.width as i32, scaled.height as i32, ColorDepth::Rgba8)
+        .map_err(|err| format!("Conversion of unquantized image to rgbimage failed: {err:?}"))?;
+    if scaling {
+        before_rgbimage.scale((width as i32) * (multiplier as i32),
+                              (height as i32) * (multiplier as i32),
+                              true, true);
+    }
+
+    if scaling && settings.pipeline.enabled(pipeline::Stage::Pad) {
+        // Pad if needed (needed when ResizeType::ToFit was used)
+
+        // While it would at first glance seem to make sense to handle padding directly in
+        // scale_image that would essentially force black into the palette of all images, and
+        // since the padding color isn't that important it's best to just do it after
+        // quantization. For now just picking whatever color 0 is, but we could eventually try
+        // to implement some fuzzy logic for picking the padding color.
+
+        time_it!(
+            "find_pad_value",
+            let pad_value: u8 = find_pad_value(&indexes, width, height);
+        );
+
+        debug!("pad_value={pad_value}");
+
+        let (unpadded_width, unpadded_height) = (width, height);
+        time_it!(
+            "pad_image",
+            (indexes, width, height) = pad_image(indexes, pad_value, width, height, scale, scale);
+        );
+        if (width, height) != (unpadded_width, unpadded_height) {
+            content = Some(((width - unpadded_width)/2, (height - unpadded_height)/2, unpadded_width, unpadded_height));
+        }
+
+        // Use the bottom padding for a caption. We only do this for wide images
+        // as we don't render vertical text.
+        if banner && letterboxed && height > unpadded_height {
+            let text = if !banner_text.is_empty() {
+                banner_text.clone()
+            } else {
+                image_path
+                    .and_then(|p| p.file_name())
+                    .map_or(String::new(), |f| f.to_string_lossy().to_string())
+            };
+            let bpadding = (height - unpadded_height).div_ceil(2);
+            let fg_index = banner::contrasting_index(&palette, pad_value);
+
+            time_it!(
+                "draw_banner",
+                let drawn = banner::draw_banner(&mut indexes, width, height,
+                                                height - bpadding, bpadding,
+                                                &text, fg_index);
+            );
+            if !drawn {
+                warn!("Banner {text:?} doesn't fit in the padding");
+            }
+        }
+    }
+
+    let img = ProcessedImage {
+        indexes: indexes,
+        palette: palette,
+        width: width,
+        height: height,
+        maxcolors: maxcolors,
+        grayscale_output: grayscale_output,
+        display_multiplier: if scaling { multiplier } else { 1 },
+        content: content,
+        settings: settings.clone(),
+    };
+
+    Ok((img, before_rgbimage))
+}
+
+// Whatever the format, as RGBA
+fn load_image(path: &Path) -> Result<image::RgbaImage, ProcessError> {
+    let image = image::ImageReader::open(path)
+        .map_err(|err| ProcessError::Open { path: path.to_path_buf(), source: err })?
+        .with_guessed_format()
+        .map_err(|err| ProcessError::Open { path: path.to_path_buf(), source: err })?
+        .decode()
+        .map_err(|err| ProcessError::Decode { path: path.to_path_buf(), source: err })?;
+    Ok(image.to_rgba8())
+}
+
+// Sources bigger than this (in pixels) get scaled down on load, 0 = never. From the config.
+static MAX_SOURCE_PIXELS: AtomicU64 = AtomicU64::new(0);
+
+// A 100 megapixel photo would go through every stage of the pipeline at full size, so huge sources
+// get scaled down to the cap right after decoding. The path stays around, so the full image can
+// still be loaded on demand ("Reload at full resolution"). The bool is whether it got scaled down.
+fn limit_source_size(image: image::RgbaImage) -> (image::RgbaImage, bool) {
+    let max_pixels = MAX_SOURCE_PIXELS.load(Ordering::Relaxed);
+    let (w, h) = image.dimensions();
+    if max_pixels == 0 || (w as u64)*(h as u64) <= max_pixels {
+        return (image, false);
+    }
+
+    let factor = ((max_pixels as f64) / ((w as f64) * (h as f64))).sqrt();
+    let (sw, sh) = (((w as f64) * factor).max(1.0) as u32, ((h as f64) * factor).max(1.0) as u32);
+    time_it!(
+        "limit_source_size",
+        let small = imageops::resize(&image, sw, sh, imageops::FilterType::Triangle);
+    );
+    info!("Scaled the {w}x{h} source down to {sw}x{sh} to save memory");
+    (small, true)
+}
+
+fn set_full_resolution_btn(active: bool) -> Result<(), String> {
+    let mut full_resolution_btn: Button = app::widget_from_id("full_resolution_btn").ok_or("widget_from_id fail")?;
+    if active != full_resolution_btn.active() {
+        if active { full_resolution_btn.activate() } else { full_resolution_btn.deactivate() }
+        fltk::app::awake();
+    }
+    Ok(())
+}
+
+// Sources bigger than this get downscaled for the quick previews while dragging sliders
+const PROXY_MAX_PIXELS: u32 = 512*512;
+const PERFORMANCE_REFRESH_INTERVAL: f64 = 0.5;
+
+fn make_proxy_image(image: &image::RgbaImage) -> image::RgbaImage {
+    let (w, h) = image.dimensions();
+    if w * h <= PROXY_MAX_PIXELS {
+        return image.clone();
+    }
+
+    let factor = ((PROXY_MAX_PIXELS as f64) / ((w as f64) * (h as f64))).sqrt();
+    let (pw, ph) = (((w as f64) * factor).max(1.0) as u32, ((h as f64) * factor).max(1.0) as u32);
+    time_it!(
+        "make_proxy_image",
+        let proxy = imageops::thumbnail(image, pw, ph);
+    );
+    proxy
+}
+
+// The max color counts shown side by side by "Compare settings"
+const COMPARE_MAXCOLORS: [i32; 4] = [4, 8, 16, 32];
+
+// Shows the results of processing with a few different settings side by side. Picking one of them
+// sets the max colors slider to it.
+fn show_compare_window(
+    appmsg: &mpsc::Sender<AppMessage>,
+    bg: &mq::MessageQueueSender<BgMessage>,
+    results: Vec<(i32, fltk::image::RgbImage)>,
+) -> Result<(), Box<dyn Error>> {
+    let image_w = results.iter().map(|(_, img)| img.w()).max().unwrap_or(128);
+    let image_h = results.iter().map(|(_, img)| img.h()).max().unwrap_or(128);
+    let width = min(((image_w + 20) * (results.len() as i32)) + 20, 1600);
+    let height = min(image_h + 100, 1000);
+
+    let appmsg_inner = appmsg.clone();
+    let bg = bg.clone();
+    appmsg.send(AppMessage::CreateWindow(
+        width, height, "Compare settings".to_string(),
+        Box::new(move |win| -> Result<(), Box<dyn Error>> {
+            win.make_resizable(true);
+            win.set_callback(|win| {
+                app::delete_widget(win.clone());
+            });
+
+            let mut row = Flex::default_fill().row();
+            row.set_margin(10);
+            row.set_spacing(10);
+
+            for (maxcolors, rgbimage) in results {
+                let mut col = Flex::default_fill().column();
+
+                let mut frame = Frame::default_fill();
+                frame.set_frame(FrameType::DownBox);
+                frame.set_image_scaled(Some(rgbimage));
+
+                let mut use_btn = Button::default().with_label(&i18n::tr("Use {} colors").replace("{}", &maxcolors.to_string()));
+                col.fixed(&use_btn, 40);
+                use_btn.set_callback({
+                    let appmsg = appmsg_inner.clone();
+                    let bg = bg.clone();
+                    move |_| {
+                        if let Some(mut slider) = app::widget_from_id::<HorValueSlider>("maxcolors_slider") {
+                            slider.set_value(maxcolors as f64);
+                            send_updateimage(&appmsg, &bg);
+                        }
+                    }
+                });
+
+                col.end();
+            }
+
+            row.end();
+            Ok(())
+        })
+    ))?;
+    fltk::app::awake();
+
+    Ok(())
+}
+
+fn start_background_process(appmsg_sender: &mpsc::Sender<AppMessage>) -> (thread::JoinHandle<()>, mq::MessageQueueSender<BgMessage>) {
+    let (sender, receiver) = mq::mq::<BgMessage>();
+
+    let appmsg = appmsg_sender.clone();
+    let sender_return = sender.clone();
+
+    let joinhandle: thread::JoinHandle<()> = thread::spawn(move || -> () {
+        let mut rgbaimage: Option<image::RgbaImage> = None;
+        let mut image_path: Option<PathBuf> = None;
+        let mut processed_image: Option<ProcessedImage> = None;
+        let mut pipeline_cache = PipelineCache::default();
+        // Downscaled version of rgbaimage (with its own cache) for PreviewImage
+        let mut proxy: Option<(image::RgbaImage, PipelineCache)> = None;
+        // The file rgbaimage came from, if it got scaled down to the megapixel cap on load
+        let mut downscaled_from: Option<PathBuf> = None;
+        // The send settings the transfer estimate is shown for
+        let mut estimate_opts: Option<send_osc::SendOSCOpts> = None;
+        // What we last actually sent, and with what, for ResendOSC
+        let mut last_send: Option<(send_osc::SendOSCOpts, SendSnapshot)> = None;
+        progress::watch(&appmsg);
+
+        // Ends on its own if everybody that could send us anything is gone, otherwise we wait
+        // for a Quit
+        for (msg, waited) in receiver.iter_waited() {
+            // Something like "UpdateImage". Only the name gets logged, as the settings in
+            // UpdateImage (dither mask and all) are big for something sent on every slider release.
+            let msg_name: &'static str = (&msg).into();
+            debug!("BG thread got {msg_name}");
+
+            if msg == BgMessage::Quit {
+                break;
+            }
+
+            metrics::record_wait(msg_name, waited);
+            let msg_is_update = msg.is_update() || matches!(msg, BgMessage::LoadImage(_) | BgMessage::LoadVideoFrame(..) | BgMessage::LoadFullResolution);
+
+            // A panic in one of the handlers shouldn't leave the GUI with a dead BG thread. The panic
+            // hook already tells the user about it, here we just throw away whatever state might
+            // have been left half-updated and carry on.
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                match msg {
+                    BgMessage::Quit => (), // Handled above
+                    BgMessage::LoadImage(path) => {
+                        match || -> Result<(), ProcessError> {
+                            progress::stage("Loading image...");
+                            let (image, downscaled) = limit_source_size(load_image(&path)?);
+                            rgbaimage = Some(image);
+                            downscaled_from = downscaled.then(|| path.clone());
+                            pipeline_cache = PipelineCache::default();
+                            proxy = None;
+                            image_path = Some(path.clone());
+                            remote::update_status(|s| *s = remote::ImageStatus { source: Some(path.to_string_lossy().to_string()), processed: None });
+                            info!("Loaded image {path:?}");
+
+                            let pathstr = path.to_string_lossy();
+                            {
+                                let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
+                                frame.set_label(&pathstr);
+                                frame.changed();
+                                frame.redraw();
                             }
 
+                            appmsg.send(AppMessage::SetTitle(pathstr.to_string())).
+                                map_err(|err| format!("Send error: {err}"))?;
+                            fltk::app::awake();
+
+                            send_updateimage(&appmsg, &sender);
+
+                            info!("Finished LoadImage for {path:?}");
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => {
+                                report_error(&appmsg, "LoadImage", &err);
+                                print_err(sender.send(BgMessage::ClearImage));
+                            }
+                        };
+                    },
+                    BgMessage::LoadVideoFrame(path, time) => {
+                        match || -> Result<(), ProcessError> {
+                            progress::stage("Decoding video frame...");
                             time_it!(
-                                "quantized_image_to_fltk_rgbimage",
-                                let mut rgbimage = quantized_image_to_fltk_rgbimage(
-                                    &indexes, &palette,
-                                    width, height,
-                                    grayscale_output,
-                                ).map_err(|err| format!("Conversion to rgbimage failed: {err:?}"))?;
+                                "video::frame_at",
+                                let image = video::frame_at(&path, time)
+                                    .map_err(|err| ProcessError::Video(format!("{time:.1} s into {path:?}: {err}")))?;
                             );
 
-                            if scaling {
-                                rgbimage.scale((width as i32) * (multiplier as i32),
-                                               (height as i32) * (multiplier as i32),
-                                               true, true); // Display pixelly image larger
+                            downscaled_from = None;
+                            rgbaimage = Some(image);
+                            pipeline_cache = PipelineCache::default();
+                            proxy = None;
+                            image_path = Some(path.clone());
+                            let title = format!("{} @ {time:.1} s", path.to_string_lossy());
+                            remote::update_status(|s| *s = remote::ImageStatus { source: Some(title.clone()), processed: None });
+                            info!("Loaded {title}");
+
+                            {
+                                let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
+                                frame.set_label(&title);
+                                frame.changed();
+                                frame.redraw();
                             }
 
+                            appmsg.send(AppMessage::SetTitle(title)).
+                                map_err(|err| format!("Send error: {err}"))?;
+                            fltk::app::awake();
+
+                            send_updateimage(&appmsg, &sender);
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "LoadVideoFrame", &err),
+                        };
+                    },
+                    BgMessage::RenderText(text_settings) => {
+                        match || -> Result<(), ProcessError> {
+                            downscaled_from = None;
+                            rgbaimage = Some(text::render(&text_settings));
+                            pipeline_cache = PipelineCache::default();
+                            proxy = None;
+                            image_path = None;
+                            remote::update_status(|s| *s = remote::ImageStatus { source: Some("Text".to_string()), processed: None });
+
                             {
                                 let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
-                                let mut palette_frame: Frame = app::widget_from_id("palette_frame").ok_or("widget_from_id fail")?;
+                                frame.set_label("Text");
+                                frame.changed();
+                                frame.redraw();
+                            }
 
-                                frame.set_image(Some(rgbimage));
+                            appmsg.send(AppMessage::SetTitle("Text".to_string())).
+                                map_err(|err| format!("Send error: {err}"))?;
+                            fltk::app::awake();
+
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "RenderText", &err),
+                        };
+                    },
+                    BgMessage::CaptureScreen => {
+                        match || -> Result<(), ProcessError> {
+                            time_it!(
+                                "capture_screen",
+                                let image = capture::capture_screen()
+                                    .map_err(|err| ProcessError::Capture(err.to_string()))?;
+                            );
+
+                            downscaled_from = None;
+                            rgbaimage = Some(image);
+                            pipeline_cache = PipelineCache::default();
+                            proxy = None;
+                            image_path = None;
+                            remote::update_status(|s| *s = remote::ImageStatus { source: Some("Screen capture".to_string()), processed: None });
+
+                            {
+                                let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
+                                frame.set_label("Screen capture");
                                 frame.changed();
                                 frame.redraw();
+                            }
+
+                            appmsg.send(AppMessage::SetTitle("Screen capture".to_string())).
+                                map_err(|err| format!("Send error: {err}"))?;
+                            fltk::app::awake();
 
-                                let palette_rgbimage = palette_to_fltk_rgbimage(&palette, grayscale_output)
-                                    .map_err(|err| format!("Couldn't generate palette RgbImage: {err:?}"))?;
-                                palette_frame.set_image_scaled(Some(palette_rgbimage));
-                                palette_frame.changed();
-                                palette_frame.redraw();
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "CaptureScreen", &err),
+                        };
+                    },
+                    BgMessage::SaveImage(path) => {
+                        match || -> Result<(), SaveError> {
+                            let path = path.with_extension("png");
+
+                            let img = processed_image.as_ref()
+                                .ok_or(SaveError::NothingToSave)?;
+
+                            let w = img.width.try_into()?;
+                            let h = img.height.try_into()?;
+
+                            let settings_json = serde_json::to_string(&img.settings)
+                                .map_err(|err| format!("Couldn't encode the settings: {err}"))?;
+
+                            save_png::save_png(
+                                &path, w, h, &img.indexes, &img.palette,
+                                match img.grayscale_output {
+                                    true  => save_png::ColorType::Grayscale,
+                                    false => save_png::ColorType::Indexed,
+                                },
+                                &[(project::SETTINGS_KEYWORD, &settings_json)],
+                            ).map_err(|err| SaveError::Write { path: path.clone(), message: err.to_string() })?;
+
+                            alert(&appmsg, format!("{} {path:?}", i18n::tr("Saved image as")));
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "SaveImage", &err),
+                        };
+                    },
+                    BgMessage::SaveProject(path, settings, send_settings) => {
+                        match || -> Result<(), SaveError> {
+                            let path = path.with_extension(project::EXTENSION);
+
+                            let image = rgbaimage.as_ref()
+                                .ok_or(SaveError::NothingToSave)?;
+                            let project = project::Project::new(image_path.clone(), settings.clone(), send_settings.clone());
+
+                            project::save(&path, image, &project)
+                                .map_err(|err| SaveError::Write { path: path.clone(), message: err.to_string() })?;
+
+                            alert(&appmsg, format!("{} {path:?}", i18n::tr("Saved project as")));
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "SaveProject", &err),
+                        };
+                    },
+                    BgMessage::OpenProject(path) => {
+                        match || -> Result<(), ProcessError> {
+                            // Settings first, so a file that isn't a project doesn't replace the image
+                            let project = project::load_settings(&path)
+                                .map_err(|err| ProcessError::Project { path: path.clone(), message: err.to_string() })?;
+
+                            progress::stage("Loading image...");
+                            let (image, downscaled) = limit_source_size(load_image(&path)?);
+                            rgbaimage = Some(image);
+                            downscaled_from = downscaled.then(|| path.clone());
+                            pipeline_cache = PipelineCache::default();
+                            proxy = None;
+                            // The original path, so the banner caption stays the same
+                            image_path = Some(project.source_path.clone().unwrap_or(path.clone()));
+                            remote::update_status(|s| *s = remote::ImageStatus { source: Some(path.to_string_lossy().to_string()), processed: None });
+                            info!("Opened project {path:?}");
+
+                            let pathstr = path.to_string_lossy();
+                            {
+                                let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
+                                frame.set_label(&pathstr);
+                                frame.changed();
+                                frame.redraw();
                             }
 
-                            processed_image = Some(ProcessedImage{
-                                indexes: indexes,
-                                palette: palette,
-                                width: width,
-                                height: height,
-                                maxcolors: maxcolors,
-                                grayscale_output: grayscale_output,
-                            });
-                            enable_save_and_send_osc_button(true)?;
-                        } else {
+                            appmsg.send(AppMessage::SetTitle(pathstr.to_string())).
+                                map_err(|err| format!("Send error: {err}"))?;
+                            // The main thread sets the widgets, and processes the image with them
+                            appmsg.send(AppMessage::ApplyProject(project)).
+                                map_err(|err| format!("Send error: {err}"))?;
+                            fltk::app::awake();
+
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "OpenProject", &err),
+                        };
+                    },
+                    BgMessage::LoadFullResolution => {
+                        match || -> Result<(), ProcessError> {
+                            let Some(path) = downscaled_from.clone() else {
+                                info!("Already at full resolution");
+                                return Ok(());
+                            };
+                            progress::stage("Loading image...");
+                            rgbaimage = Some(load_image(&path)?);
+                            downscaled_from = None;
+                            pipeline_cache = PipelineCache::default();
+                            proxy = None;
+                            info!("Reloaded {path:?} at full resolution");
+
+                            send_updateimage(&appmsg, &sender);
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "LoadFullResolution", &err),
+                        };
+                    },
+                    BgMessage::PaletteFromImage(path, settings) => {
+                        match || -> Result<(), ProcessError> {
+                            progress::stage("Making palette...");
+                            let (reference, _) = limit_source_size(load_image(&path)?);
+                            // Same as for the working image, minus the dithering (only the palette is wanted)
+                            time_it!(
+                                "reference palette",
+                                let (_, palette) = quantize_image(
+                                    reference.as_raw(), reference.width(), reference.height(),
+                                    settings.palette_colors(),
+                                    0.0,
+                                    settings.reorder_palette,
+                                    &settings.quantizer_type,
+                                    &settings.quantizr,
+                                    &dither_mask::DitherMask::default(),
+                                    &settings.forced_colors,
+                                    None,
+                                    &settings.color_space,
+                                ).map_err(|err| ProcessError::Quantize(err.to_string()))?;
+                            );
+                            info!("Palette of {} colors from {path:?}", palette.len());
+
+                            palette::set_locked(Some(palette.iter().map(palette::to_rgba).collect()));
+                            let lock_palette_toggle: CheckButton = app::widget_from_id("lock_palette_toggle").ok_or("widget_from_id fail")?;
+                            lock_palette_toggle.set_checked(true);
+                            fltk::app::awake();
+
+                            send_updateimage(&appmsg, &sender);
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "PaletteFromImage", &err),
+                        };
+                    },
+                    BgMessage::ClearImage => {
+                        match || -> Result<(), ProcessError> {
                             let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
-                            frame.set_image(Some(
-                                rgbaimage_to_fltk_rgbimage(image)
-                                    .map_err(|err| format!("Failed to convert from image::RgbaImage to fltk::image::RgbImage: {err}"))?
-                            ));
-                            frame.changed();
-                            frame.redraw();
+                            let mut palette_frame: Frame = app::widget_from_id("palette_frame").ok_or("widget_from_id fail")?;
 
-                            // TODO: there should be a fallback here maybe
                             processed_image = None;
+                            pipeline_cache = PipelineCache::default();
+                            proxy = None;
+
+                            downscaled_from = None;
+                            rgbaimage = None;
+                            image_path = None;
+                            remote::update_status(|s| *s = remote::ImageStatus::default());
+
+                            frame.set_image(None::<fltk::image::RgbImage>);
+                            frame.set_label("Clear");
+                            frame.changed();
+
+                            palette_frame.set_image(None::<fltk::image::RgbImage>);
+                            palette_frame.changed();
+
+                            set_histogram_frame("histogram_source_frame", None)?;
+                            set_histogram_frame("histogram_output_frame", None)?;
+                            compare::set_before(None);
+                            letterbox::set_content(None, 0, 0);
+                            pixel_view::set_image(None)?;
+                            set_info_text("")?;
+                            set_suggestion(None)?;
+                            set_transfer_estimate(None, None)?;
+
+                            enable_save_and_send_osc_button(false)?;
+
+                            appmsg.send(AppMessage::SetTitle("Clear".to_string()))
+                                .map_err(|err| format!("Send error: {err}"))?;
+                            fltk::app::awake();
+
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "ClearImage", &err),
+                        };
+                    },
+                    BgMessage::UpdateImage(settings) => {
+                        match || -> Result<(), ProcessError> {
+                            enable_save_and_send_osc_button(false)?;
+
+                            let Some(ref image) = rgbaimage else {
+                                info!("No image loaded");
+                                return Ok(());
+                            };
+
+                            let now = std::time::Instant::now();
+                            progress::stage("Processing...");
+
+                            time_it!(
+                                "source histogram",
+                                set_histogram_frame("histogram_source_frame", Some(&histogram::Histogram::from_rgba(image.as_raw())))?;
+                            );
+
+                            if !settings.no_quantize {
+                                let (img, before_rgbimage) = process_image(image, image_path.as_deref(), &settings, &mut pipeline_cache)?;
+
+                                time_it!(
+                                    "ProcessedImage::to_fltk_rgbimage",
+                                    let rgbimage = img.to_fltk_rgbimage()
+                                        .map_err(|err| format!("Conversion to rgbimage failed: {err:?}"))?;
+                                );
+
+                                {
+                                    let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
+                                    let mut palette_frame: Frame = app::widget_from_id("palette_frame").ok_or("widget_from_id fail")?;
+
+                                    letterbox::set_content(img.content, img.width, img.height);
+                                    frame.set_image(Some(rgbimage));
+                                    frame.changed();
+                                    frame.redraw();
+
+                                    let palette_rgbimage = palette_to_fltk_rgbimage(&img.palette, img.grayscale_output)
+                                        .map_err(|err| format!("Couldn't generate palette RgbImage: {err:?}"))?;
+                                    palette_frame.set_image_scaled(Some(palette_rgbimage));
+                                    palette_frame.changed();
+                                    palette_frame.redraw();
+                                }
+
+                                compare::set_before(Some(before_rgbimage));
+
+                                time_it!(
+                                    "output histogram",
+                                    set_histogram_frame("histogram_output_frame", Some(&histogram::Histogram::from_indexed(&img.indexes, &img.palette)))?;
+                                );
+
+                                set_info_text("")?;
+                                set_suggestion(None)?;
+
+                                set_transfer_estimate(Some(&img), estimate_opts.as_ref())?;
+                                edit::show_color(&img.palette)?;
+                                pixel_view::set_image(Some(img.to_fltk_rgbimage_1to1()
+                                    .map_err(|err| format!("Conversion to rgbimage failed: {err:?}"))?))?;
+                                remote::update_status(|s| s.processed = Some((img.width, img.height, img.palette.len())));
+                                processed_image = Some(img);
+                                enable_save_and_send_osc_button(true)?;
+
+                                // These quantize all over again (a few times for the suggestion), so they come
+                                // after, where they don't hold up Send
+                                print_err(sender.send(BgMessage::ColorBudget(settings.clone())));
+                            } else {
+                                let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
+                                let posterized = (settings.posterize < 256 || !settings.levels.is_identity()
+                                                  || settings.invert || settings.swap_rb || settings.isolate_channel != filters::Channel::All).then(|| {
+                                    let mut posterized = image.clone();
+                                    filters::levels(&mut posterized, &settings.levels);
+                                    filters::channel_ops(&mut posterized, settings.invert, settings.swap_rb, settings.isolate_channel);
+                                    filters::posterize(&mut posterized, settings.posterize);
+                                    posterized
+                                });
+                                frame.set_image(Some(
+                                    rgbaimage_to_fltk_rgbimage(posterized.as_ref().unwrap_or(image))
+                                        .map_err(|err| format!("Failed to convert from image::RgbaImage to fltk::image::RgbImage: {err}"))?
+                                ));
+                                frame.changed();
+                                frame.redraw();
+
+                                set_histogram_frame("histogram_output_frame", None)?;
+                                compare::set_before(None);
+                                letterbox::set_content(None, 0, 0);
+                                pixel_view::set_image(None)?;
+                                set_info_text("")?;
+                                set_suggestion(None)?;
+
+                                // TODO: there should be a fallback here maybe
+                                processed_image = None;
+                                remote::update_status(|s| s.processed = None);
+                                set_transfer_estimate(None, None)?;
+                                enable_save_and_send_osc_button(false)?;
+                            }
+
+                            fltk::app::awake();
+
+                            info!("Finished updating image (took {:.2?})", now.elapsed());
+
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => {
+                                report_error(&appmsg, "UpdateImage", &err);
+                                print_err(sender.send(BgMessage::ClearImage));
+                            },
+                        };
+                    },
+                    BgMessage::PreviewImage(settings) => {
+                        match || -> Result<(), ProcessError> {
+                            let Some(ref image) = rgbaimage else {
+                                return Ok(());
+                            };
+                            if settings.no_quantize {
+                                return Ok(());
+                            }
+
+                            // What's on screen is not what would get saved or sent until the full
+                            // UpdateImage comes in
                             enable_save_and_send_osc_button(false)?;
+
+                            let (proxy_image, proxy_cache) = proxy.get_or_insert_with(|| (make_proxy_image(image), PipelineCache::default()));
+                            let (img, _) = progress::quietly(|| process_image(proxy_image, image_path.as_deref(), &settings, proxy_cache))?;
+
+                            let mut rgbimage = img.to_fltk_rgbimage()
+                                .map_err(|err| format!("Conversion to rgbimage failed: {err:?}"))?;
+                            if !settings.scaling {
+                                // Show it at the size the full resolution one will have
+                                rgbimage.scale(image.width() as i32, image.height() as i32, true, true);
+                            }
+
+                            let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
+                            let mut palette_frame: Frame = app::widget_from_id("palette_frame").ok_or("widget_from_id fail")?;
+
+                            letterbox::set_content(img.content, img.width, img.height);
+                            frame.set_image(Some(rgbimage));
+                            frame.changed();
+                            frame.redraw();
+
+                            let palette_rgbimage = palette_to_fltk_rgbimage(&img.palette, img.grayscale_output)
+                                .map_err(|err| format!("Couldn't generate palette RgbImage: {err:?}"))?;
+                            palette_frame.set_image_scaled(Some(palette_rgbimage));
+                            palette_frame.changed();
+                            palette_frame.redraw();
+
+                            fltk::app::awake();
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "PreviewImage", &err),
+                        };
+                    },
+                    BgMessage::EditImage(edit) => {
+                        match || -> Result<(), ProcessError> {
+                            let Some(ref mut img) = processed_image else {
+                                return Ok(());
+                            };
+
+                            if let Some(index) = edit::apply(&edit, &mut img.indexes, img.width, img.height, img.palette.len()) {
+                                edit::set_index(index);
+                                edit::show_color(&img.palette)?;
+                                fltk::app::awake();
+                                return Ok(());
+                            }
+
+                            let rgbimage = img.to_fltk_rgbimage()
+                                .map_err(|err| format!("Conversion to rgbimage failed: {err:?}"))?;
+                            {
+                                let mut frame: Frame = app::widget_from_id("frame").ok_or("widget_from_id fail")?;
+                                frame.set_image(Some(rgbimage));
+                                frame.changed();
+                                frame.redraw();
+                            }
+
+                            set_histogram_frame("histogram_output_frame", Some(&histogram::Histogram::from_indexed(&img.indexes, &img.palette)))?;
+                            set_transfer_estimate(Some(&*img), estimate_opts.as_ref())?;
+                            pixel_view::set_image(Some(img.to_fltk_rgbimage_1to1()
+                                .map_err(|err| format!("Conversion to rgbimage failed: {err:?}"))?))?;
+                            fltk::app::awake();
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "EditImage", &err),
+                        };
+                    },
+                    BgMessage::ResendOSC => {
+                        match || -> Result<(), error::SendError> {
+                            let (options, snapshot) = last_send.clone().ok_or(error::SendError::NothingSentYet)?;
+                            // The very same image, so it's been confirmed already. Skip the confirmation (and the
+                            // warning), the whole point is not having to switch windows.
+                            print_err(sender.send(BgMessage::SendOSC(send_osc::SendOSCOpts { confirm: false, warn_after: None, ..options }, Some(snapshot))));
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "ResendOSC", &err),
+                        };
+                    },
+                    BgMessage::ColorBudget(settings) => {
+                        match || -> Result<(), ProcessError> {
+                            // Don't bother if the settings changed already, that update sends its own
+                            if processed_image.is_none() || receiver.peek_with(BgMessage::is_update)
+                                .map_err(|err| format!("Peek error: {err}"))?.unwrap_or(false) {
+                                return Ok(());
+                            }
+
+                            let advice = pipeline_cache.advice(&settings).unwrap_or_else(|err| err);
+                            set_info_text(&advice)?;
+                            match pipeline_cache.suggestion(&settings) {
+                                Ok(suggestion) => set_suggestion(Some(suggestion))?,
+                                Err(err) => {
+                                    warn!("{err}");
+                                    set_suggestion(None)?;
+                                },
+                            }
+                            fltk::app::awake();
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "ColorBudget", &err),
+                        };
+                    },
+                    BgMessage::EstimateTransfer(options) => {
+                        if let Err(errmsg) = set_transfer_estimate(processed_image.as_ref(), Some(&options)) {
+                            error_alert(&appmsg, format!("{}:\n{errmsg}", i18n::tr("Couldn't update the transfer estimate")));
                         }
+                        estimate_opts = Some(options);
+                    },
+                    BgMessage::CompareSettings(settings) => {
+                        match || -> Result<(), ProcessError> {
+                            let Some(ref image) = rgbaimage else {
+                                return Err(ProcessError::NoImage);
+                            };
+
+                            // Separate cache so we don't throw away what the preview is using. All the
+                            // variations still get to share the scaled image.
+                            let mut cache = PipelineCache::default();
+                            let mut results: Vec<(i32, fltk::image::RgbImage)> = Vec::new();
+                            for maxcolors in COMPARE_MAXCOLORS {
+                                let settings = ImageSettings { maxcolors, no_quantize: false, ..settings.clone() };
+                                let (img, _) = process_image(image, image_path.as_deref(), &settings, &mut cache)?;
+                                let rgbimage = img.to_fltk_rgbimage()
+                                    .map_err(|err| format!("Conversion to rgbimage failed: {err:?}"))?;
+                                results.push((maxcolors, rgbimage));
+                            }
+
+                            show_compare_window(&appmsg, &sender, results)
+                                .map_err(|err| format!("Couldn't create compare window: {err}"))?;
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "CompareSettings", &err),
+                        };
+                    },
+                    BgMessage::SendOSC(options, snapshot) => {
+                        info!("SendOSC({options:?}, {snapshot:?})");
+                        match || -> Result<(), error::SendError> {
+                            // Turned down now rather than after the confirmation
+                            let state = send_job::state();
+                            if state != send_job::JobState::Idle {
+                                return Err(error::SendError::Busy(state.name()));
+                            }
 
-                        fltk::app::awake();
+                            let snapshot = match snapshot {
+                                Some(snapshot) => snapshot,
+                                None => SendSnapshot::new(processed_image.as_ref().ok_or(error::SendError::NotReady)?),
+                            };
+                            let img = &snapshot.0;
+
+                            if let Some(limit) = options.warn_after {
+                                let estimate = send_osc::estimate_transfer(&img.indexes, &img.palette, img.width, &options)
+                                    .map_err(|err| format!("Couldn't estimate transfer: {err}"))?;
+                                if estimate.duration > limit {
+                                    let suggestions = send_osc::speedup_suggestions(&img.indexes, &img.palette, img.width, &options, &estimate);
+                                    // Comes back here afterwards, so the confirmation still gets asked
+                                    send_osc::warn_long_send(&appmsg, &estimate, limit, suggestions, {
+                                        let appmsg = appmsg.clone();
+                                        let sender = sender.clone();
+                                        let snapshot = snapshot.clone();
+                                        move || {
+                                            let options = send_osc::SendOSCOpts { warn_after: None, ..options };
+                                            if let Err(err) = sender.send(BgMessage::SendOSC(options, Some(snapshot))) {
+                                                error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't send message to BG thread")));
+                                            }
+                                        }
+                                    }).map_err(|err| format!("warn_long_send failed: {err}"))?;
+                                    return Ok(());
+                                }
+                            }
 
-                        println!("Finished updating image (took {:.2?})", now.elapsed());
+                            if options.check_receiver {
+                                let target = options.target_addr();
+                                match transport::probe(options.transport, target) {
+                                    Ok(true) => (),
+                                    Ok(false) => {
+                                        warn!("Nothing seems to be listening on {target}");
+                                        send_osc::warn_unreachable(&appmsg, target, {
+                                            let appmsg = appmsg.clone();
+                                            let sender = sender.clone();
+                                            let snapshot = snapshot.clone();
+                                            move || {
+                                                let options = send_osc::SendOSCOpts { check_receiver: false, ..options };
+                                                if let Err(err) = sender.send(BgMessage::SendOSC(options, Some(snapshot))) {
+                                                    error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't send message to BG thread")));
+                                                }
+                                            }
+                                        }).map_err(|err| format!("warn_unreachable failed: {err}"))?;
+                                        return Ok(());
+                                    },
+                                    Err(err) => warn!("Couldn't check whether anything is listening on {target}: {err}"),
+                                }
+                            }
 
-                        Ok(())
-                    }() {
-                        Ok(()) => (),
-                        Err(errmsg) => {
-                            error_alert(&appmsg, format!("UpdateImage fail:\n{errmsg}"));
-                            print_err(sender.send(BgMessage::ClearImage));
-                        },
-                    };
-                },
-                BgMessage::SendOSC(options) => {
-                    println!("SendOSC({options:?})");
-                    match || -> Result<(), String> {
-                        let img = processed_image.as_ref()
-                            .ok_or("Indexes and palette not generated yet")?;
-                        send_osc::send_osc(&appmsg, &img.indexes, &img.palette, img.width, img.height, options)
-                            .map_err(|err| format!("send_osc failed: {err}"))?;
-                        Ok(())
-                    }() {
-                        Ok(()) => (),
-                        Err(errmsg) => error_alert(&appmsg, format!("SendOSC fail:\n{errmsg}")),
-                    };
-                },
-            };
+                            if options.confirm {
+                                let preview = img.to_fltk_rgbimage()
+                                    .map_err(|err| format!("Conversion to rgbimage failed: {err:?}"))?;
+
+                                // Messages keep getting handled while the window is up, so the image can
+                                // change before we get the go-ahead. The snapshot is what gets sent.
+                                send_osc::confirm_send(&appmsg, preview, {
+                                    let appmsg = appmsg.clone();
+                                    let sender = sender.clone();
+                                    let snapshot = snapshot.clone();
+                                    move |dont_ask_again| {
+                                        if dont_ask_again {
+                                            if let Some(toggle) = app::widget_from_id::<CheckButton>("osc_confirm_toggle") {
+                                                toggle.set_checked(false);
+                                            }
+                                            save_confirm_send(false);
+                                        }
+                                        let options = send_osc::SendOSCOpts { confirm: false, ..options };
+                                        if let Err(err) = sender.send(BgMessage::SendOSC(options, Some(snapshot))) {
+                                            error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't send message to BG thread")));
+                                        }
+                                    }
+                                }).map_err(|err| format!("confirm_send failed: {err}"))?;
+                            } else {
+                                last_send = Some((options.clone(), snapshot.clone()));
+                                send_osc::send_osc(&appmsg, &img.indexes, &img.palette, img.width, img.height, options)
+                                    .map_err(|err| error::SendError::Send(err.to_string()))?;
+                            }
+                            Ok(())
+                        }() {
+                            Ok(()) => (),
+                            Err(err) => report_error(&appmsg, "SendOSC", &err),
+                        };
+                    },
+                };
+            }));
+            progress::done();
+            print_err(set_full_resolution_btn(downscaled_from.is_some()).map_err(|err| ProcessError::Internal(err)));
+
+            if result.is_err() {
+                warn!("BG thread recovering from a panic in {msg_name}");
+                processed_image = None;
+                pipeline_cache = PipelineCache::default();
+                proxy = None;
+                print_err(enable_save_and_send_osc_button(false).map_err(|err| ProcessError::Internal(err)));
+
+                // Redo the image with the current settings, unless that's what just blew up, as then
+                // it would most likely just happen again
+                if rgbaimage.is_some() && !msg_is_update {
+                    match get_image_settings(&appmsg) {
+                        Ok(settings) => print_err(sender.send_or_replace_if(BgMessage::is_update, BgMessage::UpdateImage(settings))),
+                        Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't restore image after a crash"))),
+                    }
+                }
+            }
         }
 
-        println!("BG Process Finished");
+        info!("BG Process Finished");
     });
 
     (joinhandle, sender_return)
 }
 
-fn send_updateimage(appmsg: &mpsc::Sender<AppMessage>, bg: &mq::MessageQueueSender::<BgMessage>) -> () {
-    match || -> Result<(), String> {
-        let no_quantize_toggle: CheckButton = app::widget_from_id("no_quantize_toggle").ok_or("widget_from_id fail")?;
-        let grayscale_toggle: CheckButton = app::widget_from_id("grayscale_toggle").ok_or("widget_from_id fail")?;
-        let grayscale_output_toggle: CheckButton = app::widget_from_id("grayscale_output_toggle").ok_or("widget_from_id fail")?;
-        let reorder_palette_toggle: CheckButton = app::widget_from_id("reorder_palette_toggle").ok_or("widget_from_id fail")?;
-        let maxcolors_slider: HorValueSlider = app::widget_from_id("maxcolors_slider").ok_or("widget_from_id fail")?;
-        let dithering_slider: HorValueSlider = app::widget_from_id("dithering_slider").ok_or("widget_from_id fail")?;
-        let scaling_toggle: CheckButton = app::widget_from_id("scaling_toggle").ok_or("widget_from_id fail")?;
-        let scale_input: IntInput = app::widget_from_id("scale_input").ok_or("widget_from_id fail")?;
-        let resize_type_choice: menu::Choice = app::widget_from_id("resize_type_choice").ok_or("widget_from_id fail")?;
-        let scaler_type_choice: menu::Choice = app::widget_from_id("scaler_type_choice").ok_or("widget_from_id fail")?;
-        let multiplier_choice: menu::Choice = app::widget_from_id("multiplier_choice").ok_or("widget_from_id fail")?;
-
-        let msg = BgMessage::UpdateImage{
-            no_quantize: no_quantize_toggle.is_checked(),
-            grayscale: grayscale_toggle.is_checked(),
-            grayscale_output: grayscale_output_toggle.is_checked(),
-            reorder_palette: reorder_palette_toggle.is_checked(),
-            scaling: scaling_toggle.is_checked(),
-            maxcolors: maxcolors_slider.value() as i32,
-            dithering: dithering_slider.value() as f32,
-            scale: {
-                let value = scale_input.value();
-                value.parse()
-                    .map_err(|err| format!("Couldn't parse scale {value:?}: {err}"))?
-            },
-            multiplier: {
-                match || -> Result<_, String> {
-                    let choice: String = multiplier_choice.choice()
-                        .ok_or("No multiplier choice selected")?;
-                    let choice = choice.strip_suffix("x")
-                        .ok_or_else(|| format!("No x suffix in multiplier choice: {choice:?}"))?;
-                    let multiplier = choice.parse()
-                        .map_err(|err| format!("Couldn't parse multiplier {choice:?}: {err}"))?;
-                    Ok(multiplier)
-                }() {
-                    Ok(res) => res,
-                    Err(msg) => {
-                        error_alert(&appmsg, msg);
-                        1
-                    },
-                }
-            },
-            resize_type: {
-                match || -> Result<ResizeType, String> {
-                    let choice = resize_type_choice.choice()
-                        .ok_or("No resize type selected")?;
-                    let parsed = choice.parse()
-                        .map_err(|err| format!("Couldn't parse resize type {choice:?}: {err}"))?;
-                    Ok(parsed)
-                }() {
-                    Ok(res) => res,
-                    Err(msg) => {
-                        error_alert(&appmsg, msg);
-                        Default::default()
-                    },
-                }
+// Read the current image settings from the widgets
+fn get_image_settings(appmsg: &mpsc::Sender<AppMessage>) -> Result<ImageSettings, String> {
+    let no_quantize_toggle: CheckButton = app::widget_from_id("no_quantize_toggle").ok_or("widget_from_id fail")?;
+    let grayscale_toggle: CheckButton = app::widget_from_id("grayscale_toggle").ok_or("widget_from_id fail")?;
+    let grayscale_output_toggle: CheckButton = app::widget_from_id("grayscale_output_toggle").ok_or("widget_from_id fail")?;
+    let reorder_palette_toggle: CheckButton = app::widget_from_id("reorder_palette_toggle").ok_or("widget_from_id fail")?;
+    let maxcolors_slider: HorValueSlider = app::widget_from_id("maxcolors_slider").ok_or("widget_from_id fail")?;
+    let dithering_slider: HorValueSlider = app::widget_from_id("dithering_slider").ok_or("widget_from_id fail")?;
+    let scaling_toggle: CheckButton = app::widget_from_id("scaling_toggle").ok_or("widget_from_id fail")?;
+    let scale_input: IntInput = app::widget_from_id("scale_input").ok_or("widget_from_id fail")?;
+    let quantizer_choice: menu::Choice = app::widget_from_id("quantizer_choice").ok_or("widget_from_id fail")?;
+    let quantizr_sample_slider: HorValueSlider = app::widget_from_id("quantizr_sample_slider").ok_or("widget_from_id fail")?;
+    let kmeans_slider: HorValueSlider = app::widget_from_id("kmeans_slider").ok_or("widget_from_id fail")?;
+    let color_space_choice: menu::Choice = app::widget_from_id("color_space_choice").ok_or("widget_from_id fail")?;
+    let flatten_choice: menu::Choice = app::widget_from_id("flatten_choice").ok_or("widget_from_id fail")?;
+    let alpha_threshold_slider: HorValueSlider = app::widget_from_id("alpha_threshold_slider").ok_or("widget_from_id fail")?;
+    let transparent_mode_choice: menu::Choice = app::widget_from_id("transparent_mode_choice").ok_or("widget_from_id fail")?;
+    let resize_type_choice: menu::Choice = app::widget_from_id("resize_type_choice").ok_or("widget_from_id fail")?;
+    let scaler_type_choice: menu::Choice = app::widget_from_id("scaler_type_choice").ok_or("widget_from_id fail")?;
+    let pre_blur_toggle: CheckButton = app::widget_from_id("pre_blur_toggle").ok_or("widget_from_id fail")?;
+    let sharpen_slider: HorValueSlider = app::widget_from_id("sharpen_slider").ok_or("widget_from_id fail")?;
+    let posterize_slider: HorValueSlider = app::widget_from_id("posterize_slider").ok_or("widget_from_id fail")?;
+    let edge_darken_slider: HorValueSlider = app::widget_from_id("edge_darken_slider").ok_or("widget_from_id fail")?;
+    let black_point_slider: HorValueSlider = app::widget_from_id("black_point_slider").ok_or("widget_from_id fail")?;
+    let white_point_slider: HorValueSlider = app::widget_from_id("white_point_slider").ok_or("widget_from_id fail")?;
+    let curve_shadows_slider: HorValueSlider = app::widget_from_id("curve_shadows_slider").ok_or("widget_from_id fail")?;
+    let curve_midtones_slider: HorValueSlider = app::widget_from_id("curve_midtones_slider").ok_or("widget_from_id fail")?;
+    let curve_highlights_slider: HorValueSlider = app::widget_from_id("curve_highlights_slider").ok_or("widget_from_id fail")?;
+    let invert_toggle: CheckButton = app::widget_from_id("invert_toggle").ok_or("widget_from_id fail")?;
+    let swap_rb_toggle: CheckButton = app::widget_from_id("swap_rb_toggle").ok_or("widget_from_id fail")?;
+    let isolate_channel_choice: menu::Choice = app::widget_from_id("isolate_channel_choice").ok_or("widget_from_id fail")?;
+    let duotone_choice: menu::Choice = app::widget_from_id("duotone_choice").ok_or("widget_from_id fail")?;
+    let duotone_dark_btn: Button = app::widget_from_id("duotone_dark_btn").ok_or("widget_from_id fail")?;
+    let duotone_light_btn: Button = app::widget_from_id("duotone_light_btn").ok_or("widget_from_id fail")?;
+    let multiplier_choice: menu::Choice = app::widget_from_id("multiplier_choice").ok_or("widget_from_id fail")?;
+    let banner_toggle: CheckButton = app::widget_from_id("banner_toggle").ok_or("widget_from_id fail")?;
+    let banner_input: Input = app::widget_from_id("banner_input").ok_or("widget_from_id fail")?;
+    let forced_colors_input: Input = app::widget_from_id("forced_colors_input").ok_or("widget_from_id fail")?;
+
+    let settings = ImageSettings{
+        no_quantize: no_quantize_toggle.is_checked(),
+        grayscale: grayscale_toggle.is_checked(),
+        grayscale_output: grayscale_output_toggle.is_checked(),
+        reorder_palette: reorder_palette_toggle.is_checked(),
+        quantizer_type: {
+            match || -> Result<QuantizerType, String> {
+                let choice = quantizer_choice.choice()
+                    .ok_or("No quantizer selected")?;
+                let parsed = choice.parse()
+                    .map_err(|err| format!("Couldn't parse quantizer {choice:?}: {err}"))?;
+                Ok(parsed)
+            }() {
+                Ok(res) => res,
+                Err(msg) => {
+                    error_alert(&appmsg, msg);
+                    Default::default()
+                },
+            }
+        },
+        quantizr: quantizer::QuantizrOptions {
+            sample_percent: quantizr_sample_slider.value() as u8,
+            kmeans_iterations: kmeans_slider.value() as u8,
+        },
+        dither_mask: dither_mask::current(),
+        forced_colors: match palette::parse_colors(&forced_colors_input.value()) {
+            Ok(colors) => colors,
+            Err(msg) => {
+                error_alert(&appmsg, format!("{}: {msg}", i18n::tr("Forced colors")));
+                Vec::new()
             },
-            scaler_type: {
-                match || -> Result<ScalerType, String> {
-                    let choice = scaler_type_choice.choice()
-                        .ok_or("No scaler type selected")?;
-                    let parsed = choice.parse()
-                        .map_err(|err| format!("Couldn't parse scaler type {choice:?}: {err}"))?;
-                    Ok(parsed)
-                }() {
-                    Ok(res) => res,
-                    Err(msg) => {
-                        error_alert(&appmsg, msg);
-                        Default::default()
-                    },
-                }
+        },
+        locked_palette: palette::locked(),
+        scaling: scaling_toggle.is_checked(),
+        maxcolors: maxcolors_slider.value() as i32,
+        dithering: dithering_slider.value() as f32,
+        scale: {
+            let value = scale_input.value();
+            value.parse()
+                .map_err(|err| format!("Couldn't parse scale {value:?}: {err}"))?
+        },
+        multiplier: {
+            match || -> Result<_, String> {
+                let choice: String = multiplier_choice.choice()
+                    .ok_or("No multiplier choice selected")?;
+                let choice = choice.strip_suffix("x")
+                    .ok_or_else(|| format!("No x suffix in multiplier choice: {choice:?}"))?;
+                let multiplier = choice.parse()
+                    .map_err(|err| format!("Couldn't parse multiplier {choice:?}: {err}"))?;
+                Ok(multiplier)
+            }() {
+                Ok(res) => res,
+                Err(msg) => {
+                    error_alert(&appmsg, msg);
+                    1
+                },
             }
-        };
+        },
+        color_space: {
+            match || -> Result<ColorSpace, String> {
+                let choice = color_space_choice.choice()
+                    .ok_or("No color space selected")?;
+                let parsed = choice.parse()
+                    .map_err(|err| format!("Couldn't parse color space {choice:?}: {err}"))?;
+                Ok(parsed)
+            }() {
+                Ok(res) => res,
+                Err(msg) => {
+                    error_alert(&appmsg, msg);
+                    Default::default()
+                },
+            }
+        },
+        flatten: {
+            match || -> Result<Flatten, String> {
+                let choice = flatten_choice.choice()
+                    .ok_or("No flatten choice selected")?;
+                let parsed = choice.parse()
+                    .map_err(|err| format!("Couldn't parse flatten choice {choice:?}: {err}"))?;
+                Ok(parsed)
+            }() {
+                Ok(res) => res,
+                Err(msg) => {
+                    error_alert(&appmsg, msg);
+                    Default::default()
+                },
+            }
+        },
+        alpha_threshold: alpha_threshold_slider.value() as u8,
+        transparent_mode: {
+            match || -> Result<TransparentMode, String> {
+                let choice = transparent_mode_choice.choice()
+                    .ok_or("No transparent pixel choice selected")?;
+                let parsed = choice.parse()
+                    .map_err(|err| format!("Couldn't parse transparent pixel choice {choice:?}: {err}"))?;
+                Ok(parsed)
+            }() {
+                Ok(res) => res,
+                Err(msg) => {
+                    error_alert(&appmsg, msg);
+                    Default::default()
+                },
+            }
+        },
+        resize_type: {
+            match || -> Result<ResizeType, String> {
+                let choice = resize_type_choice.choice()
+                    .ok_or("No resize type selected")?;
+                let parsed = choice.parse()
+                    .map_err(|err| format!("Couldn't parse resize type {choice:?}: {err}"))?;
+                Ok(parsed)
+            }() {
+                Ok(res) => res,
+                Err(msg) => {
+                    error_alert(&appmsg, msg);
+                    Default::default()
+                },
+            }
+        },
+        scaler_type: {
+            match || -> Result<ScalerType, String> {
+                let choice = scaler_type_choice.choice()
+                    .ok_or("No scaler type selected")?;
+                let parsed = choice.parse()
+                    .map_err(|err| format!("Couldn't parse scaler type {choice:?}: {err}"))?;
+                Ok(parsed)
+            }() {
+                Ok(res) => res,
+                Err(msg) => {
+                    error_alert(&appmsg, msg);
+                    Default::default()
+                },
+            }
+        },
+        pre_blur: pre_blur_toggle.is_checked(),
+        sharpen: sharpen_slider.value() as f32,
+        edge_darken: edge_darken_slider.value() as f32,
+        posterize: posterize_slider.value() as u32,
+        levels: filters::Levels {
+            black: black_point_slider.value() as u8,
+            white: white_point_slider.value() as u8,
+            curve: [curve_shadows_slider.value() as u8, curve_midtones_slider.value() as u8, curve_highlights_slider.value() as u8],
+        },
+        invert: invert_toggle.is_checked(),
+        swap_rb: swap_rb_toggle.is_checked(),
+        isolate_channel: {
+            match || -> Result<filters::Channel, String> {
+                let choice = isolate_channel_choice.choice()
+                    .ok_or("No channel selected")?;
+                let parsed = choice.parse()
+                    .map_err(|err| format!("Couldn't parse channel {choice:?}: {err}"))?;
+                Ok(parsed)
+            }() {
+                Ok(res) => res,
+                Err(msg) => {
+                    error_alert(&appmsg, msg);
+                    Default::default()
+                },
+            }
+        },
+        duotone: if duotone_choice.choice().as_deref() == Some(duotone::OFF) {
+            None
+        } else {
+            // The presets set the button colors, so these are right either way
+            let (dr, dg, db) = duotone_dark_btn.color().to_rgb();
+            let (lr, lg, lb) = duotone_light_btn.color().to_rgb();
+            Some(([dr, dg, db], [lr, lg, lb]))
+        },
+        banner: banner_toggle.is_checked(),
+        banner_text: banner_input.value(),
+        pipeline: pipeline::current(),
+    };
 
-        bg.send_or_replace_if(BgMessage::is_update, msg)
+    Ok(settings)
+}
+
+fn send_updateimage(appmsg: &mpsc::Sender<AppMessage>, bg: &mq::MessageQueueSender::<BgMessage>) -> () {
+    queue_updateimage(appmsg, bg, true);
+}
+
+// With record_history false the update doesn't become an undo step of its own (see slider_update)
+fn queue_updateimage(appmsg: &mpsc::Sender<AppMessage>, bg: &mq::MessageQueueSender::<BgMessage>, record_history: bool) -> () {
+    match || -> Result<(), String> {
+        let settings = get_image_settings(appmsg)?;
+
+        if record_history {
+            SETTINGS_HISTORY.lock()
+                .map_err(|err| format!("Couldn't lock settings history: {err}"))?
+                .push(settings.clone());
+        }
+
+        bg.send_or_replace_if(BgMessage::is_update, BgMessage::UpdateImage(settings))
             .map_err(|err| format!("Send error: {err}"))?;
 
         Ok(())
@@ -938,104 +2649,878 @@ fn send_updateimage(appmsg: &mpsc::Sender<AppMessage>, bg: &mq::MessageQueueSend
     }
 }
 
+// Like send_updateimage, but for a quick low resolution preview while a slider is being dragged.
+// Doesn't go into the undo history, as the full update follows when the drag ends.
+fn send_previewimage(appmsg: &mpsc::Sender<AppMessage>, bg: &mq::MessageQueueSender::<BgMessage>) -> () {
+    match || -> Result<(), String> {
+        let settings = get_image_settings(appmsg)?;
+        bg.send_or_replace_if(BgMessage::is_update, BgMessage::PreviewImage(settings))
+            .map_err(|err| format!("Send error: {err}"))?;
+        Ok(())
+    }() {
+        Ok(()) => (),
+        Err(errmsg) => error_alert(&appmsg, format!("{}:\n{}", function!(), errmsg)),
+    }
+}
+
+// Sliders do the quick preview while being dragged, and the real thing once let go of. Only letting
+// go (or a step with the keyboard or mouse wheel) makes an undo step, so a whole drag undoes in one go.
+fn slider_update(appmsg: &mpsc::Sender<AppMessage>, bg: &mq::MessageQueueSender::<BgMessage>) -> () {
+    match app::event() {
+        Event::Drag => send_previewimage(appmsg, bg),
+        Event::Push => queue_updateimage(appmsg, bg, false),
+        _ => send_updateimage(appmsg, bg),
+    }
+}
+
+// Read the OSC sending options from the widgets
+fn get_send_osc_opts(shader_profile: &shader_profile::ShaderProfile) -> Result<send_osc::SendOSCOpts, String> {
+    let osc_pixfmt_choice: menu::Choice = app::widget_from_id("osc_pixfmt_choice").ok_or("widget_from_id fail")?;
+    let osc_speed_slider: HorValueSlider = app::widget_from_id("osc_speed_slider").ok_or("widget_from_id fail")?;
+    let osc_rle_compression_toggle: CheckButton = app::widget_from_id("osc_rle_compression_toggle").ok_or("widget_from_id fail")?;
+    let osc_auto_compression_toggle: CheckButton = app::widget_from_id("osc_auto_compression_toggle").ok_or("widget_from_id fail")?;
+    let osc_confirm_toggle: CheckButton = app::widget_from_id("osc_confirm_toggle").ok_or("widget_from_id fail")?;
+    let osc_target_input: Input = app::widget_from_id("osc_target_input").ok_or("widget_from_id fail")?;
+    let osc_key_toggle: CheckButton = app::widget_from_id("osc_key_toggle").ok_or("widget_from_id fail")?;
+    let osc_adaptive_toggle: CheckButton = app::widget_from_id("osc_adaptive_toggle").ok_or("widget_from_id fail")?;
+    let osc_key_spinner: fltk::misc::Spinner = app::widget_from_id("osc_key_spinner").ok_or("widget_from_id fail")?;
+    let osc_warn_input: IntInput = app::widget_from_id("osc_warn_input").ok_or("widget_from_id fail")?;
+    let osc_burst_input: IntInput = app::widget_from_id("osc_burst_input").ok_or("widget_from_id fail")?;
+    let osc_burst_wait_input: IntInput = app::widget_from_id("osc_burst_wait_input").ok_or("widget_from_id fail")?;
+    let osc_retries_input: IntInput = app::widget_from_id("osc_retries_input").ok_or("widget_from_id fail")?;
+    let osc_bandwidth_input: IntInput = app::widget_from_id("osc_bandwidth_input").ok_or("widget_from_id fail")?;
+
+    let target = osc_target_input.value();
+    let (transport, target) = transport::parse_target(&target);
+    let target = if target.trim().is_empty() {
+        None
+    } else {
+        Some(target.trim().to_socket_addrs()
+             .map_err(|err| format!("Couldn't resolve OSC target {target:?}: {err}"))?
+             .next()
+             .ok_or(format!("No address found for OSC target {target:?}"))?)
+    };
+
+    Ok(send_osc::SendOSCOpts{
+        pixfmt: osc_pixfmt_choice.choice()
+            .ok_or("No PixFmt selected")?
+            .parse()
+            .map_err(|err| format!("Couldn't parse PixFmt: {err}"))?,
+        msgs_per_second: osc_speed_slider.value(),
+        rle_compression: osc_rle_compression_toggle.value(),
+        auto_compression: osc_auto_compression_toggle.value(),
+        confirm: osc_confirm_toggle.value(),
+        profile: shader_profile.clone(),
+        target: target,
+        transport: transport,
+        key_index: if osc_key_toggle.is_checked() { Some(osc_key_spinner.value() as u8) } else { None },
+        adaptive_rate: osc_adaptive_toggle.is_checked(),
+        warn_after: match osc_warn_input.value().trim() {
+            "" | "0" => None,
+            secs => Some(std::time::Duration::from_secs(secs.parse().map_err(|err| format!("Bad send warning time {secs:?}: {err}"))?)),
+        },
+        burst_chunks: match osc_burst_input.value().trim() {
+            "" => 0,
+            chunks => chunks.parse().map_err(|err| format!("Bad burst size {chunks:?}: {err}"))?,
+        },
+        burst_wait: match osc_burst_wait_input.value().trim() {
+            "" => std::time::Duration::ZERO,
+            ms => std::time::Duration::from_millis(ms.parse().map_err(|err| format!("Bad burst wait {ms:?}: {err}"))?),
+        },
+        send_retries: match osc_retries_input.value().trim() {
+            "" => 0,
+            retries => retries.parse().map_err(|err| format!("Bad retry count {retries:?}: {err}"))?,
+        },
+        bandwidth: match osc_bandwidth_input.value().trim() {
+            "" | "0" => None,
+            kbps => Some(kbps.parse::<f64>().map_err(|err| format!("Bad bandwidth budget {kbps:?}: {err}"))?*1000.0),
+        },
+        check_receiver: true,
+        ..Default::default()
+    })
+}
+
+// Have the BG thread recompute the transfer estimate for the current send settings
+fn send_estimate_transfer(appmsg: &mpsc::Sender<AppMessage>, bg: &mq::MessageQueueSender<BgMessage>, shader_profile: &shader_profile::ShaderProfile) {
+    match || -> Result<(), String> {
+        let opts = get_send_osc_opts(shader_profile)?;
+        bg.send_or_replace_if(|msg| matches!(msg, BgMessage::EstimateTransfer(..)), BgMessage::EstimateTransfer(opts))
+            .map_err(|err| format!("Couldn't send message to BG thread: {err}"))?;
+        Ok(())
+    }() {
+        Ok(()) => (),
+        Err(errmsg) => error_alert(appmsg, format!("{}:\n{}", function!(), errmsg)),
+    }
+}
+
+// Grab the screen and send it with the current settings (asking first if the confirmation is on)
+fn capture_and_send(appmsg: &mpsc::Sender<AppMessage>, bg: &mq::MessageQueueSender<BgMessage>, shader_profile: &shader_profile::ShaderProfile) {
+    match || -> Result<(), String> {
+        // There's nothing to send unless it gets quantized (like playlist::process_item)
+        let settings = ImageSettings { no_quantize: false, ..get_image_settings(appmsg)? };
+        // Confirmation included, a whole screen is just what shouldn't go out by accident
+        let opts = get_send_osc_opts(shader_profile)?;
+
+        // The BG thread handles these in order, so by the time we get to SendOSC the capture has
+        // been processed
+        for msg in [BgMessage::CaptureScreen, BgMessage::UpdateImage(settings), BgMessage::SendOSC(opts, None)] {
+            bg.send(msg).map_err(|err| format!("Couldn't send message to BG thread: {err}"))?;
+        }
+        Ok(())
+    }() {
+        Ok(()) => (),
+        Err(errmsg) => error_alert(appmsg, format!("{}:\n{}", function!(), errmsg)),
+    }
+}
+
+// Send with whatever the send widgets are set to, for the HTTP API. That includes the
+// confirmation, if it's switched on the send waits for somebody to click it.
+fn remote_send(appmsg: &mpsc::Sender<AppMessage>, bg: &mq::MessageQueueSender<BgMessage>, shader_profile: &shader_profile::ShaderProfile) {
+    match || -> Result<(), String> {
+        let opts = get_send_osc_opts(shader_profile)?;
+        bg.send(BgMessage::SendOSC(opts, None)).map_err(|err| format!("Couldn't send message to BG thread: {err}"))?;
+        Ok(())
+    }() {
+        Ok(()) => (),
+        Err(errmsg) => error_alert(appmsg, format!("{}:\n{}", function!(), errmsg)),
+    }
+}
+
+// Sets all the widgets back to the given image settings (used for undo/redo)
+fn set_image_settings_widgets(settings: &ImageSettings) -> Result<(), String> {
+    let ImageSettings{
+        no_quantize,
+        grayscale,
+        grayscale_output,
+        reorder_palette,
+        quantizer_type,
+        quantizr,
+        dither_mask,
+        forced_colors,
+        locked_palette,
+        color_space,
+        flatten,
+        alpha_threshold,
+        transparent_mode,
+        maxcolors,
+        dithering,
+        scaling,
+        scale,
+        multiplier,
+        resize_type,
+        scaler_type,
+        pre_blur,
+        sharpen,
+        edge_darken,
+        posterize,
+        levels,
+        invert,
+        swap_rb,
+        isolate_channel,
+        duotone,
+        banner,
+        banner_text,
+        pipeline,
+    } = settings;
+
+    let no_quantize_toggle: CheckButton = app::widget_from_id("no_quantize_toggle").ok_or("widget_from_id fail")?;
+    let grayscale_toggle: CheckButton = app::widget_from_id("grayscale_toggle").ok_or("widget_from_id fail")?;
+    let grayscale_output_toggle: CheckButton = app::widget_from_id("grayscale_output_toggle").ok_or("widget_from_id fail")?;
+    let reorder_palette_toggle: CheckButton = app::widget_from_id("reorder_palette_toggle").ok_or("widget_from_id fail")?;
+    let lock_palette_toggle: CheckButton = app::widget_from_id("lock_palette_toggle").ok_or("widget_from_id fail")?;
+    let mut maxcolors_slider: HorValueSlider = app::widget_from_id("maxcolors_slider").ok_or("widget_from_id fail")?;
+    let mut dithering_slider: HorValueSlider = app::widget_from_id("dithering_slider").ok_or("widget_from_id fail")?;
+    let scaling_toggle: CheckButton = app::widget_from_id("scaling_toggle").ok_or("widget_from_id fail")?;
+    let mut scale_input: IntInput = app::widget_from_id("scale_input").ok_or("widget_from_id fail")?;
+    let mut quantizer_choice: menu::Choice = app::widget_from_id("quantizer_choice").ok_or("widget_from_id fail")?;
+    let mut quantizr_sample_slider: HorValueSlider = app::widget_from_id("quantizr_sample_slider").ok_or("widget_from_id fail")?;
+    let mut kmeans_slider: HorValueSlider = app::widget_from_id("kmeans_slider").ok_or("widget_from_id fail")?;
+    let mut dither_mask_choice: menu::Choice = app::widget_from_id("dither_mask_choice").ok_or("widget_from_id fail")?;
+    let mut color_space_choice: menu::Choice = app::widget_from_id("color_space_choice").ok_or("widget_from_id fail")?;
+    let mut flatten_choice: menu::Choice = app::widget_from_id("flatten_choice").ok_or("widget_from_id fail")?;
+    let mut alpha_threshold_slider: HorValueSlider = app::widget_from_id("alpha_threshold_slider").ok_or("widget_from_id fail")?;
+    let mut transparent_mode_choice: menu::Choice = app::widget_from_id("transparent_mode_choice").ok_or("widget_from_id fail")?;
+    let mut resize_type_choice: menu::Choice = app::widget_from_id("resize_type_choice").ok_or("widget_from_id fail")?;
+    let mut scaler_type_choice: menu::Choice = app::widget_from_id("scaler_type_choice").ok_or("widget_from_id fail")?;
+    let pre_blur_toggle: CheckButton = app::widget_from_id("pre_blur_toggle").ok_or("widget_from_id fail")?;
+    let mut sharpen_slider: HorValueSlider = app::widget_from_id("sharpen_slider").ok_or("widget_from_id fail")?;
+    let mut posterize_slider: HorValueSlider = app::widget_from_id("posterize_slider").ok_or("widget_from_id fail")?;
+    let mut edge_darken_slider: HorValueSlider = app::widget_from_id("edge_darken_slider").ok_or("widget_from_id fail")?;
+    let mut black_point_slider: HorValueSlider = app::widget_from_id("black_point_slider").ok_or("widget_from_id fail")?;
+    let mut white_point_slider: HorValueSlider = app::widget_from_id("white_point_slider").ok_or("widget_from_id fail")?;
+    let mut curve_shadows_slider: HorValueSlider = app::widget_from_id("curve_shadows_slider").ok_or("widget_from_id fail")?;
+    let mut curve_midtones_slider: HorValueSlider = app::widget_from_id("curve_midtones_slider").ok_or("widget_from_id fail")?;
+    let mut curve_highlights_slider: HorValueSlider = app::widget_from_id("curve_highlights_slider").ok_or("widget_from_id fail")?;
+    let invert_toggle: CheckButton = app::widget_from_id("invert_toggle").ok_or("widget_from_id fail")?;
+    let swap_rb_toggle: CheckButton = app::widget_from_id("swap_rb_toggle").ok_or("widget_from_id fail")?;
+    let mut isolate_channel_choice: menu::Choice = app::widget_from_id("isolate_channel_choice").ok_or("widget_from_id fail")?;
+    let mut duotone_choice: menu::Choice = app::widget_from_id("duotone_choice").ok_or("widget_from_id fail")?;
+    let mut duotone_dark_btn: Button = app::widget_from_id("duotone_dark_btn").ok_or("widget_from_id fail")?;
+    let mut duotone_light_btn: Button = app::widget_from_id("duotone_light_btn").ok_or("widget_from_id fail")?;
+    let mut multiplier_choice: menu::Choice = app::widget_from_id("multiplier_choice").ok_or("widget_from_id fail")?;
+    let banner_toggle: CheckButton = app::widget_from_id("banner_toggle").ok_or("widget_from_id fail")?;
+    let mut banner_input: Input = app::widget_from_id("banner_input").ok_or("widget_from_id fail")?;
+    let mut forced_colors_input: Input = app::widget_from_id("forced_colors_input").ok_or("widget_from_id fail")?;
+
+    no_quantize_toggle.set_checked(*no_quantize);
+    grayscale_toggle.set_checked(*grayscale);
+    grayscale_output_toggle.set_checked(*grayscale_output);
+    reorder_palette_toggle.set_checked(*reorder_palette);
+    lock_palette_toggle.set_checked(locked_palette.is_some());
+    palette::set_locked(locked_palette.clone());
+    maxcolors_slider.set_value(*maxcolors as f64);
+    dithering_slider.set_value(*dithering as f64);
+    scaling_toggle.set_checked(*scaling);
+    scale_input.set_value(&scale.to_string());
+    // The enums use the same names for Debug as for VariantNames
+    color_space_choice.set_value(color_space_choice.find_index(&format!("{color_space:?}")));
+    quantizer_choice.set_value(quantizer_choice.find_index(&format!("{quantizer_type:?}")));
+    quantizr_sample_slider.set_value(quantizr.sample_percent as f64);
+    kmeans_slider.set_value(quantizr.kmeans_iterations as f64);
+    dither_mask_choice.set_value(dither_mask_choice.find_index(&format!("{:?}", dither_mask.mode)));
+    dither_mask::set_current(dither_mask.clone());
+    forced_colors_input.set_value(&palette::format_colors(forced_colors));
+    flatten_choice.set_value(flatten_choice.find_index(&format!("{flatten:?}")));
+    alpha_threshold_slider.set_value(*alpha_threshold as f64);
+    transparent_mode_choice.set_value(transparent_mode_choice.find_index(&format!("{transparent_mode:?}")));
+    resize_type_choice.set_value(resize_type_choice.find_index(&format!("{resize_type:?}")));
+    scaler_type_choice.set_value(scaler_type_choice.find_index(&format!("{scaler_type:?}")));
+    pre_blur_toggle.set_checked(*pre_blur);
+    sharpen_slider.set_value(*sharpen as f64);
+    edge_darken_slider.set_value(*edge_darken as f64);
+    posterize_slider.set_value(*posterize as f64);
+    black_point_slider.set_value(levels.black as f64);
+    white_point_slider.set_value(levels.white as f64);
+    curve_shadows_slider.set_value(levels.curve[0] as f64);
+    curve_midtones_slider.set_value(levels.curve[1] as f64);
+    curve_highlights_slider.set_value(levels.curve[2] as f64);
+    invert_toggle.set_checked(*invert);
+    swap_rb_toggle.set_checked(*swap_rb);
+    isolate_channel_choice.set_value(isolate_channel_choice.find_index(&format!("{isolate_channel:?}")));
+    match duotone {
+        None => duotone_choice.set_value(duotone_choice.find_index(duotone::OFF)),
+        Some((dark, light)) => {
+            duotone_dark_btn.set_color(Color::from_rgb(dark[0], dark[1], dark[2]));
+            duotone_light_btn.set_color(Color::from_rgb(light[0], light[1], light[2]));
+            duotone_dark_btn.redraw();
+            duotone_light_btn.redraw();
+            let name = duotone::preset_name(*dark, *light).unwrap_or(duotone::CUSTOM);
+            duotone_choice.set_value(duotone_choice.find_index(name));
+        },
+    }
+    multiplier_choice.set_value(multiplier_choice.find_index(&format!("{multiplier}x")));
+    banner_toggle.set_checked(*banner);
+    banner_input.set_value(banner_text);
+    pipeline::set_current(pipeline.clone());
+    pipeline::refresh_window();
+
+    Ok(())
+}
+
+// The send widgets as they are, for project files
+fn get_send_settings(shader_profile: &shader_profile::ShaderProfile) -> Result<project::SendSettings, String> {
+    let osc_pixfmt_choice: menu::Choice = app::widget_from_id("osc_pixfmt_choice").ok_or("widget_from_id fail")?;
+    let osc_speed_slider: HorValueSlider = app::widget_from_id("osc_speed_slider").ok_or("widget_from_id fail")?;
+    let osc_rle_compression_toggle: CheckButton = app::widget_from_id("osc_rle_compression_toggle").ok_or("widget_from_id fail")?;
+    let osc_auto_compression_toggle: CheckButton = app::widget_from_id("osc_auto_compression_toggle").ok_or("widget_from_id fail")?;
+    let osc_confirm_toggle: CheckButton = app::widget_from_id("osc_confirm_toggle").ok_or("widget_from_id fail")?;
+    let osc_target_input: Input = app::widget_from_id("osc_target_input").ok_or("widget_from_id fail")?;
+    let osc_key_toggle: CheckButton = app::widget_from_id("osc_key_toggle").ok_or("widget_from_id fail")?;
+    let osc_adaptive_toggle: CheckButton = app::widget_from_id("osc_adaptive_toggle").ok_or("widget_from_id fail")?;
+    let osc_key_spinner: fltk::misc::Spinner = app::widget_from_id("osc_key_spinner").ok_or("widget_from_id fail")?;
+    let osc_warn_input: IntInput = app::widget_from_id("osc_warn_input").ok_or("widget_from_id fail")?;
+    let osc_burst_input: IntInput = app::widget_from_id("osc_burst_input").ok_or("widget_from_id fail")?;
+    let osc_burst_wait_input: IntInput = app::widget_from_id("osc_burst_wait_input").ok_or("widget_from_id fail")?;
+    let osc_retries_input: IntInput = app::widget_from_id("osc_retries_input").ok_or("widget_from_id fail")?;
+    let osc_bandwidth_input: IntInput = app::widget_from_id("osc_bandwidth_input").ok_or("widget_from_id fail")?;
+
+    Ok(project::SendSettings {
+        target: osc_target_input.value(),
+        prefix: shader_profile.prefix.clone(),
+        pixfmt: osc_pixfmt_choice.choice().unwrap_or_default(),
+        msgs_per_second: osc_speed_slider.value(),
+        rle_compression: osc_rle_compression_toggle.is_checked(),
+        auto_compression: osc_auto_compression_toggle.is_checked(),
+        confirm: osc_confirm_toggle.is_checked(),
+        adaptive_rate: osc_adaptive_toggle.is_checked(),
+        key_index: if osc_key_toggle.is_checked() { Some(osc_key_spinner.value() as u8) } else { None },
+        warn_secs: osc_warn_input.value(),
+        burst_chunks: osc_burst_input.value(),
+        burst_wait_ms: osc_burst_wait_input.value(),
+        retries: osc_retries_input.value(),
+        bandwidth_kbps: osc_bandwidth_input.value(),
+    })
+}
+
+// Sets the send widgets back to what a project had. The shader profile prefix is up to the caller.
+fn set_send_settings_widgets(settings: &project::SendSettings) -> Result<(), String> {
+    let mut osc_pixfmt_choice: menu::Choice = app::widget_from_id("osc_pixfmt_choice").ok_or("widget_from_id fail")?;
+    let mut osc_speed_slider: HorValueSlider = app::widget_from_id("osc_speed_slider").ok_or("widget_from_id fail")?;
+    let osc_rle_compression_toggle: CheckButton = app::widget_from_id("osc_rle_compression_toggle").ok_or("widget_from_id fail")?;
+    let mut osc_auto_compression_toggle: CheckButton = app::widget_from_id("osc_auto_compression_toggle").ok_or("widget_from_id fail")?;
+    let osc_confirm_toggle: CheckButton = app::widget_from_id("osc_confirm_toggle").ok_or("widget_from_id fail")?;
+    let mut osc_target_input: Input = app::widget_from_id("osc_target_input").ok_or("widget_from_id fail")?;
+    let mut osc_key_toggle: CheckButton = app::widget_from_id("osc_key_toggle").ok_or("widget_from_id fail")?;
+    let osc_adaptive_toggle: CheckButton = app::widget_from_id("osc_adaptive_toggle").ok_or("widget_from_id fail")?;
+    let mut osc_key_spinner: fltk::misc::Spinner = app::widget_from_id("osc_key_spinner").ok_or("widget_from_id fail")?;
+    let mut osc_warn_input: IntInput = app::widget_from_id("osc_warn_input").ok_or("widget_from_id fail")?;
+    let mut osc_burst_input: IntInput = app::widget_from_id("osc_burst_input").ok_or("widget_from_id fail")?;
+    let mut osc_burst_wait_input: IntInput = app::widget_from_id("osc_burst_wait_input").ok_or("widget_from_id fail")?;
+    let mut osc_retries_input: IntInput = app::widget_from_id("osc_retries_input").ok_or("widget_from_id fail")?;
+    let mut osc_bandwidth_input: IntInput = app::widget_from_id("osc_bandwidth_input").ok_or("widget_from_id fail")?;
+
+    osc_target_input.set_value(&settings.target);
+    let pixfmt = osc_pixfmt_choice.find_index(&settings.pixfmt);
+    if pixfmt >= 0 {
+        osc_pixfmt_choice.set_value(pixfmt);
+    }
+    osc_speed_slider.set_value(settings.msgs_per_second);
+    osc_rle_compression_toggle.set_checked(settings.rle_compression);
+    osc_auto_compression_toggle.set_checked(settings.auto_compression);
+    osc_confirm_toggle.set_checked(settings.confirm);
+    osc_adaptive_toggle.set_checked(settings.adaptive_rate);
+    osc_key_toggle.set_checked(settings.key_index.is_some());
+    if let Some(key_index) = settings.key_index {
+        osc_key_spinner.set_value(key_index as f64);
+    }
+    osc_warn_input.set_value(&settings.warn_secs);
+    osc_burst_input.set_value(&settings.burst_chunks);
+    osc_burst_wait_input.set_value(&settings.burst_wait_ms);
+    osc_retries_input.set_value(&settings.retries);
+    osc_bandwidth_input.set_value(&settings.bandwidth_kbps);
+
+    // These (de)activate the widgets that depend on them, and update the transfer estimate
+    osc_auto_compression_toggle.do_callback();
+    osc_key_toggle.do_callback();
+    osc_bandwidth_input.do_callback();
+
+    Ok(())
+}
+
+const OSC_SPEED_DEFAULT: f64 = 5.0;
+
+// "Don't ask again" in the send confirmation sticks, like the other defaults in the config
+fn save_confirm_send(confirm: bool) {
+    match || -> Result<(), Box<dyn Error>> {
+        let mut config = config::Config::load()?;
+        config.confirm_send = confirm;
+        config.save()?;
+        Ok(())
+    }() {
+        Ok(()) => (),
+        Err(err) => warn!("Couldn't save the confirmation setting: {err}"),
+    }
+}
+
+// Remember the scheme/theme right away, without the user having to go for "Save as defaults"
+fn save_view_settings() {
+    match || -> Result<(), Box<dyn Error>> {
+        let mut config = config::Config::load()?;
+        (config.scheme, config.theme) = theme::current();
+        config.ui_scale = theme::ui_scale();
+        config.font_size = theme::font_size();
+        config.save()?;
+        Ok(())
+    }() {
+        Ok(()) => (),
+        Err(err) => warn!("Couldn't save view settings: {err}"),
+    }
+}
+
+// Window geometry and panel widths, so that things come back the way they were left
+fn save_layout(wind: &Window, palette_frame: &Frame, scroll: &fltk::group::Scroll) {
+    match || -> Result<(), Box<dyn Error>> {
+        let mut config = config::Config::load()?;
+        config.window = Some([wind.x(), wind.y(), wind.w(), wind.h()]);
+        config.palette_width = palette_frame.w();
+        config.control_width = scroll.w();
+        config.save()?;
+        Ok(())
+    }() {
+        Ok(()) => (),
+        Err(err) => warn!("Couldn't save window layout: {err}"),
+    }
+}
+
+fn add_view_menu(menubar: &mut menu::MenuBar) {
+    let (current_scheme, current_theme) = theme::current();
+
+    for scheme in theme::SCHEMES {
+        let path = format!("View/Scheme/{scheme}");
+        menubar.add(&path, Shortcut::None, menu::MenuFlag::Radio, move |_| {
+            theme::set_scheme(scheme);
+            save_view_settings();
+        });
+        if scheme == current_scheme {
+            if let Some(mut item) = menubar.find_item(&path) {
+                item.set();
+            }
+        }
+    }
+
+    // Being in different submenus keeps the two sets of radio items apart
+    for name in theme::THEMES {
+        let path = format!("View/Colors/{name}");
+        menubar.add(&path, Shortcut::None, menu::MenuFlag::Radio, move |_| {
+            theme::set_theme(name);
+            save_view_settings();
+        });
+        if name == current_theme {
+            if let Some(mut item) = menubar.find_item(&path) {
+                item.set();
+            }
+        }
+    }
+
+    for scale in theme::UI_SCALES {
+        let path = format!("View/UI scale/{:.0}%", scale*100.0);
+        menubar.add(&path, Shortcut::None, menu::MenuFlag::Radio, move |_| {
+            theme::set_ui_scale(scale);
+            save_view_settings();
+        });
+        if scale == theme::ui_scale() {
+            if let Some(mut item) = menubar.find_item(&path) {
+                item.set();
+            }
+        }
+    }
+
+    for size in theme::FONT_SIZES {
+        let path = format!("View/Font size/{size}");
+        menubar.add(&path, Shortcut::None, menu::MenuFlag::Radio, move |_| {
+            theme::set_font_size(size);
+            save_view_settings();
+        });
+        if size == theme::font_size() {
+            if let Some(mut item) = menubar.find_item(&path) {
+                item.set();
+            }
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let app = app::App::default().with_scheme(app::Scheme::Gleam);
+    // Command line tools, which don't need the GUI at all
+    if std::env::args().nth(1).as_deref() == Some("pack") {
+        return pack_cli::run(std::env::args().skip(2));
+    }
+
+    log_panel::init()?;
+
+    let (config, config_error) = match config::Config::load() {
+        Ok(config) => (config, None),
+        Err(err) => (config::Config::default(), Some(err.to_string())),
+    };
+
+    let app = app::App::default();
+    theme::set_scheme(&config.scheme);
+    theme::set_theme(&config.theme);
+    // Before any widgets exist, so they all start out at the right size
+    theme::set_font_size(config.font_size);
+    theme::set_ui_scale(config.ui_scale);
+    MAX_SOURCE_PIXELS.store((config.max_source_megapixels.max(0.0) * 1e6) as u64, Ordering::Relaxed);
+    // Before any widgets get created, so they get labeled in the right language from the start
+    match i18n::Language::from_code(&config.language) {
+        Some(lang) => i18n::set_language(lang),
+        None => warn!("Unknown language {:?} in config", config.language),
+    }
+    if let Some(err) = config_error {
+        error!("{err}");
+        dialog::alert_default(&format!("Couldn't load the config file, using defaults:\n{err}"));
+    }
     let screen_size = fltk::app::screen_size();
-    println!("Screen size; {}x{}", screen_size.0, screen_size.1);
+    debug!("Screen size; {}x{}", screen_size.0, screen_size.1);
     let screen_size_int: (i32, i32) = (screen_size.0 as i32, screen_size.1 as i32);
-    let mut wind = Window::default().with_size(
-        min(1600, screen_size_int.0 - 64),
-        min(1000, screen_size_int.1 - 64)
-    );
+    let mut wind = match config.window {
+        // Only if it still fits on the screen, it might have been on a monitor that's gone now
+        Some([x, y, w, h]) if w >= 200 && h >= 200
+            && x >= 0 && y >= 0 && x + w <= screen_size_int.0 && y + h <= screen_size_int.1 => {
+            Window::new(x, y, w, h, None)
+        },
+        _ => Window::default().with_size(
+            min(1600, screen_size_int.0 - 64),
+            min(1000, screen_size_int.1 - 64)
+        ),
+    };
 
     let small_screen = screen_size_int.1 < 1000;
 
+    let mut main_col = Flex::default_fill().column();
+    let mut menubar = menu::MenuBar::default();
+    main_col.fixed(&menubar, 25);
+    add_view_menu(&mut menubar);
+
     let mut row = Flex::default_fill().row();
     // row.set_margin(20);
-    row.set_spacing(20);
+    // Together with the splitters this still gives 20 pixels between the panels
+    row.set_spacing((20 - splitter::WIDTH)/2);
+    let mut image_col = Flex::default_fill().column();
     let mut frame = Frame::default_fill().with_id("frame");
     frame.set_frame(FrameType::DownBox);
-
-    let palette_frame = Frame::default_fill().with_id("palette_frame");
+    compare::attach(&mut frame);
+    // Info strip below the image
+    let mut info_row = Flex::default_fill().row();
+    let mut info_frame = Frame::default_fill().with_id("info_frame");
+    info_frame.set_align(Align::Left | Align::Inside);
+    // Busy indicator, while the BG thread is loading or processing
+    let mut progress_frame = Frame::default_fill();
+    progress_frame.set_align(Align::Right | Align::Inside);
+    info_row.fixed(&progress_frame, 220);
+    info_row.end();
+    image_col.fixed(&info_row, 30);
+    image_col.end();
+
+    let mut palette_splitter = splitter::new(&mut row);
+    let mut palette_frame = Frame::default_fill().with_id("palette_frame");
     // palette_frame.set_frame(FrameType::DownBox);
-    row.fixed(&palette_frame, 50);
-
+    row.fixed(&palette_frame, config.palette_width);
+    splitter::attach(&mut palette_splitter, &row, &palette_frame, 20, |_| ());
+
+    // Collapsible (hidden by default) histograms of the source and the quantized output
+    let mut histogram_panel = Flex::default_fill().column();
+    histogram_panel.set_spacing(5);
+    let histogram_source_label = i18n::labeled(Frame::default_fill(), "Source histogram");
+    histogram_panel.fixed(&histogram_source_label, 20);
+    let mut histogram_source_frame = Frame::default_fill().with_id("histogram_source_frame");
+    histogram_source_frame.set_frame(FrameType::DownBox);
+    let histogram_output_label = i18n::labeled(Frame::default_fill(), "Output histogram");
+    histogram_panel.fixed(&histogram_output_label, 20);
+    let mut histogram_output_frame = Frame::default_fill().with_id("histogram_output_frame");
+    histogram_output_frame.set_frame(FrameType::DownBox);
+    histogram_panel.end();
+    row.fixed(&histogram_panel, 260);
+    histogram_panel.hide();
+
+    // Also collapsible: the latest stage timings (see metrics)
+    let mut performance_panel = Flex::default_fill().column();
+    performance_panel.set_spacing(5);
+    let performance_label = i18n::labeled(Frame::default_fill(), "Performance");
+    performance_panel.fixed(&performance_label, 20);
+    let mut performance_display = fltk::text::TextDisplay::default_fill();
+    performance_display.set_buffer(fltk::text::TextBuffer::default());
+    performance_display.set_text_font(Font::Courier);
+    let mut performance_clear_btn = i18n::labeled(Button::default(), "Clear");
+    performance_panel.fixed(&performance_clear_btn, 30);
+    performance_panel.end();
+    row.fixed(&performance_panel, 300);
+    performance_panel.hide();
+
+    let mut control_splitter = splitter::new(&mut row);
     let scroll = fltk::group::Scroll::default_fill();
-    row.fixed(&scroll, 300);
+    row.fixed(&scroll, config.control_width);
 
+    // Leave room for the scrollbar
+    const SCROLLBAR_ROOM: i32 = 20;
     let mut col = Flex::default_fill().column();
-    row.fixed(&col, 280);
+    col.set_size(config.control_width - SCROLLBAR_ROOM, col.h());
+    splitter::attach(&mut control_splitter, &row, &scroll, 200, {
+        let mut col = col.clone();
+        move |w| {
+            col.set_size(w - SCROLLBAR_ROOM, col.h());
+            col.layout();
+        }
+    });
     col.set_margin(20);
     col.set_spacing(if small_screen { 15 } else { 20 });
-    let mut openbtn = Button::default().with_label("Open");
-    let mut savebtn = Button::default().with_label("Save").with_id("savebtn");
+    let mut openbtn = i18n::labeled(Button::default(), "Open");
+    let mut savebtn = i18n::labeled(Button::default(), "Save").with_id("savebtn");
+    let mut full_resolution_btn = i18n::labeled(Button::default(), "Reload at full resolution").with_id("full_resolution_btn");
+    full_resolution_btn.set_tooltip("The image was scaled down on load, as it's bigger than max_source_megapixels in the config");
+    full_resolution_btn.deactivate();
     savebtn.deactivate();
-    let mut clearbtn = Button::default().with_label("Clear");
-
-    let mut no_quantize_toggle = CheckButton::default().with_label("Disable quantization").with_id("no_quantize_toggle");
-    let mut grayscale_toggle = CheckButton::default().with_label("Grayscale the image\nbefore converting").with_id("grayscale_toggle");
-    let mut grayscale_output_toggle = CheckButton::default().with_label("Output the palette\nindexes as grayscale").with_id("grayscale_output_toggle");
-    let mut reorder_palette_toggle = CheckButton::default().with_label("Sort palette").with_id("reorder_palette_toggle");
+    let mut open_project_btn = i18n::labeled(Button::default(), "Open project");
+    let mut save_project_btn = i18n::labeled(Button::default(), "Save project");
+    let mut clearbtn = i18n::labeled(Button::default(), "Clear");
+    let mut comparebtn = i18n::labeled(Button::default(), "Compare settings");
+    let mut pipeline_btn = i18n::labeled(Button::default(), "Pipeline...");
+    let mut pixel_view_btn = i18n::labeled(Button::default(), "View 1:1");
+    let mut histogram_toggle = i18n::labeled(CheckButton::default(), "Show histograms");
+    let mut performance_toggle = i18n::labeled(CheckButton::default(), "Show performance");
+    let mut compare_toggle = i18n::labeled(CheckButton::default(), "Compare before/after");
+    let mut letterbox_toggle = i18n::labeled(CheckButton::default(), "Mark letterbox padding");
+    letterbox_toggle.set_checked(true);
+    let mut colorblind_choice = i18n::labeled(menu::Choice::default(), "Color blindness preview:");
+    colorblind_choice.add_choice(&colorblind::ColorBlindness::VARIANTS.join("|"));
+    colorblind_choice.set_value(0);
+    colorblind_choice.set_tooltip("Shows the preview as it would look with that kind of color blindness.\n\
+                                   What gets saved or sent stays the same.");
+    let mut crt_toggle = i18n::labeled(CheckButton::default(), "CRT look preview");
+    crt_toggle.set_tooltip("Scanlines, pixel gaps and a bit of bloom, roughly like the shader shows it.\n\
+                            Needs a display multiplier of 2 or more. What gets saved or sent stays the same.");
+    let mut edit_tool_choice = i18n::labeled(menu::Choice::default(), "Edit tool:");
+    edit_tool_choice.add_choice(&edit::Tool::VARIANTS.join("|"));
+    edit_tool_choice.set_value(0);
+    edit_tool_choice.set_tooltip("Pencil, fill and pick work on the preview, and pick also on the palette.\nEdits are lost when the image gets processed again.\n\
+                                  The dither tools paint the dither mask, which stays.");
+    let mut edit_color_frame = i18n::labeled(Frame::default(), "Edit color").with_id("edit_color_frame");
+    edit_color_frame.set_frame(FrameType::FlatBox);
+
+    let mut no_quantize_toggle = i18n::labeled(CheckButton::default(), "Disable quantization").with_id("no_quantize_toggle");
+    let mut grayscale_toggle = i18n::labeled(CheckButton::default(), "Grayscale the image\nbefore converting").with_id("grayscale_toggle");
+    let mut grayscale_output_toggle = i18n::labeled(CheckButton::default(), "Output the palette\nindexes as grayscale").with_id("grayscale_output_toggle");
+    let mut reorder_palette_toggle = i18n::labeled(CheckButton::default(), "Sort palette").with_id("reorder_palette_toggle");
     reorder_palette_toggle.set_checked(true);
-
-    let mut maxcolors_slider = HorValueSlider::default().with_label("Max Colors").with_id("maxcolors_slider");
+    let mut lock_palette_toggle = i18n::labeled(CheckButton::default(), "Lock palette").with_id("lock_palette_toggle");
+    lock_palette_toggle.set_tooltip("Keeps the current palette, and remaps the images after this to it instead of\n\
+                                     quantizing them, so a series of images all use the same colors.");
+    let mut palette_from_image_btn = i18n::labeled(Button::default(), "Use palette from image...");
+    palette_from_image_btn.set_tooltip("Quantizes another image (with the settings here) and locks its palette,\n\
+                                        to give this image the colors of existing pixel art.");
+
+    let mut quantizer_choice = i18n::labeled(menu::Choice::default(), "Quantizer:")
+        .with_id("quantizer_choice");
+    quantizer_choice.add_choice(&QuantizerType::VARIANTS.join("|"));
+    quantizer_choice.set_value(0);
+
+    let mut color_space_choice = i18n::labeled(menu::Choice::default(), "Quantize in color space:")
+        .with_id("color_space_choice");
+    color_space_choice.add_choice(&ColorSpace::VARIANTS.join("|"));
+    color_space_choice.set_value(0);
+
+    let mut flatten_choice = i18n::labeled(menu::Choice::default(), "Flatten alpha onto:")
+        .with_id("flatten_choice");
+    flatten_choice.add_choice(&Flatten::VARIANTS.join("|"));
+    flatten_choice.set_value(0);
+
+    // 0 leaves partial transparency alone
+    let mut alpha_threshold_slider = i18n::labeled(HorValueSlider::default(), "Alpha threshold").with_id("alpha_threshold_slider");
+    alpha_threshold_slider.set_range(0.0, 255.0);
+    alpha_threshold_slider.set_step(1.0, 1);
+    alpha_threshold_slider.set_value(0.0);
+    alpha_threshold_slider.set_trigger(CallbackTrigger::Changed | CallbackTrigger::Release);
+
+    let mut transparent_mode_choice = i18n::labeled(menu::Choice::default(), "Transparent pixels:")
+        .with_id("transparent_mode_choice");
+    transparent_mode_choice.add_choice(&TransparentMode::VARIANTS.join("|"));
+    transparent_mode_choice.set_value(0);
+
+    let mut maxcolors_slider = i18n::labeled(HorValueSlider::default(), "Max Colors").with_id("maxcolors_slider");
     maxcolors_slider.set_range(2.0, 256.0);
     maxcolors_slider.set_step(1.0, 1);
     maxcolors_slider.set_value(16.0);
-
-    let mut dithering_slider = HorValueSlider::default().with_label("Dithering Level").with_id("dithering_slider");
+    // Also call back on release, so that we get to do the full update after dragging
+    maxcolors_slider.set_trigger(CallbackTrigger::Changed | CallbackTrigger::Release);
+
+    let mut suggestion_row = Flex::default_fill().row();
+    let mut suggestion_frame = Frame::default().with_id("suggestion_frame");
+    suggestion_frame.set_align(Align::Left | Align::Inside);
+    let mut suggestion_apply_btn = i18n::labeled(Button::default(), "Apply").with_id("suggestion_apply_btn");
+    suggestion_apply_btn.deactivate();
+    suggestion_row.fixed(&suggestion_apply_btn, 80);
+    suggestion_row.end();
+
+    let mut dithering_slider = i18n::labeled(HorValueSlider::default(), "Dithering Level").with_id("dithering_slider");
     dithering_slider.set_range(0.0, 1.0);
     dithering_slider.set_value(1.0);
-
-    let mut scaling_toggle = CheckButton::default().with_label("Enable scaling").with_id("scaling_toggle");
+    dithering_slider.set_trigger(CallbackTrigger::Changed | CallbackTrigger::Release);
+
+    let mut dither_mask_choice = i18n::labeled(menu::Choice::default(), "Dither mask:")
+        .with_id("dither_mask_choice");
+    dither_mask_choice.add_choice(&dither_mask::DitherMaskMode::VARIANTS.join("|"));
+    dither_mask_choice.set_value(0);
+    dither_mask_choice.set_tooltip("Auto dithers gradients and leaves flat areas and hard edges clean.\n\
+                                    Painted is painted on the preview with the dither edit tools.");
+    let mut dither_mask_clear_btn = i18n::labeled(Button::default(), "Clear painted dither mask");
+
+    let advanced_quantization_frame = i18n::labeled(Frame::default(), "Advanced quantization");
+    let mut quantizr_sample_slider = i18n::labeled(HorValueSlider::default(), "Build palette from % of pixels").with_id("quantizr_sample_slider");
+    quantizr_sample_slider.set_range(1.0, 100.0);
+    quantizr_sample_slider.set_step(1.0, 1);
+    quantizr_sample_slider.set_value(100.0);
+    quantizr_sample_slider.set_trigger(CallbackTrigger::Changed | CallbackTrigger::Release);
+    quantizr_sample_slider.set_tooltip("quantizr only. Lower is faster on big images, at some cost in how well the palette fits.\n\
+                                        The whole image still gets remapped with the palette.");
+    let mut kmeans_slider = i18n::labeled(HorValueSlider::default(), "K-means refinement passes (0 = off)").with_id("kmeans_slider");
+    kmeans_slider.set_range(0.0, 10.0);
+    kmeans_slider.set_step(1.0, 1);
+    kmeans_slider.set_value(0.0);
+    kmeans_slider.set_trigger(CallbackTrigger::Changed | CallbackTrigger::Release);
+    kmeans_slider.set_tooltip("quantizr only. Moves the palette colors to the average of the pixels they get used for,\n\
+                               which cuts down on banding at 8-16 colors.");
+    let mut forced_colors_row = Flex::default_fill().row();
+    let forced_colors_frame = i18n::labeled(Frame::default(), "Forced colors:");
+    let mut forced_colors_input = Input::default().with_id("forced_colors_input");
+    forced_colors_input.set_trigger(CallbackTrigger::EnterKey);
+    forced_colors_input.set_tooltip("Colors the palette has to have, like #000000 #ffffff. They take up palette entries\n\
+                                     even when the image doesn't use them, the quantizer gets the rest.");
+    let mut forced_colors_add_btn = i18n::labeled(Button::default(), "Add...");
+    forced_colors_row.fixed(&forced_colors_frame, 110);
+    forced_colors_row.fixed(&forced_colors_add_btn, 60);
+    forced_colors_row.end();
+
+    let mut scaling_toggle = i18n::labeled(CheckButton::default(), "Enable scaling").with_id("scaling_toggle");
     scaling_toggle.set_checked(true);
     const SCALE_DEFAULT: &'static str = "128";
-    let mut scale_input = IntInput::default().with_size(0, 40).with_label("Scale (NxN)").with_id("scale_input").with_align(Align::Inside);
+    let mut scale_input = i18n::labeled(IntInput::default().with_size(0, 40), "Scale (NxN)").with_id("scale_input").with_align(Align::Inside);
     // scale_input.set_trigger(CallbackTrigger::Changed);
     scale_input.set_trigger(CallbackTrigger::EnterKey);
     scale_input.set_value(SCALE_DEFAULT);
     scale_input.set_maximum_size(4);
-    let mut resize_type_choice = menu::Choice::default()
-        .with_label("Scaling fit:")
+    let mut resize_type_choice = i18n::labeled(menu::Choice::default(), "Scaling fit:")
         .with_id("resize_type_choice");
     resize_type_choice.add_choice(&ResizeType::VARIANTS.join("|"));
     resize_type_choice.set_value(0);
-    let mut scaler_type_choice = menu::Choice::default()
-        .with_label("Scaler algorithm:")
+    let mut scaler_type_choice = i18n::labeled(menu::Choice::default(), "Scaler algorithm:")
         .with_id("scaler_type_choice");
     scaler_type_choice.add_choice(&ScalerType::VARIANTS.join("|"));
     scaler_type_choice.set_value(0);
+    if !config.scaler.is_empty() {
+        match scaler_type_choice.find_index(&config.scaler) {
+            -1 => warn!("Unknown scaler {:?} in config", config.scaler),
+            i => { scaler_type_choice.set_value(i); },
+        }
+    }
+    // Only does anything for the scalers that need it, see ScalerType::needs_pre_blur
+    let mut pre_blur_toggle = i18n::labeled(CheckButton::default(), "Blur before downscaling").with_id("pre_blur_toggle");
+    // Unsharp mask strength, applied after scaling
+    let mut sharpen_slider = i18n::labeled(HorValueSlider::default(), "Sharpen").with_id("sharpen_slider");
+    sharpen_slider.set_range(0.0, 2.0);
+    sharpen_slider.set_step(0.05, 1);
+    sharpen_slider.set_value(0.0);
+    sharpen_slider.set_trigger(CallbackTrigger::Changed | CallbackTrigger::Release);
+    // Outlines, also after scaling (see filters::edge_darken)
+    let mut edge_darken_slider = i18n::labeled(HorValueSlider::default(), "Darken edges").with_id("edge_darken_slider");
+    edge_darken_slider.set_range(0.0, 1.0);
+    edge_darken_slider.set_step(0.05, 1);
+    edge_darken_slider.set_value(0.0);
+    edge_darken_slider.set_trigger(CallbackTrigger::Changed | CallbackTrigger::Release);
+
+    // Levels per channel, separately from the quantization. Also works with quantization off.
+    let mut posterize_slider = i18n::labeled(HorValueSlider::default(), "Posterize levels (256 = off)").with_id("posterize_slider");
+    posterize_slider.set_range(2.0, 256.0);
+    posterize_slider.set_step(1.0, 1);
+    posterize_slider.set_value(256.0);
+    posterize_slider.set_trigger(CallbackTrigger::Changed | CallbackTrigger::Release);
+
+    // Levels and curve, before the quantizer gets the image (see filters::Levels). The curve
+    // sliders say what a dark, middle and light gray come out as, so raising the middle one lifts
+    // the midtones.
+    let levels_default = filters::Levels::default();
+    let mut level_sliders: Vec<HorValueSlider> = Vec::new();
+    for (id, label, value) in [
+        ("black_point_slider", "Black point", levels_default.black),
+        ("white_point_slider", "White point", levels_default.white),
+        ("curve_shadows_slider", "Curve: shadows", levels_default.curve[0]),
+        ("curve_midtones_slider", "Curve: midtones", levels_default.curve[1]),
+        ("curve_highlights_slider", "Curve: highlights", levels_default.curve[2]),
+    ] {
+        let mut slider = i18n::labeled(HorValueSlider::default(), label).with_id(id);
+        slider.set_range(0.0, 255.0);
+        slider.set_step(1.0, 1);
+        slider.set_value(value as f64);
+        slider.set_trigger(CallbackTrigger::Changed | CallbackTrigger::Release);
+        level_sliders.push(slider);
+    }
 
-    let mut multiplier_choice = menu::Choice::default()
-        .with_label("Display scale multiplier:")
+    // For debugging shaders that read the palette channels differently than we think
+    let invert_toggle = i18n::labeled(CheckButton::default(), "Invert colors").with_id("invert_toggle");
+    let swap_rb_toggle = i18n::labeled(CheckButton::default(), "Swap red and blue (RGB/BGR)").with_id("swap_rb_toggle");
+    let mut isolate_channel_choice = i18n::labeled(menu::Choice::default(), "Only channel:")
+        .with_id("isolate_channel_choice");
+    isolate_channel_choice.add_choice(&filters::Channel::VARIANTS.join("|"));
+    isolate_channel_choice.set_value(0);
+
+    // Maps the palette onto a gradient between two colors
+    let mut duotone_choice = i18n::labeled(menu::Choice::default(), "Duotone:")
+        .with_id("duotone_choice");
+    duotone_choice.add_choice(&duotone::choices().join("|"));
+    duotone_choice.set_value(0);
+    let mut duotone_row = Flex::default_fill().row();
+    let (default_dark, default_light) = (duotone::PRESETS[0].1, duotone::PRESETS[0].2);
+    let mut duotone_dark_btn = i18n::labeled(Button::default(), "Dark").with_id("duotone_dark_btn");
+    duotone_dark_btn.set_color(Color::from_rgb(default_dark[0], default_dark[1], default_dark[2]));
+    duotone_dark_btn.set_label_color(Color::White);
+    let mut duotone_light_btn = i18n::labeled(Button::default(), "Light").with_id("duotone_light_btn");
+    duotone_light_btn.set_color(Color::from_rgb(default_light[0], default_light[1], default_light[2]));
+    duotone_light_btn.set_label_color(Color::Black);
+    duotone_row.end();
+
+    let mut multiplier_choice = i18n::labeled(menu::Choice::default(), "Display scale multiplier:")
         .with_id("multiplier_choice");
     multiplier_choice.add_choice("1x|2x|3x|4x|5x|6x|7x|8x");
     multiplier_choice.set_value(4);
 
+    let mut banner_toggle = i18n::labeled(CheckButton::default(), "Caption in letterbox").with_id("banner_toggle");
+    let mut banner_input = i18n::labeled(Input::default(), "Caption (empty = filename)").with_id("banner_input").with_align(Align::Inside);
+    banner_input.set_trigger(CallbackTrigger::EnterKey);
+
     let mut divider = Frame::default_fill();
     divider.set_color(Color::Black);
     divider.set_frame(FrameType::FlatBox);
 
-    const OSC_SPEED_DEFAULT: f64 = 5.0;
-    let mut send_osc_btn = Button::default().with_label("Send OSC").with_id("send_osc_btn");
+    let mut send_osc_btn = i18n::labeled(Button::default(), "Send OSC").with_id("send_osc_btn");
     send_osc_btn.deactivate();
-    let mut osc_speed_slider = HorValueSlider::default().with_label("OSC updates/second").with_id("osc_speed_slider");
+    // Not i18n::labeled, the label changes with the state
+    let send_state_frame = Frame::default().with_label(i18n::tr(send_job::state().description())).with_id("send_state_frame");
+    let mut osc_speed_slider = i18n::labeled(HorValueSlider::default(), "OSC updates/second").with_id("osc_speed_slider");
     osc_speed_slider.set_range(0.5, 20.0);
     osc_speed_slider.set_step(0.5, 1);
-    osc_speed_slider.set_value(OSC_SPEED_DEFAULT);
-    let osc_rle_compression_toggle = CheckButton::default().with_label("Use RLE compression").with_id("osc_rle_compression_toggle");
+    osc_speed_slider.set_value(config.msgs_per_second);
+    // Pacing by bytes instead, the slider doesn't do anything while this is set
+    let mut osc_bandwidth_input = i18n::labeled(IntInput::default(), "Bandwidth budget (KB/s, empty = use updates/second)").with_id("osc_bandwidth_input").with_align(Align::Inside);
+    let mut osc_rle_compression_toggle = i18n::labeled(CheckButton::default(), "Use RLE compression").with_id("osc_rle_compression_toggle");
     osc_rle_compression_toggle.set_checked(true);
-    let mut osc_pixfmt_choice = menu::Choice::default()
-        .with_label("OSC Pixel format");
+    let mut osc_auto_compression_toggle = i18n::labeled(CheckButton::default(), "Pick the smaller of raw and RLE").with_id("osc_auto_compression_toggle");
+    osc_auto_compression_toggle.set_checked(true);
+    // The RLE toggle only matters when not picking automatically
+    osc_rle_compression_toggle.deactivate();
+    let osc_confirm_toggle = i18n::labeled(CheckButton::default(), "Confirm before sending").with_id("osc_confirm_toggle");
+    osc_confirm_toggle.set_checked(config.confirm_send);
+    // Starts out at the speed above. Needs an ack parameter in the shader profile.
+    let osc_adaptive_toggle = i18n::labeled(CheckButton::default(), "Adapt speed to acknowledgements").with_id("osc_adaptive_toggle");
+    // For shaders that can do cut-outs: which palette index should be see-through
+    let mut osc_key_toggle = i18n::labeled(CheckButton::default(), "Key out palette index").with_id("osc_key_toggle");
+    let mut osc_key_spinner = fltk::misc::Spinner::default().with_id("osc_key_spinner");
+    osc_key_spinner.set_range(0.0, 255.0);
+    osc_key_spinner.set_step(1.0);
+    osc_key_spinner.set_value(0.0);
+    osc_key_spinner.deactivate();
+    let mut osc_pixfmt_choice = i18n::labeled(menu::Choice::default(), "OSC Pixel format")
+        .with_id("osc_pixfmt_choice");
     // let pixfmt_choices = send_osc::PixFmt::into_iter().fold("".to_string(), |acc, s| format!("{acc}|{}", s.to_string()));
     // let pixfmt_choices = send_osc::PixFmt::into_iter().map(|p| p.to_string()).reduce(|acc, s| format!("{acc}|{s}")).unwrap();
     // let pixfmt_choices = send_osc::PixFmt::into_iter().map(|p| p.to_string()).join("|");
     let pixfmt_choices = send_osc::PixFmt::VALUES.map(|p| p.to_string()).join("|");
     osc_pixfmt_choice.add_choice(&pixfmt_choices);
-    osc_pixfmt_choice.set_callback(|c| {
-        println!("osc_pixfmt_choice: {:?}", c.choice())
-    });
     osc_pixfmt_choice.set_value(0);
+    let mut transfer_estimate_frame = Frame::default().with_id("transfer_estimate_frame");
+    transfer_estimate_frame.set_align(Align::Left | Align::Inside | Align::Wrap);
+    let mut osc_target_input = i18n::labeled(Input::default(), "OSC target (host:port, tcp://host:port for TCP)").with_id("osc_target_input").with_align(Align::Inside);
+    osc_target_input.set_value(&config.osc_target());
+    let mut osc_warn_input = i18n::labeled(IntInput::default(), "Warn when a send takes longer than (s, 0 = never)").with_id("osc_warn_input").with_align(Align::Inside);
+    osc_warn_input.set_value(&config.send_warn_secs.to_string());
+    // Burst-and-wait pacing instead of the same sleep after every chunk
+    let mut osc_burst_input = i18n::labeled(IntInput::default(), "Burst size (chunks, 0 = even pacing)").with_id("osc_burst_input").with_align(Align::Inside);
+    osc_burst_input.set_value("0");
+    let mut osc_burst_wait_input = i18n::labeled(IntInput::default(), "Wait after each burst (ms)").with_id("osc_burst_wait_input").with_align(Align::Inside);
+    osc_burst_wait_input.set_value(&send_osc::DEFAULT_BURST_WAIT.as_millis().to_string());
+    let mut osc_retries_input = i18n::labeled(IntInput::default(), "Retries on network errors").with_id("osc_retries_input").with_align(Align::Inside);
+    osc_retries_input.set_value(&send_osc::DEFAULT_SEND_RETRIES.to_string());
+    let mut shader_profile_btn = i18n::labeled(Button::default(), "Shader profile...");
+    let mut query_shader_btn = i18n::labeled(Button::default(), "Query shader");
+    let mut playlist_btn = i18n::labeled(Button::default(), "Playlist...");
+    let mut stream_btn = i18n::labeled(Button::default(), "Stream...");
+    let mut video_btn = i18n::labeled(Button::default(), "Video...");
+    let mut text_btn = i18n::labeled(Button::default(), "Text...");
+    let mut send_history_btn = i18n::labeled(Button::default(), "Send history...");
+    let mut log_btn = i18n::labeled(Button::default(), "Show log...");
+    let mut language_choice = i18n::labeled(menu::Choice::default(), "Language:");
+    language_choice.add_choice(&i18n::LANGUAGES.map(|l| l.name()).join("|"));
+    language_choice.set_value(i18n::LANGUAGES.iter().position(|l| *l == i18n::language()).unwrap_or(0) as i32);
+    let mut hotkey_input = i18n::labeled(Input::default(), "Capture+send hotkey (e.g. ctrl+shift+F9)").with_align(Align::Inside);
+    hotkey_input.set_trigger(CallbackTrigger::EnterKey);
+    let mut resend_hotkey_input = i18n::labeled(Input::default(), "Resend hotkey (e.g. ctrl+alt+P)").with_align(Align::Inside);
+    resend_hotkey_input.set_trigger(CallbackTrigger::EnterKey);
+    let mut http_port_input = i18n::labeled(IntInput::default(), "HTTP remote control port (empty = off)").with_align(Align::Inside);
+    http_port_input.set_trigger(CallbackTrigger::EnterKey);
+    http_port_input.set_tooltip(&format!("e.g. {}. Only listens on localhost.", remote::DEFAULT_PORT));
+    let mut ws_port_input = i18n::labeled(IntInput::default(), "WebSocket bridge port (empty = off)").with_align(Align::Inside);
+    ws_port_input.set_trigger(CallbackTrigger::EnterKey);
+    ws_port_input.set_tooltip(&format!("e.g. {}. Only listens on localhost.", ws_bridge::DEFAULT_PORT));
+    let mut oscquery_port_input = i18n::labeled(IntInput::default(), "OSCQuery return port (empty = off)").with_align(Align::Inside);
+    oscquery_port_input.set_trigger(CallbackTrigger::EnterKey);
+    oscquery_port_input.set_tooltip("e.g. 9011. Has VRChat send the avatar parameters (acks, checksum results, shader queries) \
+                                     to this port, found over OSCQuery, instead of 9001.");
 
     let button_size = if small_screen { 30 } else { 50 };
     let toggle_size = if small_screen { 20 } else { 30 };
@@ -1044,42 +3529,159 @@ fn main() -> Result<(), Box<dyn Error>> {
     let input_size = if small_screen { 20 } else { 30 };
     col.fixed(&openbtn, button_size);
     col.fixed(&savebtn, button_size);
+    col.fixed(&full_resolution_btn, button_size);
+    col.fixed(&open_project_btn, button_size);
+    col.fixed(&save_project_btn, button_size);
     col.fixed(&clearbtn, button_size);
+    col.fixed(&comparebtn, button_size);
+    col.fixed(&pipeline_btn, button_size);
+    col.fixed(&pixel_view_btn, button_size);
+    col.fixed(&histogram_toggle, toggle_size);
+    col.fixed(&performance_toggle, toggle_size);
+    col.fixed(&compare_toggle, toggle_size);
+    col.fixed(&letterbox_toggle, toggle_size);
+    col.fixed(&colorblind_choice, choice_size);
+    col.fixed(&crt_toggle, toggle_size);
+    col.fixed(&edit_tool_choice, choice_size);
+    col.fixed(&edit_color_frame, toggle_size);
     col.fixed(&no_quantize_toggle, toggle_size);
     col.fixed(&grayscale_toggle, toggle_size);
     col.fixed(&grayscale_output_toggle, toggle_size);
     col.fixed(&reorder_palette_toggle, toggle_size);
+    col.fixed(&lock_palette_toggle, toggle_size);
+    col.fixed(&palette_from_image_btn, button_size);
+    col.fixed(&quantizer_choice, choice_size);
+    col.fixed(&color_space_choice, choice_size);
+    col.fixed(&flatten_choice, choice_size);
+    col.fixed(&alpha_threshold_slider, slider_size);
+    col.fixed(&transparent_mode_choice, choice_size);
     col.fixed(&maxcolors_slider, slider_size);
+    col.fixed(&suggestion_row, choice_size);
     col.fixed(&dithering_slider, slider_size);
+    col.fixed(&dither_mask_choice, choice_size);
+    col.fixed(&dither_mask_clear_btn, toggle_size);
+    col.fixed(&advanced_quantization_frame, toggle_size);
+    col.fixed(&quantizr_sample_slider, slider_size);
+    col.fixed(&kmeans_slider, slider_size);
+    col.fixed(&forced_colors_row, input_size);
     col.fixed(&scaling_toggle, toggle_size);
     col.fixed(&scale_input, input_size);
     col.fixed(&resize_type_choice, choice_size);
     col.fixed(&scaler_type_choice, choice_size);
+    col.fixed(&pre_blur_toggle, toggle_size);
+    col.fixed(&sharpen_slider, slider_size);
+    col.fixed(&edge_darken_slider, slider_size);
+    col.fixed(&posterize_slider, slider_size);
+    for slider in &level_sliders {
+        col.fixed(slider, slider_size);
+    }
+    col.fixed(&invert_toggle, toggle_size);
+    col.fixed(&swap_rb_toggle, toggle_size);
+    col.fixed(&isolate_channel_choice, choice_size);
+    col.fixed(&duotone_choice, choice_size);
+    col.fixed(&duotone_row, choice_size);
     col.fixed(&multiplier_choice, choice_size);
+    col.fixed(&banner_toggle, toggle_size);
+    col.fixed(&banner_input, input_size);
     col.fixed(&divider, 5);
     col.fixed(&send_osc_btn, button_size);
+    col.fixed(&send_state_frame, 20);
     col.fixed(&osc_speed_slider, slider_size);
+    col.fixed(&osc_bandwidth_input, input_size);
     col.fixed(&osc_rle_compression_toggle, toggle_size);
+    col.fixed(&osc_auto_compression_toggle, toggle_size);
+    col.fixed(&osc_confirm_toggle, toggle_size);
+    col.fixed(&osc_adaptive_toggle, toggle_size);
+    col.fixed(&osc_key_toggle, toggle_size);
+    col.fixed(&osc_key_spinner, input_size);
     col.fixed(&osc_pixfmt_choice, choice_size);
+    col.fixed(&transfer_estimate_frame, 40);
+    col.fixed(&osc_target_input, input_size);
+    col.fixed(&osc_warn_input, input_size);
+    col.fixed(&osc_burst_input, input_size);
+    col.fixed(&osc_burst_wait_input, input_size);
+    col.fixed(&osc_retries_input, input_size);
+    col.fixed(&shader_profile_btn, button_size);
+    col.fixed(&query_shader_btn, button_size);
+    col.fixed(&playlist_btn, button_size);
+    col.fixed(&stream_btn, button_size);
+    col.fixed(&video_btn, button_size);
+    col.fixed(&text_btn, button_size);
+    col.fixed(&send_history_btn, button_size);
+    col.fixed(&log_btn, button_size);
+    col.fixed(&language_choice, choice_size);
+    col.fixed(&hotkey_input, input_size);
+    col.fixed(&resend_hotkey_input, input_size);
+    col.fixed(&http_port_input, input_size);
+    col.fixed(&ws_port_input, input_size);
+    col.fixed(&oscquery_port_input, input_size);
 
     let (appmsg, appmsg_recv) = mpsc::channel::<AppMessage>();
     let (joinhandle, bg) = start_background_process(&appmsg);
+    edit::attach(&mut palette_frame, &bg);
+
+    // Ctrl+Z to undo and Ctrl+Shift+Z to redo changes to the image processing settings
+    wind.handle({
+        let bg = bg.clone();
+        let appmsg = appmsg.clone();
+        move |_, ev| {
+            if ev != Event::Shortcut || !app::is_event_ctrl() || app::event_key() != Key::from_char('z') {
+                return false;
+            }
+
+            let redo = app::is_event_shift();
+            let state = match SETTINGS_HISTORY.lock() {
+                Ok(mut history) => if redo { history.redo() } else { history.undo() },
+                Err(err) => {
+                    error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't lock settings history")));
+                    return true;
+                },
+            };
+
+            match state {
+                Some(settings) => {
+                    info!("{}: {settings:?}", if redo { "Redo" } else { "Undo" });
+                    match set_image_settings_widgets(&settings) {
+                        Ok(()) => send_updateimage(&appmsg, &bg),
+                        Err(err) => error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't restore settings"))),
+                    }
+                },
+                None => info!("Nothing to {}", if redo { "redo" } else { "undo" }),
+            }
+
+            true
+        }
+    });
 
     openbtn.set_callback({
         let bg = bg.clone();
         let appmsg = appmsg.clone();
         move |_| {
             let Some(path) = get_file(dialog::FileDialogType::BrowseFile) else {
-                eprintln!("No file selected/cancelled");
+                info!("No file selected/cancelled");
                 return;
             };
 
+            // PNGs we saved have the settings they were made with. Set the widgets before loading,
+            // as LoadImage processes the image with whatever they say.
+            match project::load_png_settings(&path) {
+                Ok(Some(settings)) => {
+                    if dialog::choice2_default(i18n::tr("This image was saved with its processing settings. Restore them?"), i18n::tr("No"), i18n::tr("Restore"), "") == Some(1) {
+                        if let Err(err) = set_image_settings_widgets(&settings) {
+                            error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't restore settings")));
+                        }
+                    }
+                },
+                Ok(None) => (),
+                Err(err) => warn!("Couldn't read settings from {path:?}: {err}"),
+            }
+
             match || -> Result<(), Box<dyn Error>> {
                 bg.send_or_replace_if(BgMessage::is_update, BgMessage::LoadImage(path))?;
                 Ok(())
             }() {
                 Ok(()) => (),
-                Err(err) => error_alert(&appmsg, format!("Open button failed: {err}")),
+                Err(err) => error_alert(&appmsg, format!("{}: {err}", i18n::tr("Open button failed"))),
             }
         }
     });
@@ -1089,7 +3691,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         let appmsg = appmsg.clone();
         move |_| {
             let Some(path) = get_file(dialog::FileDialogType::BrowseSaveFile) else {
-                eprintln!("No file selected/cancelled");
+                info!("No file selected/cancelled");
                 return;
             };
 
@@ -1098,7 +3700,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 Ok(())
             }() {
                 Ok(()) => (),
-                Err(err) => error_alert(&appmsg, format!("Save button failed: {err}")),
+                Err(err) => error_alert(&appmsg, format!("{}: {err}", i18n::tr("Save button failed"))),
             }
         }
     });
@@ -1108,7 +3710,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         let bg = bg.clone();
         let appmsg = appmsg.clone();
         move |_| {
-            println!("Clear button pressed");
+            debug!("Clear button pressed");
 
             let sendresult = bg.send_or_replace_if(BgMessage::is_update, BgMessage::ClearImage);
             if sendresult.is_err() {
@@ -1117,19 +3719,208 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     });
 
+    pipeline_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        move |_| pipeline::show_pipeline_window({
+            let appmsg = appmsg.clone();
+            let bg = bg.clone();
+            move || send_updateimage(&appmsg, &bg)
+        })
+    });
+    comparebtn.set_callback({
+        let bg = bg.clone();
+        let appmsg = appmsg.clone();
+        move |_| {
+            match || -> Result<(), String> {
+                let settings = get_image_settings(&appmsg)?;
+                bg.send(BgMessage::CompareSettings(settings))
+                    .map_err(|err| format!("Couldn't send message to BG thread: {err}"))?;
+                Ok(())
+            }() {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}: {err}", i18n::tr("Compare settings button failed"))),
+            }
+        }
+    });
+
+    pixel_view_btn.set_callback(|_| pixel_view::show_pixel_view_window());
+
+    histogram_toggle.set_callback({
+        let mut row = row.clone();
+        let mut histogram_panel = histogram_panel.clone();
+        move |t| {
+            if t.is_checked() {
+                histogram_panel.show();
+            } else {
+                histogram_panel.hide();
+            }
+            row.layout();
+        }
+    });
+
+    performance_toggle.set_callback({
+        let mut row = row.clone();
+        let mut performance_panel = performance_panel.clone();
+        move |t| {
+            if t.is_checked() {
+                performance_panel.show();
+            } else {
+                performance_panel.hide();
+            }
+            row.layout();
+        }
+    });
+    performance_clear_btn.set_callback(|_| metrics::clear());
+    // Timings come in from all threads, so poll for new ones like the log window does
+    app::add_timeout3(PERFORMANCE_REFRESH_INTERVAL, {
+        let performance_panel = performance_panel.clone();
+        let mut shown_generation: Option<u64> = None;
+        move |handle| {
+            let generation = metrics::generation();
+            if performance_panel.visible() && shown_generation != Some(generation) {
+                if let Some(mut buffer) = performance_display.buffer() {
+                    buffer.set_text(&metrics::report());
+                }
+                shown_generation = Some(generation);
+            }
+            app::repeat_timeout3(PERFORMANCE_REFRESH_INTERVAL, handle);
+        }
+    });
+
+    compare_toggle.set_callback({
+        let mut frame = frame.clone();
+        move |t| {
+            compare::set_enabled(t.is_checked());
+            frame.redraw();
+        }
+    });
+
+    edit_tool_choice.set_callback(|c| {
+        edit::set_tool(c.choice().unwrap_or_default().parse().unwrap_or_default());
+    });
+
+    letterbox_toggle.set_callback({
+        let mut frame = frame.clone();
+        move |t| {
+            letterbox::set_enabled(t.is_checked());
+            frame.redraw();
+        }
+    });
+
+    colorblind_choice.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        move |c| {
+            colorblind::set_current(c.choice().unwrap_or_default().parse().unwrap_or_default());
+            // The pipeline is cached, so this only redraws
+            send_updateimage(&appmsg, &bg);
+        }
+    });
+
+    crt_toggle.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        move |t| {
+            crt::set_enabled(t.is_checked());
+            send_updateimage(&appmsg, &bg);
+        }
+    });
+
     no_quantize_toggle.set_callback(     { let a = appmsg.clone(); let b = bg.clone(); move |_| { send_updateimage(&a, &b); } });
     grayscale_toggle.set_callback(       { let a = appmsg.clone(); let b = bg.clone(); move |_| { send_updateimage(&a, &b); } });
     grayscale_output_toggle.set_callback({ let a = appmsg.clone(); let b = bg.clone(); move |_| { send_updateimage(&a, &b); } });
     reorder_palette_toggle.set_callback( { let a = appmsg.clone(); let b = bg.clone(); move |_| { send_updateimage(&a, &b); } });
-    maxcolors_slider.set_callback(       { let a = appmsg.clone(); let b = bg.clone(); move |_| { send_updateimage(&a, &b); } });
-    dithering_slider.set_callback(       { let a = appmsg.clone(); let b = bg.clone(); move |_| { send_updateimage(&a, &b); } });
+    lock_palette_toggle.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        move |t| {
+            if !t.is_checked() {
+                palette::unlock();
+            } else if !palette::lock() {
+                t.set_checked(false);
+                error_alert(&appmsg, i18n::tr("No palette to lock yet, load an image first").to_string());
+                return;
+            }
+            send_updateimage(&appmsg, &bg);
+        }
+    });
+    maxcolors_slider.set_callback(       { let a = appmsg.clone(); let b = bg.clone(); move |_| { slider_update(&a, &b); } });
+    dithering_slider.set_callback(       { let a = appmsg.clone(); let b = bg.clone(); move |_| { slider_update(&a, &b); } });
+    quantizr_sample_slider.set_callback( { let a = appmsg.clone(); let b = bg.clone(); move |_| { slider_update(&a, &b); } });
+    kmeans_slider.set_callback(          { let a = appmsg.clone(); let b = bg.clone(); move |_| { slider_update(&a, &b); } });
+    forced_colors_input.set_callback({ let a = appmsg.clone(); let b = bg.clone(); move |_| { send_updateimage(&a, &b); } });
+    forced_colors_add_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        let mut forced_colors_input = forced_colors_input.clone();
+        move |btn| {
+            let Some((r, g, b)) = dialog::color_chooser(&btn.label(), dialog::ColorMode::Byte) else {
+                return;
+            };
+            let value = forced_colors_input.value();
+            let separator = if value.trim().is_empty() { "" } else { " " };
+            forced_colors_input.set_value(&format!("{}{separator}{}", value.trim(), palette::format_color([r, g, b])));
+            send_updateimage(&appmsg, &bg);
+        }
+    });
+    dither_mask_choice.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        move |c| {
+            dither_mask::set_mode(c.choice().unwrap_or_default().parse().unwrap_or_default());
+            send_updateimage(&appmsg, &bg);
+        }
+    });
+    dither_mask_clear_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        move |_| {
+            dither_mask::clear();
+            send_updateimage(&appmsg, &bg);
+        }
+    });
+    // Strokes with the dither edit tools
+    dither_mask::on_change({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        move || send_updateimage(&appmsg, &bg)
+    });
+    suggestion_apply_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        move |_| {
+            match || -> Result<(), String> {
+                let suggestion = SUGGESTION.lock()
+                    .map_err(|err| format!("Couldn't lock suggestion: {err}"))?
+                    .ok_or("No suggestion")?;
+                let mut maxcolors_slider: HorValueSlider = app::widget_from_id("maxcolors_slider").ok_or("widget_from_id fail")?;
+                let mut osc_pixfmt_choice: menu::Choice = app::widget_from_id("osc_pixfmt_choice").ok_or("widget_from_id fail")?;
+
+                maxcolors_slider.set_value(suggestion.colors as f64);
+                // Auto follows the palette size anyway, a fixed bit depth gets changed to match
+                let pixfmt: send_osc::PixFmt = osc_pixfmt_choice.choice()
+                    .ok_or("No PixFmt selected")?
+                    .parse()?;
+                let pixfmt = pixfmt.with_bitdepth(suggestion.bitdepth);
+                osc_pixfmt_choice.set_value(osc_pixfmt_choice.find_index(&pixfmt.to_string()));
+                osc_pixfmt_choice.do_callback();
+
+                send_updateimage(&appmsg, &bg);
+                Ok(())
+            }() {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't apply the suggestion"))),
+            }
+        }
+    });
     scaling_toggle.set_callback(         { let a = appmsg.clone(); let b = bg.clone(); move |_| { send_updateimage(&a, &b); } });
     scale_input.set_callback({
         let bg = bg.clone();
         let appmsg = appmsg.clone();
         move |i| {
             let value = i.value();
-            println!("scale_input: i.value() = {:?}, i.active={:?}", i.value(), i.active());
+            debug!("scale_input: i.value() = {:?}, i.active={:?}", i.value(), i.active());
             if value.len() > 0 {
                 send_updateimage(&appmsg, &bg);
             } else {
@@ -1137,36 +3928,441 @@ fn main() -> Result<(), Box<dyn Error>> {
             }
         }
     });
+    quantizer_choice.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
+    color_space_choice.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
+    flatten_choice.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
+    alpha_threshold_slider.set_callback({ let a = appmsg.clone(); let b = bg.clone(); move |_| { slider_update(&a, &b); } });
+    transparent_mode_choice.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
     resize_type_choice.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
     scaler_type_choice.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
+    pre_blur_toggle.set_callback(        { let a = appmsg.clone(); let b = bg.clone(); move |_| { send_updateimage(&a, &b); } });
+    duotone_choice.set_callback({
+        let bg = bg.clone();
+        let appmsg = appmsg.clone();
+        let mut duotone_dark_btn = duotone_dark_btn.clone();
+        let mut duotone_light_btn = duotone_light_btn.clone();
+        move |c| {
+            if let Some((dark, light)) = c.choice().and_then(|name| duotone::preset(&name)) {
+                duotone_dark_btn.set_color(Color::from_rgb(dark[0], dark[1], dark[2]));
+                duotone_light_btn.set_color(Color::from_rgb(light[0], light[1], light[2]));
+                duotone_dark_btn.redraw();
+                duotone_light_btn.redraw();
+            }
+            send_updateimage(&appmsg, &bg);
+        }
+    });
+    for btn in [&mut duotone_dark_btn, &mut duotone_light_btn] {
+        btn.set_callback({
+            let bg = bg.clone();
+            let appmsg = appmsg.clone();
+            let mut duotone_choice = duotone_choice.clone();
+            move |b| {
+                let color = dialog::color_chooser_with_default(&b.label(), dialog::ColorMode::Byte, b.color().to_rgb());
+                b.set_color(Color::from_rgb(color.0, color.1, color.2));
+                b.redraw();
+                // Picking a color turns it on if it was off
+                let name = duotone_choice.choice().unwrap_or_default();
+                if name == duotone::OFF || duotone::preset(&name).is_some() {
+                    duotone_choice.set_value(duotone_choice.find_index(duotone::CUSTOM));
+                }
+                send_updateimage(&appmsg, &bg);
+            }
+        });
+    }
+    posterize_slider.set_callback({ let a = appmsg.clone(); let b = bg.clone(); move |_| { slider_update(&a, &b); } });
+    for slider in &mut level_sliders {
+        slider.set_callback({ let a = appmsg.clone(); let b = bg.clone(); move |_| { slider_update(&a, &b); } });
+    }
+    invert_toggle.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
+    swap_rb_toggle.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
+    isolate_channel_choice.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
+    sharpen_slider.set_callback({ let a = appmsg.clone(); let b = bg.clone(); move |_| { slider_update(&a, &b); } });
+    edge_darken_slider.set_callback({ let a = appmsg.clone(); let b = bg.clone(); move |_| { slider_update(&a, &b); } });
     multiplier_choice.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
+    banner_toggle.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
+    banner_input.set_callback({ let bg = bg.clone(); let appmsg = appmsg.clone(); move |_| { send_updateimage(&appmsg, &bg); } });
+
+    // Only ever touched from the main thread (widget callbacks)
+    let shader_profile = Rc::new(RefCell::new(shader_profile::ShaderProfile {
+        prefix: config.prefix.clone(),
+        ..Default::default()
+    }));
+
+    // Ask the shader what it can do, and set the scale to match
+    query_shader_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        let shader_profile = Rc::clone(&shader_profile);
+        move |_| {
+            match || -> Result<(), String> {
+                let options = get_send_osc_opts(&shader_profile.borrow())?;
+                if options.profile.query_param.is_none() {
+                    return Err("The shader profile has no query parameter".to_string());
+                }
+
+                // Waiting for the answer takes a moment
+                let appmsg = appmsg.clone();
+                let bg = bg.clone();
+                thread::spawn(move || {
+                    match || -> Result<handshake::Capabilities, Box<dyn Error>> {
+                        let conn = transport::Connection::open(options.transport, options.target_addr(), 0)?;
+                        Ok(handshake::query(&conn, adaptive_rate::listen_port(), &options.profile)?
+                           .ok_or("The shader profile has no query parameter")?)
+                    }() {
+                        Ok(capabilities) => {
+                            if let Some(mut scale_input) = app::widget_from_id::<IntInput>("scale_input") {
+                                scale_input.set_value(&capabilities.width.min(capabilities.height).to_string());
+                                send_updateimage(&appmsg, &bg);
+                            }
+                            alert(&appmsg, format!("{}:\n{}", i18n::tr("Shader capabilities"), capabilities.description()));
+                        },
+                        Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't query the shader"))),
+                    }
+                });
+                Ok(())
+            }() {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't query the shader"))),
+            }
+        }
+    });
+
+    shader_profile_btn.set_callback({
+        let shader_profile = Rc::clone(&shader_profile);
+        move |_| {
+            shader_profile::show_profile_window(&shader_profile);
+        }
+    });
+
+    palette_from_image_btn.set_callback({
+        let bg = bg.clone();
+        let appmsg = appmsg.clone();
+        move |_| {
+            let Some(path) = get_file(dialog::FileDialogType::BrowseFile) else {
+                info!("No file selected/cancelled");
+                return;
+            };
+
+            match || -> Result<(), Box<dyn Error>> {
+                let settings = get_image_settings(&appmsg)?;
+                bg.send(BgMessage::PaletteFromImage(path, settings))?;
+                Ok(())
+            }() {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}: {err}", i18n::tr("Palette from image button failed"))),
+            }
+        }
+    });
+
+    full_resolution_btn.set_callback({
+        let bg = bg.clone();
+        let appmsg = appmsg.clone();
+        move |_| {
+            if let Err(err) = bg.send_or_replace_if(BgMessage::is_update, BgMessage::LoadFullResolution) {
+                error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't send message to BG thread")));
+            }
+        }
+    });
+
+    open_project_btn.set_callback({
+        let bg = bg.clone();
+        let appmsg = appmsg.clone();
+        move |_| {
+            let Some(path) = get_file(dialog::FileDialogType::BrowseFile) else {
+                info!("No file selected/cancelled");
+                return;
+            };
+
+            match || -> Result<(), Box<dyn Error>> {
+                bg.send_or_replace_if(BgMessage::is_update, BgMessage::OpenProject(path))?;
+                Ok(())
+            }() {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}: {err}", i18n::tr("Open project button failed"))),
+            }
+        }
+    });
+
+    save_project_btn.set_callback({
+        let bg = bg.clone();
+        let appmsg = appmsg.clone();
+        let shader_profile = Rc::clone(&shader_profile);
+        move |_| {
+            let Some(path) = get_file(dialog::FileDialogType::BrowseSaveFile) else {
+                info!("No file selected/cancelled");
+                return;
+            };
+
+            match || -> Result<(), Box<dyn Error>> {
+                let settings = get_image_settings(&appmsg)?;
+                let send_settings = get_send_settings(&shader_profile.borrow())?;
+                bg.send(BgMessage::SaveProject(path, settings, send_settings))?;
+                Ok(())
+            }() {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}: {err}", i18n::tr("Save project button failed"))),
+            }
+        }
+    });
+
+    osc_pixfmt_choice.set_callback(          { let a = appmsg.clone(); let b = bg.clone(); let p = Rc::clone(&shader_profile); move |_| { send_estimate_transfer(&a, &b, &p.borrow()); } });
+    osc_burst_input.set_callback(            { let a = appmsg.clone(); let b = bg.clone(); let p = Rc::clone(&shader_profile); move |_| { send_estimate_transfer(&a, &b, &p.borrow()); } });
+    osc_burst_wait_input.set_callback(       { let a = appmsg.clone(); let b = bg.clone(); let p = Rc::clone(&shader_profile); move |_| { send_estimate_transfer(&a, &b, &p.borrow()); } });
+    osc_bandwidth_input.set_callback({
+        let a = appmsg.clone();
+        let b = bg.clone();
+        let p = Rc::clone(&shader_profile);
+        let mut osc_speed_slider = osc_speed_slider.clone();
+        move |input| {
+            if matches!(input.value().trim(), "" | "0") {
+                osc_speed_slider.activate();
+            } else {
+                osc_speed_slider.deactivate();
+            }
+            send_estimate_transfer(&a, &b, &p.borrow());
+        }
+    });
+    osc_speed_slider.set_callback(           { let a = appmsg.clone(); let b = bg.clone(); let p = Rc::clone(&shader_profile); move |_| { send_estimate_transfer(&a, &b, &p.borrow()); } });
+    osc_rle_compression_toggle.set_callback( { let a = appmsg.clone(); let b = bg.clone(); let p = Rc::clone(&shader_profile); move |_| { send_estimate_transfer(&a, &b, &p.borrow()); } });
+    osc_auto_compression_toggle.set_callback({
+        let a = appmsg.clone();
+        let b = bg.clone();
+        let p = Rc::clone(&shader_profile);
+        let mut rle_toggle = osc_rle_compression_toggle.clone();
+        move |t| {
+            if t.is_checked() { rle_toggle.deactivate(); } else { rle_toggle.activate(); }
+            send_estimate_transfer(&a, &b, &p.borrow());
+        }
+    });
+    osc_key_toggle.set_callback({
+        let mut osc_key_spinner = osc_key_spinner.clone();
+        move |t| {
+            if t.is_checked() {
+                osc_key_spinner.activate();
+            } else {
+                osc_key_spinner.deactivate();
+            }
+        }
+    });
+    send_estimate_transfer(&appmsg, &bg, &shader_profile.borrow());
+
+    playlist_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        let shader_profile = Rc::clone(&shader_profile);
+        move |_| {
+            playlist::show_playlist_window(
+                &appmsg, &bg,
+                { let a = appmsg.clone(); move || get_image_settings(&a) },
+                { let p = Rc::clone(&shader_profile); move || get_send_osc_opts(&p.borrow()) },
+            );
+        }
+    });
+    stream_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let shader_profile = Rc::clone(&shader_profile);
+        move |_| {
+            stream::show_stream_window(
+                &appmsg,
+                { let a = appmsg.clone(); move || get_image_settings(&a) },
+                { let p = Rc::clone(&shader_profile); move || get_send_osc_opts(&p.borrow()) },
+            );
+        }
+    });
+    video_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        let shader_profile = Rc::clone(&shader_profile);
+        move |_| {
+            video::show_video_window(
+                &appmsg, &bg,
+                { let a = appmsg.clone(); move || get_image_settings(&a) },
+                { let p = Rc::clone(&shader_profile); move || get_send_osc_opts(&p.borrow()) },
+            );
+        }
+    });
+    text_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        let shader_profile = Rc::clone(&shader_profile);
+        move |_| {
+            text::show_text_window(
+                &appmsg, &bg,
+                { let a = appmsg.clone(); move || get_image_settings(&a) },
+                { let p = Rc::clone(&shader_profile); move || get_send_osc_opts(&p.borrow()) },
+            );
+        }
+    });
+    send_history_btn.set_callback(|_| send_stats::show_history_window());
+    log_btn.set_callback(|_| log_panel::show_log_window());
+    // Menu items aren't widgets, so i18n::labeled can't relabel this one, the language choice does it
+    let save_defaults_idx = menubar.add(&format!("Settings/{}", i18n::tr("Save as defaults")), Shortcut::None, menu::MenuFlag::Normal, {
+        let appmsg = appmsg.clone();
+        let shader_profile = Rc::clone(&shader_profile);
+        move |_| {
+            match || -> Result<PathBuf, Box<dyn Error>> {
+                let osc_target_input: Input = app::widget_from_id("osc_target_input").ok_or("widget_from_id fail")?;
+                let osc_speed_slider: HorValueSlider = app::widget_from_id("osc_speed_slider").ok_or("widget_from_id fail")?;
+                let scaler_type_choice: menu::Choice = app::widget_from_id("scaler_type_choice").ok_or("widget_from_id fail")?;
+                let osc_warn_input: IntInput = app::widget_from_id("osc_warn_input").ok_or("widget_from_id fail")?;
+                let osc_confirm_toggle: CheckButton = app::widget_from_id("osc_confirm_toggle").ok_or("widget_from_id fail")?;
+
+                let target = osc_target_input.value();
+                let (host, port) = target.trim().rsplit_once(':')
+                    .ok_or(format!("OSC target {target:?} should be host:port"))?;
+                let config = config::Config {
+                    osc_host: host.to_string(),
+                    osc_port: port.parse().map_err(|err| format!("Bad port in OSC target {target:?}: {err}"))?,
+                    prefix: shader_profile.borrow().prefix.clone(),
+                    msgs_per_second: osc_speed_slider.value(),
+                    send_warn_secs: osc_warn_input.value().trim().parse().unwrap_or(0),
+                    scaler: scaler_type_choice.choice().unwrap_or_default(),
+                    scheme: theme::current().0,
+                    theme: theme::current().1,
+                    ui_scale: theme::ui_scale(),
+                    font_size: theme::font_size(),
+                    language: i18n::language().code().to_string(),
+                    confirm_send: osc_confirm_toggle.is_checked(),
+                    // The layout gets saved on exit anyway
+                    ..config::Config::load().unwrap_or_default()
+                };
+                config.save()
+            }() {
+                Ok(path) => alert(&appmsg, format!("{} {path:?}", i18n::tr("Saved defaults to"))),
+                Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't save defaults"))),
+            }
+        }
+    });
+    language_choice.set_callback({
+        let menubar = menubar.clone();
+        move |c| {
+            if let Some(&lang) = usize::try_from(c.value()).ok().and_then(|i| i18n::LANGUAGES.get(i)) {
+                i18n::set_language(lang);
+                if let Some(mut item) = menubar.at(save_defaults_idx) {
+                    item.set_label(i18n::tr("Save as defaults"));
+                }
+            }
+        }
+    });
 
+    send_job::on_change(show_send_state);
     send_osc_btn.set_callback({
         let bg = bg.clone();
         let appmsg = appmsg.clone();
+        let shader_profile = Rc::clone(&shader_profile);
         move |_| {
             match || -> Result<(), String> {
-                bg.send(
-                    BgMessage::SendOSC(send_osc::SendOSCOpts{
-                        pixfmt: osc_pixfmt_choice.choice()
-                            .ok_or("No PixFmt selected")?
-                            .parse()?,
-                        msgs_per_second: osc_speed_slider.value(),
-                        rle_compression: osc_rle_compression_toggle.value(),
-                        ..Default::default()
-                    })
-                ).map_err(|err| format!("Couldn't send message to BG thread: {err}"))?;
+                bg.send(BgMessage::SendOSC(get_send_osc_opts(&shader_profile.borrow())?, None))
+                    .map_err(|err| format!("Couldn't send message to BG thread: {err}"))?;
                 Ok(())
             }() {
                 Ok(()) => (),
-                Err(err) => error_alert(&appmsg, format!("Send OSC button error:\n{err}")),
+                Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Send OSC button error"))),
+            }
+        }
+    });
+
+    http_port_input.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        let http_server: RefCell<Option<remote::HttpServer>> = RefCell::new(None);
+        move |input| {
+            // Stop the old one first, in case we're just restarting on the same port
+            *http_server.borrow_mut() = None;
+
+            let value = input.value();
+            if value.trim().is_empty() {
+                return;
+            }
+            match value.trim().parse::<u16>() {
+                Ok(port) => match remote::HttpServer::start(port, &appmsg, &bg) {
+                    Ok(server) => *http_server.borrow_mut() = Some(server),
+                    Err(err) => error_alert(&appmsg, format!("{} {port}:\n{err}", i18n::tr("Couldn't start HTTP server on port"))),
+                },
+                Err(err) => error_alert(&appmsg, format!("{} {value:?}: {err}", i18n::tr("Bad port"))),
+            }
+        }
+    });
+
+    ws_port_input.set_callback({
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        let ws_server: RefCell<Option<ws_bridge::WsServer>> = RefCell::new(None);
+        move |input| {
+            *ws_server.borrow_mut() = None;
+
+            let value = input.value();
+            if value.trim().is_empty() {
+                return;
+            }
+            match value.trim().parse::<u16>() {
+                Ok(port) => match ws_bridge::WsServer::start(port, &appmsg, &bg) {
+                    Ok(server) => *ws_server.borrow_mut() = Some(server),
+                    Err(err) => error_alert(&appmsg, format!("{} {port}:\n{err}", i18n::tr("Couldn't start WebSocket bridge on port"))),
+                },
+                Err(err) => error_alert(&appmsg, format!("{} {value:?}: {err}", i18n::tr("Bad port"))),
+            }
+        }
+    });
+
+    oscquery_port_input.set_callback({
+        let appmsg = appmsg.clone();
+        let oscquery_service: RefCell<Option<oscquery::OscQueryService>> = RefCell::new(None);
+        move |input| {
+            *oscquery_service.borrow_mut() = None;
+            adaptive_rate::set_listen_port(adaptive_rate::DEFAULT_LISTEN_PORT);
+
+            let value = input.value();
+            if value.trim().is_empty() {
+                return;
+            }
+            match value.trim().parse::<u16>() {
+                Ok(port) => match oscquery::OscQueryService::start(port) {
+                    Ok(service) => {
+                        *oscquery_service.borrow_mut() = Some(service);
+                        adaptive_rate::set_listen_port(port);
+                    },
+                    Err(err) => error_alert(&appmsg, format!("{} {port}:\n{err}", i18n::tr("Couldn't start OSCQuery service for port"))),
+                },
+                Err(err) => error_alert(&appmsg, format!("{} {value:?}: {err}", i18n::tr("Bad port"))),
             }
         }
     });
 
+    // Lives on the main thread, as that's where the hotkey events get delivered
+    match hotkeys::Hotkeys::new(&appmsg) {
+        Ok(global_hotkeys) => {
+            let global_hotkeys = Rc::new(RefCell::new(global_hotkeys));
+            hotkey_input.set_callback({
+                let global_hotkeys = Rc::clone(&global_hotkeys);
+                let appmsg = appmsg.clone();
+                move |input| {
+                    if let Err(err) = global_hotkeys.borrow_mut().set(hotkeys::HotkeyAction::CaptureAndSend, &input.value()) {
+                        error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't set capture-and-send hotkey")));
+                    }
+                }
+            });
+            resend_hotkey_input.set_callback({
+                let global_hotkeys = Rc::clone(&global_hotkeys);
+                let appmsg = appmsg.clone();
+                move |input| {
+                    if let Err(err) = global_hotkeys.borrow_mut().set(hotkeys::HotkeyAction::Resend, &input.value()) {
+                        error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't set resend hotkey")));
+                    }
+                }
+            });
+        },
+        Err(err) => {
+            warn!("Global hotkeys unavailable: {err}");
+            hotkey_input.deactivate();
+            resend_hotkey_input.deactivate();
+        },
+    }
+
     scroll.end();
     col.end();
     row.end();
+    main_col.end();
     wind.end();
 
     wind.make_resizable(true);
@@ -1174,6 +4370,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let orig_hook = panic::take_hook();
     panic::set_hook(Box::new({
+        let appmsg = appmsg.clone();
         move |panic_info| {
             // invoke the default handler, but then display an alert message
             orig_hook(panic_info);
@@ -1188,15 +4385,21 @@ fn main() -> Result<(), Box<dyn Error>> {
         match appmsg_recv.try_recv() {
             Ok(msg) => match msg {
                 AppMessage::Alert(s)    => dialog::alert_default(&s),
+                AppMessage::Error(report) => report.show(),
                 AppMessage::SetTitle(s) => wind.set_label(&s),
+                AppMessage::Progress(stage) => {
+                    progress_frame.set_label(stage.map(i18n::tr).unwrap_or(""));
+                    progress_frame.redraw();
+                    wind.set_cursor(if stage.is_some() { Cursor::Wait } else { Cursor::Default });
+                },
                 AppMessage::CreateWindow(width, height, title, f) => {
-                    println!("Creating window {title}({width},{height})");
+                    debug!("Creating window {title}({width},{height})");
                     let mut wind = Window::default().with_size(width, height);
                     wind.set_label(&title);
                     let res = f(&mut wind);
                     if let Err(err) = res {
                         let msg = format!("CreateWindow error: {err}");
-                        eprintln!("{}", msg);
+                        error!("{}", msg);
                         dialog::alert_default(&msg);
                         // Something failed, delete the window
                         Window::delete(wind);
@@ -1209,17 +4412,38 @@ fn main() -> Result<(), Box<dyn Error>> {
                     window.hide();
                     Window::delete(window);
                 },
+                AppMessage::CaptureAndSend => capture_and_send(&appmsg, &bg, &shader_profile.borrow()),
+                AppMessage::RemoteSend => remote_send(&appmsg, &bg, &shader_profile.borrow()),
+                AppMessage::ApplyProject(project) => {
+                    if !project.send_settings.prefix.is_empty() {
+                        shader_profile.borrow_mut().prefix = project.send_settings.prefix.clone();
+                    }
+                    match set_image_settings_widgets(&project.image_settings)
+                        .and_then(|()| set_send_settings_widgets(&project.send_settings)) {
+                        Ok(()) => send_updateimage(&appmsg, &bg),
+                        Err(err) => error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't restore the project settings"))),
+                    }
+                },
+                AppMessage::Resend => {
+                    if let Err(err) = bg.send(BgMessage::ResendOSC) {
+                        error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't send message to BG thread")));
+                    }
+                },
             },
             Err(mpsc::TryRecvError::Empty) => (),
-            Err(err) => eprintln!("Channel error: {err}"),
+            Err(err) => error!("Channel error: {err}"),
         }
     }
 
-    println!("App finished");
+    info!("App finished");
+
+    save_layout(&wind, &palette_frame, &scroll);
+    playlist::stop();
+    stream::stop();
 
     bg.send_or_replace(BgMessage::Quit)?;
     joinhandle.join().map_err(|err| format!("Joining failed: {err:?}"))?;
-    println!("BG Thread joined");
+    info!("BG Thread joined");
 
     Ok(())
 }
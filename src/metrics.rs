@@ -0,0 +1,79 @@
+// The latest timings for the Performance panel: how long each pipeline stage took (everything
+// time_it! times gets recorded here), and how long the BG messages sat in the queue before the BG
+// thread got to them. Only the latest of each is kept, as the point is to see what a setting just
+// did to the numbers.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+static STAGES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+static WAITS: Mutex<Vec<(String, Duration)>> = Mutex::new(Vec::new());
+static GENERATION: Mutex<u64> = Mutex::new(0);
+
+fn bump() {
+    if let Ok(mut generation) = GENERATION.lock() {
+        *generation += 1;
+    }
+}
+
+// Goes up whenever something gets recorded, so the panel knows when to refresh
+pub fn generation() -> u64 {
+    GENERATION.lock().map_or(0, |generation| *generation)
+}
+
+pub fn record(stage: &'static str, duration: Duration) {
+    match STAGES.lock() {
+        Ok(mut stages) => match stages.iter_mut().find(|(name, _)| *name == stage) {
+            Some(entry) => entry.1 = duration,
+            None => stages.push((stage, duration)),
+        },
+        Err(err) => warn!("Couldn't lock stage timings: {err}"),
+    }
+    bump();
+}
+
+pub fn record_wait(message: &str, waited: Duration) {
+    match WAITS.lock() {
+        Ok(mut waits) => match waits.iter_mut().find(|(name, _)| name == message) {
+            Some(entry) => entry.1 = waited,
+            None => waits.push((message.to_string(), waited)),
+        },
+        Err(err) => warn!("Couldn't lock queue wait timings: {err}"),
+    }
+    bump();
+}
+
+pub fn clear() {
+    if let Ok(mut stages) = STAGES.lock() {
+        stages.clear();
+    }
+    if let Ok(mut waits) = WAITS.lock() {
+        waits.clear();
+    }
+    bump();
+}
+
+fn format_duration(d: Duration) -> String {
+    if d >= Duration::from_secs(1) {
+        format!("{:.2} s", d.as_secs_f64())
+    } else {
+        format!("{:.1} ms", d.as_secs_f64() * 1000.0)
+    }
+}
+
+// The stages slowest first, then the queue waits
+pub fn report() -> String {
+    let mut stages = STAGES.lock().map(|stages| stages.clone()).unwrap_or_default();
+    stages.sort_by(|a, b| b.1.cmp(&a.1));
+    let waits = WAITS.lock().map(|waits| waits.clone()).unwrap_or_default();
+
+    let mut lines: Vec<String> = stages.iter()
+        .map(|(name, d)| format!("{:>9}  {name}", format_duration(*d)))
+        .collect();
+    if !waits.is_empty() {
+        lines.push(String::new());
+        lines.push("Queue waits:".to_string());
+        lines.extend(waits.iter().map(|(name, d)| format!("{:>9}  {name}", format_duration(*d))));
+    }
+    lines.join("\n")
+}
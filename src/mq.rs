@@ -1,51 +1,112 @@
-// TODO: Need to support "Disconnected" state like e.g. std::mpsc::chanel. If the sender disconnects the receiver might need to know
+// Like std::sync::mpsc::channel, but the sender can replace the last queued message instead of
+// adding another one. Also like mpsc, the queue knows when the other side is gone: once all the
+// senders have been dropped recv() returns RecvError::Disconnected (after the queue has been
+// emptied) instead of blocking forever, and sending fails once the receiver has been dropped.
+//
+// Every message remembers when it was queued (or replaced), so the receiver can tell how long it
+// waited (see recv_waited).
 
 use std::sync::{Arc, Condvar, Mutex, MutexGuard};
 use std::collections::vec_deque::{VecDeque};
 use std::error::Error;
+use std::time::{Duration, Instant};
+use log::{debug, trace};
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
+struct State<T> {
+    items: VecDeque<(T, Instant)>,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+type Shared<T> = Arc<(Mutex<State<T>>, Condvar)>;
+
+#[derive(Debug)]
 pub struct MessageQueueSender<T> {
-    queue: Arc<(Mutex<VecDeque<T>>, Condvar)>,
+    queue: Shared<T>,
 }
 
 #[derive(Debug)]
 pub struct MessageQueueReceiver<T> {
-    queue: Arc<(Mutex<VecDeque<T>>, Condvar)>,
+    queue: Shared<T>,
 }
 
 pub fn mq<T>() -> (MessageQueueSender<T>, MessageQueueReceiver<T>) {
-    let q = Arc::new((Mutex::new(VecDeque::<T>::new()), Condvar::new()));
+    let q = Arc::new((
+        Mutex::new(State::<T> { items: VecDeque::new(), senders: 1, receiver_alive: true }),
+        Condvar::new(),
+    ));
     let q2 = Arc::clone(&q);
 
     (MessageQueueSender::<T> { queue: q }, MessageQueueReceiver::<T> { queue: q2 })
 }
 
+impl<T> Clone for MessageQueueSender<T> {
+    fn clone(&self) -> Self {
+        // Even with a poisoned mutex we need to keep the count straight
+        let mut state = self.queue.0.lock().unwrap_or_else(|err| err.into_inner());
+        state.senders += 1;
+        MessageQueueSender::<T> { queue: Arc::clone(&self.queue) }
+    }
+}
+
+impl<T> Drop for MessageQueueSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.queue.0.lock().unwrap_or_else(|err| err.into_inner());
+        state.senders -= 1;
+        if state.senders == 0 {
+            debug!("mq: last sender dropped");
+            // Wake up the receiver so it can notice
+            self.queue.1.notify_all();
+        }
+    }
+}
+
+impl<T> Drop for MessageQueueReceiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.queue.0.lock().unwrap_or_else(|err| err.into_inner());
+        state.receiver_alive = false;
+        debug!("mq: receiver dropped");
+    }
+}
+
 impl<T> MessageQueueSender<T> {
+    // The error is the message and whether it's because the receiver is gone
+    fn lock(&self) -> Result<MutexGuard<'_, State<T>>, (String, bool)> {
+        let q = self.queue.0.lock()
+            .map_err(|err| (format!("Error locking mutex: {err}"), false))?;
+        if !q.receiver_alive {
+            return Err(("Receiver disconnected".to_string(), true));
+        }
+        Ok(q)
+    }
+
     pub fn send(&self, val: T) -> Result<(), SendError<T>> {
-        let mut q = match self.queue.0.lock() {
+        let mut q = match self.lock() {
             Ok(q) => q,
-            Err(err) => return Err(SendError::<T> { data: val, message: format!("Error locking mutex: {err}") }),
+            Err((message, disconnected)) => return Err(SendError::<T> { data: val, message: message, disconnected: disconnected }),
         };
 
-        q.push_back(val);
+        q.items.push_back((val, Instant::now()));
+        trace!("mq send: {} queued", q.items.len());
         self.queue.1.notify_all(); // Might only be neccessary when the queue was empty prior to push_back
 
         Ok(())
     }
 
     pub fn send_or_replace(&self, val: T) -> Result<(), SendError<T>> {
-        let mut q = match self.queue.0.lock() {
+        let mut q = match self.lock() {
             Ok(q) => q,
-            Err(err) => return Err(SendError::<T> { data: val, message: format!("Error locking mutex: {err}") }),
+            Err((message, disconnected)) => return Err(SendError::<T> { data: val, message: message, disconnected: disconnected }),
         };
 
-        match q.back_mut() {
+        match q.items.back_mut() {
             Some(x) => {
-                *x = val;
+                debug!("mq send_or_replace: replaced last queued {}", std::any::type_name::<T>());
+                *x = (val, Instant::now());
             },
             None => {
-                q.push_back(val);
+                q.items.push_back((val, Instant::now()));
                 self.queue.1.notify_all();
             },
         }
@@ -54,22 +115,23 @@ impl<T> MessageQueueSender<T> {
     }
 
     pub fn send_or_replace_if<F: FnOnce(&T) -> bool>(&self, pred: F, val: T) -> Result<(), SendError<T>> {
-        let mut q = match self.queue.0.lock() {
+        let mut q = match self.lock() {
             Ok(q) => q,
-            Err(err) => return Err(SendError::<T> { data: val, message: format!("Error locking mutex: {err}") }),
+            Err((message, disconnected)) => return Err(SendError::<T> { data: val, message: message, disconnected: disconnected }),
         };
 
-        match q.back_mut() {
+        match q.items.back_mut() {
             Some(x) => {
-                if pred(x) {
-                    *x = val;
+                if pred(&x.0) {
+                    debug!("mq send_or_replace_if: replaced last queued {}", std::any::type_name::<T>());
+                    *x = (val, Instant::now());
                 } else {
-                    q.push_back(val);
+                    q.items.push_back((val, Instant::now()));
                     self.queue.1.notify_all(); // Might be unneccessary since queue was already not empty
                 }
             },
             None => {
-                q.push_back(val);
+                q.items.push_back((val, Instant::now()));
                 self.queue.1.notify_all();
             },
         }
@@ -79,53 +141,165 @@ impl<T> MessageQueueSender<T> {
 
     pub fn is_empty(&self) -> Result<bool, SendError<()>> {
         let q = self.queue.0.lock()
-            .map_err(|err| SendError::<()> { data: (), message: format!("Error locking mutex: {err}") })?;
-        Ok(q.is_empty())
+            .map_err(|err| SendError::<()> { data: (), message: format!("Error locking mutex: {err}"), disconnected: false })?;
+        Ok(q.items.is_empty())
+    }
+
+    // Whether the receiving end is still around
+    pub fn is_connected(&self) -> bool {
+        self.queue.0.lock().map_or(false, |q| q.receiver_alive)
     }
 }
 
 impl<T> MessageQueueReceiver<T> {
-    fn wait_until_nonempty(&self) -> Result<MutexGuard<'_, VecDeque<T>>, RecvError> {
+    fn wait_until_nonempty(&self) -> Result<MutexGuard<'_, State<T>>, RecvError> {
         let (lock, cvar) = &*self.queue;
         let guard = cvar.wait_while(
             lock.lock()
-                .map_err(|err| RecvError{ message: format!("Error locking mutex: {err}") })?,
-            |vd| { vd.is_empty() },
-        ).map_err(|err| RecvError{ message: format!("Error waiting on Condvar: {err}") })?;
+                .map_err(|err| RecvError::Error(format!("Error locking mutex: {err}")))?,
+            |state| { state.items.is_empty() && state.senders > 0 },
+        ).map_err(|err| RecvError::Error(format!("Error waiting on Condvar: {err}")))?;
+
+        // Whatever was sent before the senders went away still gets delivered
+        if guard.items.is_empty() {
+            return Err(RecvError::Disconnected);
+        }
         Ok(guard)
     }
 
     pub fn drain(&self) -> Result<Box<[T]>, RecvError> {
         let mut guard = self.wait_until_nonempty()?;
-        let drain = guard.drain(..).collect();
+        trace!("mq drain: {} messages", guard.items.len());
+        let drain = guard.items.drain(..).map(|(val, _)| val).collect();
+        Ok(drain)
+    }
+
+    // Like drain(), but takes at most max messages and leaves the rest queued. Useful for
+    // coalescing a burst without swallowing everything that came in behind it.
+    pub fn drain_max(&self, max: usize) -> Result<Box<[T]>, RecvError> {
+        let mut guard = self.wait_until_nonempty()?;
+        let n = max.min(guard.items.len());
+        trace!("mq drain_max: {} of {} messages", n, guard.items.len());
+        let drain = guard.items.drain(..n).map(|(val, _)| val).collect();
         Ok(drain)
     }
 
+    // Look at the next message without taking it out of the queue. Doesn't block, None means the
+    // queue is empty.
+    pub fn peek_with<R, F: FnOnce(&T) -> R>(&self, f: F) -> Result<Option<R>, RecvError> {
+        let q = self.queue.0.lock()
+            .map_err(|err| RecvError::Error(format!("Error locking mutex: {err}")))?;
+        Ok(q.items.front().map(|(val, _)| f(val)))
+    }
+
+    pub fn peek(&self) -> Result<Option<T>, RecvError> where T: Clone {
+        self.peek_with(T::clone)
+    }
+
     pub fn recv(&self) -> Result<T, RecvError> {
+        Ok(self.recv_waited()?.0)
+    }
+
+    // Along with how long the message was in the queue
+    pub fn recv_waited(&self) -> Result<(T, Duration), RecvError> {
         let mut guard = self.wait_until_nonempty()?;
-        Ok(guard.pop_front().unwrap())
+        trace!("mq recv: {} queued", guard.items.len());
+        let (val, queued) = guard.items.pop_front().unwrap();
+        Ok((val, queued.elapsed()))
     }
 
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
         let mut q = self.queue.0.lock()
-            .map_err(|err| TryRecvError::RecvError(RecvError{ message: format!("Error locking mutex: {err}") }))?;
-        if q.is_empty() {
-            Err(TryRecvError::Empty)
-        } else {
-            Ok(q.pop_front().unwrap())
+            .map_err(|err| TryRecvError::RecvError(RecvError::Error(format!("Error locking mutex: {err}"))))?;
+        match q.items.pop_front() {
+            Some((val, _)) => Ok(val),
+            None if q.senders == 0 => Err(TryRecvError::RecvError(RecvError::Disconnected)),
+            None => Err(TryRecvError::Empty),
         }
     }
 }
 
+// Blocking iterators over the received messages, so consumers can just do `for msg in receiver`.
+// They end when all the senders are gone and the queue has been emptied. Any other receive error
+// also ends the iteration (it would only keep failing anyway), but gets logged first.
+impl<T> MessageQueueReceiver<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { receiver: self }
+    }
+
+    // Like iter(), along with how long each message was in the queue
+    pub fn iter_waited(&self) -> impl Iterator<Item = (T, Duration)> + '_ {
+        std::iter::from_fn(move || ok_or_end(self.recv_waited()))
+    }
+}
+
+fn next_or_end<T>(receiver: &MessageQueueReceiver<T>) -> Option<T> {
+    ok_or_end(receiver.recv())
+}
+
+fn ok_or_end<T>(result: Result<T, RecvError>) -> Option<T> {
+    match result {
+        Ok(val) => Some(val),
+        Err(RecvError::Disconnected) => None,
+        Err(err) => {
+            log::error!("mq: ending iteration: {err}");
+            None
+        },
+    }
+}
+
+pub struct Iter<'a, T> {
+    receiver: &'a MessageQueueReceiver<T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        next_or_end(self.receiver)
+    }
+}
+
+pub struct IntoIter<T> {
+    receiver: MessageQueueReceiver<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        next_or_end(&self.receiver)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a MessageQueueReceiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for MessageQueueReceiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { receiver: self }
+    }
+}
+
 // ERROR HANDLING
 pub struct SendError<T> {
     pub data: T,
     pub message: String,
+    pub disconnected: bool, // The receiver is gone, as opposed to something going wrong with the queue
 }
 
 impl<T> std::fmt::Debug for SendError<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "SendError<{}> {{ data: .., message: {:?} }}", std::any::type_name::<T>(), self.message)
+        write!(f, "SendError<{}> {{ data: .., message: {:?}, disconnected: {:?} }}", std::any::type_name::<T>(), self.message, self.disconnected)
     }
 }
 
@@ -138,13 +312,17 @@ impl<T> std::fmt::Display for SendError<T> {
 impl<T> Error for SendError<T> {}
 
 #[derive(Debug)]
-pub struct RecvError {
-    pub message: String,
+pub enum RecvError {
+    Disconnected, // All senders are gone and the queue is empty
+    Error(String),
 }
 
 impl std::fmt::Display for RecvError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.message)
+        match self {
+            RecvError::Disconnected => write!(f, "All senders disconnected"),
+            RecvError::Error(message) => write!(f, "{}", message),
+        }
     }
 }
 
@@ -155,4 +333,3 @@ pub enum TryRecvError {
     RecvError(RecvError),
     Empty,
 }
-
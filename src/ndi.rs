@@ -0,0 +1,262 @@
+// NDI video input for the streaming mode, so that OBS, VJ tools and the like can push frames
+// straight into the pipeline. Talks to the NDI runtime (from ndi.video, installed separately) by
+// loading it at runtime, so it's only needed by those who actually use it.
+//
+// Spout isn't supported, as it shares GPU textures between processes and we have no GPU context
+// to receive them in. Spout to NDI bridges (e.g. SpoutToNDI) work for getting those in.
+
+use crate::stream::FrameSource;
+
+use image::RgbaImage;
+use libloading::Library;
+use std::error::Error;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::ptr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// How long to look for sources on the network before giving up
+const FIND_TIMEOUT: Duration = Duration::from_secs(5);
+// How long to wait for a frame before giving up
+const CAPTURE_TIMEOUT_MS: u32 = 5000;
+
+// From Processing.NDI.Lib.h. Filled in by the runtime, so not every field gets looked at.
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct NdiSource {
+    p_ndi_name: *const c_char,
+    p_url_address: *const c_char,
+}
+
+#[repr(C)]
+struct NdiRecvCreateV3 {
+    source_to_connect_to: NdiSource,
+    color_format: i32,
+    bandwidth: i32,
+    allow_video_fields: bool,
+    p_ndi_recv_name: *const c_char,
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct NdiVideoFrameV2 {
+    xres: i32,
+    yres: i32,
+    fourcc: u32,
+    frame_rate_n: i32,
+    frame_rate_d: i32,
+    picture_aspect_ratio: f32,
+    frame_format_type: i32,
+    timecode: i64,
+    p_data: *mut u8,
+    line_stride_in_bytes: i32,
+    p_metadata: *const c_char,
+    timestamp: i64,
+}
+
+const RECV_COLOR_FORMAT_RGBX_RGBA: i32 = 2;
+const RECV_BANDWIDTH_HIGHEST: i32 = 100;
+const FRAME_TYPE_VIDEO: i32 = 1;
+const FRAME_TYPE_ERROR: i32 = 4;
+
+const fn fourcc(s: &[u8; 4]) -> u32 {
+    (s[0] as u32) | ((s[1] as u32) << 8) | ((s[2] as u32) << 16) | ((s[3] as u32) << 24)
+}
+const FOURCC_RGBA: u32 = fourcc(b"RGBA");
+const FOURCC_RGBX: u32 = fourcc(b"RGBX");
+const FOURCC_BGRA: u32 = fourcc(b"BGRA");
+const FOURCC_BGRX: u32 = fourcc(b"BGRX");
+
+type Instance = *mut c_void;
+
+// The NDI runtime, and the functions we use out of it
+struct NdiLib {
+    _lib: Library, // Has to stay loaded for the function pointers to stay good
+    find_create_v2: unsafe extern "C" fn(*const c_void) -> Instance,
+    find_wait_for_sources: unsafe extern "C" fn(Instance, u32) -> bool,
+    find_get_current_sources: unsafe extern "C" fn(Instance, *mut u32) -> *const NdiSource,
+    find_destroy: unsafe extern "C" fn(Instance),
+    recv_create_v3: unsafe extern "C" fn(*const NdiRecvCreateV3) -> Instance,
+    recv_capture_v2: unsafe extern "C" fn(Instance, *mut NdiVideoFrameV2, *mut c_void, *mut c_void, u32) -> i32,
+    recv_free_video_v2: unsafe extern "C" fn(Instance, *const NdiVideoFrameV2),
+    recv_destroy: unsafe extern "C" fn(Instance),
+}
+
+// Where the runtime installer puts it, or just the name and hope it's on the library path
+fn library_candidates() -> Vec<String> {
+    if cfg!(windows) {
+        let name = "Processing.NDI.Lib.x64.dll";
+        std::env::var("NDI_RUNTIME_DIR_V6").into_iter()
+            .chain(std::env::var("NDI_RUNTIME_DIR_V5"))
+            .map(|dir| format!("{dir}\\{name}"))
+            .chain(std::iter::once(name.to_string()))
+            .collect()
+    } else if cfg!(target_os = "macos") {
+        vec!["/usr/local/lib/libndi.dylib".to_string(), "libndi.dylib".to_string()]
+    } else {
+        vec!["libndi.so.6".to_string(), "libndi.so.5".to_string(), "libndi.so".to_string()]
+    }
+}
+
+unsafe fn symbol<T: Copy>(lib: &Library, name: &[u8]) -> Result<T, libloading::Error> {
+    Ok(*lib.get::<T>(name)?)
+}
+
+impl NdiLib {
+    fn load() -> Result<NdiLib, Box<dyn Error>> {
+        let candidates = library_candidates();
+        // Loading a library runs its initialization code, which we just have to trust the NDI
+        // runtime with
+        let lib = candidates.iter()
+            .find_map(|name| unsafe { Library::new(name) }.ok())
+            .ok_or(format!("Couldn't load the NDI runtime (tried {}), is it installed?", candidates.join(", ")))?;
+
+        // The signatures are from Processing.NDI.Lib.h
+        unsafe {
+            let initialize: unsafe extern "C" fn() -> bool = symbol(&lib, b"NDIlib_initialize\0")?;
+            if !initialize() {
+                return Err("NDIlib_initialize failed (unsupported CPU?)".into());
+            }
+            Ok(NdiLib {
+                find_create_v2: symbol(&lib, b"NDIlib_find_create_v2\0")?,
+                find_wait_for_sources: symbol(&lib, b"NDIlib_find_wait_for_sources\0")?,
+                find_get_current_sources: symbol(&lib, b"NDIlib_find_get_current_sources\0")?,
+                find_destroy: symbol(&lib, b"NDIlib_find_destroy\0")?,
+                recv_create_v3: symbol(&lib, b"NDIlib_recv_create_v3\0")?,
+                recv_capture_v2: symbol(&lib, b"NDIlib_recv_capture_v2\0")?,
+                recv_free_video_v2: symbol(&lib, b"NDIlib_recv_free_video_v2\0")?,
+                recv_destroy: symbol(&lib, b"NDIlib_recv_destroy\0")?,
+                _lib: lib,
+            })
+        }
+    }
+}
+
+// Receives the frames of one NDI source
+pub struct NdiReceiver {
+    lib: Arc<NdiLib>,
+    recv: Instance,
+    name: String,
+}
+
+// The NDI receiver functions can be called from any thread
+unsafe impl Send for NdiReceiver {}
+
+impl NdiReceiver {
+    // Connects to the first source with name in its name (or just the first one, if name is empty)
+    pub fn connect(name: &str) -> Result<NdiReceiver, Box<dyn Error>> {
+        let lib = Arc::new(NdiLib::load()?);
+
+        unsafe {
+            let find = (lib.find_create_v2)(ptr::null());
+            if find.is_null() {
+                return Err("Couldn't create an NDI finder".into());
+            }
+
+            // The sources only stay valid until the finder is destroyed, so do all the looking and
+            // connecting before that
+            let result = || -> Result<(Instance, String), Box<dyn Error>> {
+                let start = Instant::now();
+                let mut found: Vec<String> = Vec::new();
+                while start.elapsed() < FIND_TIMEOUT {
+                    (lib.find_wait_for_sources)(find, 500);
+                    let mut count: u32 = 0;
+                    let sources = (lib.find_get_current_sources)(find, &mut count);
+                    if sources.is_null() {
+                        continue;
+                    }
+                    let sources = std::slice::from_raw_parts(sources, count as usize);
+                    found = sources.iter()
+                        .map(|s| if s.p_ndi_name.is_null() { String::new() } else { CStr::from_ptr(s.p_ndi_name).to_string_lossy().to_string() })
+                        .collect();
+                    let Some(i) = found.iter().position(|n| n.contains(name)) else {
+                        continue;
+                    };
+
+                    let recv_name = CString::new("OSCPixelSender")?;
+                    let settings = NdiRecvCreateV3 {
+                        source_to_connect_to: sources[i],
+                        color_format: RECV_COLOR_FORMAT_RGBX_RGBA,
+                        bandwidth: RECV_BANDWIDTH_HIGHEST,
+                        allow_video_fields: false,
+                        p_ndi_recv_name: recv_name.as_ptr(),
+                    };
+                    let recv = (lib.recv_create_v3)(&settings);
+                    if recv.is_null() {
+                        return Err(format!("Couldn't create an NDI receiver for {:?}", found[i]).into());
+                    }
+                    return Ok((recv, found[i].clone()));
+                }
+                Err(if found.is_empty() {
+                    "No NDI sources found".to_string()
+                } else {
+                    format!("No NDI source called {name:?} (found {})", found.join(", "))
+                }.into())
+            }();
+            (lib.find_destroy)(find);
+
+            let (recv, name) = result?;
+            info!("Connected to NDI source {name:?}");
+            Ok(NdiReceiver { lib: lib, recv: recv, name: name })
+        }
+    }
+}
+
+impl FrameSource for NdiReceiver {
+    fn frame(&mut self) -> Result<RgbaImage, Box<dyn Error>> {
+        let start = Instant::now();
+        loop {
+            if start.elapsed() > Duration::from_millis(CAPTURE_TIMEOUT_MS as u64) {
+                return Err(format!("No frames from NDI source {:?}", self.name).into());
+            }
+
+            let mut frame: NdiVideoFrameV2 = unsafe { std::mem::zeroed() };
+            let frame_type = unsafe {
+                (self.lib.recv_capture_v2)(self.recv, &mut frame, ptr::null_mut(), ptr::null_mut(), CAPTURE_TIMEOUT_MS)
+            };
+            match frame_type {
+                FRAME_TYPE_VIDEO => (),
+                FRAME_TYPE_ERROR => return Err(format!("Lost the connection to NDI source {:?}", self.name).into()),
+                _ => continue, // Nothing yet, or something that isn't video
+            }
+
+            let image = frame_to_image(&frame);
+            unsafe { (self.lib.recv_free_video_v2)(self.recv, &frame) };
+            return image;
+        }
+    }
+}
+
+fn frame_to_image(frame: &NdiVideoFrameV2) -> Result<RgbaImage, Box<dyn Error>> {
+    let (width, height) = (frame.xres.max(0) as usize, frame.yres.max(0) as usize);
+    let stride = frame.line_stride_in_bytes.max(0) as usize;
+    if frame.p_data.is_null() || width == 0 || height == 0 || stride < width*4 {
+        return Err("Bad NDI video frame".into());
+    }
+    let (bgr, opaque) = match frame.fourcc {
+        FOURCC_RGBA => (false, false),
+        FOURCC_RGBX => (false, true),
+        FOURCC_BGRA => (true, false),
+        FOURCC_BGRX => (true, true),
+        fourcc => return Err(format!("Unexpected NDI pixel format {:?}", fourcc.to_le_bytes().map(|b| b as char)).into()),
+    };
+
+    // Valid until the frame gets freed, which is after we're done here
+    let data = unsafe { std::slice::from_raw_parts(frame.p_data, stride*height) };
+    let mut bytes: Vec<u8> = Vec::with_capacity(width*height*4);
+    for row in data.chunks_exact(stride) {
+        for px in row[..width*4].chunks_exact(4) {
+            let (r, b) = if bgr { (px[2], px[0]) } else { (px[0], px[2]) };
+            bytes.extend_from_slice(&[r, px[1], b, if opaque { 255 } else { px[3] }]);
+        }
+    }
+    RgbaImage::from_raw(width as u32, height as u32, bytes).ok_or("Couldn't make an image of the NDI frame".into())
+}
+
+impl Drop for NdiReceiver {
+    fn drop(&mut self) {
+        unsafe { (self.lib.recv_destroy)(self.recv) };
+        debug!("Disconnected from NDI source {:?}", self.name);
+    }
+}
@@ -0,0 +1,131 @@
+// Reading of the per-avatar OSC config JSON files VRChat writes locally, so we can figure out what
+// PixelSendCRT-style parameters an avatar has and create matching shader profiles.
+
+use crate::shader_profile::{self, ShaderProfile};
+
+use serde::Deserialize;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+struct OscConfig {
+    #[allow(dead_code)]
+    id: String,
+    name: String,
+    parameters: Vec<OscConfigParameter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OscConfigParameter {
+    name: String,
+    input: Option<OscConfigEndpoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OscConfigEndpoint {
+    address: String,
+    #[serde(rename = "type")]
+    typ: String,
+}
+
+// Where VRChat puts the OSC configs (there is one folder per user under it, which then has an
+// Avatars folder in it)
+pub fn osc_config_dir() -> Option<PathBuf> {
+    let userprofile = std::env::var_os("USERPROFILE")?;
+    Some(PathBuf::from(userprofile).join("AppData").join("LocalLow").join("VRChat").join("VRChat").join("OSC"))
+}
+
+impl OscConfig {
+    fn input_of(&self, name: &str, typ: &str) -> Option<&OscConfigEndpoint> {
+        self.parameters.iter()
+            .find(|p| p.name == name)
+            .and_then(|p| p.input.as_ref())
+            .filter(|input| input.typ == typ)
+    }
+}
+
+fn read_osc_config(path: &Path) -> Result<OscConfig, Box<dyn Error>> {
+    let contents = std::fs::read_to_string(path)?;
+    // VRChat writes these with a BOM
+    let contents = contents.trim_start_matches('\u{feff}');
+    Ok(serde_json::from_str(contents)?)
+}
+
+// Find all parameter sets in the config that look like PixelSendCRT: a CLK and a Reset bool, and
+// int data parameters V0, V1, ... next to each other.
+fn detect_profiles(config: &OscConfig) -> Vec<ShaderProfile> {
+    let mut profiles = Vec::new();
+    for param in &config.parameters {
+        let Some(param_prefix) = param.name.strip_suffix("/CLK") else { continue; };
+        let Some(clk_input) = config.input_of(&param.name, "Bool") else { continue; };
+        let Some(prefix) = clk_input.address.strip_suffix("/CLK") else { continue; };
+
+        if config.input_of(&format!("{param_prefix}/Reset"), "Bool").is_none() {
+            continue;
+        }
+
+        let data_params: Vec<String> = (0..36)
+            .map(shader_profile::default_data_param)
+            .take_while(|v| config.input_of(&format!("{param_prefix}/{v}"), "Int").is_some())
+            .collect();
+        if data_params.len() < shader_profile::MIN_BYTES_PER_SEND {
+            warn!("{}: {param_prefix} only has {} data parameters, skipping", config.name, data_params.len());
+            continue;
+        }
+
+        profiles.push(ShaderProfile {
+            name: format!("{} ({param_prefix})", config.name),
+            prefix: prefix.to_string(),
+            clk_param: "CLK".to_string(),
+            reset_param: "Reset".to_string(),
+            data_params: data_params,
+            checksum_param: config.input_of(&format!("{param_prefix}/Checksum"), "Bool").map(|_| "Checksum".to_string()),
+            checksum_result_param: config.parameters.iter()
+                .any(|p| p.name == format!("{param_prefix}/ChecksumResult"))
+                .then(|| "ChecksumResult".to_string()),
+            query_param: config.input_of(&format!("{param_prefix}/Query"), "Bool").map(|_| "Query".to_string()),
+            ..Default::default()
+        });
+    }
+
+    profiles
+}
+
+// Scans all the avatar OSC configs under dir (recursively) for PixelSendCRT-style parameter
+// sets. Files we fail to read or parse are skipped.
+pub fn scan_osc_configs(dir: &Path) -> Vec<ShaderProfile> {
+    let mut profiles: Vec<ShaderProfile> = Vec::new();
+
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!("Couldn't read directory {dir:?}: {err}");
+                continue;
+            },
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "json") {
+                match read_osc_config(&path) {
+                    Ok(config) => {
+                        for p in detect_profiles(&config) {
+                            // The same avatar shows up once per user that has used it
+                            if !profiles.contains(&p) {
+                                info!("Found shader profile {:?} in {path:?}", p.name);
+                                profiles.push(p);
+                            }
+                        }
+                    },
+                    Err(err) => warn!("Couldn't read OSC config {path:?}: {err}"),
+                }
+            }
+        }
+    }
+
+    profiles
+}
@@ -0,0 +1,135 @@
+// OSCQuery host (see https://github.com/Vidvox/OSCQueryProposal). VRChat looks for OSC apps over mDNS
+// and sends the avatar parameters to every one that says it wants /avatar, which is how the ack and
+// checksum result parameters get back to us without routing port 9001 by hand (or fighting over it
+// with other OSC apps). Advertises the given UDP port, which the listeners then use instead of
+// DEFAULT_LISTEN_PORT (see adaptive_rate::set_listen_port).
+//
+// The HTTP side only listens on localhost, on whatever port is free, as that's what goes in the
+// mDNS record anyway.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::thread;
+
+pub const SERVICE_NAME: &'static str = "OSCPixelSender";
+const OSCJSON_SERVICE: &'static str = "_oscjson._tcp.local.";
+const OSC_SERVICE: &'static str = "_osc._udp.local.";
+const HOST_NAME: &'static str = "oscpixelsender.local.";
+const HOST_IP: &'static str = "127.0.0.1";
+
+fn host_info(osc_port: u16) -> serde_json::Value {
+    serde_json::json!({
+        "NAME": SERVICE_NAME,
+        "OSC_IP": HOST_IP,
+        "OSC_PORT": osc_port,
+        "OSC_TRANSPORT": "UDP",
+        "EXTENSIONS": {
+            "ACCESS": true,
+            "VALUE": true,
+            "DESCRIPTION": true,
+        },
+    })
+}
+
+// Just enough of a tree for VRChat to see that we want the avatar parameters
+fn tree() -> serde_json::Value {
+    let node = |path: &str, description: &str| serde_json::json!({
+        "FULL_PATH": path,
+        "ACCESS": 2, // Write only, VRChat sends and we listen
+        "DESCRIPTION": description,
+    });
+    serde_json::json!({
+        "FULL_PATH": "/",
+        "ACCESS": 0,
+        "CONTENTS": {
+            "avatar": {
+                "FULL_PATH": "/avatar",
+                "ACCESS": 0,
+                "CONTENTS": {
+                    "change": node("/avatar/change", "Avatar changes"),
+                    "parameters": node("/avatar/parameters", "Avatar parameters, for the acks and checksum results"),
+                },
+            },
+        },
+    })
+}
+
+pub struct OscQueryService {
+    server: Arc<tiny_http::Server>,
+    thread: Option<thread::JoinHandle<()>>,
+    mdns: mdns_sd::ServiceDaemon,
+    fullnames: Vec<String>,
+}
+
+impl OscQueryService {
+    pub fn start(osc_port: u16) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let server = Arc::new(tiny_http::Server::http((HOST_IP, 0))?);
+        let http_port = server.server_addr().to_ip().ok_or("OSCQuery server isn't on an IP address")?.port();
+
+        let thread = thread::spawn({
+            let server = Arc::clone(&server);
+            move || {
+                for request in server.incoming_requests() {
+                    // Everything but HOST_INFO gets the whole tree, it's small enough
+                    let body = if request.url().contains("HOST_INFO") { host_info(osc_port) } else { tree() };
+                    debug!("OSCQuery {} {}", request.method(), request.url());
+                    let response = tiny_http::Response::from_string(body.to_string())
+                        .with_header(tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap());
+                    if let Err(err) = request.respond(response) {
+                        warn!("Couldn't respond to OSCQuery request: {err}");
+                    }
+                }
+                info!("OSCQuery server stopped");
+            }
+        });
+
+        let mut fullnames: Vec<String> = Vec::new();
+        let mdns = match || -> Result<mdns_sd::ServiceDaemon, Box<dyn Error + Send + Sync>> {
+            let mdns = mdns_sd::ServiceDaemon::new()?;
+            match || -> Result<(), Box<dyn Error + Send + Sync>> {
+                for (service, port) in [(OSCJSON_SERVICE, http_port), (OSC_SERVICE, osc_port)] {
+                    let info = mdns_sd::ServiceInfo::new(service, SERVICE_NAME, HOST_NAME, HOST_IP, port, HashMap::<String, String>::new())?;
+                    fullnames.push(info.get_fullname().to_string());
+                    mdns.register(info)?;
+                }
+                Ok(())
+            }() {
+                Ok(()) => Ok(mdns),
+                Err(err) => {
+                    let _ = mdns.shutdown();
+                    Err(err)
+                },
+            }
+        }() {
+            Ok(mdns) => mdns,
+            Err(err) => {
+                // No struct means no Drop, so stop the HTTP thread here or it hangs around forever
+                server.unblock();
+                let _ = thread.join();
+                return Err(err);
+            },
+        };
+        info!("Advertising OSCQuery on port {http_port} for OSC on port {osc_port}");
+
+        Ok(OscQueryService { server: server, thread: Some(thread), mdns: mdns, fullnames: fullnames })
+    }
+}
+
+impl Drop for OscQueryService {
+    fn drop(&mut self) {
+        // Say goodbye, so VRChat stops sending to us right away instead of when the record times out
+        for fullname in &self.fullnames {
+            if let Err(err) = self.mdns.unregister(fullname) {
+                warn!("Couldn't unregister {fullname}: {err}");
+            }
+        }
+        if let Err(err) = self.mdns.shutdown() {
+            warn!("Couldn't shut down mDNS: {err}");
+        }
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
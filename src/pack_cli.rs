@@ -0,0 +1,112 @@
+// `pack` subcommand: turns an indexed (or grayscale) PNG, like the ones Save writes, into the exact
+// byte stream the shader gets, for unit testing shaders without VRChat. No GUI gets started.
+//
+//   rust_image_fiddler pack input.png [--bpp 4] [--rle] [--bytes-per-send 24] [-o out.bin]
+//
+// --bpp defaults to the bit depth of the PNG, and --bytes-per-send (which the RLE encoding depends
+// on) to the default shader profile. The output goes next to the input as .bin if not given.
+
+use crate::send_osc;
+use crate::shader_profile::ShaderProfile;
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+struct Args {
+    input: PathBuf,
+    output: Option<PathBuf>,
+    bitdepth: Option<u8>,
+    rle_compression: bool,
+    bytes_per_send: usize,
+}
+
+impl Args {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Args, Box<dyn Error>> {
+        let mut input: Option<PathBuf> = None;
+        let mut output: Option<PathBuf> = None;
+        let mut bitdepth: Option<u8> = None;
+        let mut rle_compression = false;
+        let mut bytes_per_send = ShaderProfile::default().bytes_per_send();
+
+        let mut iter = args;
+        while let Some(arg) = iter.next() {
+            let mut value = || iter.next().ok_or(format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--bpp" => bitdepth = Some(value()?.parse()?),
+                "--rle" => rle_compression = true,
+                "--bytes-per-send" => bytes_per_send = value()?.parse()?,
+                "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+                _ if arg.starts_with('-') => return Err(format!("Unknown argument {arg:?}").into()),
+                _ if input.is_none() => input = Some(PathBuf::from(arg)),
+                _ => return Err(format!("More than one input file given ({arg:?})").into()),
+            }
+        }
+
+        Ok(Args {
+            input: input.ok_or("No input PNG given")?,
+            output: output,
+            bitdepth: bitdepth,
+            rle_compression: rle_compression,
+            bytes_per_send: bytes_per_send,
+        })
+    }
+}
+
+// The palette indexes of an indexed PNG, one byte per pixel. Grayscale works too, as save_png
+// writes the indexes as the gray levels for grayscale output.
+fn read_indexes(path: &Path) -> Result<(Vec<u8>, u32, u8), Box<dyn Error>> {
+    let file = std::fs::File::open(path)
+        .map_err(|err| format!("Couldn't open {path:?}: {err}"))?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    // Keep the indexes as they are, rather than expanding them to colors
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+
+    if !matches!(info.color_type, png::ColorType::Indexed | png::ColorType::Grayscale) {
+        return Err(format!("{path:?} is {:?}, it needs to be indexed or grayscale", info.color_type).into());
+    }
+    let bitdepth: u8 = match info.bit_depth {
+        png::BitDepth::One => 1,
+        png::BitDepth::Two => 2,
+        png::BitDepth::Four => 4,
+        png::BitDepth::Eight => 8,
+        png::BitDepth::Sixteen => return Err("16 bit PNGs aren't supported".into()),
+    };
+
+    // Each line is padded out to a whole byte
+    let width = info.width as usize;
+    let per_byte = 8/(bitdepth as usize);
+    let mask = ((1u16 << bitdepth) - 1) as u8;
+    let indexes: Vec<u8> = buf[..info.buffer_size()]
+        .chunks_exact(info.line_size)
+        .flat_map(|line| (0..width).map(move |x| {
+            let shift = 8 - (bitdepth as usize)*(x % per_byte + 1);
+            (line[x/per_byte] >> shift) & mask
+        }))
+        .collect();
+
+    Ok((indexes, info.width, bitdepth))
+}
+
+pub fn run(args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(args)?;
+    let (indexes, width, png_bitdepth) = read_indexes(&args.input)?;
+    let bitdepth = args.bitdepth.unwrap_or(png_bitdepth);
+
+    let max_index = indexes.iter().copied().max().unwrap_or(0);
+    if bitdepth < 8 && max_index >= (1 << bitdepth) {
+        return Err(format!("Index {max_index} doesn't fit in {bitdepth} bpp").into());
+    }
+
+    let bytes = send_osc::shader_bytes(&indexes, width as usize, bitdepth, args.rle_compression, args.bytes_per_send)?;
+    let output = args.output.unwrap_or_else(|| args.input.with_extension("bin"));
+    std::fs::write(&output, &bytes)
+        .map_err(|err| format!("Couldn't write {output:?}: {err}"))?;
+
+    eprintln!("{}x{} at {bitdepth} bpp{}: {} bytes ({} chunks of {}) written to {output:?}",
+              width, indexes.len()/(width as usize), if args.rle_compression { ", RLE" } else { "" },
+              bytes.len(), bytes.len().div_ceil(args.bytes_per_send), args.bytes_per_send);
+    Ok(())
+}
@@ -0,0 +1,118 @@
+// Colors the palette has to have in it, whatever the quantizer thinks (pure black and white for
+// text, a brand color, ...). They get their palette entries reserved, the quantizer comes up with
+// the rest, and then the image gets remapped against the lot (see quantize_image). In the GUI
+// they're a list of hex colors, "#000000 #ffffff".
+//
+// A palette can also be locked: the last quantized palette gets kept, and every image after that
+// gets remapped to it rather than quantized, so a series of images all use the same colors.
+
+use std::sync::Mutex;
+
+pub type Rgb = [u8; 3];
+pub type Rgba = [u8; 4];
+
+pub fn parse_color(s: &str) -> Result<Rgb, String> {
+    let hex = s.trim().trim_start_matches('#');
+    let hex = match hex.len() {
+        // #abc is #aabbcc like in CSS
+        3 => hex.chars().flat_map(|c| [c, c]).collect::<String>(),
+        6 => hex.to_string(),
+        _ => return Err(format!("{s:?} isn't a color, it should look like #ff8000")),
+    };
+    let channel = |i: usize| u8::from_str_radix(&hex[i*2..i*2 + 2], 16)
+        .map_err(|_| format!("{s:?} isn't a color, it should look like #ff8000"));
+    Ok([channel(0)?, channel(1)?, channel(2)?])
+}
+
+// Separated by spaces or commas
+pub fn parse_colors(s: &str) -> Result<Vec<Rgb>, String> {
+    s.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|c| !c.is_empty())
+        .map(parse_color)
+        .collect()
+}
+
+pub fn format_color(c: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", c[0], c[1], c[2])
+}
+
+pub fn format_colors(colors: &[Rgb]) -> String {
+    colors.iter().map(|&c| format_color(c)).collect::<Vec<_>>().join(" ")
+}
+
+pub fn to_color(c: Rgb) -> quantizr::Color {
+    quantizr::Color { r: c[0], g: c[1], b: c[2], a: 255 }
+}
+
+fn same(a: &quantizr::Color, b: &quantizr::Color) -> bool {
+    (a.r, a.g, a.b, a.a) == (b.r, b.g, b.b, b.a)
+}
+
+// The forced colors that aren't in the palette yet
+pub fn missing(forced: &[Rgb], palette: &[quantizr::Color]) -> Vec<quantizr::Color> {
+    let mut missing: Vec<quantizr::Color> = Vec::new();
+    for c in forced.iter().map(|&c| to_color(c)) {
+        if !palette.iter().chain(&missing).any(|p| same(p, &c)) {
+            missing.push(c);
+        }
+    }
+    missing
+}
+
+// The forced colors first, then the rest of the palette minus whatever is already in there, cut
+// down to max_colors
+pub fn with_forced(forced: &[Rgb], palette: &[quantizr::Color], max_colors: usize) -> Vec<quantizr::Color> {
+    let mut result: Vec<quantizr::Color> = Vec::with_capacity(max_colors);
+    for c in forced.iter().map(|&c| to_color(c)).chain(palette.iter().copied()) {
+        if result.len() >= max_colors {
+            break;
+        }
+        if !result.iter().any(|r| same(r, &c)) {
+            result.push(c);
+        }
+    }
+    result
+}
+
+pub fn to_rgba(c: &quantizr::Color) -> Rgba {
+    [c.r, c.g, c.b, c.a]
+}
+
+pub fn from_rgba(palette: &[Rgba]) -> Vec<quantizr::Color> {
+    palette.iter().map(|&[r, g, b, a]| quantizr::Color { r: r, g: g, b: b, a: a }).collect()
+}
+
+// The palette the quantizer came up with last, for locking (set from the BG thread)
+static LAST: Mutex<Option<Vec<Rgba>>> = Mutex::new(None);
+// What get_image_settings hands out as the locked palette (like dither_mask::CURRENT)
+static LOCKED: Mutex<Option<Vec<Rgba>>> = Mutex::new(None);
+
+pub fn set_last(palette: &[quantizr::Color]) {
+    match LAST.lock() {
+        Ok(mut last) => *last = Some(palette.iter().map(to_rgba).collect()),
+        Err(err) => warn!("Couldn't lock last palette: {err}"),
+    }
+}
+
+// Locks the last palette, false if there isn't one yet
+pub fn lock() -> bool {
+    let last = LAST.lock().ok().and_then(|last| last.clone());
+    let found = last.is_some();
+    set_locked(last);
+    found
+}
+
+pub fn unlock() {
+    set_locked(None);
+}
+
+pub fn locked() -> Option<Vec<Rgba>> {
+    LOCKED.lock().ok().and_then(|locked| locked.clone())
+}
+
+pub fn set_locked(palette: Option<Vec<Rgba>>) {
+    match LOCKED.lock() {
+        Ok(mut locked) => *locked = palette,
+        Err(err) => warn!("Couldn't lock locked palette: {err}"),
+    }
+}
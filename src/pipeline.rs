@@ -0,0 +1,205 @@
+// The order the image gets processed in, which used to be fixed as grayscale, scale, quantize, pad.
+// Scaling after quantizing (nearest neighbour on the palette indexes) gives a chunky pixel look, and
+// going grayscale after quantizing picks the colors from the color image. Stages can also be
+// switched off, which skips them without losing their settings. Quantizing always happens, there's
+// "Disable quantization" for that, and padding has to come after both scaling and quantizing.
+//
+// Edited in the pipeline window, and goes along with the rest of ImageSettings (undo included).
+
+use crate::i18n;
+
+use fltk::{prelude::*, browser::HoldBrowser, button::Button, dialog, group::Flex, window::Window};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stage {
+    Grayscale,
+    Scale,
+    Quantize,
+    Pad,
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Grayscale => i18n::tr("Grayscale"),
+            Stage::Scale => i18n::tr("Scale"),
+            Stage::Quantize => i18n::tr("Quantize"),
+            Stage::Pad => i18n::tr("Pad"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub stages: Vec<(Stage, bool)>, // Every stage once, with whether it's switched on
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Pipeline {
+            stages: vec![(Stage::Grayscale, true), (Stage::Scale, true), (Stage::Quantize, true), (Stage::Pad, true)],
+        }
+    }
+}
+
+impl Pipeline {
+    fn position(&self, stage: Stage) -> usize {
+        self.stages.iter().position(|(s, _)| *s == stage).unwrap_or(usize::MAX)
+    }
+
+    pub fn enabled(&self, stage: Stage) -> bool {
+        self.stages.iter().any(|(s, enabled)| *s == stage && *enabled)
+    }
+
+    // Whether the stage is on and happens on the image before it gets quantized
+    pub fn before_quantize(&self, stage: Stage) -> bool {
+        self.enabled(stage) && self.position(stage) < self.position(Stage::Quantize)
+    }
+
+    // The stages that are on and happen on the quantized image, in order
+    pub fn after_quantize(&self) -> Vec<Stage> {
+        let quantize = self.position(Stage::Quantize);
+        self.stages.iter().enumerate()
+            .filter(|(i, (_, enabled))| *i > quantize && *enabled)
+            .map(|(_, (stage, _))| *stage)
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for stage in [Stage::Grayscale, Stage::Scale, Stage::Quantize, Stage::Pad] {
+            if self.stages.iter().filter(|(s, _)| *s == stage).count() != 1 {
+                return Err(i18n::tr("{} should be in the pipeline once").replace("{}", stage.name()));
+            }
+        }
+        if !self.enabled(Stage::Quantize) {
+            return Err(i18n::tr("Quantize can't be switched off here, use \"Disable quantization\"").to_string());
+        }
+        let pad = self.position(Stage::Pad);
+        if pad < self.position(Stage::Scale) || pad < self.position(Stage::Quantize) {
+            return Err(i18n::tr("Padding has to come after scaling and quantizing").to_string());
+        }
+        Ok(())
+    }
+
+    pub fn description(&self) -> String {
+        self.stages.iter()
+            .map(|(stage, enabled)| if *enabled { stage.name().to_string() } else { format!("({})", stage.name()) })
+            .collect::<Vec<String>>()
+            .join(" → ")
+    }
+}
+
+// What the widgets say, as far as get_image_settings is concerned
+static CURRENT: Mutex<Option<Pipeline>> = Mutex::new(None);
+
+pub fn current() -> Pipeline {
+    CURRENT.lock().ok().and_then(|current| current.clone()).unwrap_or_default()
+}
+
+pub fn set_current(pipeline: Pipeline) {
+    match CURRENT.lock() {
+        Ok(mut current) => *current = Some(pipeline),
+        Err(err) => warn!("Couldn't lock pipeline: {err}"),
+    }
+}
+
+// For when the pipeline changes from outside the window (undo)
+pub fn refresh_window() {
+    if let Some(mut browser) = fltk::app::widget_from_id::<HoldBrowser>("pipeline_browser") {
+        let selected = usize::try_from(browser.value() - 1).unwrap_or(0);
+        fill_browser(&mut browser, &current(), selected);
+    }
+}
+
+fn fill_browser(browser: &mut HoldBrowser, pipeline: &Pipeline, selected: usize) {
+    browser.clear();
+    for (stage, enabled) in &pipeline.stages {
+        browser.add(&format!("{} {}", if *enabled { "[x]" } else { "[  ]" }, stage.name()));
+    }
+    browser.select((selected + 1) as i32);
+}
+
+// on_change gets called after every change, to process the image again
+pub fn show_pipeline_window(on_change: impl Fn() + Clone + 'static) {
+    let mut win = i18n::labeled(Window::default().with_size(300, 250), "Processing pipeline");
+    win.set_callback(|win| {
+        fltk::app::delete_widget(win.clone());
+    });
+    win.make_resizable(true);
+
+    let mut col = Flex::default_fill().column();
+    col.set_margin(10);
+
+    let mut browser = HoldBrowser::default_fill().with_id("pipeline_browser");
+    fill_browser(&mut browser, &current(), 0);
+
+    let mut row = Flex::default_fill().row();
+    let mut up_btn = i18n::labeled(Button::default(), "Up");
+    let mut down_btn = i18n::labeled(Button::default(), "Down");
+    let mut toggle_btn = i18n::labeled(Button::default(), "On/off");
+    let mut reset_btn = i18n::labeled(Button::default(), "Reset");
+    row.end();
+    col.fixed(&row, 30);
+    col.end();
+    win.end();
+    win.show();
+
+    // Makes the change to a copy, and only keeps it if the pipeline still makes sense
+    let change = {
+        let browser = browser.clone();
+        move |f: &dyn Fn(&mut Pipeline, usize) -> usize| {
+            let mut browser = browser.clone();
+            let Some(selected) = usize::try_from(browser.value() - 1).ok() else {
+                return;
+            };
+            let mut pipeline = current();
+            let selected = f(&mut pipeline, selected);
+            if let Err(err) = pipeline.validate() {
+                dialog::alert_default(&err);
+                return;
+            }
+            debug!("Pipeline: {}", pipeline.description());
+            fill_browser(&mut browser, &pipeline, selected);
+            set_current(pipeline);
+            on_change();
+        }
+    };
+
+    up_btn.set_callback({
+        let change = change.clone();
+        move |_| change(&|pipeline, i| {
+            if i > 0 && i < pipeline.stages.len() {
+                pipeline.stages.swap(i - 1, i);
+                i - 1
+            } else {
+                i
+            }
+        })
+    });
+    down_btn.set_callback({
+        let change = change.clone();
+        move |_| change(&|pipeline, i| {
+            if i + 1 < pipeline.stages.len() {
+                pipeline.stages.swap(i, i + 1);
+                i + 1
+            } else {
+                i
+            }
+        })
+    });
+    toggle_btn.set_callback({
+        let change = change.clone();
+        move |_| change(&|pipeline, i| {
+            if let Some((_, enabled)) = pipeline.stages.get_mut(i) {
+                *enabled = !*enabled;
+            }
+            i
+        })
+    });
+    reset_btn.set_callback(move |_| change(&|pipeline, i| {
+        *pipeline = Pipeline::default();
+        i
+    }));
+}
@@ -0,0 +1,205 @@
+// What send_osc actually sends, split out from the sending itself so that receivers other than the
+// CRT shader (LED matrix bridges, other avatar shaders) can be added as more implementations of
+// PixelProtocol. send_osc takes care of the socket, pacing, progress, cancelling and checksum
+// retries, and just runs whatever commands the protocol plans for it.
+
+use crate::protocol_profile::CleanupCommand;
+use crate::send_osc::Color;
+use crate::shader_profile::ShaderProfile;
+
+use std::error::Error;
+use std::time::Duration;
+
+// Parameter names are without the prefix
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Bool(String, bool),
+    Int(String, i32),
+    Data(Vec<u8>), // Over all the data parameters, zero padded
+    Clock,         // Flip the clock, starting out high after the setup
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub description: String,
+    pub commands: Vec<Command>,
+    pub delay: Option<Duration>, // None = the same sleep as the pixel chunks
+    pub progress: f64,
+}
+
+fn step(description: &str, commands: Vec<Command>, delay: Option<Duration>) -> Step {
+    Step {
+        description: description.to_string(),
+        commands: commands,
+        delay: delay,
+        progress: 0.0,
+    }
+}
+
+// What the setup needs to know about the send
+#[derive(Debug, Clone)]
+pub struct SetupParams<'a> {
+    pub bitdepth: u8,
+    pub color: Color,
+    pub rle_compression: bool,
+    pub key_index: Option<u8>,
+    pub keep_palette: bool,
+    pub palette: &'a [quantizr::Color],
+}
+
+pub trait PixelProtocol: Send {
+    // Everything that goes out before the pixel data, in order. Called before the send starts, so
+    // settings the receiver can't take are an error up front.
+    fn plan_setup(&self, params: &SetupParams) -> Result<Vec<Step>, Box<dyn Error>>;
+
+    // One chunk of the (packed and possibly compressed) pixel data
+    fn plan_chunk(&self, chunk: &[u8]) -> Vec<Command>;
+
+    // A checksum over the block of chunks since the last one (see checksum.rs), None if the
+    // receiver doesn't do checksums
+    fn plan_checksum(&self, _block: usize, _sum: u16) -> Option<Vec<Command>> {
+        None
+    }
+
+    // Whatever goes out after the last chunk
+    fn finish(&self) -> Vec<Step> {
+        Vec::new()
+    }
+
+    // What goes out instead of the rest when the send gets cancelled
+    fn plan_cancel(&self) -> Vec<Step> {
+        Vec::new()
+    }
+}
+
+// The PixelSendCRT shader: commands to control pixels during reset, then the pixel data clocked in
+// over the data parameters
+pub struct CrtProtocol {
+    profile: ShaderProfile,
+}
+
+impl CrtProtocol {
+    pub fn new(profile: ShaderProfile) -> Self {
+        CrtProtocol { profile: profile }
+    }
+}
+
+impl PixelProtocol for CrtProtocol {
+    fn plan_setup(&self, params: &SetupParams) -> Result<Vec<Step>, Box<dyn Error>> {
+        let profile = &self.profile;
+        let protocol = &profile.protocol;
+        let delays = &profile.preamble_delays;
+        let setpixel = |pixel: [u8; 2], rgba: [u8; 4]| -> Vec<Command> {
+            vec![
+                Command::Data([[protocol.setpixel_command, pixel[0], pixel[1]].as_slice(), &rgba].concat()),
+                Command::Clock,
+            ]
+        };
+        let mut steps: Vec<Step> = Vec::new();
+
+        // Reset CLK
+        steps.push(step("Reset CLK", vec![Command::Bool(profile.clk_param.clone(), true)], delays.clk_reset));
+        steps.push(step("Reset CLK", vec![Command::Bool(profile.clk_param.clone(), false)], delays.clk_reset));
+
+        // Reset pixel pos (the profile always has at least MIN_BYTES_PER_SEND data parameters)
+        steps.push(step("Reset pixel pos", vec![
+            Command::Int(profile.data_params[0].clone(), 0),
+            Command::Bool(profile.reset_param.clone(), true),
+            Command::Clock,
+        ], delays.reset));
+
+        // Set compression mode. Red channel 0 is off, red channel 255 is on
+        steps.push(step(
+            if params.rle_compression { "Enable RLE compression" } else { "Disable RLE compression" },
+            setpixel(protocol.compressionctrl_pixel, [if params.rle_compression { 255 } else { 0 }, 0, 0, 0]),
+            delays.compression,
+        ));
+
+        // Set BPP (red channel)
+        steps.push(step(
+            &format!("Set BPP {}", params.bitdepth),
+            setpixel(protocol.bitdepth_pixel, [protocol.bitdepth_encoding(params.bitdepth)?, 0, 0, 0]),
+            delays.bitdepth,
+        ));
+
+        // Set palette
+        match params.color {
+            Color::Indexed => {
+                if params.keep_palette {
+                    debug!("Keeping the palette from the last send");
+                } else {
+                    // red channel: wridx 0
+                    steps.push(step("Reset palette write index", setpixel(protocol.palettewridx_pixel, [0, 0, 0, 0]), delays.palette));
+
+                    let palette_colors_per_send = (profile.bytes_per_send() - 1)/3; // -1 because 1 byte is used up as a command byte
+                    let palette_chunks = params.palette.chunks(palette_colors_per_send);
+                    let palette_numchunks = palette_chunks.len();
+                    for (n, chunk) in palette_chunks.enumerate() {
+                        let mut data: Vec<u8> = vec![protocol.palettewrite_command];
+                        for col in chunk {
+                            data.extend_from_slice(&[col.r, col.g, col.b]);
+                        }
+                        steps.push(Step {
+                            progress: ((n as f64)/(palette_numchunks as f64))*100.0,
+                            ..step(&format!("Sent palette chunk {n}/{palette_numchunks}"),
+                                   vec![Command::Data(data), Command::Clock], delays.palette)
+                        });
+                    }
+                }
+
+                // red channel: palette active, green channel: palette write mode inactive
+                steps.push(step("Enable indexed colors", setpixel(protocol.palettectrl_pixel, [255, 0, 0, 0]), delays.palette));
+            },
+            Color::Grayscale => {
+                // red channel: palette inactive, green channel: palette write mode not active
+                steps.push(step("Set to grayscale mode", setpixel(protocol.palettectrl_pixel, [0, 0, 0, 0]), delays.palette));
+            },
+        }
+
+        // Set the key index (cut-out transparency). red channel: the index, green channel: keying active
+        steps.push(step(
+            &match params.key_index {
+                Some(key_index) => format!("Set key index {key_index}"),
+                None => "Disable key index".to_string(),
+            },
+            setpixel(protocol.keyctrl_pixel, [params.key_index.unwrap_or(0), if params.key_index.is_some() { 255 } else { 0 }, 0, 0]),
+            delays.palette,
+        ));
+
+        // Reset the reset bit
+        steps.push(step("Clear the reset bit", vec![Command::Bool(profile.reset_param.clone(), false)], delays.reset_clear));
+
+        Ok(steps)
+    }
+
+    fn plan_chunk(&self, chunk: &[u8]) -> Vec<Command> {
+        vec![Command::Data(chunk.to_vec()), Command::Clock]
+    }
+
+    fn plan_checksum(&self, block: usize, sum: u16) -> Option<Vec<Command>> {
+        let checksum_param = self.profile.checksum_param.as_ref()?;
+        Some(vec![
+            Command::Bool(checksum_param.clone(), true),
+            Command::Data(crate::checksum::checksum_chunk(block, sum)),
+            Command::Clock,
+            Command::Bool(checksum_param.clone(), false),
+        ])
+    }
+
+    fn plan_cancel(&self) -> Vec<Step> {
+        let profile = &self.profile;
+        profile.protocol.cancel_cleanup.iter()
+            .map(|command| match command {
+                CleanupCommand::ClearData => step("Clear data", vec![Command::Data(Vec::new())], None),
+                CleanupCommand::Reset => step("Set the reset bit", vec![Command::Bool(profile.reset_param.clone(), true)], profile.preamble_delays.reset),
+                CleanupCommand::ClearReset => step("Clear the reset bit", vec![Command::Bool(profile.reset_param.clone(), false)], profile.preamble_delays.reset_clear),
+                CleanupCommand::Clock => step("Clock", vec![Command::Clock], profile.preamble_delays.reset),
+            })
+            .collect()
+    }
+}
+
+// The protocol to talk to the receiver described by the profile. Only the CRT shader so far.
+pub fn for_profile(profile: &ShaderProfile) -> Box<dyn PixelProtocol> {
+    Box::new(CrtProtocol::new(profile.clone()))
+}
@@ -0,0 +1,165 @@
+// A window showing the processed image at exact pixel scale (or a whole multiple of it), unlike the
+// main preview which gets scaled by the display multiplier to whatever fits. For judging what the
+// dithering actually looks like. Optionally with a grid between the pixels once they're big enough.
+
+use crate::i18n;
+
+use fltk::{prelude::*, button::CheckButton, draw, enums::Color, frame::Frame, group::{Flex, Scroll}, menu::Choice, window::Window};
+use std::sync::Mutex;
+
+const ZOOMS: [u32; 5] = [1, 2, 4, 8, 16];
+// Below this there's no room for grid lines between the pixels
+const MIN_GRID_ZOOM: u32 = 4;
+const GRID_COLOR: Color = Color::from_rgb(80, 80, 80);
+
+struct PixelViewState {
+    image: Option<fltk::image::RgbImage>, // Unscaled
+    zoom: u32,
+    grid: bool,
+}
+
+static PIXEL_VIEW_STATE: Mutex<PixelViewState> = Mutex::new(PixelViewState {
+    image: None,
+    zoom: 1,
+    grid: false,
+});
+
+// Called by the BG thread whenever the processed image changes
+pub fn set_image(image: Option<fltk::image::RgbImage>) -> Result<(), String> {
+    match PIXEL_VIEW_STATE.lock() {
+        Ok(mut state) => state.image = image,
+        Err(err) => return Err(format!("Couldn't lock pixel view state: {err}")),
+    }
+    refresh()
+}
+
+// Nearest neighbour, so every pixel stays a sharp zoom x zoom square
+pub fn zoomed(image: &fltk::image::RgbImage, zoom: u32) -> Result<fltk::image::RgbImage, String> {
+    let (w, h) = (image.data_w() as usize, image.data_h() as usize);
+    let depth = image.depth() as usize;
+    let zoom = zoom as usize;
+    let data = image.to_rgb_data();
+
+    let mut fb: Vec<u8> = Vec::with_capacity(w*zoom*h*zoom*depth);
+    for row in data.chunks_exact(w*depth).take(h) {
+        let mut zoomed_row: Vec<u8> = Vec::with_capacity(w*zoom*depth);
+        for pixel in row.chunks_exact(depth) {
+            for _ in 0..zoom {
+                zoomed_row.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..zoom {
+            fb.extend_from_slice(&zoomed_row);
+        }
+    }
+
+    fltk::image::RgbImage::new(&fb, (w*zoom) as i32, (h*zoom) as i32, image.depth())
+        .map_err(|err| format!("Couldn't make zoomed image: {err}"))
+}
+
+// Redo the image in the window, if it's open
+fn refresh() -> Result<(), String> {
+    let Some(mut frame) = fltk::app::widget_from_id::<Frame>("pixel_view_frame") else {
+        return Ok(());
+    };
+    let state = PIXEL_VIEW_STATE.lock().map_err(|err| format!("Couldn't lock pixel view state: {err}"))?;
+
+    match state.image.as_ref() {
+        Some(image) => {
+            let image = zoomed(image, state.zoom)?;
+            frame.resize(frame.x(), frame.y(), image.w(), image.h());
+            frame.set_label("");
+            frame.set_image(Some(image));
+        },
+        None => {
+            frame.set_image(None::<fltk::image::RgbImage>);
+            frame.set_label(i18n::tr("No processed image"));
+            frame.resize(frame.x(), frame.y(), 200, 50);
+        },
+    }
+    frame.changed();
+    if let Some(mut parent) = frame.parent() {
+        parent.redraw();
+    }
+    fltk::app::awake();
+    Ok(())
+}
+
+fn draw_grid(f: &Frame) {
+    let Ok(state) = PIXEL_VIEW_STATE.lock() else {
+        return;
+    };
+    if !state.grid || state.zoom < MIN_GRID_ZOOM || f.image().is_none() {
+        return;
+    }
+    let zoom = state.zoom as i32;
+
+    draw::set_draw_color(GRID_COLOR);
+    for x in (0..=f.w()).step_by(zoom as usize) {
+        draw::draw_line(f.x() + x, f.y(), f.x() + x, f.y() + f.h() - 1);
+    }
+    for y in (0..=f.h()).step_by(zoom as usize) {
+        draw::draw_line(f.x(), f.y() + y, f.x() + f.w() - 1, f.y() + y);
+    }
+}
+
+pub fn show_pixel_view_window() {
+    // Just the one window, it's all the same image anyway
+    if let Some(mut win) = fltk::app::widget_from_id::<Frame>("pixel_view_frame").and_then(|f| f.window()) {
+        win.show();
+        return;
+    }
+
+    let mut win = i18n::labeled(Window::default().with_size(600, 600), "View 1:1");
+    win.set_callback(|win| {
+        fltk::app::delete_widget(win.clone());
+    });
+    win.make_resizable(true);
+
+    let mut col = Flex::default_fill().column();
+    col.set_margin(10);
+
+    let row = Flex::default_fill().row();
+    let mut zoom_choice = Choice::default();
+    zoom_choice.add_choice(&ZOOMS.map(|z| format!("{z}x")).join("|"));
+    let mut grid_toggle = CheckButton::default().with_label(&i18n::tr("Grid ({}x and up)").replace("{}", &MIN_GRID_ZOOM.to_string()));
+    row.end();
+    col.fixed(&row, 30);
+
+    let scroll = Scroll::default_fill();
+    let mut frame = Frame::new(scroll.x(), scroll.y(), 200, 50, None).with_id("pixel_view_frame");
+    frame.draw(draw_grid);
+    scroll.end();
+
+    col.end();
+    win.end();
+    win.show();
+
+    if let Ok(state) = PIXEL_VIEW_STATE.lock() {
+        zoom_choice.set_value(ZOOMS.iter().position(|z| *z == state.zoom).unwrap_or(0) as i32);
+        grid_toggle.set_checked(state.grid);
+    }
+
+    zoom_choice.set_callback(|c| {
+        if let Ok(mut state) = PIXEL_VIEW_STATE.lock() {
+            state.zoom = ZOOMS.get(c.value().max(0) as usize).copied().unwrap_or(1);
+        }
+        if let Err(err) = refresh() {
+            warn!("{err}");
+        }
+    });
+
+    grid_toggle.set_callback({
+        let mut frame = frame.clone();
+        move |t| {
+            if let Ok(mut state) = PIXEL_VIEW_STATE.lock() {
+                state.grid = t.is_checked();
+            }
+            frame.redraw();
+        }
+    });
+
+    if let Err(err) = refresh() {
+        warn!("{err}");
+    }
+}
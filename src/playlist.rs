@@ -0,0 +1,424 @@
+// A list of images that each keep the processing settings they were added with, for sending one
+// after the other. Either just once in order, or over and over with a pause between images, which
+// is what you want for an in-world picture frame. The next images get loaded and processed ahead
+// of time by a Prefetcher while the current one is going out.
+
+use crate::{AppMessage, BgMessage, ImageSettings, PipelineCache};
+use crate::config;
+use crate::i18n;
+use crate::mq;
+use crate::prefetch::Prefetcher;
+use crate::send_osc::{self, SendOSCOpts};
+use crate::utility::error_alert;
+
+use fltk::{prelude::*, app, browser::HoldBrowser, button::{Button, CheckButton}, dialog, group::Flex, window::Window};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_INTERVAL_SECS: f64 = 60.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaylistItem {
+    pub path: PathBuf,
+    pub settings: ImageSettings,
+}
+
+impl PlaylistItem {
+    // What goes in the list
+    fn description(&self) -> String {
+        let name = self.path.file_name().unwrap_or(self.path.as_os_str()).to_string_lossy();
+        let size = if self.settings.scaling { format!(", {}px", self.settings.scale) } else { String::new() };
+        format!("{name} ({} colors{size})", self.settings.maxcolors)
+    }
+}
+
+// Kept around between openings of the window
+static PLAYLIST: Mutex<Vec<PlaylistItem>> = Mutex::new(Vec::new());
+
+// The stop flag of the playlist that is playing, if any
+static PLAYING: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+// Returns false if nothing was playing
+pub fn stop() -> bool {
+    let flag = match PLAYING.lock() {
+        Ok(mut playing) => playing.take(),
+        Err(err) => {
+            warn!("Couldn't lock playlist state: {err}");
+            None
+        },
+    };
+    let Some(flag) = flag else {
+        return false;
+    };
+    info!("Stopping the playlist");
+    flag.store(true, Ordering::Relaxed);
+    send_osc::cancel_current();
+    true
+}
+
+fn process_item(item: &PlaylistItem) -> Result<crate::ProcessedImage, String> {
+    let (image, _) = crate::limit_source_size(crate::load_image(&item.path).map_err(|err| err.to_string())?);
+    // There's nothing to send unless it gets quantized
+    let settings = ImageSettings { no_quantize: false, ..item.settings.clone() };
+    let (img, _) = crate::process_image(&image, Some(&item.path), &settings, &mut PipelineCache::default())
+        .map_err(|err| err.to_string())?;
+    Ok(img)
+}
+
+// Waits for the interval to be over, or for stop to be set. Returns false if stopped.
+fn wait_until(deadline: Instant, stop: &AtomicBool) -> bool {
+    while Instant::now() < deadline {
+        if stop.load(Ordering::Relaxed) {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(100).min(deadline.saturating_duration_since(Instant::now())));
+    }
+    !stop.load(Ordering::Relaxed)
+}
+
+// Sends the items in order. With an interval it loops, starting the next image no sooner than
+// interval after the last one started. Up to prefetch images get processed ahead of time. Stops any
+// playlist that is already playing.
+pub fn play(
+    appmsg: &mpsc::Sender<AppMessage>,
+    items: Vec<PlaylistItem>,
+    options: SendOSCOpts,
+    interval: Option<Duration>,
+    prefetch: usize,
+) -> Result<(), String> {
+    if items.is_empty() {
+        return Err("The playlist is empty".to_string());
+    }
+
+    stop();
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    match PLAYING.lock() {
+        Ok(mut playing) => *playing = Some(Arc::clone(&stop_flag)),
+        Err(err) => return Err(format!("Couldn't lock playlist state: {err}")),
+    }
+
+    // One after the other without asking, and without a summary window for each of them
+    let options = SendOSCOpts { confirm: false, quiet: true, ..options };
+    let appmsg = appmsg.clone();
+    thread::spawn(move || {
+        let mut prefetcher: Prefetcher<Result<crate::ProcessedImage, String>> = Prefetcher::new(prefetch);
+        let queue_item = |prefetcher: &mut Prefetcher<_>, i: usize| {
+            let item = items[i].clone();
+            prefetcher.push(move || process_item(&item));
+        };
+        for i in 0..items.len() {
+            queue_item(&mut prefetcher, i);
+        }
+
+        let mut i = 0;
+        let mut queried = false; // The shader only needs asking for the first send
+        while let Some(result) = prefetcher.next() {
+            if stop_flag.load(Ordering::Relaxed) {
+                break;
+            }
+            let started = Instant::now();
+            let path = &items[i].path;
+            select_item(i);
+
+            match result {
+                Ok(img) => {
+                    info!("Playlist: sending {path:?} ({}/{})", i + 1, items.len());
+                    let sent = send_osc::send_osc(&appmsg, &img.indexes, &img.palette, img.width, img.height,
+                                                  SendOSCOpts { skip_query: queried, ..options.clone() })
+                        .map_err(|err| err.to_string())
+                        .and_then(|handle| handle.join().map_err(|_| "Send thread panicked".to_string()));
+                    queried = true;
+                    match sent {
+                        Ok(true) => (),
+                        // Cancelling the send cancels the whole playlist
+                        Ok(false) => break,
+                        Err(err) => {
+                            error_alert(&appmsg, format!("{} {path:?}:\n{err}", i18n::tr("Playlist: couldn't send")));
+                            break;
+                        },
+                    }
+                },
+                // Skip it, one broken file shouldn't stop the picture frame
+                Err(err) => error_alert(&appmsg, format!("{} {path:?}:\n{err}", i18n::tr("Playlist: skipping"))),
+            }
+
+            // Around again, if looping
+            if interval.is_some() {
+                queue_item(&mut prefetcher, i);
+            }
+            i = (i + 1) % items.len();
+
+            if let Some(interval) = interval {
+                if !wait_until(started + interval, &stop_flag) {
+                    break;
+                }
+            }
+        }
+
+        // Only clear it if it's still ours, another play() might have taken over already
+        if let Ok(mut playing) = PLAYING.lock() {
+            if playing.as_ref().is_some_and(|flag| Arc::ptr_eq(flag, &stop_flag)) {
+                *playing = None;
+            }
+        }
+        info!("Playlist finished");
+    });
+
+    Ok(())
+}
+
+// Highlights the item that is being sent, if the window is open
+fn select_item(i: usize) {
+    if let Some(mut browser) = app::widget_from_id::<HoldBrowser>("playlist_browser") {
+        browser.select(i as i32 + 1);
+        fltk::app::awake();
+    }
+}
+
+fn refresh_browser(browser: &mut HoldBrowser, items: &[PlaylistItem], selected: Option<usize>) {
+    browser.clear();
+    for item in items {
+        // "@." keeps FLTK from taking any '@' in the file name for a formatting code
+        browser.add(&format!("@.{}", item.description()));
+    }
+    if let Some(i) = selected.filter(|&i| i < items.len()) {
+        browser.select(i as i32 + 1);
+    }
+}
+
+fn selected(browser: &HoldBrowser) -> Option<usize> {
+    usize::try_from(browser.value() - 1).ok()
+}
+
+// Runs f on the playlist and then shows the result. f returns what should be selected afterwards.
+fn edit_playlist<F>(browser: &mut HoldBrowser, f: F)
+where
+    F: FnOnce(&mut Vec<PlaylistItem>, Option<usize>) -> Option<usize>,
+{
+    let mut playlist = match PLAYLIST.lock() {
+        Ok(playlist) => playlist,
+        Err(err) => {
+            dialog::alert_default(&format!("{}: {err}", i18n::tr("Couldn't lock playlist")));
+            return;
+        },
+    };
+    let selected = f(&mut playlist, selected(browser));
+    refresh_browser(browser, &playlist, selected);
+}
+
+// get_settings reads the processing settings from the main window, get_send_opts the send settings
+pub fn show_playlist_window<S, O>(
+    appmsg: &mpsc::Sender<AppMessage>,
+    bg: &mq::MessageQueueSender<BgMessage>,
+    get_settings: S,
+    get_send_opts: O,
+)
+where
+    S: Fn() -> Result<ImageSettings, String> + Clone + 'static,
+    O: Fn() -> Result<SendOSCOpts, String> + 'static,
+{
+    let mut win = i18n::labeled(Window::default().with_size(450, 500), "Playlist");
+    win.set_callback(|win| {
+        fltk::app::delete_widget(win.clone());
+    });
+    win.make_resizable(true);
+
+    let mut col = Flex::default_fill().column();
+    col.set_margin(10);
+
+    let mut browser = HoldBrowser::default_fill().with_id("playlist_browser");
+    if let Ok(playlist) = PLAYLIST.lock() {
+        refresh_browser(&mut browser, &playlist, None);
+    }
+
+    let edit_row = Flex::default_fill().row();
+    let mut add_btn = i18n::labeled(Button::default(), "Add images...");
+    let mut remove_btn = i18n::labeled(Button::default(), "Remove");
+    let mut up_btn = i18n::labeled(Button::default(), "Up");
+    let mut down_btn = i18n::labeled(Button::default(), "Down");
+    edit_row.end();
+    col.fixed(&edit_row, 30);
+
+    let item_row = Flex::default_fill().row();
+    let mut update_btn = i18n::labeled(Button::default(), "Use current settings");
+    update_btn.set_tooltip("Replace the selected image's settings with the ones in the main window");
+    let mut show_btn = i18n::labeled(Button::default(), "Show");
+    show_btn.set_tooltip("Open the selected image in the main window, with its settings");
+    item_row.end();
+    col.fixed(&item_row, 30);
+
+    let mut loop_row = Flex::default_fill().row();
+    let loop_toggle = i18n::labeled(CheckButton::default(), "Loop, seconds per image:");
+    let mut interval_spinner = fltk::misc::Spinner::default();
+    interval_spinner.set_range(1.0, 24.0*60.0*60.0);
+    interval_spinner.set_step(1.0);
+    interval_spinner.set_value(DEFAULT_INTERVAL_SECS);
+    loop_row.fixed(&interval_spinner, 100);
+    loop_row.end();
+    col.fixed(&loop_row, 30);
+
+    // More takes more memory, but evens out images that are slow to load
+    let mut prefetch_row = Flex::default_fill().row();
+    i18n::labeled(fltk::frame::Frame::default(), "Images to prepare ahead:");
+    let mut prefetch_spinner = fltk::misc::Spinner::default();
+    prefetch_spinner.set_range(1.0, 16.0);
+    prefetch_spinner.set_step(1.0);
+    prefetch_spinner.set_value(config::Config::load().unwrap_or_default().playlist_prefetch as f64);
+    prefetch_row.fixed(&prefetch_spinner, 100);
+    prefetch_row.end();
+    col.fixed(&prefetch_row, 30);
+
+    // Remembered for next time
+    prefetch_spinner.set_callback(|s| {
+        match || -> Result<(), Box<dyn std::error::Error>> {
+            let mut config = config::Config::load()?;
+            config.playlist_prefetch = s.value() as usize;
+            config.save()?;
+            Ok(())
+        }() {
+            Ok(()) => (),
+            Err(err) => warn!("Couldn't save the prefetch count: {err}"),
+        }
+    });
+
+    let play_row = Flex::default_fill().row();
+    let mut play_btn = i18n::labeled(Button::default(), "Send playlist");
+    let mut stop_btn = i18n::labeled(Button::default(), "Stop");
+    play_row.end();
+    col.fixed(&play_row, 40);
+
+    add_btn.set_callback({
+        let mut browser = browser.clone();
+        let get_settings = get_settings.clone();
+        move |_| {
+            let mut nfc = dialog::NativeFileChooser::new(dialog::FileDialogType::BrowseMultiFile);
+            nfc.show();
+            let paths = nfc.filenames();
+            if paths.is_empty() {
+                info!("No files selected/cancelled");
+                return;
+            }
+            // Everything added in one go gets the settings the main window has right now
+            let settings = match get_settings() {
+                Ok(settings) => settings,
+                Err(err) => {
+                    dialog::alert_default(&format!("{}:\n{err}", i18n::tr("Couldn't read the image settings")));
+                    return;
+                },
+            };
+            edit_playlist(&mut browser, |playlist, _| {
+                playlist.extend(paths.into_iter().map(|path| PlaylistItem { path: path, settings: settings.clone() }));
+                playlist.len().checked_sub(1)
+            });
+        }
+    });
+
+    remove_btn.set_callback({
+        let mut browser = browser.clone();
+        move |_| {
+            edit_playlist(&mut browser, |playlist, selected| {
+                let i = selected.filter(|&i| i < playlist.len())?;
+                playlist.remove(i);
+                Some(i.min(playlist.len().saturating_sub(1)))
+            });
+        }
+    });
+
+    up_btn.set_callback({
+        let mut browser = browser.clone();
+        move |_| {
+            edit_playlist(&mut browser, |playlist, selected| {
+                let i = selected.filter(|&i| i > 0 && i < playlist.len())?;
+                playlist.swap(i - 1, i);
+                Some(i - 1)
+            });
+        }
+    });
+
+    down_btn.set_callback({
+        let mut browser = browser.clone();
+        move |_| {
+            edit_playlist(&mut browser, |playlist, selected| {
+                let i = selected.filter(|&i| i + 1 < playlist.len())?;
+                playlist.swap(i, i + 1);
+                Some(i + 1)
+            });
+        }
+    });
+
+    update_btn.set_callback({
+        let mut browser = browser.clone();
+        move |_| {
+            let settings = match get_settings() {
+                Ok(settings) => settings,
+                Err(err) => {
+                    dialog::alert_default(&format!("{}:\n{err}", i18n::tr("Couldn't read the image settings")));
+                    return;
+                },
+            };
+            edit_playlist(&mut browser, |playlist, selected| {
+                let i = selected.filter(|&i| i < playlist.len())?;
+                playlist[i].settings = settings;
+                Some(i)
+            });
+        }
+    });
+
+    let show_selected = {
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        let browser = browser.clone();
+        move || {
+            let item = PLAYLIST.lock().ok().and_then(|playlist| selected(&browser).and_then(|i| playlist.get(i).cloned()));
+            let Some(item) = item else {
+                return;
+            };
+            // Widgets first, as LoadImage processes the image with whatever they say
+            match || -> Result<(), String> {
+                crate::set_image_settings_widgets(&item.settings)?;
+                bg.send_or_replace_if(BgMessage::is_update, BgMessage::LoadImage(item.path.clone()))
+                    .map_err(|err| format!("Couldn't send message to BG thread: {err}"))?;
+                Ok(())
+            }() {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't show playlist item"))),
+            }
+        }
+    };
+    show_btn.set_callback({
+        let show_selected = show_selected.clone();
+        move |_| show_selected()
+    });
+    browser.set_callback(move |_| {
+        if app::event_clicks() {
+            show_selected();
+        }
+    });
+
+    play_btn.set_callback({
+        let appmsg = appmsg.clone();
+        move |_| {
+            match || -> Result<(), String> {
+                let items = PLAYLIST.lock().map_err(|err| format!("Couldn't lock playlist: {err}"))?.clone();
+                let interval = loop_toggle.is_checked().then(|| Duration::from_secs_f64(interval_spinner.value()));
+                play(&appmsg, items, get_send_opts()?, interval, prefetch_spinner.value() as usize)
+            }() {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't start the playlist"))),
+            }
+        }
+    });
+
+    stop_btn.set_callback(|_| {
+        if !stop() {
+            info!("No playlist playing");
+        }
+    });
+
+    col.end();
+    win.end();
+    win.show();
+}
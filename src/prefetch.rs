@@ -0,0 +1,54 @@
+// Runs a queue of jobs (e.g. loading and processing the next images of a slideshow) ahead of time
+// on their own threads, with at most `prefetch` of them in flight at once. Results come back in the
+// order the jobs were queued, so while the current image is being sent the next ones are already
+// getting ready, and the gap between images is down to the network. The playlist is what drives it,
+// with the prefetch count from the config.
+
+use std::collections::VecDeque;
+use std::sync::mpsc;
+use std::thread;
+
+pub const DEFAULT_PREFETCH: usize = 2;
+
+type Job<T> = Box<dyn FnOnce() -> T + Send + 'static>;
+
+pub struct Prefetcher<T: Send + 'static> {
+    prefetch: usize,
+    queued: VecDeque<Job<T>>,
+    running: VecDeque<mpsc::Receiver<T>>,
+}
+
+impl<T: Send + 'static> Prefetcher<T> {
+    pub fn new(prefetch: usize) -> Self {
+        Prefetcher { prefetch: prefetch.max(1), queued: VecDeque::new(), running: VecDeque::new() }
+    }
+
+    pub fn push<F: FnOnce() -> T + Send + 'static>(&mut self, job: F) {
+        self.queued.push_back(Box::new(job));
+        self.start_jobs();
+    }
+
+    // Blocks until the oldest job is done and returns its result. None when there is nothing left
+    // (or if the job panicked).
+    pub fn next(&mut self) -> Option<T> {
+        let receiver = self.running.pop_front()?;
+        let result = receiver.recv().ok();
+        self.start_jobs();
+        result
+    }
+
+    fn start_jobs(&mut self) {
+        while self.running.len() < self.prefetch {
+            let Some(job) = self.queued.pop_front() else {
+                break;
+            };
+
+            let (tx, rx) = mpsc::channel::<T>();
+            thread::spawn(move || {
+                // The receiver being gone just means that nobody wants the result anymore
+                let _ = tx.send(job());
+            });
+            self.running.push_back(rx);
+        }
+    }
+}
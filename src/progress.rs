@@ -0,0 +1,57 @@
+// What the BG thread is busy with, for the busy indicator below the image ("Quantizing..."). The
+// pipeline reports its stages from wherever it runs, but only the thread that called watch() gets
+// them shown, so streams and prefetching (which process images on threads of their own) don't
+// flash the indicator. Quick low resolution previews while dragging sliders go quietly() too.
+
+use crate::AppMessage;
+use crate::utility::print_err;
+
+use std::cell::Cell;
+use std::sync::{mpsc, Mutex};
+use std::thread::{self, ThreadId};
+
+static WATCHED: Mutex<Option<(ThreadId, mpsc::Sender<AppMessage>)>> = Mutex::new(None);
+
+thread_local! {
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+    static SHOWING: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn watch(appmsg: &mpsc::Sender<AppMessage>) {
+    match WATCHED.lock() {
+        Ok(mut watched) => *watched = Some((thread::current().id(), appmsg.clone())),
+        Err(err) => warn!("Couldn't lock progress state: {err}"),
+    }
+}
+
+fn send(stage: Option<&'static str>) {
+    let Ok(watched) = WATCHED.lock() else {
+        return;
+    };
+    if let Some((_, appmsg)) = watched.as_ref().filter(|(id, _)| *id == thread::current().id()) {
+        print_err(appmsg.send(AppMessage::Progress(stage)));
+        fltk::app::awake();
+        SHOWING.set(stage.is_some());
+    }
+}
+
+pub fn stage(name: &'static str) {
+    if !QUIET.get() {
+        send(Some(name));
+    }
+}
+
+// Back to idle, once the message is handled
+pub fn done() {
+    QUIET.set(false); // In case quietly() got panicked out of
+    if SHOWING.get() {
+        send(None);
+    }
+}
+
+pub fn quietly<T>(f: impl FnOnce() -> T) -> T {
+    QUIET.set(true);
+    let result = f();
+    QUIET.set(false);
+    result
+}
@@ -0,0 +1,116 @@
+// Project files (.opsproj), so that a setup can be opened again exactly as it was: the source image,
+// all the processing settings and the send settings in one file. The file is a plain RGBA PNG of
+// the source image (so it opens in any image viewer, and loading it as an image just works) with
+// the settings as JSON in an iTXt chunk.
+//
+// The pixels go in rather than just the path, as the source might have been a screen capture, a
+// video frame or rendered text, or the file might have moved. The path still goes along for the
+// banner caption and the title.
+//
+// A locked palette goes along in the image settings, along with the forced colors.
+//
+// Plain exported PNGs get just the image settings in a chunk of their own (see load_png_settings),
+// which is enough to redo an export.
+
+use crate::atomic_write::write_atomically;
+use crate::ImageSettings;
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+pub const EXTENSION: &'static str = "opsproj";
+const KEYWORD: &'static str = "OSCPixelSender project";
+pub const SETTINGS_KEYWORD: &'static str = "OSCPixelSender settings";
+const VERSION: u32 = 1;
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+// What the send widgets are set to. The number fields are kept as typed, empty included, since
+// empty means something different from 0 for some of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SendSettings {
+    pub target: String,
+    pub prefix: String, // Of the shader profile
+    pub pixfmt: String,
+    pub msgs_per_second: f64,
+    pub rle_compression: bool,
+    pub auto_compression: bool,
+    pub confirm: bool,
+    pub adaptive_rate: bool,
+    pub key_index: Option<u8>,
+    pub warn_secs: String,
+    pub burst_chunks: String,
+    pub burst_wait_ms: String,
+    pub retries: String,
+    pub bandwidth_kbps: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    pub source_path: Option<PathBuf>,
+    pub image_settings: ImageSettings,
+    pub send_settings: SendSettings,
+}
+
+impl Project {
+    pub fn new(source_path: Option<PathBuf>, image_settings: ImageSettings, send_settings: SendSettings) -> Self {
+        Project {
+            version: VERSION,
+            source_path: source_path,
+            image_settings: image_settings,
+            send_settings: send_settings,
+        }
+    }
+}
+
+pub fn save(path: &Path, image: &image::RgbaImage, project: &Project) -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_string_pretty(project)?;
+
+    write_atomically(path, |w| {
+        let mut encoder = png::Encoder::new(w, image.width(), image.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        // Goes in before the image data, so reading it back doesn't need to decode the image
+        encoder.add_itxt_chunk(KEYWORD.to_string(), json)?;
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(image.as_raw())?;
+        writer.finish()?;
+        Ok(())
+    })
+}
+
+// Just the settings, the image gets loaded like any other
+pub fn load_settings(path: &Path) -> Result<Project, Box<dyn Error>> {
+    let file = std::fs::File::open(path)?;
+    let reader = png::Decoder::new(std::io::BufReader::new(file)).read_info()?;
+    let chunk = reader.info().utf8_text.iter()
+        .find(|chunk| chunk.keyword == KEYWORD)
+        .ok_or("Not an OSCPixelSender project (no project settings in the file)")?;
+    let project: Project = serde_json::from_str(&chunk.get_text()?)?;
+    if project.version > VERSION {
+        return Err(format!("The project is from a newer version (project version {}, this reads up to {VERSION})", project.version).into());
+    }
+    project.image_settings.pipeline.validate()?;
+    Ok(project)
+}
+
+// The image settings an exported PNG was made with, if it has them. Anything that isn't a PNG just
+// doesn't.
+pub fn load_png_settings(path: &Path) -> Result<Option<ImageSettings>, Box<dyn Error>> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut signature = [0u8; 8];
+    if std::io::Read::read_exact(&mut file, &mut signature).is_err() || signature != PNG_SIGNATURE {
+        return Ok(None);
+    }
+    std::io::Seek::rewind(&mut file)?;
+
+    let reader = png::Decoder::new(file).read_info()?;
+    let Some(chunk) = reader.info().utf8_text.iter().find(|chunk| chunk.keyword == SETTINGS_KEYWORD) else {
+        return Ok(None);
+    };
+    let settings: ImageSettings = serde_json::from_str(&chunk.get_text()?)?;
+    settings.pipeline.validate()?;
+    Ok(Some(settings))
+}
@@ -0,0 +1,143 @@
+// The command bytes and control pixel layout the shader understands, so that a new shader revision
+// only needs a new TOML file instead of a new build. Extra ones go in the protocols folder next to
+// config.toml (see config::protocols_dir), and anything a file leaves out gets the PixelSendCRT default. For example:
+//
+//   name = "PixelSendCRT v2"
+//   setpixel_command = 0x80
+//   keyctrl_pixel = [7, 0]
+//   cancel_cleanup = ["reset", "clock", "clear-reset"]
+//
+//   [bitdepth_encodings]
+//   bpp1 = 192
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+// The red channel values of the bitdepth control pixel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BitdepthEncodings {
+    pub bpp1: u8,
+    pub bpp2: u8,
+    pub bpp4: u8,
+    pub bpp8: u8,
+}
+
+impl Default for BitdepthEncodings {
+    fn default() -> Self {
+        BitdepthEncodings {
+            bpp1: 192,
+            bpp2: 128,
+            bpp4: 64,
+            bpp8: 0,
+        }
+    }
+}
+
+// What goes out when a send gets cancelled, in order, so the shader isn't left half painted with the
+// reset bit set
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CleanupCommand {
+    ClearData,  // All the data parameters to 0
+    Reset,      // Reset bit on
+    ClearReset, // Reset bit off
+    Clock,
+}
+
+// Control pixels are [x, y]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtocolProfile {
+    pub name: String,
+    pub setpixel_command: u8,
+    pub palettewrite_command: u8,
+    pub bitdepth_pixel: [u8; 2],
+    pub palettectrl_pixel: [u8; 2],
+    pub palettewridx_pixel: [u8; 2],
+    pub compressionctrl_pixel: [u8; 2],
+    pub keyctrl_pixel: [u8; 2],
+    pub bitdepth_encodings: BitdepthEncodings,
+    // How many bytes the shader can unpack from each int parameter (little endian), for the
+    // experimental packed mode. 1 = it can't.
+    pub bytes_per_int: u8,
+    pub cancel_cleanup: Vec<CleanupCommand>, // Empty = leave the shader as it is
+}
+
+impl Default for ProtocolProfile {
+    fn default() -> Self {
+        ProtocolProfile {
+            name: "PixelSendCRT".to_string(),
+            setpixel_command: 0x80,
+            palettewrite_command: 0xc0,
+            bitdepth_pixel: [2, 0],
+            palettectrl_pixel: [3, 0],
+            palettewridx_pixel: [4, 0],
+            compressionctrl_pixel: [5, 0],
+            keyctrl_pixel: [6, 0],
+            bitdepth_encodings: Default::default(),
+            bytes_per_int: 1,
+            // The same as resetting the pixel position before a send, which clears the screen
+            cancel_cleanup: vec![CleanupCommand::ClearData, CleanupCommand::Reset, CleanupCommand::Clock, CleanupCommand::ClearReset],
+        }
+    }
+}
+
+impl ProtocolProfile {
+    pub fn load(path: &Path) -> Result<ProtocolProfile, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("Couldn't read {path:?}: {err}"))?;
+        let table: toml::Table = contents.parse()
+            .map_err(|err| format!("Couldn't parse {path:?}: {err}"))?;
+        // Go by the file name when it doesn't say, so they can be told apart in the list
+        let named = table.contains_key("name");
+        let mut protocol: ProtocolProfile = table.try_into()
+            .map_err(|err| format!("Couldn't parse {path:?}: {err}"))?;
+        if !named {
+            if let Some(stem) = path.file_stem() {
+                protocol.name = stem.to_string_lossy().to_string();
+            }
+        }
+        Ok(protocol)
+    }
+
+    pub fn bitdepth_encoding(&self, bitdepth: u8) -> Result<u8, String> {
+        match bitdepth {
+            1 => Ok(self.bitdepth_encodings.bpp1),
+            2 => Ok(self.bitdepth_encodings.bpp2),
+            4 => Ok(self.bitdepth_encodings.bpp4),
+            8 => Ok(self.bitdepth_encodings.bpp8),
+            _ => Err(format!("No encoding for bitdepth {bitdepth}")),
+        }
+    }
+}
+
+// The built-in protocol first, then whatever is in the protocols folder. Broken files get logged and
+// left out.
+pub fn available_protocols(dir: Option<PathBuf>) -> Vec<ProtocolProfile> {
+    let mut protocols = vec![ProtocolProfile::default()];
+    let Some(dir) = dir else {
+        return protocols;
+    };
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        debug!("No protocols folder at {dir:?}");
+        return protocols;
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml")))
+        .collect();
+    paths.sort();
+    for path in paths {
+        match ProtocolProfile::load(&path) {
+            Ok(protocol) => {
+                info!("Loaded protocol {:?} from {path:?}", protocol.name);
+                protocols.push(protocol);
+            },
+            Err(err) => warn!("{err}"),
+        }
+    }
+    protocols
+}
@@ -0,0 +1,541 @@
+// Different ways of coming up with a palette for an image. quantizr is the default, but it
+// sometimes does a poor job with flat-shaded art, so there are a couple of classic algorithms to
+// pick from as well.
+
+use std::collections::HashMap;
+use std::error::Error;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::{VariantNames, EnumString};
+
+// Images with at least this many pixels get quantized in strips, in parallel (see quantize_tiled)
+pub const TILED_MIN_PIXELS: u64 = 1024*1024;
+const TILE_MIN_ROWS: u32 = 64;
+// About how many pixels the merged strip palettes get quantized down from
+const MERGE_SAMPLES: u64 = 65536;
+
+pub trait Quantizer: Send + Sync {
+    // Returns the palette indexes (one byte per pixel) and the palette (at most max_colors entries)
+    // for an RGBA buffer
+    fn quantize(
+        &self,
+        bytes: &[u8],
+        width: u32, height: u32,
+        max_colors: i32,
+        dithering_level: f32,
+    ) -> Result<(Vec<u8>, Vec<quantizr::Color>), Box<dyn Error>>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, VariantNames, EnumString, Serialize, Deserialize)]
+pub enum QuantizerType {
+    #[default]
+    Quantizr,
+    MedianCut,
+    Octree,
+}
+
+impl QuantizerType {
+    pub fn quantizer(&self, quantizr_options: &QuantizrOptions) -> Box<dyn Quantizer> {
+        match self {
+            QuantizerType::Quantizr => Box::new(QuantizrQuantizer { options: *quantizr_options }),
+            QuantizerType::MedianCut => Box::new(MedianCutQuantizer),
+            QuantizerType::Octree => Box::new(OctreeQuantizer),
+        }
+    }
+}
+
+// quantizr only takes the color count (and the dithering level when remapping), there's no quality
+// or speed setting like libimagequant has. What does help on big images is building the palette
+// from a sample of the pixels, as that's where the time goes, and then remapping the whole image
+// with it.
+//
+// The palette can also be refined with a few k-means passes against all the pixels (see
+// kmeans_refine). We can't hand a palette back to quantizr, so the remapping is then ours.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuantizrOptions {
+    pub sample_percent: u8, // Of the pixels to build the palette from, 100 = all of them
+    pub kmeans_iterations: u8, // 0 = off
+}
+
+impl Default for QuantizrOptions {
+    fn default() -> Self {
+        QuantizrOptions { sample_percent: 100, kmeans_iterations: 0 }
+    }
+}
+
+// Evenly spread out pixels, percent of them
+fn sample_pixels(bytes: &[u8], percent: u8) -> Vec<u8> {
+    let pixels = bytes.len()/4;
+    let samples = (pixels * (percent as usize) / 100).max(1);
+    (0..samples)
+        .flat_map(|n| {
+            let i = n * pixels / samples;
+            bytes[i*4..i*4 + 4].iter().copied()
+        })
+        .collect()
+}
+
+pub struct QuantizrQuantizer {
+    pub options: QuantizrOptions,
+}
+
+impl Quantizer for QuantizrQuantizer {
+    fn quantize(
+        &self,
+        bytes: &[u8],
+        width: u32, height: u32,
+        max_colors: i32,
+        dithering_level: f32,
+    ) -> Result<(Vec<u8>, Vec<quantizr::Color>), Box<dyn Error>> {
+        let qimage = quantizr::Image::new(bytes, width as usize, height as usize)?;
+        let mut qopts = quantizr::Options::default();
+        qopts.set_max_colors(max_colors)?;
+
+        let mut result = if self.options.sample_percent < 100 {
+            let sample = sample_pixels(bytes, self.options.sample_percent);
+            let sample_image = quantizr::Image::new(&sample, sample.len()/4, 1)?;
+            quantizr::QuantizeResult::quantize(&sample_image, &qopts)
+        } else {
+            quantizr::QuantizeResult::quantize(&qimage, &qopts)
+        };
+        let palette = result.get_palette();
+        let palette = palette.entries[0..(palette.count as usize)].to_vec();
+
+        if self.options.kmeans_iterations > 0 {
+            let palette = kmeans_refine(bytes, &palette, self.options.kmeans_iterations);
+            let indexes = remap(bytes, width, height, &palette, dithering_level);
+            return Ok((indexes, palette));
+        }
+
+        result.set_dithering_level(dithering_level)?;
+        let mut indexes = vec![0u8; (width*height) as usize];
+        result.remap_image(&qimage, indexes.as_mut_slice())?;
+        Ok((indexes, palette))
+    }
+}
+
+// Distinct colors of the image, and how many pixels have each of them
+fn color_counts(bytes: &[u8]) -> Vec<([u8; 4], u64)> {
+    let mut counts: HashMap<[u8; 4], u64> = HashMap::new();
+    for px in bytes.chunks_exact(4) {
+        *counts.entry([px[0], px[1], px[2], px[3]]).or_default() += 1;
+    }
+    counts.into_iter().collect()
+}
+
+fn weighted_average(colors: &[([u8; 4], u64)]) -> quantizr::Color {
+    let mut sum = [0u64; 4];
+    let mut total: u64 = 0;
+    for (c, count) in colors {
+        for ch in 0..4 {
+            sum[ch] += (c[ch] as u64) * count;
+        }
+        total += count;
+    }
+    let avg = |ch: usize| ((sum[ch] + total/2) / total.max(1)) as u8;
+    quantizr::Color { r: avg(0), g: avg(1), b: avg(2), a: avg(3) }
+}
+
+fn color_distance(px: &[i32; 4], c: &quantizr::Color) -> i32 {
+    let d = [px[0] - c.r as i32, px[1] - c.g as i32, px[2] - c.b as i32, px[3] - c.a as i32];
+    d.iter().map(|d| d*d).sum()
+}
+
+fn nearest_index(px: &[i32; 4], palette: &[quantizr::Color]) -> usize {
+    palette.iter()
+        .enumerate()
+        .min_by_key(|(_, c)| color_distance(px, c))
+        .map_or(0, |(i, _)| i)
+}
+
+// Moves every palette entry to the average of the pixels closest to it, a few times over (Lloyd's
+// k-means, starting from the given palette). Works on the distinct colors weighted by how many
+// pixels have them, so it's cheap on the small images we deal with. Entries no pixel is closest
+// to stay where they are.
+pub fn kmeans_refine(bytes: &[u8], palette: &[quantizr::Color], iterations: u8) -> Vec<quantizr::Color> {
+    let colors = color_counts(bytes);
+    let mut palette = palette.to_vec();
+    if palette.is_empty() {
+        return palette;
+    }
+
+    for _ in 0..iterations {
+        let mut clusters: Vec<Vec<([u8; 4], u64)>> = vec![Vec::new(); palette.len()];
+        for &(c, count) in &colors {
+            let index = nearest_index(&[c[0] as i32, c[1] as i32, c[2] as i32, c[3] as i32], &palette);
+            clusters[index].push((c, count));
+        }
+
+        let mut moved = false;
+        for (entry, cluster) in palette.iter_mut().zip(&clusters) {
+            if cluster.is_empty() {
+                continue;
+            }
+            let average = weighted_average(cluster);
+            if (average.r, average.g, average.b, average.a) != (entry.r, entry.g, entry.b, entry.a) {
+                *entry = average;
+                moved = true;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    palette
+}
+
+// The image's own colors as the palette (in the order they first show up), if there are no more
+// than max_colors of them. For pixel art and the like, which then comes through exactly.
+pub fn exact_palette(bytes: &[u8], max_colors: i32) -> Option<(Vec<u8>, Vec<quantizr::Color>)> {
+    let max_colors = usize::try_from(max_colors).ok()?.min(256);
+    let mut lookup: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut palette: Vec<quantizr::Color> = Vec::new();
+    let mut indexes: Vec<u8> = Vec::with_capacity(bytes.len()/4);
+    for px in bytes.chunks_exact(4) {
+        let key = [px[0], px[1], px[2], px[3]];
+        let index = match lookup.get(&key) {
+            Some(&index) => index,
+            None => {
+                if palette.len() >= max_colors {
+                    return None;
+                }
+                let index = palette.len() as u8;
+                palette.push(quantizr::Color { r: px[0], g: px[1], b: px[2], a: px[3] });
+                lookup.insert(key, index);
+                index
+            },
+        };
+        indexes.push(index);
+    }
+    Some((indexes, palette))
+}
+
+// For every color in from, the index of the closest color in to
+pub fn palette_mapping(from: &[quantizr::Color], to: &[quantizr::Color]) -> Vec<u8> {
+    assert!(!to.is_empty() && to.len() <= 256);
+    from.iter()
+        .map(|c| nearest_index(&[c.r as i32, c.g as i32, c.b as i32, c.a as i32], to) as u8)
+        .collect()
+}
+
+// Maps every pixel to its closest palette entry, with Floyd-Steinberg dithering scaled by
+// dithering_level (0.0 = no dithering)
+pub fn remap(bytes: &[u8], width: u32, height: u32, palette: &[quantizr::Color], dithering_level: f32) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    assert!(width * height * 4 == bytes.len());
+    assert!(!palette.is_empty() && palette.len() <= 256);
+
+    if dithering_level <= 0.0 {
+        // Plenty of images have a lot of repeated colors, so cache the lookups
+        let mut cache: HashMap<[u8; 4], u8> = HashMap::new();
+        return bytes.chunks_exact(4)
+            .map(|px| {
+                *cache.entry([px[0], px[1], px[2], px[3]])
+                    .or_insert_with(|| nearest_index(&[px[0] as i32, px[1] as i32, px[2] as i32, px[3] as i32], palette) as u8)
+            })
+            .collect();
+    }
+
+    remap_masked(bytes, width as u32, height as u32, palette, &vec![dithering_level; width * height])
+}
+
+// Like remap, but with a dithering level for every pixel (see dither_mask)
+pub fn remap_masked(bytes: &[u8], width: u32, height: u32, palette: &[quantizr::Color], dithering: &[f32]) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    assert!(width * height * 4 == bytes.len() && width * height == dithering.len());
+    assert!(!palette.is_empty() && palette.len() <= 256);
+
+    // Accumulated error, in 1/16ths
+    let mut errors: Vec<[i32; 4]> = vec![[0; 4]; width * height];
+    let mut indexes = vec![0u8; width * height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = x + y*width;
+            let mut px = [0i32; 4];
+            for ch in 0..4 {
+                px[ch] = ((bytes[i*4 + ch] as i32) + (errors[i][ch] as f32 * dithering[i] / 16.0) as i32).clamp(0, 255);
+            }
+
+            let index = nearest_index(&px, palette);
+            indexes[i] = index as u8;
+
+            let c = &palette[index];
+            let err = [px[0] - c.r as i32, px[1] - c.g as i32, px[2] - c.b as i32, px[3] - c.a as i32];
+            let mut spread = |x: usize, y: usize, weight: i32| {
+                if x < width && y < height {
+                    for ch in 0..4 {
+                        errors[x + y*width][ch] += err[ch] * weight;
+                    }
+                }
+            };
+            spread(x + 1, y, 7);
+            if x > 0 {
+                spread(x - 1, y + 1, 3);
+            }
+            spread(x, y + 1, 5);
+            spread(x + 1, y + 1, 1);
+        }
+    }
+
+    indexes
+}
+
+// For big images (without scaling, mostly), where quantizing is what takes the time: the image gets
+// split into horizontal strips that are quantized each on a thread of their own. The strip palettes
+// are then merged by quantizing them once more, every color repeated by how many pixels it's used
+// for, and the strips get remapped to the merged palette in parallel too. The dithering starts over
+// at the top of every strip, which doesn't show at these sizes.
+pub fn quantize_tiled(
+    quantizer: &dyn Quantizer,
+    bytes: &[u8],
+    width: u32, height: u32,
+    max_colors: i32,
+    dithering_level: f32,
+) -> Result<(Vec<u8>, Vec<quantizr::Color>), Box<dyn Error>> {
+    let strips = (rayon::current_num_threads() as u32).min(height / TILE_MIN_ROWS).max(1);
+    if strips == 1 {
+        return quantizer.quantize(bytes, width, height, max_colors, dithering_level);
+    }
+    let rows_per_strip = height.div_ceil(strips);
+    let strip_len = (rows_per_strip * width * 4) as usize;
+
+    // The palette of every strip, and how many pixels use each entry
+    let strip_palettes: Vec<(Vec<quantizr::Color>, Vec<u64>)> = bytes.par_chunks(strip_len)
+        .map(|strip| {
+            let rows = (strip.len() / 4) as u32 / width;
+            let (indexes, palette) = quantizer.quantize(strip, width, rows, max_colors, 0.0)
+                .map_err(|err| err.to_string())?;
+            let mut counts = vec![0u64; palette.len()];
+            for &i in &indexes {
+                counts[i as usize] += 1;
+            }
+            Ok((palette, counts))
+        })
+        .collect::<Result<_, String>>()?;
+
+    let total = (width as u64) * (height as u64);
+    let merged: Vec<u8> = strip_palettes.iter()
+        .flat_map(|(palette, counts)| palette.iter().zip(counts))
+        .filter(|(_, &count)| count > 0)
+        .flat_map(|(c, &count)| {
+            let repeat = (count * MERGE_SAMPLES / total).max(1) as usize;
+            std::iter::repeat([c.r, c.g, c.b, c.a]).take(repeat).flatten()
+        })
+        .collect();
+    let (_, palette) = quantizer.quantize(&merged, (merged.len() / 4) as u32, 1, max_colors, 0.0)?;
+    debug!("Quantized {strips} strips, merged {} strip palette colors into {}", merged.len() / 4, palette.len());
+
+    let indexes: Vec<u8> = bytes.par_chunks(strip_len)
+        .map(|strip| {
+            let rows = (strip.len() / 4) as u32 / width;
+            remap(strip, width, rows, &palette, dithering_level)
+        })
+        .flatten()
+        .collect();
+    Ok((indexes, palette))
+}
+
+// Classic median cut: keep splitting the box with the widest channel range at the median until we
+// have enough boxes, then use the average color of each box.
+pub struct MedianCutQuantizer;
+
+impl Quantizer for MedianCutQuantizer {
+    fn quantize(
+        &self,
+        bytes: &[u8],
+        width: u32, height: u32,
+        max_colors: i32,
+        dithering_level: f32,
+    ) -> Result<(Vec<u8>, Vec<quantizr::Color>), Box<dyn Error>> {
+        if !(2..=256).contains(&max_colors) {
+            return Err(format!("max_colors out of range: {max_colors}").into());
+        }
+
+        let mut boxes: Vec<Vec<([u8; 4], u64)>> = vec![color_counts(bytes)];
+
+        while boxes.len() < max_colors as usize {
+            // (box index, channel, range) of the box with the widest range of any channel
+            let widest = boxes.iter()
+                .enumerate()
+                .filter(|(_, b)| b.len() > 1)
+                .flat_map(|(i, b)| (0..4).map(move |ch| {
+                    let min = b.iter().map(|(c, _)| c[ch]).min().unwrap_or(0);
+                    let max = b.iter().map(|(c, _)| c[ch]).max().unwrap_or(0);
+                    (i, ch, max - min)
+                }))
+                .max_by_key(|&(_, _, range)| range);
+            let Some((i, ch, range)) = widest else {
+                break; // Every box is down to a single color
+            };
+            if range == 0 {
+                break;
+            }
+
+            let mut b = boxes.swap_remove(i);
+            b.sort_unstable_by_key(|(c, _)| c[ch]);
+
+            // Split where half of the pixels (not half of the colors) are on either side
+            let total: u64 = b.iter().map(|(_, count)| count).sum();
+            let mut acc: u64 = 0;
+            let mut split = b.len() - 1;
+            for (n, (_, count)) in b.iter().enumerate() {
+                acc += count;
+                if acc*2 >= total {
+                    split = n + 1;
+                    break;
+                }
+            }
+            let split = split.clamp(1, b.len() - 1);
+
+            let rest = b.split_off(split);
+            boxes.push(b);
+            boxes.push(rest);
+        }
+
+        let palette: Vec<quantizr::Color> = boxes.iter().map(|b| weighted_average(b)).collect();
+        let indexes = remap(bytes, width, height, &palette, dithering_level);
+        Ok((indexes, palette))
+    }
+}
+
+// Octree quantization: build a tree 8 levels deep with the bits of the RGB values, then fold the
+// deepest nodes into their parents until we have few enough leaves.
+pub struct OctreeQuantizer;
+
+const OCTREE_DEPTH: usize = 8;
+
+struct OctreeNode {
+    sum: [u64; 4],
+    count: u64,
+    children: [Option<usize>; 8],
+    leaf: bool,
+}
+
+impl OctreeNode {
+    fn new(leaf: bool) -> Self {
+        OctreeNode { sum: [0; 4], count: 0, children: [None; 8], leaf: leaf }
+    }
+}
+
+impl Quantizer for OctreeQuantizer {
+    fn quantize(
+        &self,
+        bytes: &[u8],
+        width: u32, height: u32,
+        max_colors: i32,
+        dithering_level: f32,
+    ) -> Result<(Vec<u8>, Vec<quantizr::Color>), Box<dyn Error>> {
+        if !(2..=256).contains(&max_colors) {
+            return Err(format!("max_colors out of range: {max_colors}").into());
+        }
+
+        let mut nodes: Vec<OctreeNode> = vec![OctreeNode::new(false)];
+        // Inner nodes at each level, so we know where to start folding
+        let mut levels: Vec<Vec<usize>> = vec![Vec::new(); OCTREE_DEPTH];
+        levels[0].push(0);
+        let mut leaves: usize = 0;
+
+        for (c, count) in color_counts(bytes) {
+            let mut node = 0;
+            for level in 0..OCTREE_DEPTH {
+                let bit = 7 - level;
+                let child = (((c[0] >> bit) & 1) << 2 | ((c[1] >> bit) & 1) << 1 | ((c[2] >> bit) & 1)) as usize;
+                node = match nodes[node].children[child] {
+                    Some(n) => n,
+                    None => {
+                        let leaf = level + 1 == OCTREE_DEPTH;
+                        nodes.push(OctreeNode::new(leaf));
+                        let n = nodes.len() - 1;
+                        nodes[node].children[child] = Some(n);
+                        if leaf {
+                            leaves += 1;
+                        } else {
+                            levels[level + 1].push(n);
+                        }
+                        n
+                    },
+                };
+            }
+            for ch in 0..4 {
+                nodes[node].sum[ch] += (c[ch] as u64) * count;
+            }
+            nodes[node].count += count;
+        }
+
+        // Fold the least used nodes of the deepest level first
+        for level in (0..OCTREE_DEPTH).rev() {
+            levels[level].sort_unstable_by_key(|&n| std::cmp::Reverse(subtree_count(&nodes, n)));
+            while leaves > max_colors as usize {
+                let Some(n) = levels[level].pop() else {
+                    break;
+                };
+                let children: Vec<usize> = nodes[n].children.iter().flatten().copied().collect();
+                for &child in &children {
+                    let (sum, count) = (nodes[child].sum, nodes[child].count);
+                    for ch in 0..4 {
+                        nodes[n].sum[ch] += sum[ch];
+                    }
+                    nodes[n].count += count;
+                    // Folded into n, so it's not a palette entry anymore
+                    nodes[child].leaf = false;
+                    nodes[child].count = 0;
+                }
+                nodes[n].children = [None; 8];
+                nodes[n].leaf = true;
+                leaves = leaves + 1 - children.len();
+            }
+        }
+
+        let palette: Vec<quantizr::Color> = nodes.iter()
+            .filter(|n| n.leaf && n.count > 0)
+            .map(|n| {
+                let avg = |ch: usize| ((n.sum[ch] + n.count/2) / n.count) as u8;
+                quantizr::Color { r: avg(0), g: avg(1), b: avg(2), a: avg(3) }
+            })
+            .collect();
+        let indexes = remap(bytes, width, height, &palette, dithering_level);
+        Ok((indexes, palette))
+    }
+}
+
+// Pixels in the subtree. Only used for deciding what to fold first, when all the counts are still
+// in the leaves.
+fn subtree_count(nodes: &[OctreeNode], n: usize) -> u64 {
+    let node = &nodes[n];
+    node.count + node.children.iter().flatten().map(|&c| subtree_count(nodes, c)).sum::<u64>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every color a different one, so the tree has to fold a lot
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..height).flat_map(|y| (0..width).flat_map(move |x| {
+            [(x*255/(width - 1)) as u8, (y*255/(height - 1)) as u8, ((x + y)*255/(width + height - 2)) as u8, 255]
+        })).collect()
+    }
+
+    #[test]
+    fn octree_palette_fits_max_colors() {
+        let bytes = gradient(64, 64);
+        for max_colors in [2, 16, 256] {
+            let (indexes, palette) = OctreeQuantizer.quantize(&bytes, 64, 64, max_colors, 0.0).unwrap();
+            assert!(!palette.is_empty() && palette.len() <= max_colors as usize, "{} colors for max {max_colors}", palette.len());
+            assert_eq!(indexes.len(), 64*64);
+            assert!(indexes.iter().all(|&i| (i as usize) < palette.len()));
+        }
+    }
+
+    #[test]
+    fn octree_tiled_palette_fits_max_colors() {
+        let (width, height) = (1024, 1024); // TILED_MIN_PIXELS
+        let bytes = gradient(width, height);
+        let (indexes, palette) = quantize_tiled(&OctreeQuantizer, &bytes, width, height, 16, 0.0).unwrap();
+        assert!(!palette.is_empty() && palette.len() <= 16, "{} colors for max 16", palette.len());
+        assert_eq!(indexes.len(), (width*height) as usize);
+    }
+}
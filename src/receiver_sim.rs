@@ -0,0 +1,236 @@
+// A model of the CRT shader, fed the same OSC parameter changes as the real one, to show what the
+// avatar should be displaying. Used for the preview in the progress window while sending, and by
+// the crt-receiver-sim binary as something to send to without VRChat. Goes by the values as they go
+// out (zero padding included), so a mismatch between this and the avatar points at the protocol or
+// the network rather than at the image.
+
+use crate::protocol_profile::ProtocolProfile;
+
+use fltk::enums::ColorDepth;
+use rosc::{OscPacket, OscType};
+
+const KEY_COLOR: [u8; 4] = [255, 0, 255, 255]; // Keyed out pixels, so they stand out
+
+// The parameter names (without the prefix) the receiver listens to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiverParams {
+    pub clk_param: String,
+    pub reset_param: String,
+    pub checksum_param: Option<String>,
+    pub data_params: Vec<String>,
+    pub bytes_per_int: usize,
+}
+
+pub struct ReceiverSim {
+    protocol: ProtocolProfile,
+    params: ReceiverParams,
+    width: u32,
+    height: u32,
+
+    clk: Option<bool>,
+    reset: bool,
+    checksum: bool,
+    values: Vec<i32>, // The data parameters as they were last set
+
+    rle: bool,
+    bitdepth: u8,
+    palette_active: bool,
+    palette: Vec<[u8; 3]>,
+    palette_wridx: usize,
+    key_index: Option<u8>,
+    data: Vec<u8>,      // Packed pixel bytes received since the reset
+    checked_len: usize, // data.len() as of the last checksum
+}
+
+impl ReceiverSim {
+    pub fn new(protocol: ProtocolProfile, params: ReceiverParams, width: u32, height: u32) -> Self {
+        let values = vec![0; params.data_params.len()];
+        ReceiverSim {
+            protocol: protocol,
+            params: params,
+            width: width,
+            height: height,
+            clk: None,
+            reset: false,
+            checksum: false,
+            values: values,
+            rle: false,
+            bitdepth: 8,
+            palette_active: false,
+            palette: vec![[0, 0, 0]; 256],
+            palette_wridx: 0,
+            key_index: None,
+            data: Vec::new(),
+            checked_len: 0,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // What came in since the reset, and the palette, for checking plans against in the tests
+    #[cfg(test)]
+    pub fn received(&self) -> (&[u8], &[[u8; 3]]) {
+        (&self.data, &self.palette)
+    }
+
+    pub fn set_bool(&mut self, param: &str, b: bool) {
+        if param == self.params.clk_param {
+            // The data gets taken in whenever the clock changes
+            if self.clk.is_some_and(|clk| clk != b) {
+                self.clock();
+            }
+            self.clk = Some(b);
+        } else if param == self.params.reset_param {
+            self.reset = b;
+            if b {
+                self.data.clear();
+                self.checked_len = 0;
+            }
+        } else if Some(param) == self.params.checksum_param.as_deref() {
+            self.checksum = b;
+        }
+    }
+
+    pub fn set_int(&mut self, param: &str, i: i32) {
+        if let Some(n) = self.params.data_params.iter().position(|p| p == param) {
+            self.values[n] = i;
+        }
+    }
+
+    // Takes the messages under the prefix, ignoring everything else
+    pub fn packet(&mut self, packet: &OscPacket, prefix: &str) {
+        match packet {
+            OscPacket::Message(msg) => {
+                let Some(param) = msg.addr.strip_prefix(prefix).and_then(|p| p.strip_prefix('/')) else {
+                    return;
+                };
+                match msg.args.first() {
+                    Some(OscType::Bool(b)) => self.set_bool(param, *b),
+                    Some(OscType::Int(i)) => self.set_int(param, *i),
+                    _ => (),
+                }
+            },
+            OscPacket::Bundle(bundle) => bundle.content.iter().for_each(|p| self.packet(p, prefix)),
+        }
+    }
+
+    // The avatar went back to the last checksum, the block is coming again
+    pub fn rewind(&mut self) {
+        self.data.truncate(self.checked_len);
+    }
+
+    // The bytes in the data parameters, unpacked if there's more than one per int
+    fn chunk(&self) -> Vec<u8> {
+        let bytes_per_int = self.params.bytes_per_int.clamp(1, 4);
+        let mut chunk: Vec<u8> = self.values.iter()
+            .flat_map(|value| value.to_le_bytes().into_iter().take(bytes_per_int))
+            .collect();
+        chunk.truncate(self.values.len());
+        chunk
+    }
+
+    fn clock(&mut self) {
+        let chunk = self.chunk();
+        if chunk.is_empty() {
+            return;
+        }
+        if self.checksum {
+            self.checked_len = self.data.len();
+        } else if self.reset {
+            self.control(&chunk);
+        } else if self.rle {
+            self.rle_decode(&chunk);
+        } else {
+            self.data.extend_from_slice(&chunk);
+        }
+    }
+
+    fn control(&mut self, chunk: &[u8]) {
+        let protocol = &self.protocol;
+        if chunk[0] == protocol.palettewrite_command {
+            for rgb in chunk[1..].chunks_exact(3) {
+                if let Some(entry) = self.palette.get_mut(self.palette_wridx) {
+                    *entry = [rgb[0], rgb[1], rgb[2]];
+                }
+                self.palette_wridx += 1;
+            }
+            return;
+        }
+        if chunk[0] != protocol.setpixel_command || chunk.len() < 7 {
+            return;
+        }
+
+        let pixel = [chunk[1], chunk[2]];
+        let (r, g) = (chunk[3], chunk[4]);
+        if pixel == protocol.compressionctrl_pixel {
+            self.rle = r == 255;
+        } else if pixel == protocol.bitdepth_pixel {
+            let encodings = &protocol.bitdepth_encodings;
+            self.bitdepth = [(encodings.bpp1, 1), (encodings.bpp2, 2), (encodings.bpp4, 4), (encodings.bpp8, 8)]
+                .iter()
+                .find(|(encoding, _)| *encoding == r)
+                .map_or(self.bitdepth, |(_, bitdepth)| *bitdepth);
+        } else if pixel == protocol.palettewridx_pixel {
+            self.palette_wridx = r as usize;
+        } else if pixel == protocol.palettectrl_pixel {
+            self.palette_active = r == 255;
+        } else if pixel == protocol.keyctrl_pixel {
+            self.key_index = if g == 255 { Some(r) } else { None };
+        }
+    }
+
+    // The inverse of send_osc's rle_encode. Runs are value, value, count, and can't start in the
+    // last two bytes of a chunk.
+    fn rle_decode(&mut self, chunk: &[u8]) {
+        let mut i = 0;
+        while i < chunk.len() {
+            if i + 2 < chunk.len() && chunk[i] == chunk[i + 1] {
+                self.data.extend(std::iter::repeat(chunk[i]).take(chunk[i + 2] as usize));
+                i += 3;
+            } else {
+                self.data.push(chunk[i]);
+                i += 1;
+            }
+        }
+    }
+
+    // What should be on screen, with whatever hasn't arrived yet black. Every pixel a zoom x zoom
+    // square.
+    pub fn render(&self, zoom: u32) -> Result<fltk::image::RgbImage, String> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let zoom = zoom.max(1) as usize;
+        let bitdepth = self.bitdepth as usize;
+        let pixels_per_byte = 8/bitdepth;
+        let bytes_per_line = width.div_ceil(pixels_per_byte);
+        let max_value = (1u32 << bitdepth) - 1;
+
+        let mut fb: Vec<u8> = vec![0; width*zoom*height*zoom*4];
+        for (y, line) in self.data.chunks(bytes_per_line).take(height).enumerate() {
+            for x in 0..width.min(line.len()*pixels_per_byte) {
+                let byte = line[x/pixels_per_byte];
+                let shift = 8 - bitdepth*(x % pixels_per_byte + 1);
+                let index = ((byte as u32) >> shift) & max_value;
+                let color = if self.palette_active && self.key_index.is_some_and(|k| k as u32 == index) {
+                    KEY_COLOR
+                } else if self.palette_active {
+                    let [r, g, b] = self.palette[index as usize];
+                    [r, g, b, 255]
+                } else {
+                    let v = (index*255/max_value) as u8;
+                    [v, v, v, 255]
+                };
+                for zy in 0..zoom {
+                    for zx in 0..zoom {
+                        let offset = ((y*zoom + zy)*width*zoom + x*zoom + zx)*4;
+                        fb[offset..offset + 4].copy_from_slice(&color);
+                    }
+                }
+            }
+        }
+
+        fltk::image::RgbImage::new(&fb, (width*zoom) as i32, (height*zoom) as i32, ColorDepth::Rgba8)
+            .map_err(|err| format!("Couldn't make receiver preview: {err}"))
+    }
+}
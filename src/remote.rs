@@ -0,0 +1,162 @@
+// Local HTTP API for driving the app from stream decks and scripts. Only listens on localhost.
+//
+//   POST /load    body is the path of the image to load
+//   POST /send    send the current image with the current send settings
+//   GET  /status  JSON with the current image and the last send
+//
+// Listening on localhost isn't enough on its own: browsers will happily POST to it from any web
+// page without asking first. So anything that looks like it came from a browser (an Origin header,
+// or a Host that isn't localhost, as with DNS rebinding) gets turned away.
+//
+// Everything gets forwarded to the BG thread (or the main thread, for things that need the
+// widgets) the same way the buttons do it.
+
+use crate::{AppMessage, BgMessage, mq, send_stats};
+
+use std::error::Error;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+pub const DEFAULT_PORT: u16 = 9120;
+
+// What the BG thread last told us about the image, for /status
+#[derive(Debug, Clone, Default)]
+pub struct ImageStatus {
+    pub source: Option<String>, // Path, or "Screen capture"
+    pub processed: Option<(u32, u32, usize)>, // Width, height, colors
+}
+
+static IMAGE_STATUS: Mutex<ImageStatus> = Mutex::new(ImageStatus { source: None, processed: None });
+
+pub fn update_status<F: FnOnce(&mut ImageStatus)>(f: F) {
+    match IMAGE_STATUS.lock() {
+        Ok(mut status) => f(&mut status),
+        Err(err) => warn!("Couldn't lock remote status: {err}"),
+    }
+}
+
+fn status_json() -> serde_json::Value {
+    let status = IMAGE_STATUS.lock().map(|s| s.clone()).unwrap_or_default();
+    let last_send = send_stats::last().map(|s| serde_json::json!({
+        "number": s.number,
+        "cancelled": s.cancelled,
+        "elapsed_secs": s.elapsed.as_secs_f64(),
+        "messages": s.messages,
+        "sent_bytes": s.sent_bytes,
+    }));
+    serde_json::json!({
+        "image": status.source,
+        "processed": status.processed.map(|(w, h, colors)| serde_json::json!({
+            "width": w,
+            "height": h,
+            "colors": colors,
+        })),
+        "last_send": last_send,
+    })
+}
+
+pub struct HttpServer {
+    server: Arc<tiny_http::Server>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl HttpServer {
+    pub fn start(
+        port: u16,
+        appmsg: &mpsc::Sender<AppMessage>,
+        bg: &mq::MessageQueueSender<BgMessage>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let server = Arc::new(tiny_http::Server::http(("127.0.0.1", port))?);
+        info!("HTTP remote control listening on 127.0.0.1:{port}");
+
+        let thread = thread::spawn({
+            let server = Arc::clone(&server);
+            let appmsg = appmsg.clone();
+            let bg = bg.clone();
+            move || {
+                for mut request in server.incoming_requests() {
+                    let (code, body) = match handle(&mut request, &appmsg, &bg) {
+                        Ok(body) => (200, body),
+                        Err((code, msg)) => (code, serde_json::json!({ "error": msg }).to_string()),
+                    };
+                    debug!("HTTP {} {} -> {code}", request.method(), request.url());
+                    let response = tiny_http::Response::from_string(body)
+                        .with_status_code(code)
+                        .with_header(tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap());
+                    if let Err(err) = request.respond(response) {
+                        warn!("Couldn't respond to HTTP request: {err}");
+                    }
+                }
+                info!("HTTP remote control stopped");
+            }
+        });
+
+        Ok(HttpServer { server: server, thread: Some(thread) })
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// The host part of a Host header (or an Origin without the scheme) is one of the loopback names
+pub fn is_local_host(host: &str) -> bool {
+    let name = host.rsplit_once(':')
+        .filter(|(_, port)| !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()))
+        .map_or(host, |(name, _)| name);
+    matches!(name, "127.0.0.1" | "localhost" | "[::1]")
+}
+
+fn from_browser(request: &tiny_http::Request) -> bool {
+    request.headers().iter().any(|header| {
+        header.field.equiv("Origin") || (header.field.equiv("Host") && !is_local_host(header.value.as_str()))
+    })
+}
+
+fn handle(
+    request: &mut tiny_http::Request,
+    appmsg: &mpsc::Sender<AppMessage>,
+    bg: &mq::MessageQueueSender<BgMessage>,
+) -> Result<String, (u16, String)> {
+    let ok = || serde_json::json!({ "ok": true }).to_string();
+
+    if from_browser(request) {
+        return Err((403, "Requests from web pages aren't allowed".to_string()));
+    }
+
+    let method = request.method().clone();
+    let url = request.url().to_string();
+
+    match (&method, url.as_str()) {
+        (tiny_http::Method::Get, "/status") => Ok(status_json().to_string()),
+        (tiny_http::Method::Post, "/load") => {
+            let mut path = String::new();
+            request.as_reader().read_to_string(&mut path)
+                .map_err(|err| (400, format!("Couldn't read request body: {err}")))?;
+            let path = PathBuf::from(path.trim());
+            if !path.is_file() {
+                return Err((404, format!("No such file: {path:?}")));
+            }
+            bg.send(BgMessage::LoadImage(path))
+                .map_err(|err| (500, format!("Couldn't send message to BG thread: {err}")))?;
+            fltk::app::awake();
+            Ok(ok())
+        },
+        (tiny_http::Method::Post, "/send") => {
+            // The send settings live in the widgets, so this needs to go through the main thread
+            appmsg.send(AppMessage::RemoteSend)
+                .map_err(|err| (500, format!("Couldn't send message to main thread: {err}")))?;
+            fltk::app::awake();
+            Ok(ok())
+        },
+        (_, "/status" | "/load" | "/send") => Err((405, "Method not allowed".to_string())),
+        (_, url) => Err((404, format!("Unknown endpoint {url:?}"))),
+    }
+}
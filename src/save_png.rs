@@ -1,10 +1,10 @@
 extern crate png;
 extern crate quantizr;
 
+use crate::atomic_write::write_atomically;
+
 use std::error::Error;
 use std::path::Path;
-use std::fs::File;
-use std::io::BufWriter;
 use std::num::NonZero;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -18,15 +18,11 @@ pub fn save_png(
     width: NonZero<u32>, height: NonZero<u32>,
     indexes: &[u8], palette: &[quantizr::Color],
     colortype: ColorType,
+    text: &[(&str, &str)], // iTXt chunks to add, keyword and text
 ) -> Result<(), Box<dyn Error>> {
 
-    let png_palette: Vec<u8>;
     let png_data: Vec<u8>;
 
-    let file = File::create(path).
-        map_err(|err| format!("Couldn't create file: {err}"))?;
-    let ref mut bufw = BufWriter::new(file);
-
     let bitdepth = {
         match palette.len() {
             ..=2     => png::BitDepth::One,
@@ -40,11 +36,39 @@ pub fn save_png(
         }
     };
 
+    // Only bother with transparency when there actually is some
+    let has_alpha = palette.iter().any(|c| c.a != 255);
+
+    // Grayscale with transparency needs the GrayscaleAlpha color type,
+    // which only supports 8 (or 16) bits per channel. So we scale the
+    // indexes up to 8 bits the same way a PNG reader would have done
+    // for the lower bitdepths, and put the alpha of the palette entry
+    // next to it.
+    let grayscale_alpha = colortype == ColorType::Grayscale && has_alpha;
+
     // We need to do the conversion per line, because it might happen
     // that the width doesn't divide evenly when we are using 4bpp,
     // 2bpp or 1bpp modes. In that case each line must be padded out
     // some pixels.
     let data: &[u8] = match bitdepth {
+        _ if grayscale_alpha => {
+            let max: u32 = match bitdepth {
+                png::BitDepth::One   => 0b1,
+                png::BitDepth::Two   => 0b11,
+                png::BitDepth::Four  => 0b1111,
+                png::BitDepth::Eight => 0xff,
+                png::BitDepth::Sixteen => return Err("Unsupported bitdepth".into()),
+            };
+            png_data = indexes
+                .iter()
+                .flat_map(|&i| {
+                    let gray = ((i as u32) * 255 / max) as u8;
+                    let alpha = palette.get(i as usize).map_or(255, |c| c.a);
+                    [gray, alpha]
+                })
+                .collect();
+            &png_data
+        },
         png::BitDepth::One => {
             png_data = indexes
                 .chunks_exact(u32::try_into(width.into())?)
@@ -89,28 +113,49 @@ pub fn save_png(
         png::BitDepth::Eight => indexes,
         png::BitDepth::Sixteen => return Err("Unsupported bitdepth".into()),
     };
+    let bitdepth = if grayscale_alpha { png::BitDepth::Eight } else { bitdepth };
 
-    let mut encoder = png::Encoder::new(bufw, width.into(), height.into());
-    if colortype == ColorType::Indexed {
-        png_palette = palette.iter().flat_map(|c| [c.r, c.g, c.b]).collect();
-        encoder.set_palette(&png_palette);
-    }
-    let typ = match colortype {
-        ColorType::Grayscale => png::ColorType::Grayscale,
-        ColorType::Indexed => png::ColorType::Indexed,
-    };
-    encoder.set_color(typ);
-    encoder.set_depth(bitdepth);
-    encoder.set_compression(png::Compression::Best);
-    encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+    write_atomically(path, |bufw| {
+        let mut encoder = png::Encoder::new(bufw, width.into(), height.into());
+        if colortype == ColorType::Indexed {
+            let png_palette: Vec<u8> = palette.iter().flat_map(|c| [c.r, c.g, c.b]).collect();
+            encoder.set_palette(png_palette);
+
+            if has_alpha {
+                // The tRNS chunk is allowed to be shorter than the palette,
+                // in which case the remaining entries are fully opaque
+                let mut trns: Vec<u8> = palette.iter().map(|c| c.a).collect();
+                while trns.last() == Some(&255) {
+                    trns.pop();
+                }
+                encoder.set_trns(trns);
+            }
+        }
+        let typ = match colortype {
+            ColorType::Grayscale if grayscale_alpha => png::ColorType::GrayscaleAlpha,
+            ColorType::Grayscale => png::ColorType::Grayscale,
+            ColorType::Indexed => png::ColorType::Indexed,
+        };
+        encoder.set_color(typ);
+        encoder.set_depth(bitdepth);
+        encoder.set_compression(png::Compression::Best);
+        encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+        for (keyword, text) in text {
+            encoder.add_itxt_chunk(keyword.to_string(), text.to_string())
+                .map_err(|err| format!("Failed when adding {keyword:?} text: {err}"))?;
+        }
+
+        info!("Saving PNG of color {typ:?} with bit depth {bitdepth:?}");
 
-    println!("Saving PNG of color {typ:?} with bit depth {bitdepth:?}");
+        let mut writer = encoder.write_header()
+            .map_err(|err| format!("Failed when writing header: {err}"))?;
 
-    let mut writer = encoder.write_header()
-        .map_err(|err| format!("Failed when writing header: {err}"))?;
+        writer.write_image_data(data)
+            .map_err(|err| format!("Failed when writing image data: {err}"))?;
 
-    writer.write_image_data(data)
-        .map_err(|err| format!("Failed when writing image data: {err}"))?;
+        writer.finish()
+            .map_err(|err| format!("Failed when finishing PNG: {err}"))?;
 
-    Ok(())
+        Ok(())
+    })
 }
@@ -0,0 +1,112 @@
+// Keeps track of the one send that can be going on at a time. Two sends at once would fight over the
+// socket (and the local send port) and interleave their packets, so while one is going any other
+// gets turned down. The state gets shown under the Send OSC button and goes out to WebSocket
+// clients.
+
+use crate::ws_bridge;
+
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum JobState {
+    #[default]
+    Idle,
+    Preparing,  // Packing, planning and asking the shader, before anything goes out
+    Sending,
+    Paused,     // Stuck on network errors, waiting for the user to retry or cancel
+    Cancelling, // Cancelled, but the send thread hasn't noticed yet
+}
+
+impl JobState {
+    // Also what WebSocket clients get
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobState::Idle => "idle",
+            JobState::Preparing => "preparing",
+            JobState::Sending => "sending",
+            JobState::Paused => "paused",
+            JobState::Cancelling => "cancelling",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            JobState::Idle => "Ready to send",
+            JobState::Preparing => "Preparing to send...",
+            JobState::Sending => "Sending...",
+            JobState::Paused => "Paused on network errors",
+            JobState::Cancelling => "Cancelling...",
+        }
+    }
+}
+
+static STATE: Mutex<JobState> = Mutex::new(JobState::Idle);
+static ON_CHANGE: Mutex<Option<fn(JobState)>> = Mutex::new(None);
+
+pub fn state() -> JobState {
+    STATE.lock().map_or_else(|err| *err.into_inner(), |state| *state)
+}
+
+// Called with the new state whenever it changes, from whichever thread changed it
+pub fn on_change(f: fn(JobState)) {
+    match ON_CHANGE.lock() {
+        Ok(mut on_change) => *on_change = Some(f),
+        Err(err) => warn!("Couldn't lock send job listener: {err}"),
+    }
+}
+
+// Moves to the new state if allowed from the current one, returns whether it did. Once cancelling,
+// the only way out is finishing up.
+fn transition(allowed: impl Fn(JobState) -> bool, to: JobState) -> bool {
+    {
+        let mut state = match STATE.lock() {
+            Ok(state) => state,
+            Err(err) => err.into_inner(),
+        };
+        let from = *state;
+        if from == to || !allowed(from) {
+            return false;
+        }
+        debug!("Send job {} -> {}", from.name(), to.name());
+        *state = to;
+    }
+
+    ws_bridge::broadcast(serde_json::json!({ "event": "state", "state": to.name() }));
+    if let Some(f) = ON_CHANGE.lock().ok().and_then(|on_change| *on_change) {
+        f(to);
+    }
+    true
+}
+
+// The send in progress. Dropping it (when the send thread is done, or when a send fails before
+// getting going) makes room for the next one.
+pub struct Job {
+    _private: (),
+}
+
+impl Job {
+    // Only used for Sending and Paused, which can't take over from cancelling
+    pub fn set(&self, state: JobState) {
+        transition(|from| from != JobState::Cancelling, state);
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        transition(|_| true, JobState::Idle);
+    }
+}
+
+// Starts a send, or says why it can't
+pub fn begin() -> Result<Job, String> {
+    if transition(|from| from == JobState::Idle, JobState::Preparing) {
+        Ok(Job { _private: () })
+    } else {
+        Err(format!("Another send is in progress ({}), wait for it to finish or cancel it", state().name()))
+    }
+}
+
+// The send is being cancelled, there's a cancel flag for actually stopping it
+pub fn cancelling() {
+    transition(|from| from != JobState::Idle, JobState::Cancelling);
+}
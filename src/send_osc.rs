@@ -1,6 +1,17 @@
 use crate::AppMessage;
-use crate::utility::error_alert;
-use crate::static_assert;
+use crate::i18n;
+use crate::utility::{error_alert, duration_to_string};
+use crate::send_stats::{self, SendSummary};
+use crate::send_job::{self, JobState};
+use crate::shader_profile::{self, ShaderProfile};
+use crate::ws_bridge;
+use crate::adaptive_rate::{self, AckListener, RateController};
+use crate::checksum;
+use crate::handshake;
+use crate::pixel_protocol::{self, Command, SetupParams, Step};
+use crate::transport::{self, Connection, Transport};
+use crate::receiver_sim::ReceiverSim;
+use crate::pixel_view;
 
 use fltk::prelude::*;
 use std::thread;
@@ -9,13 +20,14 @@ use std::sync::mpsc;
 use std::string::ToString;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::iter::Iterator;
 
 extern crate rosc;
 use rosc::encoder;
 use rosc::{OscMessage, OscPacket, OscType};
-use std::net::{SocketAddrV4, UdpSocket};
+use rayon::prelude::*;
+use std::net::SocketAddr;
 use std::time::Duration;
 
 // TODO: To cut down on repetition in these enums: Either use something like strum. Or make your own macro maybe?
@@ -91,6 +103,19 @@ impl FromStr for PixFmt {
 }
 
 impl PixFmt {
+    // The same color, at the given bit depth. Auto stays Auto.
+    pub fn with_bitdepth(self, bitdepth: u8) -> PixFmt {
+        match self {
+            PixFmt::Auto(_) => self,
+            PixFmt::Bpp1(col) | PixFmt::Bpp2(col) | PixFmt::Bpp4(col) | PixFmt::Bpp8(col) => match bitdepth {
+                1 => PixFmt::Bpp1(col),
+                2 => PixFmt::Bpp2(col),
+                4 => PixFmt::Bpp4(col),
+                _ => PixFmt::Bpp8(col),
+            },
+        }
+    }
+
     pub const VALUES: [PixFmt; 10] = [
         PixFmt::Auto(Color::Indexed),
         PixFmt::Auto(Color::Grayscale),
@@ -109,47 +134,52 @@ impl PixFmt {
     }
 }
 
-fn duration_to_string(dur: Duration) -> String {
-    let total: u64 = dur.as_secs();
-    let mins: u64 = total/60;
-
-    if mins == 0 {
-        // Use the debug output for a very fine-grained output (two decimal places) when we are below a minute
-        format!("{:.2?}", dur)
-    } else {
-        // Above one minute we only care about whole seconds
-        let secs: u64 = total % 60;
-        format!("{mins} min {secs} s")
-    }
+struct ProgressWindow {
+    cancel_flag: Arc<AtomicBool>,
+    // The retry button is hidden until sending runs into network trouble it can't get past by
+    // itself, and sets the resume flag when pressed
+    resume_flag: Arc<AtomicBool>,
+    win: fltk::window::Window,
+    progressbar: fltk::misc::Progress,
+    retry_btn: fltk::button::Button,
+    preview_frame: fltk::frame::Frame, // What the receiver should be showing (see receiver_sim)
 }
 
+const PREVIEW_HEIGHT: i32 = 200;
+
 fn create_progressbar_window(
     appmsg: &mpsc::Sender<AppMessage>,
     text_string: Option<String>,
-) -> Result<(Arc<AtomicBool>, fltk::window::Window, fltk::misc::Progress),
-            Box<dyn Error>> {
+) -> Result<ProgressWindow, Box<dyn Error>> {
 
     let cancel_flag = Arc::new(AtomicBool::new(false));
-    let (tx, rx) = mpsc::channel::<(fltk::window::Window, fltk::misc::Progress)>();
+    let resume_flag = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel::<(fltk::window::Window, fltk::misc::Progress, fltk::button::Button, fltk::frame::Frame)>();
 
     // New windows need to be created on the main thread, so we message the main thread
     appmsg.send({
         let cancel_flag = Arc::clone(&cancel_flag);
+        let resume_flag = Arc::clone(&resume_flag);
         AppMessage::CreateWindow(
-            600, 200, "Sending OSC".to_string(),
+            600, 200 + PREVIEW_HEIGHT, i18n::tr("Sending OSC").to_string(),
             Box::new(move |win| -> Result<(), Box<dyn Error>> {
                 win.set_callback({
                     let cancel_flag = Arc::clone(&cancel_flag);
                     move |_win| {
                         if fltk::app::event() == fltk::enums::Event::Close {
-                            println!("Send OSC window got Event::close");
+                            debug!("Send OSC window got Event::close");
                             cancel_flag.store(true, Ordering::Relaxed);
+                            send_job::cancelling();
                         }
                     }
                 });
 
                 let mut col = fltk::group::Flex::default_fill().column();
 
+                let mut preview_frame = fltk::frame::Frame::default_fill();
+                preview_frame.set_frame(fltk::enums::FrameType::DownBox);
+                col.fixed(&preview_frame, PREVIEW_HEIGHT);
+
                 let mut progressbar = fltk::misc::Progress::default_fill();
                 progressbar.set_minimum(0.0);
                 progressbar.set_maximum(100.0);
@@ -160,18 +190,26 @@ fn create_progressbar_window(
                     col.fixed(&text_frame, 30);
                 }
 
-                let mut cancel_btn = fltk::button::Button::default().with_label("Cancel");
+                let mut retry_btn = i18n::labeled(fltk::button::Button::default(), "Retry");
+                retry_btn.set_callback(move |_btn| {
+                    debug!("Send OSC window retry button pressed");
+                    resume_flag.store(true, Ordering::Relaxed);
+                });
+                retry_btn.hide();
+
+                let mut cancel_btn = i18n::labeled(fltk::button::Button::default(), "Cancel");
                 cancel_btn.set_callback({
                     let cancel_flag = Arc::clone(&cancel_flag);
                     move |_btn| {
-                        println!("Send OSC window cancel button pressed");
+                        debug!("Send OSC window cancel button pressed");
                         cancel_flag.store(true, Ordering::Relaxed);
+                        send_job::cancelling();
                     }
                 });
 
                 col.end();
 
-                tx.send((win.clone(), progressbar))?;
+                tx.send((win.clone(), progressbar, retry_btn, preview_frame))?;
 
                 Ok(())
             })
@@ -179,10 +217,368 @@ fn create_progressbar_window(
     })?;
     fltk::app::awake();
 
-    let (mut win, progressbar) = rx.recv()?;
+    let (mut win, progressbar, retry_btn, preview_frame) = rx.recv()?;
     win.set_on_top();
 
-    Ok((cancel_flag, win, progressbar))
+    Ok(ProgressWindow {
+        cancel_flag: cancel_flag,
+        resume_flag: resume_flag,
+        win: win,
+        progressbar: progressbar,
+        retry_btn: retry_btn,
+        preview_frame: preview_frame,
+    })
+}
+
+const PROGRESS_UPDATES_PER_SECOND: f64 = 10.0;
+
+// The cancel flag of the send in progress, so it can also be cancelled from outside the progress
+// window (e.g. the WebSocket bridge)
+static CURRENT_CANCEL_FLAG: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+fn set_current_cancel_flag(flag: Option<Arc<AtomicBool>>) {
+    match CURRENT_CANCEL_FLAG.lock() {
+        Ok(mut current) => *current = flag,
+        Err(err) => warn!("Couldn't lock current cancel flag: {err}"),
+    }
+}
+
+// Returns false if there was nothing to cancel
+pub fn cancel_current() -> bool {
+    match CURRENT_CANCEL_FLAG.lock() {
+        Ok(current) => match current.as_ref() {
+            Some(flag) => {
+                info!("Cancelling the current send");
+                flag.store(true, Ordering::Relaxed);
+                send_job::cancelling();
+                true
+            },
+            None => false,
+        },
+        Err(err) => {
+            warn!("Couldn't lock current cancel flag: {err}");
+            false
+        },
+    }
+}
+
+// Pushes the latest progress message to the progress bar from a single helper thread, at most
+// PROGRESS_UPDATES_PER_SECOND times a second. This keeps the sending thread from getting held by
+// the app main thread (currently the file choosers cause an issue for one), without spawning a
+// thread for every chunk we send.
+struct ProgressUpdater {
+    latest: Arc<Mutex<Option<(String, f64)>>>,
+    preview: Arc<Mutex<Option<fltk::image::RgbImage>>>,
+    progress: std::cell::Cell<f64>,
+    done: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl ProgressUpdater {
+    fn new(progressbar: fltk::misc::Progress, preview_frame: fltk::frame::Frame) -> Self {
+        let latest = Arc::new(Mutex::new(None::<(String, f64)>));
+        let preview = Arc::new(Mutex::new(None::<fltk::image::RgbImage>));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let handle = thread::spawn({
+            let latest = Arc::clone(&latest);
+            let preview = Arc::clone(&preview);
+            let done = Arc::clone(&done);
+            let mut progressbar = progressbar;
+            let mut preview_frame = preview_frame;
+            move || {
+                let interval = Duration::from_secs_f64(1.0/PROGRESS_UPDATES_PER_SECOND);
+                loop {
+                    // Check before taking the message so the last one always makes it through
+                    let finished = done.load(Ordering::Relaxed);
+
+                    let update = match latest.lock() {
+                        Ok(mut latest) => latest.take(),
+                        Err(err) => {
+                            warn!("Progress updater couldn't lock mutex: {err}");
+                            break;
+                        },
+                    };
+                    if let Some((msg, progress)) = update {
+                        progressbar.set_label(&msg);
+                        progressbar.set_value(progress);
+                        fltk::app::awake();
+                        ws_bridge::broadcast(serde_json::json!({
+                            "event": "progress",
+                            "message": msg,
+                            "progress": progress,
+                        }));
+                    }
+
+                    let image = match preview.lock() {
+                        Ok(mut preview) => preview.take(),
+                        Err(err) => {
+                            warn!("Progress updater couldn't lock preview mutex: {err}");
+                            None
+                        },
+                    };
+                    if let Some(image) = image {
+                        // Blown up by a whole number so the pixels stay sharp, then down to fit if need be
+                        let zoom = ((preview_frame.h() - 4)/image.h().max(1)).clamp(1, 16) as u32;
+                        match pixel_view::zoomed(&image, zoom) {
+                            Ok(mut image) => {
+                                if image.h() > preview_frame.h() - 4 || image.w() > preview_frame.w() - 4 {
+                                    image.scale(preview_frame.w() - 4, preview_frame.h() - 4, true, true);
+                                }
+                                preview_frame.set_image(Some(image));
+                                preview_frame.redraw();
+                                fltk::app::awake();
+                            },
+                            Err(err) => warn!("{err}"),
+                        }
+                    }
+
+                    if finished {
+                        break;
+                    }
+                    thread::sleep(interval);
+                }
+            }
+        });
+
+        ProgressUpdater { latest, preview, progress: std::cell::Cell::new(0.0), done, handle: Some(handle) }
+    }
+
+    fn update(&self, msg: String, progress: f64) {
+        debug!("{}", msg);
+        self.progress.set(progress);
+        match self.latest.lock() {
+            Ok(mut latest) => *latest = Some((msg, progress)),
+            Err(err) => warn!("Couldn't lock progress mutex: {err}"),
+        }
+    }
+
+    fn update_preview(&self, image: fltk::image::RgbImage) {
+        match self.preview.lock() {
+            Ok(mut preview) => *preview = Some(image),
+            Err(err) => warn!("Couldn't lock preview mutex: {err}"),
+        }
+    }
+
+    // Leaves the progress bar where it was
+    fn update_message(&self, msg: String) {
+        self.update(msg, self.progress.get());
+    }
+
+    // Stops the helper thread and waits for it, so that the progress bar can safely be deleted after this
+    fn finish(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            if let Err(err) = handle.join() {
+                error!("Progress updater thread panicked: {err:?}");
+            }
+        }
+    }
+}
+
+impl Drop for ProgressUpdater {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+// Shows the final image at display size and only calls on_confirm once the user has explicitly
+// agreed to send it. Guards against accidentally sending the wrong screenshot to a public instance.
+// on_confirm gets passed whether "Don't ask again" was checked.
+pub fn confirm_send<F>(
+    appmsg: &mpsc::Sender<AppMessage>,
+    preview: fltk::image::RgbImage,
+    on_confirm: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(bool) + Send + Sync + 'static,
+{
+    let width = std::cmp::max(preview.w() + 40, 400);
+    let height = preview.h() + 140;
+
+    appmsg.send(AppMessage::CreateWindow(
+        width, height, i18n::tr("Confirm send").to_string(),
+        Box::new(move |win| -> Result<(), Box<dyn Error>> {
+            win.make_modal(true);
+            win.set_callback(|win| {
+                debug!("Confirm send window closed");
+                fltk::app::delete_widget(win.clone());
+            });
+
+            let mut col = fltk::group::Flex::default_fill().column();
+            col.set_margin(10);
+
+            let mut text_frame = i18n::labeled(fltk::frame::Frame::default_fill(), "This is what will be sent. Are you sure?");
+            col.fixed(&text_frame, 30);
+            text_frame.set_label_size(16);
+
+            let mut image_frame = fltk::frame::Frame::default_fill();
+            image_frame.set_frame(fltk::enums::FrameType::DownBox);
+            image_frame.set_image(Some(preview));
+
+            let dont_ask_toggle = i18n::labeled(fltk::button::CheckButton::default(), "Don't ask again");
+            col.fixed(&dont_ask_toggle, 30);
+
+            let btnrow = fltk::group::Flex::default_fill().row();
+            let mut send_btn = i18n::labeled(fltk::button::Button::default(), "Send");
+            let mut cancel_btn = i18n::labeled(fltk::button::Button::default(), "Cancel");
+            btnrow.end();
+            col.fixed(&btnrow, 40);
+
+            col.end();
+
+            send_btn.set_callback({
+                let win = win.clone();
+                let mut on_confirm = Some(on_confirm);
+                move |_btn| {
+                    debug!("Confirm send window send button pressed");
+                    if let Some(f) = on_confirm.take() {
+                        f(dont_ask_toggle.is_checked());
+                    }
+                    fltk::app::delete_widget(win.clone());
+                }
+            });
+
+            cancel_btn.set_callback({
+                let win = win.clone();
+                move |_btn| {
+                    debug!("Confirm send window cancel button pressed");
+                    fltk::app::delete_widget(win.clone());
+                }
+            });
+
+            Ok(())
+        })
+    ))?;
+    fltk::app::awake();
+
+    Ok(())
+}
+
+// Ways of making a send go faster, to go along with the warning about it taking long
+pub fn speedup_suggestions(
+    indexes: &[u8],
+    palette: &[quantizr::Color],
+    width: u32,
+    options: &SendOSCOpts,
+    estimate: &TransferEstimate,
+) -> Vec<String> {
+    let mut suggestions: Vec<String> = Vec::new();
+    if estimate.rle_bytes.is_none() && !options.auto_compression {
+        let with_rle = SendOSCOpts { rle_compression: true, ..options.clone() };
+        match estimate_transfer(indexes, palette, width, &with_rle) {
+            Ok(rle_estimate) if rle_estimate.duration < estimate.duration =>
+                suggestions.push(format!("Enable RLE compression (ETA {})", duration_to_string(rle_estimate.duration))),
+            _ => (),
+        }
+    }
+    if estimate.bitdepth > 1 {
+        let bitdepth = estimate.bitdepth/2;
+        suggestions.push(format!("Use {} colors or fewer for {bitdepth}bpp instead of {}bpp (about half the pixel data)",
+                                 1u32 << bitdepth, estimate.bitdepth));
+    }
+    suggestions.push("Raise the send speed, or scale the image down".to_string());
+    suggestions
+}
+
+// Like confirm_send, but for sends that would take longer than options.warn_after
+pub fn warn_long_send<F>(
+    appmsg: &mpsc::Sender<AppMessage>,
+    estimate: &TransferEstimate,
+    limit: Duration,
+    suggestions: Vec<String>,
+    on_confirm: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce() + Send + Sync + 'static,
+{
+    let text = format!(
+        "{}\n\n{}\n{}",
+        i18n::tr("This send will take about {}, which is more than {}.")
+            .replacen("{}", &duration_to_string(estimate.duration), 1)
+            .replacen("{}", &duration_to_string(limit), 1),
+        i18n::tr("To make it faster:"),
+        suggestions.iter().map(|s| format!("- {s}")).collect::<Vec<_>>().join("\n"),
+    );
+    let height = 120 + 20*(suggestions.len() as i32);
+    ask_send_anyway(appmsg, i18n::tr("Long send"), text, height, on_confirm)
+}
+
+// Asks before sending to a target nothing seems to be listening on (see transport::probe)
+pub fn warn_unreachable<F>(
+    appmsg: &mpsc::Sender<AppMessage>,
+    target: SocketAddr,
+    on_confirm: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce() + Send + Sync + 'static,
+{
+    let text = format!(
+        "{}\n\n{}",
+        i18n::tr("VRChat doesn't appear to be listening on {}.").replace("{}", &target.to_string()),
+        i18n::tr("Is it running, with OSC enabled in the action menu? If the target is right, the whole send would go nowhere."),
+    );
+    ask_send_anyway(appmsg, i18n::tr("Nothing listening"), text, 120, on_confirm)
+}
+
+// A modal window with the text, and buttons for sending anyway and cancelling
+fn ask_send_anyway<F>(
+    appmsg: &mpsc::Sender<AppMessage>,
+    title: &str,
+    text: String,
+    height: i32,
+    on_confirm: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce() + Send + Sync + 'static,
+{
+    appmsg.send(AppMessage::CreateWindow(
+        500, height + 60, title.to_string(),
+        Box::new(move |win| -> Result<(), Box<dyn Error>> {
+            win.make_modal(true);
+            win.set_callback(|win| {
+                fltk::app::delete_widget(win.clone());
+            });
+
+            let mut col = fltk::group::Flex::default_fill().column();
+            col.set_margin(10);
+
+            let mut text_frame = fltk::frame::Frame::default_fill().with_label(&text);
+            text_frame.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside | fltk::enums::Align::Wrap);
+
+            let btnrow = fltk::group::Flex::default_fill().row();
+            let mut send_btn = i18n::labeled(fltk::button::Button::default(), "Send anyway");
+            let mut cancel_btn = i18n::labeled(fltk::button::Button::default(), "Cancel");
+            btnrow.end();
+            col.fixed(&btnrow, 40);
+
+            col.end();
+
+            send_btn.set_callback({
+                let win = win.clone();
+                let mut on_confirm = Some(on_confirm);
+                move |_btn| {
+                    if let Some(f) = on_confirm.take() {
+                        f();
+                    }
+                    fltk::app::delete_widget(win.clone());
+                }
+            });
+
+            cancel_btn.set_callback({
+                let win = win.clone();
+                move |_btn| {
+                    debug!("Send cancelled");
+                    fltk::app::delete_widget(win.clone());
+                }
+            });
+
+            Ok(())
+        })
+    ))?;
+    fltk::app::awake();
+
+    Ok(())
 }
 
 // Pack bytes while cloning (even in case we don't need to pack, we still need to clone to pass the
@@ -193,11 +589,12 @@ fn pack_bytes_clone(indexes: &[u8], width: usize, bitdepth: u8) -> Vec<u8> {
     // We need to do the conversion per line, because it might
     // happen that the width doesn't divide evenly when we are using 4bpp, 2bpp or 1bpp modes. In
     // that case each line must be padded out some pixels.
+    // The lines are packed in parallel using rayon (collect keeps them in order).
     match bitdepth {
         1 =>
             indexes
-            .chunks_exact(width)
-            .flat_map(|line|
+            .par_chunks_exact(width)
+            .flat_map_iter(|line|
                       line.chunks(8)
                       .map(|p|
                            p.get(0).map_or(0, |v| (v & 0b1) << 7) |
@@ -211,8 +608,8 @@ fn pack_bytes_clone(indexes: &[u8], width: usize, bitdepth: u8) -> Vec<u8> {
             ).collect(),
         2 =>
             indexes
-            .chunks_exact(width)
-            .flat_map(|line|
+            .par_chunks_exact(width)
+            .flat_map_iter(|line|
                       line.chunks(4)
                       .map(|p|
                            p.get(0).map_or(0, |v| (v & 0b11) << 6) |
@@ -222,8 +619,8 @@ fn pack_bytes_clone(indexes: &[u8], width: usize, bitdepth: u8) -> Vec<u8> {
             ).collect(),
         4 =>
             indexes
-            .chunks_exact(width)
-            .flat_map(|line|
+            .par_chunks_exact(width)
+            .flat_map_iter(|line|
                       line.chunks(2)
                       .map(|p|
                            p.get(0).map_or(0, |v| (v & 0b1111) << 4) |
@@ -234,7 +631,16 @@ fn pack_bytes_clone(indexes: &[u8], width: usize, bitdepth: u8) -> Vec<u8> {
     }
 }
 
-fn rle_encode(indexes: &[u8]) -> Vec<u8> {
+// Whether to send the RLE compressed data, given the sizes with and without
+fn use_rle(packed_len: usize, rle_len: usize, options: &SendOSCOpts) -> bool {
+    if options.auto_compression {
+        rle_len < packed_len
+    } else {
+        options.rle_compression
+    }
+}
+
+fn rle_encode(indexes: &[u8], bytes_per_send: usize) -> Vec<u8> {
     // We will likely be smaller, but it probably doesn't hurt to allocate ahead of time even if we
     // waste a little memory. There is a small chance we will be larger too
     let mut result: Vec<u8> = Vec::with_capacity(indexes.len());
@@ -266,9 +672,9 @@ fn rle_encode(indexes: &[u8]) -> Vec<u8> {
 
     for &value in &indexes[..] {
         // determine whether or not we are at the end two bytes of a
-        // BYTES_PER_SEND chunk and then simply put two bytes as is, because
+        // bytes_per_send chunk and then simply put two bytes as is, because
         // we cannot fit an escaped RLE sequence thingamajig here
-        if (result.len() % BYTES_PER_SEND) >= (BYTES_PER_SEND - 2) {
+        if (result.len() % bytes_per_send) >= (bytes_per_send - 2) {
             assert!(count == 1u8);
             result.push(current_value.expect("current_value should always be Some(x) here"));
             current_value = Some(value);
@@ -296,55 +702,131 @@ fn rle_encode(indexes: &[u8]) -> Vec<u8> {
     result
 }
 
-#[derive(Debug, Clone, Default)]
+// The byte stream the shader ends up with, before it gets split up into chunks: the packed pixels,
+// RLE compressed or not. Same as what plan_send does, for checking shaders offline (see pack_cli).
+pub fn shader_bytes(indexes: &[u8], width: usize, bitdepth: u8, rle_compression: bool, bytes_per_send: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    if width == 0 || indexes.len() % width != 0 {
+        return Err("width not matching length of indexes array".into());
+    }
+    if ![1, 2, 4, 8].contains(&bitdepth) {
+        return Err(format!("Unsupported bitdepth: {bitdepth}").into());
+    }
+    if bytes_per_send < shader_profile::MIN_BYTES_PER_SEND {
+        return Err(format!("Too few bytes per send ({bytes_per_send}, at least {})", shader_profile::MIN_BYTES_PER_SEND).into());
+    }
+    let packed = pack_bytes_clone(indexes, width, bitdepth);
+    Ok(if rle_compression { rle_encode(&packed, bytes_per_send) } else { packed })
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct SendOSCOpts {
     pub pixfmt: PixFmt,
     pub msgs_per_second: f64,
     pub linesync: bool,
     pub rle_compression: bool,
+    // Send whichever of the raw and the RLE compressed data comes out smaller, ignoring rle_compression
+    pub auto_compression: bool,
+    // Ask for confirmation (see confirm_send) before sending. Not looked at by send_osc itself.
+    pub confirm: bool,
+    pub profile: ShaderProfile,
+    pub target: Option<SocketAddr>, // None = VRChat's default port on localhost
+    pub transport: Transport,
+    // Palette index for shaders that support cut-out display to treat as transparent. Sent as
+    // disabled when None, so that a key from an earlier send doesn't stick around.
+    pub key_index: Option<u8>,
+    // Adjust the rate to how fast the avatar acknowledges the chunks (see adaptive_rate), starting
+    // out at msgs_per_second. Needs an ack parameter in the shader profile.
+    pub adaptive_rate: bool,
+    // Don't pop up the summary when done, for when there's a whole playlist going out
+    pub quiet: bool,
+    // The avatar already has this palette from the last send (streaming), so skip uploading it
+    pub keep_palette: bool,
+    // Don't ask the shader what it can do first (see handshake), it was already asked for an earlier
+    // send of the same stream or playlist
+    pub skip_query: bool,
+    // Ask first (see warn_long_send) when the send looks like it will take longer than this. Not
+    // looked at by send_osc itself either.
+    pub warn_after: Option<Duration>,
+    // Burst-and-wait pacing, for going along with how VRChat batches up parameter syncs: send
+    // burst_chunks pixel chunks back to back, then wait burst_wait. 0 = the same sleep after every
+    // chunk. The adaptive rate overrides this.
+    pub burst_chunks: usize,
+    pub burst_wait: Duration,
+    // How many times to try sending a message again after a transient network error (with backoff)
+    // before stopping to ask whether to carry on
+    pub send_retries: usize,
+    // Bytes per second of OSC to stay within, instead of going by msgs_per_second (see
+    // SendPlan::chunk_rate). None = go by msgs_per_second.
+    pub bandwidth: Option<f64>,
+    // Check that something is listening on the target first (see transport::probe), and ask before
+    // sending if not. Not looked at by send_osc itself.
+    pub check_receiver: bool,
 }
 
-const OSC_PREFIX: &'static str = "/avatar/parameters/PixelSendCRT";
+impl SendOSCOpts {
+    pub fn target_addr(&self) -> SocketAddr {
+        self.target.unwrap_or(SocketAddr::from(([127, 0, 0, 1], crate::config::DEFAULT_OSC_PORT)))
+    }
+}
 
-const BYTES_PER_SEND: usize = 24;
-const PALETTE_COLORS_PER_SEND: usize = (BYTES_PER_SEND-1)/3; // -1 because 1 byte is used up as a command byte
+pub const DEFAULT_SEND_RETRIES: usize = 5;
 
-// Defines for communication with the shader
-const SETPIXEL_COMMAND: u8 = 0x80;
-const PALETTEWRITE_COMMAND: u8 = 0xc0;
-const BITDEPTH_PIXEL: u8 = 2;
-const PALETTECTRL_PIXEL: u8 = 3;
-const PALETTEWRIDX_PIXEL: u8 = 4;
-const COMPRESSIONCTRL_PIXEL: u8 = 5;
+// Errors that might clear up by themselves, like the receiver not being up yet or the network
+// dropping out for a moment
+fn is_transient(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind::*;
+    matches!(err.kind(), WouldBlock | Interrupted | TimedOut | ConnectionRefused | ConnectionReset | ConnectionAborted | AddrNotAvailable)
+}
 
-pub fn send_osc(
-    appmsg: &mpsc::Sender<AppMessage>,
-    indexes: &[u8],
-    palette: &[quantizr::Color],
-    width: u32,
-    height: u32,
-    options: SendOSCOpts,
-) -> Result<(), Box<dyn Error>> {
-    if indexes.len() == 0 || width == 0 || height == 0 {
-        return Err("indexes, width or height are 0 and they shouldn't be".into());
+fn retry_backoff(attempt: usize) -> Duration {
+    Duration::from_millis(50*(1 << attempt.min(5)))
+}
+
+// Sleeps against deadlines counted from the start rather than for a fixed time per chunk, so the time
+// spent sending doesn't add up over a long send and throw the ETA off
+struct Pacer {
+    deadline: std::time::Instant,
+}
+
+// When we've fallen further behind than this (a stall, waiting on a checksum result), start counting
+// from now again rather than rushing out a pile of chunks to catch up
+const MAX_PACING_LAG: Duration = Duration::from_millis(250);
+
+impl Pacer {
+    fn new() -> Self {
+        Pacer { deadline: std::time::Instant::now() }
     }
 
-    if indexes.len() != (width as usize) * (height as usize) {
-        return Err("width and height not matching length of indexes array".into());
+    fn wait(&mut self, sleep: Duration) {
+        self.deadline += sleep;
+        let now = std::time::Instant::now();
+        if self.deadline > now {
+            thread::sleep(self.deadline - now);
+        } else if now - self.deadline > MAX_PACING_LAG {
+            self.deadline = now;
+        }
     }
+}
 
-    let host_addr = SocketAddrV4::from_str("127.0.0.1:9002")?;
-    let to_addr = SocketAddrV4::from_str("127.0.0.1:9000")?;
-    let sock = UdpSocket::bind(host_addr)?;
+// About how often VRChat sends out parameter changes
+pub const DEFAULT_BURST_WAIT: Duration = Duration::from_millis(200);
 
-    let sleep_time = 1.0/options.msgs_per_second;
+// The sleep after pixel chunk number count (counting from 1) when not adapting the rate
+fn chunk_sleep(count: usize, duration: Duration, options: &SendOSCOpts) -> Duration {
+    match options.burst_chunks {
+        0 => duration,
+        n if count % n == 0 => options.burst_wait,
+        _ => Duration::ZERO,
+    }
+}
 
-    // Get the bitdepth and whether we should be indexed or grayscale from pixfmt
-    // TODO: Perhaps it would've made more sense with a regular old struct for
-    //       pixfmt. then we wouldn't need to pick it apart like this.
-    let (bitdepth, color) = match options.pixfmt {
+// Get the bitdepth and whether we should be indexed or grayscale from pixfmt
+// TODO: Perhaps it would've made more sense with a regular old struct for
+//       pixfmt. then we wouldn't need to pick it apart like this.
+fn bitdepth_and_color(pixfmt: PixFmt, palette_len: usize) -> Result<(u8, Color), Box<dyn Error>> {
+    Ok(match pixfmt {
         PixFmt::Auto(col) => (
-            match palette.len() {
+            match palette_len {
                 ..=2     => 1,
                 ..=4     => 2,
                 ..=16    => 4,
@@ -357,269 +839,822 @@ pub fn send_osc(
         PixFmt::Bpp2(col) => (2, col),
         PixFmt::Bpp4(col) => (4, col),
         PixFmt::Bpp8(col) => (8, col),
-    };
+    })
+}
+
+// What sending an image would amount to, without actually sending anything
+#[derive(Debug, Clone)]
+pub struct TransferEstimate {
+    pub bitdepth: u8,
+    pub packed_bytes: usize,
+    pub rle_bytes: Option<usize>,
+    // RLE size/packed size, worked out even with RLE off so it can be shown while tweaking settings
+    pub rle_ratio: f64,
+    pub chunks: usize, // Pixel data chunks (not counting the palette and the rest of the preamble)
+    pub duration: Duration,
+}
 
-    let mut indexes = pack_bytes_clone(&indexes[..], width.try_into()?, bitdepth);
+impl TransferEstimate {
+    pub fn description(&self) -> String {
+        let rle = match self.rle_bytes {
+            Some(rle_bytes) => format!(", RLE {rle_bytes} bytes ({:.0}%)", self.rle_ratio*100.0),
+            None => format!(", RLE would be {:.0}%", self.rle_ratio*100.0),
+        };
+        format!("{}bpp: {} bytes{rle}\n{} chunks, ETA {}",
+                self.bitdepth, self.packed_bytes, self.chunks, duration_to_string(self.duration))
+    }
+}
 
-    // Optionally apply RLE compression
-    let mut misc_string: Option<String> = None;
-    if options.rle_compression {
-        // TODO: Also implement an alternative, more efficient, encoding for the case where the
-        //  palette color count is 254 or lower for 8bpp, 15 or lower for 4bpp, 3 for 2bpp (kinda
-        //  pointless), and perhaps not that usable for 8bpp: instead of duplicated byte as escape,
-        //  use a 255 byte as the escape as that won't appear in the uncompressed bytestream when
-        //  this is true. (could work without this req too, but then we have to escape single 255s
-        //  as 255, 1)
+// What a step of a send plan is for, which decides how it gets paced
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepKind {
+    Control,      // Setting up the receiver before the pixel data, or finishing up after
+    Chunk(usize), // Pixel chunk number n, counting from 0
+    // A checksum over the chunks from the step at block_start on, which get sent again if the
+    // receiver says they came through wrong
+    Checksum { block: usize, block_start: usize },
+}
 
-        let result = rle_encode(&indexes[..]);
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanStep {
+    pub kind: StepKind,
+    pub description: String,
+    pub progress: f64,
+    pub packets: Vec<OscPacket>,
+    pub delay: Option<Duration>, // Only for control steps, None = the same sleep as the pixel chunks
+}
 
-        let rle_compression_string =
-            format!("RLE Compression ratio: {:.2}% (original length: {}, compressed length: {})",
-                     ((result.len() as f64) / (indexes.len() as f64))*100.0, indexes.len(), result.len());
-        println!("{}", rle_compression_string);
-        misc_string = Some(rle_compression_string);
+// Everything a send consists of, in order, down to the OSC packets. Built by plan_send without
+// touching the network, so that it can be looked at for dry runs and estimates, and then run by
+// send_osc, which only adds the pacing.
+#[derive(Debug, Clone)]
+pub struct SendPlan {
+    pub bitdepth: u8,
+    pub color: Color,
+    pub rle_compression: bool, // Whether the pixel data goes out RLE compressed
+    pub packed_bytes: usize,
+    pub rle_bytes: usize,      // What RLE compression makes of the packed bytes, used or not
+    pub data: Vec<u8>,         // The pixel data as it goes out
+    pub chunks: usize,
+    // The clock gets flipped for every chunk. The packets in the plan have it going back and forth
+    // already, but send_osc flips it for real every time, so that it still changes when a block gets
+    // sent again.
+    pub clk_address: String,
+    pub steps: Vec<PlanStep>,
+    pub cancel_steps: Vec<PlanStep>, // Sent instead of the rest if the send gets cancelled
+}
 
-        indexes = result;
+// The sleep after a step when not adapting the rate
+fn step_sleep(step: &PlanStep, duration: Duration, options: &SendOSCOpts) -> Duration {
+    match step.kind {
+        StepKind::Control => step.delay.unwrap_or(duration),
+        StepKind::Chunk(n) => chunk_sleep(n + 1, duration, options),
+        StepKind::Checksum { .. } => duration,
     }
+}
 
-    let (cancel_flag, win, progressbar) = create_progressbar_window(appmsg, misc_string)?;
+impl SendPlan {
+    pub fn messages(&self) -> usize {
+        self.steps.iter().map(|step| step.packets.len()).sum()
+    }
 
-    let palette = palette.to_owned(); // Clone the palette for the thread to own it
-    let appmsg = appmsg.clone();
-    thread::spawn(move || -> () {
-
-        let send_bool = |var: &str, b: bool| -> Result<usize, Box<dyn Error>> {
-            let msg_buf = encoder::encode(&OscPacket::Message(OscMessage {
-                addr: format!("{OSC_PREFIX}/{var}"),
-                args: vec![OscType::Bool(b)],
-            }))?;
-            Ok(sock.send_to(&msg_buf, to_addr)?)
-        };
+    // How long the steps for which include() is true take, going by the sleeps alone
+    fn duration_of(&self, options: &SendOSCOpts, include: impl Fn(&StepKind) -> bool) -> Duration {
+        let duration = Duration::from_secs_f64(1.0/self.chunk_rate(options));
+        self.steps.iter()
+            .filter(|step| include(&step.kind))
+            .map(|step| step_sleep(step, duration, options))
+            .sum()
+    }
 
-        let send_int = |var: &str, i: i32| -> Result<usize, Box<dyn Error>> {
-            let msg_buf = encoder::encode(&OscPacket::Message(OscMessage {
-                addr: format!("{OSC_PREFIX}/{var}"),
-                args: vec![OscType::Int(i)],
-            }))?;
-            Ok(sock.send_to(&msg_buf, to_addr)?)
+    // The pixel chunks per second to go at (what the updates/second slider sets). With a bandwidth
+    // budget that's worked out from the size of a chunk as it goes out, OSC addresses, type tags and
+    // padding included. The UDP/IP (or SLIP) framing isn't counted, that depends on the network.
+    pub fn chunk_rate(&self, options: &SendOSCOpts) -> f64 {
+        let Some(bandwidth) = options.bandwidth else {
+            return options.msgs_per_second;
         };
+        let step = self.steps.iter()
+            .find(|step| matches!(step.kind, StepKind::Chunk(_)))
+            .or(self.steps.first());
+        let bytes: usize = step.map_or(0, |step| {
+            step.packets.iter()
+                .map(|packet| encoder::encode(packet).map_or(0, |buf| buf.len()))
+                .sum()
+        });
+        if bytes == 0 || bandwidth <= 0.0 {
+            options.msgs_per_second
+        } else {
+            bandwidth/(bytes as f64)
+        }
+    }
 
-        let mut send_clk = {
-            let mut clk: bool = true;
-            move || -> Result<usize, Box<dyn Error>> {
-                let result = send_bool("CLK", clk);
-                clk = !clk;
-                result
-            }
-        };
+    pub fn duration(&self, options: &SendOSCOpts) -> Duration {
+        self.duration_of(options, |_| true)
+    }
 
-        #[allow(non_snake_case)]
-        const fn vNumberToChar(n: u8) -> u8 {
-            assert!((n as usize) < BYTES_PER_SEND);
-            let result = if n <= 9 { b'0' + n } else { b'A' + (n - 10) };
-            result & 0x7f
+    pub fn estimate(&self, options: &SendOSCOpts) -> TransferEstimate {
+        TransferEstimate {
+            bitdepth: self.bitdepth,
+            packed_bytes: self.packed_bytes,
+            rle_bytes: if self.rle_compression { Some(self.rle_bytes) } else { None },
+            rle_ratio: if self.packed_bytes == 0 { 1.0 } else { (self.rle_bytes as f64)/(self.packed_bytes as f64) },
+            chunks: self.chunks,
+            duration: self.duration(options),
         }
+    }
+}
 
-        // Doing it C-style to avoid heap allocations in a case of
-        // premature optimization for the sake of learning myself some
-        // more esoteric rust. (The sane thing would've been to just
-        // return String)
-        #[allow(non_snake_case)]
-        fn vStr(n: u8) -> &'static str {
-            thread_local! {
-                static BUFFER: std::cell::RefCell<[u8; 2]> = std::cell::RefCell::new(*b"V0");
-            }
+// The OSC packets for the commands, keeping track of where the clock is
+fn to_packets(commands: &[Command], profile: &ShaderProfile, clk: &mut bool) -> Vec<OscPacket> {
+    let message = |param: &str, arg: OscType| OscPacket::Message(OscMessage {
+        addr: profile.address(param),
+        args: vec![arg],
+    });
 
-            BUFFER.with(|buffer| {
-                let mut buf = buffer.borrow_mut();
-                buf[1] = vNumberToChar(n);
-                // Safety: Guaranteed to always be 7bit ASCII (by extension UTF8)
-                //         Users of this function promise to use the value referenced before calling the function again
-                unsafe { std::str::from_utf8_unchecked(&*std::ptr::addr_of!(*buf)) }
-            })
+    let mut packets: Vec<OscPacket> = Vec::new();
+    for command in commands {
+        match command {
+            Command::Bool(param, b) => {
+                if *param == profile.clk_param {
+                    *clk = !*b;
+                }
+                packets.push(message(param, OscType::Bool(*b)));
+            },
+            Command::Int(param, i) => packets.push(message(param, OscType::Int(*i))),
+            Command::Data(data) => packets.extend(
+                profile.data_params.iter()
+                    .zip(profile.pack_data(data))
+                    .map(|(param, value)| message(param, OscType::Int(value)))
+            ),
+            Command::Clock => {
+                packets.push(message(&profile.clk_param, OscType::Bool(*clk)));
+                *clk = !*clk;
+            },
         }
+    }
+    packets
+}
 
-        let send_cmd = |cmd: &[u8]| -> Result<(), Box<dyn Error>> {
-            for n in 0..BYTES_PER_SEND {
-                static_assert!(BYTES_PER_SEND <= 255);
-                send_int(vStr(n as u8), // BYTES_PER_SEND never larger than u8
-                         cmd.get(n).copied().unwrap_or_default().into()
-                )?;
-            }
-            Ok(())
-        };
+fn control_steps(steps: Vec<Step>, profile: &ShaderProfile, clk: &mut bool) -> Vec<PlanStep> {
+    steps.into_iter()
+        .map(|step| PlanStep {
+            kind: StepKind::Control,
+            packets: to_packets(&step.commands, profile, clk),
+            description: step.description,
+            progress: step.progress,
+            delay: step.delay,
+        })
+        .collect()
+}
 
-        let progress_message = |msg: String, progress: f64| -> () {
-            println!("{}", msg);
-            // Hack to avoid this thread getting held by the app main thread (currently the file choosers cause an issue for one)
-            thread::spawn({
-                let mut progressbar = progressbar.clone();
-                move || {
-                    progressbar.set_label(&msg);
-                    progressbar.set_value(progress);
-                    fltk::app::awake();
-                }
+// Does the packing and compressing, and works out every packet that would go out
+pub fn plan_send(
+    indexes: &[u8],
+    palette: &[quantizr::Color],
+    width: u32,
+    options: &SendOSCOpts,
+) -> Result<SendPlan, Box<dyn Error>> {
+    if width == 0 || indexes.len() % (width as usize) != 0 {
+        return Err("width not matching length of indexes array".into());
+    }
+
+    let profile = &options.profile;
+    let bytes_per_send = profile.bytes_per_send();
+    if bytes_per_send < shader_profile::MIN_BYTES_PER_SEND {
+        return Err(format!("Shader profile {:?} has too few data parameters ({bytes_per_send})", profile.name).into());
+    }
+
+    let (bitdepth, color) = bitdepth_and_color(options.pixfmt, palette.len())?;
+    let packed = pack_bytes_clone(indexes, width.try_into()?, bitdepth);
+
+    // TODO: Also implement an alternative, more efficient, encoding for the case where the
+    //  palette color count is 254 or lower for 8bpp, 15 or lower for 4bpp, 3 for 2bpp (kinda
+    //  pointless), and perhaps not that usable for 8bpp: instead of duplicated byte as escape,
+    //  use a 255 byte as the escape as that won't appear in the uncompressed bytestream when
+    //  this is true. (could work without this req too, but then we have to escape single 255s
+    //  as 255, 1)
+    let rle = rle_encode(&packed, bytes_per_send);
+    let rle_compression = use_rle(packed.len(), rle.len(), options);
+    let (packed_bytes, rle_bytes) = (packed.len(), rle.len());
+    let data = if rle_compression { rle } else { packed };
+
+    let backend = pixel_protocol::for_profile(profile);
+    let mut clk = true;
+    let mut steps = control_steps(backend.plan_setup(&SetupParams {
+        bitdepth: bitdepth,
+        color: color,
+        rle_compression: rle_compression,
+        key_index: options.key_index,
+        keep_palette: options.keep_palette,
+        palette: palette,
+    })?, profile, &mut clk);
+
+    // A checksum at the end of every block, and after the last chunk, if the shader supports them
+    let chunks_per_checksum = if profile.checksum_param.is_some() { profile.chunks_per_checksum } else { 0 };
+    let chunks: Vec<&[u8]> = data.chunks(bytes_per_send).collect();
+    let mut block_start = steps.len();
+    let mut block_first_chunk: usize = 0;
+    for (n, chunk) in chunks.iter().enumerate() {
+        let progress = ((n as f64)/(chunks.len() as f64))*100.0;
+        steps.push(PlanStep {
+            kind: StepKind::Chunk(n),
+            description: format!("Pixel chunk {}/{}", n + 1, chunks.len()),
+            progress: progress,
+            packets: to_packets(&backend.plan_chunk(chunk), profile, &mut clk),
+            delay: None,
+        });
+
+        let count = n + 1;
+        if chunks_per_checksum == 0 || (count % chunks_per_checksum != 0 && count != chunks.len()) {
+            continue;
+        }
+        let block = block_first_chunk/chunks_per_checksum;
+        let sum = checksum::fletcher16(chunks[block_first_chunk..count].iter().copied(), bytes_per_send);
+        if let Some(commands) = backend.plan_checksum(block, sum) {
+            steps.push(PlanStep {
+                kind: StepKind::Checksum { block: block, block_start: block_start },
+                description: format!("Checksum of block {block}: {sum:04x}"),
+                progress: progress,
+                packets: to_packets(&commands, profile, &mut clk),
+                delay: None,
             });
+        }
+        block_start = steps.len();
+        block_first_chunk = count;
+    }
+
+    steps.extend(control_steps(backend.finish(), profile, &mut clk));
+    // The clock for these depends on where the send got cancelled, send_osc takes care of it
+    let cancel_steps = control_steps(backend.plan_cancel(), profile, &mut true);
+
+    Ok(SendPlan {
+        bitdepth: bitdepth,
+        color: color,
+        rle_compression: rle_compression,
+        packed_bytes: packed_bytes,
+        rle_bytes: rle_bytes,
+        chunks: chunks.len(),
+        data: data,
+        clk_address: profile.address(&profile.clk_param),
+        steps: steps,
+        cancel_steps: cancel_steps,
+    })
+}
+
+// Works out how long sending would take with the sleeps send_osc does
+pub fn estimate_transfer(
+    indexes: &[u8],
+    palette: &[quantizr::Color],
+    width: u32,
+    options: &SendOSCOpts,
+) -> Result<TransferEstimate, Box<dyn Error>> {
+    Ok(plan_send(indexes, palette, width, options)?.estimate(options))
+}
+
+// The sending happens on a thread of its own. It can be joined to wait for the send to be over, and
+// gives true if everything went out (as opposed to being cancelled or failing).
+pub fn send_osc(
+    appmsg: &mpsc::Sender<AppMessage>,
+    indexes: &[u8],
+    palette: &[quantizr::Color],
+    width: u32,
+    height: u32,
+    options: SendOSCOpts,
+) -> Result<thread::JoinHandle<bool>, Box<dyn Error>> {
+    if indexes.len() == 0 || width == 0 || height == 0 {
+        return Err("indexes, width or height are 0 and they shouldn't be".into());
+    }
+
+    if indexes.len() != (width as usize) * (height as usize) {
+        return Err("width and height not matching length of indexes array".into());
+    }
+
+    // Held by the send thread until it's done, gets dropped right away if anything below fails
+    let job = send_job::begin()?;
+
+    let plan = plan_send(indexes, palette, width, &options)?;
+    if let (Color::Indexed, Some(key_index)) = (plan.color, options.key_index) {
+        if (key_index as usize) >= palette.len() {
+            return Err(format!("Key index {key_index} is outside of the palette ({} colors)", palette.len()).into());
+        }
+    }
+    let eta = plan.duration(&options);
+    // Just the pixel data, for the progress messages
+    let data_eta = plan.duration_of(&options, |kind| *kind != StepKind::Control);
+
+    let profile = options.profile.clone();
+    let bytes_per_send = profile.bytes_per_send();
+    let to_addr = options.target_addr();
+    let conn = Connection::open(options.transport, to_addr, transport::LOCAL_SEND_PORT)?;
+
+    let rate = plan.chunk_rate(&options);
+    if let Some(bandwidth) = options.bandwidth {
+        info!("Bandwidth budget of {bandwidth} bytes/s comes to {rate:.2} chunks/s");
+    }
+    let sleep_time = 1.0/rate;
+
+    let misc_string = if options.rle_compression || options.auto_compression {
+        let rle_compression_string =
+            format!("RLE Compression ratio: {:.2}% (original length: {}, compressed length: {}){}",
+                     ((plan.rle_bytes as f64) / (plan.packed_bytes as f64))*100.0, plan.packed_bytes, plan.rle_bytes,
+                     match (options.auto_compression, plan.rle_compression) {
+                         (true, true) => ", sending compressed",
+                         (true, false) => ", sending uncompressed",
+                         (false, _) => "",
+                     });
+        info!("{}", rle_compression_string);
+        Some(rle_compression_string)
+    } else {
+        None
+    };
+
+    // Make sure the shader can show this, if it can tell us. Shaders that should be able to answer
+    // but don't might just be older ones, so that's not the end of the world.
+    if !options.skip_query {
+        match handshake::query(&conn, adaptive_rate::listen_port(), &profile) {
+            Ok(Some(capabilities)) => capabilities.check(width, height, plan.bitdepth, plan.rle_compression)?,
+            Ok(None) => (),
+            Err(err) => warn!("Couldn't query the shader, sending anyway: {err}"),
+        }
+    }
+
+    // Checksums go out whenever the shader supports them, but can only be acted on if it reports back
+    let verify = plan.steps.iter().any(|step| matches!(step.kind, StepKind::Checksum { .. }))
+        && profile.checksum_result_param.is_some();
+
+    let ack_listener = if options.adaptive_rate || verify {
+        let ack_address = if options.adaptive_rate {
+            let ack_param = profile.ack_param.as_ref()
+                .ok_or("Adaptive rate needs an ack parameter in the shader profile")?;
+            Some(profile.address(ack_param))
+        } else {
+            None
         };
+        let result_address = if verify { profile.checksum_result_param.as_ref().map(|p| profile.address(p)) } else { None };
+        Some(AckListener::start(adaptive_rate::listen_port(), ack_address, result_address)?)
+    } else {
+        None
+    };
 
-        println!("palette.len(): {}, indexes.len(): {}", palette.len(), indexes.len());
+    let ProgressWindow { cancel_flag, resume_flag, win, progressbar, retry_btn, preview_frame } =
+        create_progressbar_window(appmsg, misc_string)?;
 
-        match || -> Result<(), Box<dyn Error>> {
-            let duration = Duration::from_secs_f64(sleep_time);
+    let quiet = options.quiet;
+    let send_retries = options.send_retries;
+    let appmsg = appmsg.clone();
+    let handle = thread::spawn(move || -> bool {
+        job.set(JobState::Sending);
+        let start = std::time::Instant::now();
+        let number = send_stats::next_number();
+        set_current_cancel_flag(Some(Arc::clone(&cancel_flag)));
+        ws_bridge::broadcast(serde_json::json!({ "event": "started", "number": number }));
+        let messages = std::cell::Cell::new(0usize);
+        let chunks_sent = std::cell::Cell::new(0usize);
+
+        let mut progress_updater = ProgressUpdater::new(progressbar, preview_frame);
+        let progress_message = |msg: String, progress: f64| -> () {
+            progress_updater.update(msg, progress);
+        };
 
-            // Reset CLK (we can use the send_clk helper after here)
-            progress_message("Reset CLK".to_string(), 0.0);
-            send_bool("CLK", true)?;
-            thread::sleep(duration);
-            send_bool("CLK", false)?;
-            thread::sleep(duration);
-
-            // Reset pixel pos
-            progress_message("Reset pixel pos".to_string(), 0.0);
-            send_int("V0", 0)?;
-            send_bool("Reset", true)?;
-            send_clk()?;
-            thread::sleep(duration);
-
-            // Set compression mode
-            progress_message((if options.rle_compression { "Enable RLE compression" } else { "Disable RLE compression" }).to_string(), 0.0);
-            send_cmd(&[SETPIXEL_COMMAND,
-                       COMPRESSIONCTRL_PIXEL, 0, // Controls compression. Red channel 0 is off, red channel 255 is on
-                       if options.rle_compression { 255 } else { 0 },
-                       0, 0, 0])?;
-            send_clk()?;
-            thread::sleep(duration);
-
-            // Set BPP
-            progress_message(format!("Set BPP {bitdepth}"), 0.0);
-            send_cmd(&[SETPIXEL_COMMAND, // Set data pixel command (when Reset is active)
-                       BITDEPTH_PIXEL, 0, // BITDEPTH_PIXEL at 2,0 controls BPP (red channel)
-                       match bitdepth {
-                           1 => 192,
-                           2 => 128,
-                           4 => 64,
-                           8 => 0,
-                           _ => panic!("This is unreachable"),
-                       },
-                       0, 0, 0])?;
-            send_clk()?;
-            thread::sleep(duration);
-
-            // Set palette
-            match color {
-                Color::Indexed => {
-                    progress_message("Reset palette write index".to_string(), 0.0);
-                    send_cmd(&[
-                        SETPIXEL_COMMAND,
-                        PALETTEWRIDX_PIXEL, 0,
-                        0,    // red channel: wridx 0
-                        0,    // green channel: unused
-                        0,    // blue channel: unused
-                        0,    // alpha channel: unused
-                    ])?;
-                    send_clk()?;
-                    thread::sleep(duration);
-
-                    const COLORS_AT_A_TIME: usize = (BYTES_PER_SEND.div_ceil(3)) - 1;
-                    let palette_chunks = palette.chunks(PALETTE_COLORS_PER_SEND);
-                    let palette_numchunks = palette_chunks.len();
-                    for (n, chunk) in palette.chunks(COLORS_AT_A_TIME).enumerate() {
-                        if cancel_flag.load(Ordering::Relaxed) {
-                            println!("{}", "Send OSC thread cancelled");
-                            return Ok(());
-                        }
+        // Stops for the user to press retry or cancel. Returns false if cancelled.
+        let wait_for_resume = |err: &std::io::Error| -> bool {
+            progress_updater.update_message(format!("{}: {err}\n{}", i18n::tr("Network error"), i18n::tr("Retry, or cancel the send?")));
+            resume_flag.store(false, Ordering::Relaxed);
+            job.set(JobState::Paused);
+            retry_btn.clone().show();
+            fltk::app::awake();
+            while !resume_flag.load(Ordering::Relaxed) && !cancel_flag.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(50));
+            }
+            retry_btn.clone().hide();
+            fltk::app::awake();
+            job.set(JobState::Sending);
+            !cancel_flag.load(Ordering::Relaxed)
+        };
 
-                        let mut data: [u8; BYTES_PER_SEND] = [0; BYTES_PER_SEND];
-                        data[0] = PALETTEWRITE_COMMAND;
-                        debug_assert!(chunk.len()*3 <= (data.len() - 1));
-                        for (i, col) in chunk.iter().enumerate() {
-                            // Note that what looks like an off-by-one here is actually us making sure to not overwrite
-                            // PALETTEWRITE_COMMAND in the first byte
-                            data[i*3 + 1] = col.r;
-                            data[i*3 + 2] = col.g;
-                            data[i*3 + 3] = col.b;
+        // Tries again on transient errors, first by itself and then if the user says so. Once the
+        // send has been cancelled nothing more goes out.
+        let send_packet = |msg_buf: &[u8]| -> Result<usize, Box<dyn Error>> {
+            let mut attempt: usize = 0;
+            loop {
+                if cancel_flag.load(Ordering::Relaxed) {
+                    return Ok(0);
+                }
+                match conn.send(msg_buf) {
+                    Ok(len) => return Ok(len),
+                    Err(err) if !is_transient(&err) => return Err(err.into()),
+                    Err(err) if attempt < send_retries => {
+                        let backoff = retry_backoff(attempt);
+                        warn!("Sending failed ({err}), trying again in {backoff:?}");
+                        thread::sleep(backoff);
+                        attempt += 1;
+                    },
+                    Err(err) => {
+                        warn!("Sending still failing after {attempt} retries: {err}");
+                        if !wait_for_resume(&err) {
+                            return Ok(0);
                         }
-                        send_cmd(&data)?;
-                        send_clk()?;
+                        attempt = 0;
+                    },
+                }
+            }
+        };
 
-                        let progress: f64 = ((n as f64)/(palette_numchunks as f64))*100.0;
-                        progress_message(format!("Sent palette chunk {n}/{palette_numchunks}"), progress);
+        // Everything that goes out also goes to the simulated receiver for the preview
+        let sim = std::cell::RefCell::new(ReceiverSim::new(profile.protocol.clone(), profile.receiver_params(), width, height));
+
+        let last_clk = std::cell::Cell::new(None::<bool>);
+        let flip_clk = |packet: &OscPacket| -> OscPacket {
+            match packet {
+                OscPacket::Message(msg) if msg.addr == plan.clk_address => {
+                    let clk = match (last_clk.get(), msg.args.first()) {
+                        (Some(last), _) => !last,
+                        (None, Some(OscType::Bool(b))) => *b,
+                        (None, _) => true,
+                    };
+                    last_clk.set(Some(clk));
+                    OscPacket::Message(OscMessage { addr: msg.addr.clone(), args: vec![OscType::Bool(clk)] })
+                },
+                _ => packet.clone(),
+            }
+        };
 
-                        thread::sleep(duration);
-                    }
+        let send = |packet: &OscPacket| -> Result<(), Box<dyn Error>> {
+            let packet = flip_clk(packet);
+            messages.set(messages.get() + 1);
+            trace!("{packet:?}");
+            send_packet(&encoder::encode(&packet)?)?;
+            sim.borrow_mut().packet(&packet, &profile.prefix);
+            Ok(())
+        };
 
-                    progress_message("Enable indexed colors".to_string(), 0.0);
-                    send_cmd(&[
-                        SETPIXEL_COMMAND,
-                        PALETTECTRL_PIXEL, 0,
-                        255,  // red channel: palette active
-                        0,    // green channel: palette write mode inactive
-                        0,    // blue channel: unused
-                        0,    // alpha channel: unused
-                    ])?;
-                    send_clk()?;
-                    thread::sleep(duration);
-                },
-                Color::Grayscale => {
-                    progress_message("Set to grayscale mode".to_string(), 0.0);
-                    send_cmd(&[
-                        SETPIXEL_COMMAND,
-                        PALETTECTRL_PIXEL, 0,
-                        0,    // red channel: palette inactive
-                        0,    // green channel: palette write mode not active
-                        0,    // blue channel: unused/reset palette
-                        0,    // alpha unused
-                    ])?;
-                    send_clk()?;
-                    thread::sleep(duration);
-                }
+        // Rendering the preview for every chunk would be a waste, it only gets shown so often anyway
+        let last_preview = std::cell::Cell::new(None::<std::time::Instant>);
+        let show_preview = |force: bool| {
+            let interval = Duration::from_secs_f64(1.0/PROGRESS_UPDATES_PER_SECOND);
+            if !force && last_preview.get().is_some_and(|t| t.elapsed() < interval) {
+                return;
+            }
+            last_preview.set(Some(std::time::Instant::now()));
+            match sim.borrow().render(1) {
+                Ok(image) => progress_updater.update_preview(image),
+                Err(err) => warn!("{err}"),
             }
+        };
+
+        debug!("Send plan: {} steps, {} messages, {} bytes of pixel data", plan.steps.len(), plan.messages(), plan.data.len());
 
-            // Reset the reset bit
-            progress_message("Clear the reset bit".to_string(), 0.0);
-            send_bool("Reset", false)?;
-            thread::sleep(duration);
+        let sent = match || -> Result<(), Box<dyn Error>> {
+            let duration = Duration::from_secs_f64(sleep_time);
 
-            let now = std::time::Instant::now();
+            // When the pixel data started going out, and how much had been acknowledged by then
+            let mut data_start: Option<(std::time::Instant, usize)> = None;
+            let mut rate_controller = if options.adaptive_rate { Some(RateController::new(rate)) } else { None };
 
-            let chunks = indexes.chunks(BYTES_PER_SEND);
-            let countmax: usize = chunks.len();
-            let eta = Duration::from_secs_f64((countmax as f64) * sleep_time);
-            for (count, index16) in chunks.enumerate() {
+            // Waits for the avatar to report back on a checksum
+            let wait_for_result = |listener: &AckListener| -> Option<i32> {
+                let wait_start = std::time::Instant::now();
+                while wait_start.elapsed() < checksum::RESULT_TIMEOUT && !cancel_flag.load(Ordering::Relaxed) {
+                    if let Some(result) = listener.take_result() {
+                        return Some(result);
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                None
+            };
+
+            let mut clocked: usize = 0; // Chunks clocked in, checksums and ones sent again included
+            let mut retries: usize = 0;
+            let mut pacer = Pacer::new();
+            let mut i: usize = 0;
+            while i < plan.steps.len() {
                 if cancel_flag.load(Ordering::Relaxed) {
-                    println!("{}", "Send OSC thread cancelled");
+                    info!("{}", "Send OSC thread cancelled");
                     return Ok(());
                 }
 
-                //dbg!(&index16);
-                println!("{index16:?}");
-                send_cmd(index16)?;
-
-                send_clk()?;
-
-                let progress = ((count as f64)/(countmax as f64))*100.0;
-                let elapsed = now.elapsed();
-                let msg = format!("Sent pixel chunk {}/{} {:.1}%\t ETA: {}/{}", count+1, countmax, progress, duration_to_string(elapsed), duration_to_string(eta));
-                progress_message(msg, progress);
+                let step = &plan.steps[i];
+                i += 1;
+                match step.kind {
+                    StepKind::Control => progress_message(step.description.clone(), step.progress),
+                    StepKind::Chunk(_) => {
+                        // Whatever got acknowledged during the preamble doesn't count
+                        if data_start.is_none() {
+                            data_start = Some((std::time::Instant::now(), ack_listener.as_ref().map_or(0, |l| l.acks())));
+                        }
+                    },
+                    StepKind::Checksum { .. } => {
+                        if let Some(listener) = &ack_listener {
+                            listener.clear_results();
+                        }
+                    },
+                }
+                trace!("{}", step.description);
+                for packet in &step.packets {
+                    send(packet)?;
+                }
 
-                thread::sleep(duration);
+                match step.kind {
+                    StepKind::Control => thread::sleep(step.delay.unwrap_or(duration)),
+                    StepKind::Chunk(n) => {
+                        let (now, acks_before) = data_start.unwrap_or((start, 0));
+                        show_preview(false);
+                        clocked += 1;
+                        let count = n + 1;
+                        chunks_sent.set(chunks_sent.get().max(count));
+
+                        let elapsed = now.elapsed();
+                        let mut msg = format!("Sent pixel chunk {}/{} {:.1}%\t ETA: {}/{}", count, plan.chunks, step.progress, duration_to_string(elapsed), duration_to_string(data_eta));
+
+                        let sleep = match (&ack_listener, rate_controller.as_mut()) {
+                            (Some(listener), Some(controller)) => {
+                                // Give a stall a chance to clear up before piling on more
+                                let stall_start = std::time::Instant::now();
+                                while RateController::is_stalled(clocked, listener.acks() - acks_before)
+                                    && stall_start.elapsed() < adaptive_rate::STALL_TIMEOUT
+                                    && !cancel_flag.load(Ordering::Relaxed)
+                                {
+                                    thread::sleep(Duration::from_millis(10));
+                                }
+                                let sleep = controller.update(clocked, listener.acks() - acks_before);
+                                msg += &format!(" ({:.1} msgs/s)", controller.rate());
+                                sleep
+                            },
+                            _ => step_sleep(step, duration, &options),
+                        };
+                        progress_message(msg, step.progress);
+
+                        pacer.wait(sleep);
+                    },
+                    StepKind::Checksum { block, block_start } => {
+                        clocked += 1;
+                        pacer.wait(duration);
+
+                        match ack_listener.as_ref().filter(|_| verify).map(|listener| wait_for_result(listener)) {
+                            Some(Some(checksum::RESULT_MISMATCH)) => {
+                                retries += 1;
+                                if retries > checksum::MAX_RETRIES {
+                                    return Err(format!("Block {block} still came through wrong after {} tries", checksum::MAX_RETRIES).into());
+                                }
+                                warn!("Block {block} came through wrong, sending it again");
+                                progress_message(format!("Block {block} came through wrong, sending it again"), step.progress);
+                                sim.borrow_mut().rewind();
+                                i = block_start;
+                            },
+                            Some(None) => {
+                                if !cancel_flag.load(Ordering::Relaxed) {
+                                    warn!("No checksum result for block {block}, carrying on");
+                                }
+                                retries = 0;
+                            },
+                            Some(Some(result)) => {
+                                if result != checksum::RESULT_OK {
+                                    warn!("Unknown checksum result {result} for block {block}, taking it as good");
+                                }
+                                retries = 0;
+                            },
+                            None => retries = 0,
+                        }
+                    },
+                }
             }
+            show_preview(true);
             if !cancel_flag.load(Ordering::Relaxed) {
-                println!("Send OSC thread finished sending all");
+                info!("Send OSC thread finished sending all");
             }
 
             Ok(())
         }() {
-            Ok(()) => (),
-            Err(err) => error_alert(&appmsg, format!("send_osc background process failed: {err}"))
+            Ok(()) => {
+                let cancelled = cancel_flag.load(Ordering::Relaxed);
+                let summary = SendSummary {
+                    number: number,
+                    cancelled: cancelled,
+                    elapsed: start.elapsed(),
+                    eta: eta,
+                    messages: messages.get(),
+                    chunks: chunks_sent.get(),
+                    packed_bytes: plan.packed_bytes,
+                    sent_bytes: plan.data.chunks(bytes_per_send).take(chunks_sent.get()).map(|c| c.len()).sum(),
+                };
+                if !cancelled && !quiet {
+                    if let Err(err) = send_stats::show_summary(&appmsg, &summary) {
+                        warn!("Couldn't show send summary: {err}");
+                    }
+                }
+                ws_bridge::broadcast(serde_json::json!({
+                    "event": "finished",
+                    "number": number,
+                    "cancelled": cancelled,
+                    "elapsed_secs": summary.elapsed.as_secs_f64(),
+                }));
+                send_stats::record(summary);
+                !cancelled
+            },
+            Err(err) => {
+                ws_bridge::broadcast(serde_json::json!({ "event": "failed", "error": err.to_string() }));
+                error_alert(&appmsg, format!("{}: {err}", i18n::tr("send_osc background process failed")));
+                false
+            },
         };
 
+        // Don't leave the shader half painted with the reset bit set. This goes out even though the
+        // send has been cancelled, so it's just one try per message.
+        if cancel_flag.load(Ordering::Relaxed) && messages.get() > 0 && !plan.cancel_steps.is_empty() {
+            progress_updater.update_message(i18n::tr("Cancelled, cleaning up").to_string());
+            match || -> Result<(), Box<dyn Error>> {
+                for step in &plan.cancel_steps {
+                    trace!("{}", step.description);
+                    for packet in &step.packets {
+                        let packet = flip_clk(packet);
+                        conn.send(&encoder::encode(&packet)?)?;
+                        sim.borrow_mut().packet(&packet, &profile.prefix);
+                    }
+                    thread::sleep(step.delay.unwrap_or(Duration::from_secs_f64(sleep_time)));
+                }
+                Ok(())
+            }() {
+                Ok(()) => debug!("Cleaned up after cancelling"),
+                Err(err) => warn!("Couldn't clean up after cancelling: {err}"),
+            }
+        }
+        set_current_cancel_flag(None);
+
+        progress_updater.finish();
+
         if let Err(err) = appmsg.send(AppMessage::DeleteWindow(win)) {
-            error_alert(&appmsg, format!("send_osc background process failed while sending delete window command: {err}"));
+            error_alert(&appmsg, format!("{}: {err}", i18n::tr("send_osc background process failed while sending delete window command")));
         };
         fltk::app::awake();
+
+        sent
     });
 
+    Ok(handle)
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray_palette(n: usize) -> Vec<quantizr::Color> {
+        (0..n).map(|i| {
+            let v = (i*255/(n - 1).max(1)) as u8;
+            quantizr::Color { r: v, g: v, b: 255 - v, a: 255 }
+        }).collect()
+    }
+
+    fn options() -> SendOSCOpts {
+        SendOSCOpts { msgs_per_second: 10.0, ..Default::default() }
+    }
+
+    fn descriptions(plan: &SendPlan) -> Vec<&str> {
+        plan.steps.iter().map(|step| step.description.as_str()).collect()
+    }
+
+    // Everything the plan sends, through the receiver model
+    fn receive(plan: &SendPlan, profile: &ShaderProfile, width: u32, height: u32) -> ReceiverSim {
+        let mut sim = ReceiverSim::new(profile.protocol.clone(), profile.receiver_params(), width, height);
+        for packet in plan.steps.iter().flat_map(|step| &step.packets) {
+            sim.packet(packet, &profile.prefix);
+        }
+        sim
+    }
+
+    #[test]
+    fn setup_steps_in_order() {
+        let palette = gray_palette(10); // Two palette chunks of 7 colors with 24 bytes per send
+        let indexes: Vec<u8> = (0..64).map(|i| (i % 10) as u8).collect();
+        let options = SendOSCOpts { key_index: Some(3), ..options() };
+        let plan = plan_send(&indexes, &palette, 8, &options).unwrap();
+
+        assert_eq!(plan.bitdepth, 4);
+        assert_eq!(plan.color, Color::Indexed);
+        assert!(!plan.rle_compression);
+        let setup: Vec<&str> = descriptions(&plan).into_iter().take_while(|d| !d.starts_with("Pixel chunk")).collect();
+        assert_eq!(setup, [
+            "Reset CLK",
+            "Reset CLK",
+            "Reset pixel pos",
+            "Disable RLE compression",
+            "Set BPP 4",
+            "Reset palette write index",
+            "Sent palette chunk 0/2",
+            "Sent palette chunk 1/2",
+            "Enable indexed colors",
+            "Set key index 3",
+            "Clear the reset bit",
+        ]);
+
+        let chunks: Vec<&PlanStep> = plan.steps.iter().filter(|step| matches!(step.kind, StepKind::Chunk(_))).collect();
+        assert_eq!(chunks.len(), plan.chunks);
+        assert_eq!(plan.chunks, (32usize).div_ceil(24)); // 64 pixels at 4bpp
+        // The pixel data comes last, nothing after it for the CRT shader
+        assert!(matches!(plan.steps.last().unwrap().kind, StepKind::Chunk(_)));
+    }
+
+    #[test]
+    fn reset_uses_first_data_param() {
+        let profile = ShaderProfile {
+            data_params: (0..8).map(|n| format!("D{n}")).collect(),
+            ..Default::default()
+        };
+        let options = SendOSCOpts { profile: profile.clone(), ..options() };
+        let plan = plan_send(&[0, 1, 1, 0], &gray_palette(2), 2, &options).unwrap();
+        let reset = plan.steps.iter().find(|step| step.description == "Reset pixel pos").unwrap();
+        let addrs: Vec<String> = reset.packets.iter()
+            .filter_map(|packet| match packet {
+                OscPacket::Message(msg) => Some(msg.addr.clone()),
+                _ => None,
+            })
+            .collect();
+        assert!(addrs.contains(&profile.address("D0")));
+        assert!(!addrs.iter().any(|addr| addr.ends_with("/V0")));
+    }
+
+    #[test]
+    fn grayscale_skips_palette() {
+        let options = SendOSCOpts { pixfmt: PixFmt::Bpp8(Color::Grayscale), ..options() };
+        let plan = plan_send(&[0, 128, 255, 64], &gray_palette(4), 4, &options).unwrap();
+        let steps = descriptions(&plan);
+        assert!(steps.contains(&"Set to grayscale mode"));
+        assert!(!steps.iter().any(|d| d.contains("palette")));
+        assert!(steps.contains(&"Disable key index"));
+    }
+
+    #[test]
+    fn rle_round_trip() {
+        let (width, height) = (32u32, 16u32);
+        // Long runs with some noise in between, so there's both runs and literals
+        let indexes: Vec<u8> = (0..width*height)
+            .map(|i| if (i/50) % 2 == 0 { 1 } else { (i % 7) as u8 })
+            .collect();
+        let palette = gray_palette(8);
+        let profile = ShaderProfile::default();
+        let options = SendOSCOpts { pixfmt: PixFmt::Bpp8(Color::Indexed), rle_compression: true, ..options() };
+        let plan = plan_send(&indexes, &palette, width, &options).unwrap();
+        assert!(plan.rle_compression);
+        assert!(plan.rle_bytes < plan.packed_bytes);
+        assert!(descriptions(&plan).contains(&"Enable RLE compression"));
+
+        let sim = receive(&plan, &profile, width, height);
+        let (data, received_palette) = sim.received();
+        assert!(data.len() >= indexes.len());
+        assert_eq!(&data[..indexes.len()], indexes.as_slice());
+        // Whatever is after the image is the zero padding of the last chunk
+        assert!(data[indexes.len()..].iter().all(|&b| b == 0));
+        for (c, received) in palette.iter().zip(received_palette) {
+            assert_eq!([c.r, c.g, c.b], *received);
+        }
+    }
+
+    #[test]
+    fn uncompressed_round_trip() {
+        let (width, height) = (10u32, 3u32); // Not a whole number of bytes per line at 2bpp
+        let indexes: Vec<u8> = (0..width*height).map(|i| (i % 4) as u8).collect();
+        let profile = ShaderProfile::default();
+        let plan = plan_send(&indexes, &gray_palette(4), width, &options()).unwrap();
+        assert_eq!(plan.bitdepth, 2);
+
+        let sim = receive(&plan, &profile, width, height);
+        let packed = pack_bytes_clone(&indexes, width as usize, 2);
+        let (data, _) = sim.received();
+        assert_eq!(&data[..packed.len()], packed.as_slice());
+    }
+
+    #[test]
+    fn auto_compression_picks_smaller() {
+        let flat = vec![0u8; 256];
+        let options = SendOSCOpts { pixfmt: PixFmt::Bpp8(Color::Indexed), auto_compression: true, ..options() };
+        assert!(plan_send(&flat, &gray_palette(2), 16, &options).unwrap().rle_compression);
+
+        let noisy: Vec<u8> = (0..256).map(|i| (i % 251) as u8).collect();
+        assert!(!plan_send(&noisy, &gray_palette(256), 16, &options).unwrap().rle_compression);
+    }
+
+    #[test]
+    fn estimate_matches_plan() {
+        let indexes: Vec<u8> = (0..48*48).map(|i| (i % 16) as u8).collect();
+        let options = options();
+        let plan = plan_send(&indexes, &gray_palette(16), 48, &options).unwrap();
+        let estimate = plan.estimate(&options);
+
+        assert_eq!(estimate.bitdepth, 4);
+        assert_eq!(estimate.packed_bytes, 48*48/2);
+        assert_eq!(estimate.rle_bytes, None);
+        assert_eq!(estimate.chunks, plan.chunks);
+        assert_eq!(estimate.chunks, (48usize*48/2).div_ceil(24));
+        // No preamble delays in the default profile, so every step is one 1/10 s sleep
+        assert_eq!(estimate.duration, Duration::from_millis(100)*(plan.steps.len() as u32));
+
+        let faster = SendOSCOpts { msgs_per_second: 20.0, ..options.clone() };
+        assert_eq!(plan.estimate(&faster).duration, estimate.duration/2);
+    }
+
+    #[test]
+    fn bad_width() {
+        assert!(plan_send(&[0, 1, 2], &gray_palette(4), 2, &options()).is_err());
+        assert!(plan_send(&[0, 1, 2], &gray_palette(4), 0, &options()).is_err());
+    }
 }
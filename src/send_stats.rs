@@ -0,0 +1,162 @@
+// Summaries of finished sends, so that it's possible to see afterwards how long things actually
+// took compared to the estimate, and what rate we really got.
+
+use crate::AppMessage;
+use crate::i18n;
+use crate::utility::duration_to_string;
+
+use fltk::{prelude::*, window::Window, group::Flex, button::Button, text};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::time::Duration;
+
+const MAX_SUMMARIES: usize = 20;
+
+#[derive(Debug, Clone)]
+pub struct SendSummary {
+    pub number: usize,
+    pub cancelled: bool,
+    pub elapsed: Duration,
+    pub eta: Duration,
+    pub messages: usize, // OSC messages, each parameter update is one
+    pub chunks: usize,
+    pub packed_bytes: usize,
+    pub sent_bytes: usize, // Pixel data bytes after compression
+}
+
+struct SummaryHistory {
+    sends: usize,
+    summaries: VecDeque<SendSummary>,
+}
+
+static SUMMARY_HISTORY: Mutex<SummaryHistory> = Mutex::new(SummaryHistory {
+    sends: 0,
+    summaries: VecDeque::new(),
+});
+
+impl SendSummary {
+    pub fn description(&self) -> String {
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        format!(
+            "Send #{}{}\n\
+             Elapsed: {} (ETA was {})\n\
+             Messages sent: {} ({} chunks)\n\
+             Bytes: {} sent, {} packed ({:.1}% compression ratio)\n\
+             Average rate: {:.1} messages/s, {:.1} chunks/s, {:.1} bytes/s",
+            self.number, if self.cancelled { " (cancelled)" } else { "" },
+            duration_to_string(self.elapsed), duration_to_string(self.eta),
+            self.messages, self.chunks,
+            self.sent_bytes, self.packed_bytes,
+            ((self.sent_bytes as f64) / (self.packed_bytes.max(1) as f64))*100.0,
+            (self.messages as f64)/secs, (self.chunks as f64)/secs, (self.sent_bytes as f64)/secs,
+        )
+    }
+}
+
+// Hands out the number for the next send
+pub fn next_number() -> usize {
+    match SUMMARY_HISTORY.lock() {
+        Ok(mut history) => {
+            history.sends += 1;
+            history.sends
+        },
+        Err(err) => {
+            warn!("Couldn't lock send summary history: {err}");
+            0
+        },
+    }
+}
+
+pub fn record(summary: SendSummary) {
+    info!("{}", summary.description());
+    match SUMMARY_HISTORY.lock() {
+        Ok(mut history) => {
+            history.summaries.push_back(summary);
+            if history.summaries.len() > MAX_SUMMARIES {
+                history.summaries.pop_front();
+            }
+        },
+        Err(err) => warn!("Couldn't lock send summary history: {err}"),
+    }
+}
+
+fn summaries() -> Vec<SendSummary> {
+    match SUMMARY_HISTORY.lock() {
+        Ok(history) => history.summaries.iter().cloned().collect(),
+        Err(err) => {
+            warn!("Couldn't lock send summary history: {err}");
+            Vec::new()
+        },
+    }
+}
+
+// The most recent send, if any
+pub fn last() -> Option<SendSummary> {
+    match SUMMARY_HISTORY.lock() {
+        Ok(history) => history.summaries.back().cloned(),
+        Err(err) => {
+            warn!("Couldn't lock send summary history: {err}");
+            None
+        },
+    }
+}
+
+// Used from the send thread once it's done
+pub fn show_summary(appmsg: &mpsc::Sender<AppMessage>, summary: &SendSummary) -> Result<(), Box<dyn Error>> {
+    let description = summary.description();
+    appmsg.send(AppMessage::CreateWindow(
+        450, 180, "Send finished".to_string(),
+        Box::new(move |win| -> Result<(), Box<dyn Error>> {
+            win.set_callback(|win| {
+                fltk::app::delete_widget(win.clone());
+            });
+
+            let mut col = Flex::default_fill().column();
+            col.set_margin(10);
+            let mut text_frame = fltk::frame::Frame::default_fill().with_label(&description);
+            text_frame.set_align(fltk::enums::Align::Left | fltk::enums::Align::Inside);
+            let mut ok_btn = i18n::labeled(Button::default(), "OK");
+            col.fixed(&ok_btn, 30);
+            col.end();
+
+            ok_btn.set_callback({
+                let win = win.clone();
+                move |_| fltk::app::delete_widget(win.clone())
+            });
+
+            Ok(())
+        })
+    ))?;
+    fltk::app::awake();
+
+    Ok(())
+}
+
+// The last MAX_SUMMARIES sends, newest first. Called from the main thread.
+pub fn show_history_window() {
+    let mut win = i18n::labeled(Window::default().with_size(500, 500), "Send history");
+    win.set_callback(|win| {
+        fltk::app::delete_widget(win.clone());
+    });
+    win.make_resizable(true);
+
+    let mut col = Flex::default_fill().column();
+    col.set_margin(10);
+
+    let summaries = summaries();
+    let mut buf = text::TextBuffer::default();
+    if summaries.is_empty() {
+        buf.set_text("Nothing has been sent yet");
+    } else {
+        let descriptions: Vec<String> = summaries.iter().rev().map(|s| s.description()).collect();
+        buf.set_text(&descriptions.join("\n\n"));
+    }
+    let mut display = text::TextDisplay::default_fill();
+    display.set_buffer(buf);
+
+    col.end();
+    win.end();
+    win.show();
+}
@@ -0,0 +1,348 @@
+use crate::checksum;
+use crate::osc_config;
+use crate::config;
+use crate::i18n;
+use crate::protocol_profile::{self, ProtocolProfile};
+use crate::receiver_sim::ReceiverParams;
+
+use fltk::{prelude::*, window::Window, group::Flex, valuator::HorValueSlider, button::Button, menu, dialog};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+// Settle times for the individual steps of the preamble that sets up the shader before the pixel
+// data gets sent. Some steps (like reset and changing the BPP) might need longer than the pixel
+// chunks. None means we just use the regular sleep derived from the OSC message rate.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PreambleDelays {
+    pub clk_reset: Option<Duration>,
+    pub reset: Option<Duration>,
+    pub compression: Option<Duration>,
+    pub bitdepth: Option<Duration>,
+    pub palette: Option<Duration>, // Used for every step of setting up the palette (or grayscale mode)
+    pub reset_clear: Option<Duration>,
+}
+
+pub const DEFAULT_PREFIX: &'static str = "/avatar/parameters/PixelSendCRT";
+pub const DEFAULT_BYTES_PER_SEND: usize = 24;
+
+// The smallest amount of data parameters we can work with. The SETPIXEL command takes 7 bytes.
+pub const MIN_BYTES_PER_SEND: usize = 7;
+
+// PixelSendCRT names its data parameters V0-V9, then VA, VB, and so on
+pub fn default_data_param(n: usize) -> String {
+    assert!(n < 36);
+    let n = n as u8;
+    let c = if n <= 9 { b'0' + n } else { b'A' + (n - 10) };
+    format!("V{}", c as char)
+}
+
+// Describes the receiving shader and how to talk to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderProfile {
+    pub name: String,
+    pub prefix: String, // OSC address prefix, the parameter names get appended to this
+    pub clk_param: String,
+    pub reset_param: String,
+    pub data_params: Vec<String>, // One parameter per byte we can send at a time
+    pub preamble_delays: PreambleDelays,
+    // A parameter the avatar changes for every chunk it has received, which VRChat then reports
+    // back to us. Only needed for the adaptive send rate.
+    pub ack_param: Option<String>,
+    // Bool parameter marking a chunk as a checksum, for shaders that support them (see checksum.rs)
+    pub checksum_param: Option<String>,
+    // Int parameter the avatar reports the checksum outcome on. Without one the checksums still
+    // get sent, but nothing gets sent again.
+    pub checksum_result_param: Option<String>,
+    pub chunks_per_checksum: usize,
+    // Bool parameter for asking the shader what it can do (see handshake.rs)
+    pub query_param: Option<String>,
+    // Command bytes and control pixels, for shader revisions that moved them around
+    pub protocol: ProtocolProfile,
+    // Experimental: pack several bytes into each data parameter, if the protocol says the shader
+    // can unpack them. Same chunks, fewer messages.
+    pub pack_bytes: bool,
+}
+
+impl Default for ShaderProfile {
+    fn default() -> Self {
+        ShaderProfile {
+            name: "PixelSendCRT".to_string(),
+            prefix: DEFAULT_PREFIX.to_string(),
+            clk_param: "CLK".to_string(),
+            reset_param: "Reset".to_string(),
+            data_params: (0..DEFAULT_BYTES_PER_SEND).map(default_data_param).collect(),
+            preamble_delays: Default::default(),
+            ack_param: None,
+            checksum_param: None,
+            checksum_result_param: None,
+            chunks_per_checksum: checksum::DEFAULT_CHUNKS_PER_CHECKSUM,
+            query_param: None,
+            protocol: Default::default(),
+            pack_bytes: false,
+        }
+    }
+}
+
+impl ShaderProfile {
+    pub fn bytes_per_send(&self) -> usize {
+        self.data_params.len()
+    }
+
+    // Bytes per data parameter, as actually sent
+    pub fn bytes_per_int(&self) -> usize {
+        if self.pack_bytes { self.protocol.bytes_per_int.clamp(1, 4) as usize } else { 1 }
+    }
+
+    // The ints for a chunk of data, over as many of the data parameters as needed
+    pub fn pack_data(&self, data: &[u8]) -> Vec<i32> {
+        let bytes_per_int = self.bytes_per_int();
+        let mut padded = data.to_vec();
+        padded.resize(self.bytes_per_send(), 0);
+        padded.chunks(bytes_per_int)
+            .map(|bytes| bytes.iter().rev().fold(0u32, |acc, &b| (acc << 8) | (b as u32)) as i32)
+            .collect()
+    }
+
+    pub fn receiver_params(&self) -> ReceiverParams {
+        ReceiverParams {
+            clk_param: self.clk_param.clone(),
+            reset_param: self.reset_param.clone(),
+            checksum_param: self.checksum_param.clone(),
+            data_params: self.data_params.clone(),
+            bytes_per_int: self.bytes_per_int(),
+        }
+    }
+
+    pub fn address(&self, param: &str) -> String {
+        format!("{}/{}", self.prefix, param)
+    }
+
+    pub fn description(&self) -> String {
+        format!("{}\n{} ({} bytes per send, protocol {})",
+                self.name, self.prefix, self.bytes_per_send(), self.protocol.name)
+    }
+}
+
+fn delay_slider(
+    label: &str,
+    profile: &Rc<RefCell<ShaderProfile>>,
+    field: fn(&mut PreambleDelays) -> &mut Option<Duration>,
+) -> HorValueSlider {
+    let mut slider = HorValueSlider::default().with_label(label);
+    slider.set_range(0.0, 2000.0);
+    slider.set_step(10.0, 1);
+    slider.set_value(
+        field(&mut profile.borrow_mut().preamble_delays)
+            .map_or(0.0, |dur| dur.as_secs_f64() * 1000.0)
+    );
+    slider.set_callback({
+        let profile = Rc::clone(profile);
+        move |s| {
+            let ms = s.value();
+            *field(&mut profile.borrow_mut().preamble_delays) =
+                if ms > 0.0 { Some(Duration::from_secs_f64(ms / 1000.0)) } else { None };
+        }
+    });
+    slider
+}
+
+// Opens a window for editing the shader profile. Changes take effect directly in the shared profile.
+// Should only be called from the main thread.
+pub fn show_profile_window(profile: &Rc<RefCell<ShaderProfile>>) {
+    let mut win = i18n::labeled(Window::default().with_size(400, 1080), "Shader profile");
+    win.set_callback(|win| {
+        fltk::app::delete_widget(win.clone());
+    });
+
+    let mut col = Flex::default_fill().column();
+    col.set_margin(20);
+    col.set_spacing(25);
+
+    let mut info_frame = fltk::frame::Frame::default_fill()
+        .with_label(&profile.borrow().description());
+    info_frame.set_frame(fltk::enums::FrameType::DownBox);
+    col.fixed(&info_frame, 50);
+
+    let mut scan_btn = i18n::labeled(Button::default(), "Scan avatar OSC configs");
+    col.fixed(&scan_btn, 30);
+    let mut detected_choice = i18n::labeled(menu::Choice::default(), "Detected profiles");
+    col.fixed(&detected_choice, 30);
+    detected_choice.deactivate();
+
+    // The profiles we found when scanning, in the same order as in detected_choice
+    let detected: Rc<RefCell<Vec<ShaderProfile>>> = Rc::new(RefCell::new(Vec::new()));
+
+    scan_btn.set_callback({
+        let detected = Rc::clone(&detected);
+        let mut detected_choice = detected_choice.clone();
+        move |_| {
+            let Some(dir) = osc_config::osc_config_dir()
+                .filter(|dir| dir.is_dir())
+                .or_else(|| {
+                    let mut nfc = dialog::NativeFileChooser::new(dialog::FileDialogType::BrowseDir);
+                    nfc.set_title("Couldn't find the VRChat OSC folder, please select it");
+                    nfc.show();
+                    let dir = nfc.filename();
+                    if dir.as_os_str().is_empty() { None } else { Some(dir) }
+                })
+            else {
+                info!("No OSC config folder selected/cancelled");
+                return;
+            };
+
+            let profiles = osc_config::scan_osc_configs(&dir);
+            detected_choice.clear();
+            if profiles.is_empty() {
+                dialog::alert_default(&format!("{} {dir:?}", i18n::tr("No PixelSendCRT-style parameters found in")));
+                detected_choice.deactivate();
+            } else {
+                for p in &profiles {
+                    // Escape '/' so the menu doesn't turn it into submenus
+                    detected_choice.add_choice(&p.name.replace("/", "\\/"));
+                }
+                detected_choice.activate();
+            }
+            *detected.borrow_mut() = profiles;
+        }
+    });
+
+    detected_choice.set_callback({
+        let profile = Rc::clone(profile);
+        let detected = Rc::clone(&detected);
+        let mut info_frame = info_frame.clone();
+        move |c| {
+            let Some(p) = usize::try_from(c.value()).ok().and_then(|i| detected.borrow().get(i).cloned()) else {
+                return;
+            };
+            // Keep our delays, ack parameter, checksum interval and protocol, those aren't part of the
+            // avatar config
+            let mut profile = profile.borrow_mut();
+            *profile = ShaderProfile {
+                preamble_delays: profile.preamble_delays.clone(),
+                ack_param: profile.ack_param.clone(),
+                chunks_per_checksum: profile.chunks_per_checksum,
+                protocol: profile.protocol.clone(),
+                pack_bytes: profile.pack_bytes,
+                ..p
+            };
+            info!("Using shader profile {profile:?}");
+            info_frame.set_label(&profile.description());
+        }
+    });
+
+    // Built-in plus whatever is in the protocols folder, read every time the window opens so that new
+    // files show up without restarting
+    let protocols = protocol_profile::available_protocols(config::protocols_dir());
+    let mut protocol_choice = i18n::labeled(menu::Choice::default(), "Protocol");
+    for p in &protocols {
+        protocol_choice.add_choice(&p.name.replace("/", "\\/"));
+    }
+    protocol_choice.set_value(protocols.iter().position(|p| *p == profile.borrow().protocol).map_or(-1, |i| i as i32));
+    if let Some(dir) = config::protocols_dir() {
+        protocol_choice.set_tooltip(&format!("Protocol files (*.toml) go in {}", dir.display()));
+    }
+    col.fixed(&protocol_choice, 30);
+
+    let mut pack_toggle = i18n::labeled(fltk::button::CheckButton::default(), "Pack bytes into ints (experimental)");
+    pack_toggle.set_checked(profile.borrow().pack_bytes);
+    pack_toggle.set_tooltip("Needs a protocol with bytes_per_int above 1");
+    if profile.borrow().protocol.bytes_per_int <= 1 {
+        pack_toggle.deactivate();
+    }
+    col.fixed(&pack_toggle, 30);
+    pack_toggle.set_callback({
+        let profile = Rc::clone(profile);
+        move |t| profile.borrow_mut().pack_bytes = t.is_checked()
+    });
+
+    protocol_choice.set_callback({
+        let profile = Rc::clone(profile);
+        let mut info_frame = info_frame.clone();
+        let mut pack_toggle = pack_toggle.clone();
+        move |c| {
+            let Some(p) = usize::try_from(c.value()).ok().and_then(|i| protocols.get(i).cloned()) else {
+                return;
+            };
+            info!("Using protocol {p:?}");
+            let mut profile = profile.borrow_mut();
+            profile.protocol = p;
+            info_frame.set_label(&profile.description());
+            if profile.protocol.bytes_per_int > 1 { pack_toggle.activate() } else { pack_toggle.deactivate() }
+        }
+    });
+
+    let mut text_frame = i18n::labeled(fltk::frame::Frame::default_fill(), "Preamble step delays in ms\n(0 = same as pixel chunks)");
+    text_frame.set_label_size(14);
+    col.fixed(&text_frame, 40);
+
+    let sliders = [
+        delay_slider("Reset CLK",           profile, |d| &mut d.clk_reset),
+        delay_slider("Reset pixel pos",     profile, |d| &mut d.reset),
+        delay_slider("Set compression",     profile, |d| &mut d.compression),
+        delay_slider("Set BPP",             profile, |d| &mut d.bitdepth),
+        delay_slider("Palette/color mode",  profile, |d| &mut d.palette),
+        delay_slider("Clear reset bit",     profile, |d| &mut d.reset_clear),
+    ];
+    for slider in &sliders {
+        col.fixed(slider, 30);
+    }
+
+    let mut ack_input = i18n::labeled(fltk::input::Input::default(), "Ack parameter (for adaptive rate)");
+    ack_input.set_align(fltk::enums::Align::TopLeft);
+    ack_input.set_value(profile.borrow().ack_param.as_deref().unwrap_or(""));
+    ack_input.set_trigger(fltk::enums::CallbackTrigger::Changed);
+    ack_input.set_callback({
+        let profile = Rc::clone(profile);
+        move |i| {
+            let value = i.value();
+            profile.borrow_mut().ack_param = if value.trim().is_empty() { None } else { Some(value.trim().to_string()) };
+        }
+    });
+    col.fixed(&ack_input, 30);
+
+    let param_input = |label: &str, field: fn(&mut ShaderProfile) -> &mut Option<String>| {
+        let mut input = fltk::input::Input::default().with_label(label);
+        input.set_align(fltk::enums::Align::TopLeft);
+        input.set_value(field(&mut profile.borrow_mut()).as_deref().unwrap_or(""));
+        input.set_trigger(fltk::enums::CallbackTrigger::Changed);
+        input.set_callback({
+            let profile = Rc::clone(profile);
+            move |i| {
+                let value = i.value();
+                *field(&mut profile.borrow_mut()) = if value.trim().is_empty() { None } else { Some(value.trim().to_string()) };
+            }
+        });
+        input
+    };
+    let checksum_input = param_input("Checksum parameter (empty = not supported)", |p| &mut p.checksum_param);
+    col.fixed(&checksum_input, 30);
+    let checksum_result_input = param_input("Checksum result parameter", |p| &mut p.checksum_result_param);
+    col.fixed(&checksum_result_input, 30);
+
+    let mut checksum_slider = i18n::labeled(HorValueSlider::default(), "Chunks per checksum");
+    checksum_slider.set_range(1.0, 256.0);
+    checksum_slider.set_step(1.0, 1);
+    checksum_slider.set_value(profile.borrow().chunks_per_checksum as f64);
+    checksum_slider.set_callback({
+        let profile = Rc::clone(profile);
+        move |s| profile.borrow_mut().chunks_per_checksum = s.value() as usize
+    });
+    col.fixed(&checksum_slider, 30);
+
+    let query_input = param_input("Query parameter (empty = not supported)", |p| &mut p.query_param);
+    col.fixed(&query_input, 30);
+
+    let mut close_btn = i18n::labeled(Button::default(), "Close");
+    col.fixed(&close_btn, 40);
+    close_btn.set_callback({
+        let win = win.clone();
+        move |_| {
+            fltk::app::delete_widget(win.clone());
+        }
+    });
+
+    col.end();
+    win.end();
+    win.show();
+}
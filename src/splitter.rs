@@ -0,0 +1,57 @@
+// Draggable divider for Flex rows. Flex has no way for the user to resize things, so this is a
+// thin frame sitting between two children that changes the fixed width of the one to its right
+// when dragged.
+
+use fltk::{prelude::*, app, draw, enums::{Cursor, Event, FrameType}, frame::Frame, group::Flex};
+
+pub const WIDTH: i32 = 6;
+
+// What's left for the stretchy parts of the row (the preview) no matter how wide things get dragged
+const MIN_REST: i32 = 200;
+
+// Creates the divider in the row currently being built, call attach() on it once the panel to the
+// right of it exists
+pub fn new(row: &mut Flex) -> Frame {
+    let mut splitter = Frame::default_fill();
+    splitter.set_frame(FrameType::FlatBox);
+    row.fixed(&splitter, WIDTH);
+    splitter
+}
+
+// on_resize gets the new width of target after each drag step
+pub fn attach<W, F>(splitter: &mut Frame, row: &Flex, target: &W, min_width: i32, mut on_resize: F)
+where
+    W: WidgetExt + Clone + 'static,
+    F: FnMut(i32) + 'static,
+{
+    let mut row = row.clone();
+    let target = target.clone();
+    let mut drag_start = (0, 0);
+    splitter.handle(move |_, ev| match ev {
+        Event::Enter => {
+            draw::set_cursor(Cursor::WE);
+            true
+        },
+        Event::Leave => {
+            draw::set_cursor(Cursor::Default);
+            true
+        },
+        Event::Push => {
+            drag_start = (app::event_x(), target.w());
+            true
+        },
+        Event::Drag => {
+            // Dragging to the left makes the panel wider
+            let max_width = (row.w() - MIN_REST).max(min_width);
+            let width = (drag_start.1 - (app::event_x() - drag_start.0)).clamp(min_width, max_width);
+            if width != target.w() {
+                row.fixed(&target, width);
+                row.layout();
+                on_resize(width);
+                row.redraw();
+            }
+            true
+        },
+        _ => false,
+    });
+}
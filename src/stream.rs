@@ -0,0 +1,452 @@
+// Streaming mode: keeps grabbing frames from a source (the frames of an animated GIF, the screen,
+// or NDI), runs them through the pipeline and sends them one after the other, for a crude live
+// feed. The shader writes the pixels in order from the top, so as long as the palette stays the
+// same only the rows down to the last one that changed need to go out. Whatever is below that is
+// still on the CRT from the frame before. There's no webcam source yet, nothing we depend on can
+// talk to cameras, but anything that implements FrameSource can be streamed.
+//
+// Animations (GIFs, video clips) have all their frames up front, so they can get one palette for
+// the whole thing (see animation_palette), which only has to go out with the first frame.
+
+use crate::{AppMessage, ImageSettings, PipelineCache, ProcessedImage};
+use crate::capture;
+use crate::i18n;
+use crate::dither_mask::DitherMask;
+use crate::ndi;
+use crate::palette;
+use crate::quantizer;
+use crate::send_osc::{self, SendOSCOpts};
+use crate::utility::error_alert;
+
+use fltk::{prelude::*, app, button::{Button, CheckButton}, dialog, enums::Align, frame::Frame, group::Flex, input::Input, menu::Choice, window::Window};
+use image::{imageops, AnimationDecoder, RgbaImage};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long to wait before looking again when nothing changed
+const IDLE_WAIT: Duration = Duration::from_millis(200);
+// Frames that go into a global palette at most, spread out over the animation
+const MAX_PALETTE_FRAMES: usize = 64;
+
+pub trait FrameSource: Send {
+    // The frame that should be showing right now
+    fn frame(&mut self) -> Result<RgbaImage, Box<dyn Error>>;
+
+    // All the frames, for sources that have them up front
+    fn all_frames(&self) -> Option<Vec<&RgbaImage>> {
+        None
+    }
+}
+
+// Plays back a sequence of frames (each with how long it shows for) in real time, looping. Frames
+// that go by while a frame is being sent just get skipped.
+pub struct AnimationSource {
+    frames: Vec<(RgbaImage, Duration)>,
+    total: Duration,
+    start: Instant,
+}
+
+impl AnimationSource {
+    pub fn new(frames: Vec<(RgbaImage, Duration)>) -> Result<AnimationSource, Box<dyn Error>> {
+        if frames.is_empty() {
+            return Err("No frames".into());
+        }
+        let total = frames.iter().map(|(_, delay)| *delay).sum();
+        Ok(AnimationSource { frames: frames, total: total, start: Instant::now() })
+    }
+
+    pub fn open_gif(path: &Path) -> Result<AnimationSource, Box<dyn Error>> {
+        let file = File::open(path).map_err(|err| format!("Couldn't open {path:?}: {err}"))?;
+        let decoder = image::codecs::gif::GifDecoder::new(BufReader::new(file))?;
+        let frames: Vec<(RgbaImage, Duration)> = decoder.into_frames()
+            .collect_frames()?
+            .into_iter()
+            .map(|frame| {
+                let (numer, denom) = frame.delay().numer_denom_ms();
+                // Browsers treat 0 as "as fast as possible" too, but that's not very useful here
+                let delay = Duration::from_secs_f64((numer as f64)/(denom.max(1) as f64)/1000.0).max(Duration::from_millis(10));
+                (frame.into_buffer(), delay)
+            })
+            .collect();
+        info!("Loaded {} frames from {path:?}", frames.len());
+        AnimationSource::new(frames).map_err(|err| format!("{path:?}: {err}").into())
+    }
+}
+
+impl FrameSource for AnimationSource {
+    fn frame(&mut self) -> Result<RgbaImage, Box<dyn Error>> {
+        let mut t = Duration::from_secs_f64(self.start.elapsed().as_secs_f64() % self.total.as_secs_f64());
+        for (image, delay) in &self.frames {
+            if t < *delay {
+                return Ok(image.clone());
+            }
+            t -= *delay;
+        }
+        // Rounding, we're at the very end
+        self.frames.last().map(|(image, _)| image.clone()).ok_or("No frames".into())
+    }
+
+    fn all_frames(&self) -> Option<Vec<&RgbaImage>> {
+        Some(self.frames.iter().map(|(image, _)| image).collect())
+    }
+}
+
+pub struct ScreenSource {
+    region: Option<(u32, u32, u32, u32)>, // x, y, w, h
+}
+
+impl ScreenSource {
+    pub fn new(region: Option<(u32, u32, u32, u32)>) -> ScreenSource {
+        ScreenSource { region: region }
+    }
+}
+
+impl FrameSource for ScreenSource {
+    fn frame(&mut self) -> Result<RgbaImage, Box<dyn Error>> {
+        let image = capture::capture_screen()?;
+        Ok(match self.region {
+            Some((x, y, w, h)) => {
+                if x >= image.width() || y >= image.height() {
+                    return Err(format!("Region {x},{y} is outside of the screen ({}x{})", image.width(), image.height()).into());
+                }
+                imageops::crop_imm(&image, x, y, w, h).to_image()
+            },
+            None => image,
+        })
+    }
+}
+
+// "x,y,w,h", or empty for the whole screen
+pub fn parse_region(s: &str) -> Result<Option<(u32, u32, u32, u32)>, String> {
+    if s.trim().is_empty() {
+        return Ok(None);
+    }
+    let parts = s.split(',')
+        .map(|part| part.trim().parse::<u32>())
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|err| format!("Couldn't parse region {s:?}: {err}"))?;
+    match parts[..] {
+        [x, y, w, h] if w > 0 && h > 0 => Ok(Some((x, y, w, h))),
+        _ => Err(format!("Region {s:?} should be x,y,w,h")),
+    }
+}
+
+// What the avatar is showing, as far as we know
+struct SentFrame {
+    indexes: Vec<u8>,
+    palette: Vec<quantizr::Color>,
+    width: u32,
+}
+
+// How many rows from the top need to go out to get from previous to img. All of them if the size
+// or the palette changed.
+fn rows_to_send(previous: Option<&SentFrame>, img: &ProcessedImage) -> u32 {
+    let Some(previous) = previous.filter(|p| p.width == img.width && p.indexes.len() == img.indexes.len() && p.palette == img.palette) else {
+        return img.height;
+    };
+    let width = img.width as usize;
+    img.indexes.chunks_exact(width)
+        .zip(previous.indexes.chunks_exact(width))
+        .rposition(|(a, b)| a != b)
+        .map_or(0, |row| (row + 1) as u32)
+}
+
+// Point the indexes at the closest colors of palette instead, so that the palette stays the same
+// from frame to frame
+fn lock_palette(img: &mut ProcessedImage, palette: &[quantizr::Color]) {
+    if img.palette == palette {
+        return;
+    }
+    let mapping = quantizer::palette_mapping(&img.palette, palette);
+    for i in img.indexes.iter_mut() {
+        *i = mapping[*i as usize];
+    }
+    img.palette = palette.to_vec();
+}
+
+// One palette for all the frames: the frames scaled like they will be, and quantized together as
+// one tall image. Every frame then gets remapped to it (as a locked palette), so the colors don't
+// shift from frame to frame.
+fn animation_palette(frames: &[&RgbaImage], settings: &ImageSettings) -> Result<Vec<palette::Rgba>, Box<dyn Error>> {
+    let step = frames.len().div_ceil(MAX_PALETTE_FRAMES).max(1);
+    let mut bytes: Vec<u8> = Vec::new();
+    let (mut width, mut height) = (0u32, 0u32);
+    for frame in frames.iter().step_by(step) {
+        let mut cache = PipelineCache::default();
+        let scaled = cache.scaled(frame, settings)?;
+        if height > 0 && scaled.width != width {
+            return Err("The frames aren't all the same size".into());
+        }
+        width = scaled.width;
+        height += scaled.height;
+        bytes.extend_from_slice(&scaled.bytes);
+    }
+
+    let (_, palette) = crate::quantize_image(
+        &bytes, width, height,
+        settings.palette_colors(),
+        0.0,
+        settings.reorder_palette,
+        &settings.quantizer_type,
+        &settings.quantizr,
+        &DitherMask::default(),
+        &settings.forced_colors,
+        None,
+        &settings.color_space,
+    )?;
+    Ok(palette.iter().map(palette::to_rgba).collect())
+}
+
+// The stop flag of the stream that is running, if any
+static STREAMING: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+// Returns false if nothing was streaming
+pub fn stop() -> bool {
+    let flag = match STREAMING.lock() {
+        Ok(mut streaming) => streaming.take(),
+        Err(err) => {
+            warn!("Couldn't lock stream state: {err}");
+            None
+        },
+    };
+    let Some(flag) = flag else {
+        return false;
+    };
+    info!("Stopping the stream");
+    flag.store(true, Ordering::Relaxed);
+    send_osc::cancel_current();
+    true
+}
+
+fn set_status(text: &str) {
+    if let Some(mut frame) = app::widget_from_id::<Frame>("stream_status_frame") {
+        frame.set_label(text);
+        frame.redraw();
+        fltk::app::awake();
+    }
+}
+
+// Keeps sending frames from source until stopped, or until a send gets cancelled or fails. With
+// same_palette every frame gets mapped to the palette of the first one, which costs some color
+// but means only the changed rows have to be sent. With global_palette sources that have all
+// their frames up front get a palette made from all of them instead (unless the palette is
+// locked already).
+pub fn start(
+    appmsg: &mpsc::Sender<AppMessage>,
+    mut source: Box<dyn FrameSource>,
+    settings: ImageSettings,
+    options: SendOSCOpts,
+    same_palette: bool,
+    global_palette: bool,
+) -> Result<(), String> {
+    stop();
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    match STREAMING.lock() {
+        Ok(mut streaming) => *streaming = Some(Arc::clone(&stop_flag)),
+        Err(err) => return Err(format!("Couldn't lock stream state: {err}")),
+    }
+
+    let settings = ImageSettings { no_quantize: false, ..settings };
+    let options = SendOSCOpts { confirm: false, quiet: true, ..options };
+    let appmsg = appmsg.clone();
+    thread::spawn(move || {
+        let mut previous: Option<SentFrame> = None;
+        let mut frames = 0usize;
+        let mut skipped = 0usize;
+
+        let mut settings = settings;
+        if let Some(all_frames) = source.all_frames().filter(|_| global_palette && settings.locked_palette.is_none()) {
+            set_status(&i18n::tr("Making a palette from {} frames...").replace("{}", &all_frames.len().to_string()));
+            match animation_palette(&all_frames, &settings) {
+                Ok(palette) => {
+                    info!("Global palette of {} colors for {} frames", palette.len(), all_frames.len());
+                    settings.locked_palette = Some(palette);
+                },
+                // Still works, just with the palette of the first frame
+                Err(err) => warn!("Couldn't make a global palette: {err}"),
+            }
+        }
+
+        while !stop_flag.load(Ordering::Relaxed) {
+            let result = || -> Result<bool, Box<dyn Error>> {
+                let image = source.frame()?;
+                // Every frame is a new image, so there's nothing to reuse from a cache
+                let (mut img, _) = crate::process_image(&image, None, &settings, &mut PipelineCache::default())?;
+                if let Some(previous) = previous.as_ref().filter(|_| same_palette) {
+                    lock_palette(&mut img, &previous.palette);
+                }
+
+                let rows = rows_to_send(previous.as_ref(), &img);
+                if rows == 0 {
+                    skipped += 1;
+                    set_status(&i18n::tr("Frame {}: no change ({} skipped)").replacen("{}", &frames.to_string(), 1).replacen("{}", &skipped.to_string(), 1));
+                    return Ok(false);
+                }
+
+                let keep_palette = previous.as_ref().is_some_and(|p| p.palette == img.palette);
+                let len = (rows as usize) * (img.width as usize);
+                let status = if keep_palette { i18n::tr("Frame {}: sending {}/{} rows") } else { i18n::tr("Frame {}: sending {}/{} rows and the palette") };
+                set_status(&status.replacen("{}", &frames.to_string(), 1).replacen("{}", &rows.to_string(), 1).replacen("{}", &img.height.to_string(), 1));
+                let handle = send_osc::send_osc(&appmsg, &img.indexes[..len], &img.palette, img.width, rows,
+                                                SendOSCOpts { keep_palette: keep_palette, skip_query: previous.is_some(), ..options.clone() })?;
+                if !handle.join().map_err(|_| "Send thread panicked")? {
+                    return Err("Send cancelled".into());
+                }
+
+                frames += 1;
+                previous = Some(SentFrame { indexes: img.indexes, palette: img.palette, width: img.width });
+                Ok(true)
+            }();
+
+            match result {
+                Ok(true) => (),
+                Ok(false) => thread::sleep(IDLE_WAIT),
+                Err(err) => {
+                    if !stop_flag.load(Ordering::Relaxed) {
+                        error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Stream stopped")));
+                    }
+                    break;
+                },
+            }
+        }
+
+        if let Ok(mut streaming) = STREAMING.lock() {
+            if streaming.as_ref().is_some_and(|flag| Arc::ptr_eq(flag, &stop_flag)) {
+                *streaming = None;
+            }
+        }
+        set_status(&i18n::tr("Stopped after {} frames").replace("{}", &frames.to_string()));
+        info!("Stream finished after {frames} frames");
+    });
+
+    Ok(())
+}
+
+const SOURCE_GIF: &'static str = "Animated GIF";
+const SOURCE_SCREEN: &'static str = "Screen";
+const SOURCE_NDI: &'static str = "NDI";
+
+fn set_active<W: WidgetExt>(widget: &mut W, active: bool) {
+    if active {
+        widget.activate();
+    } else {
+        widget.deactivate();
+    }
+}
+
+// get_settings reads the processing settings from the main window, get_send_opts the send settings
+pub fn show_stream_window<S, O>(appmsg: &mpsc::Sender<AppMessage>, get_settings: S, get_send_opts: O)
+where
+    S: Fn() -> Result<ImageSettings, String> + 'static,
+    O: Fn() -> Result<SendOSCOpts, String> + 'static,
+{
+    let mut win = i18n::labeled(Window::default().with_size(400, 330), "Stream");
+    win.set_callback(|win| {
+        fltk::app::delete_widget(win.clone());
+    });
+    win.make_resizable(true);
+
+    let mut col = Flex::default_fill().column();
+    col.set_margin(10);
+
+    let mut source_choice = i18n::labeled(Choice::default(), "Source:");
+    source_choice.add_choice(&[SOURCE_GIF, SOURCE_SCREEN, SOURCE_NDI].join("|"));
+    source_choice.set_value(0);
+    col.fixed(&source_choice, 30);
+
+    let mut gif_btn = i18n::labeled(Button::default(), "Pick GIF...");
+    col.fixed(&gif_btn, 30);
+    let mut region_input = i18n::labeled(Input::default(), "Screen region x,y,w,h (empty = all)").with_align(Align::Inside);
+    region_input.deactivate();
+    col.fixed(&region_input, 30);
+    let mut ndi_input = i18n::labeled(Input::default(), "NDI source (empty = first found)").with_align(Align::Inside);
+    ndi_input.deactivate();
+    col.fixed(&ndi_input, 30);
+
+    let mut same_palette_toggle = i18n::labeled(CheckButton::default(), "Keep the first frame's palette (smaller updates)");
+    same_palette_toggle.set_checked(true);
+    col.fixed(&same_palette_toggle, 30);
+    let global_palette_toggle = i18n::labeled(CheckButton::default(), "One palette made from all the frames (GIFs)");
+    global_palette_toggle.set_checked(true);
+    col.fixed(&global_palette_toggle, 30);
+
+    let mut status_frame = Frame::default_fill().with_id("stream_status_frame");
+    status_frame.set_align(Align::Left | Align::Inside | Align::Wrap);
+
+    let button_row = Flex::default_fill().row();
+    let mut start_btn = i18n::labeled(Button::default(), "Start streaming");
+    let mut stop_btn = i18n::labeled(Button::default(), "Stop");
+    button_row.end();
+    col.fixed(&button_row, 40);
+
+    let gif_path: Arc<Mutex<Option<PathBuf>>> = Arc::new(Mutex::new(None));
+
+    source_choice.set_callback({
+        let mut gif_btn = gif_btn.clone();
+        let mut region_input = region_input.clone();
+        let mut ndi_input = ndi_input.clone();
+        move |c| {
+            let choice = c.choice();
+            set_active(&mut gif_btn, choice.as_deref() == Some(SOURCE_GIF));
+            set_active(&mut region_input, choice.as_deref() == Some(SOURCE_SCREEN));
+            set_active(&mut ndi_input, choice.as_deref() == Some(SOURCE_NDI));
+        }
+    });
+
+    gif_btn.set_callback({
+        let gif_path = Arc::clone(&gif_path);
+        let mut status_frame = status_frame.clone();
+        move |_| {
+            let mut nfc = dialog::NativeFileChooser::new(dialog::FileDialogType::BrowseFile);
+            nfc.set_filter("*.gif");
+            nfc.show();
+            let path = nfc.filename();
+            if path.as_os_str().is_empty() {
+                info!("No file selected/cancelled");
+                return;
+            }
+            status_frame.set_label(&path.to_string_lossy());
+            if let Ok(mut gif_path) = gif_path.lock() {
+                *gif_path = Some(path);
+            }
+        }
+    });
+
+    start_btn.set_callback({
+        let appmsg = appmsg.clone();
+        move |_| {
+            match || -> Result<(), String> {
+                let source: Box<dyn FrameSource> = match source_choice.choice().as_deref() {
+                    Some(SOURCE_GIF) => {
+                        let path = gif_path.lock().map_err(|err| format!("Couldn't lock GIF path: {err}"))?.clone()
+                            .ok_or("Pick a GIF first")?;
+                        Box::new(AnimationSource::open_gif(&path).map_err(|err| err.to_string())?)
+                    },
+                    // Finding the source can take a few seconds, but at least the user just asked for it
+                    Some(SOURCE_NDI) => Box::new(ndi::NdiReceiver::connect(ndi_input.value().trim()).map_err(|err| err.to_string())?),
+                    _ => Box::new(ScreenSource::new(parse_region(&region_input.value())?)),
+                };
+                start(&appmsg, source, get_settings()?, get_send_opts()?, same_palette_toggle.is_checked(), global_palette_toggle.is_checked())
+            }() {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't start streaming"))),
+            }
+        }
+    });
+
+    stop_btn.set_callback(|_| {
+        if !stop() {
+            info!("Not streaming");
+        }
+    });
+
+    col.end();
+    win.end();
+    win.show();
+}
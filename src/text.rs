@@ -0,0 +1,233 @@
+// Text mode: renders a message with the bitmap font from banner.rs straight at the output
+// resolution, for quick signs without having to go through an image editor. The result goes in as
+// the source image, so it takes the rest of the pipeline like anything else.
+
+use crate::{AppMessage, BgMessage, ImageSettings};
+use crate::banner::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::i18n;
+use crate::mq;
+use crate::send_osc::SendOSCOpts;
+use crate::utility::error_alert;
+
+use fltk::{prelude::*, button::Button, dialog, enums::{Align, Color}, group::Flex, input::MultilineInput, menu::Choice, window::Window};
+use image::{Rgba, RgbaImage};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::mpsc;
+use strum::VariantNames;
+use strum_macros::{VariantNames, EnumString};
+
+// The size to render at when the main window isn't scaling
+const DEFAULT_SIZE: u32 = 128;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, VariantNames, EnumString)]
+pub enum TextFont {
+    #[default]
+    Small, // The plain 5x7 font
+    Bold,  // 5x7, every column drawn twice
+    Large, // 5x7 at double size
+    Huge,  // 5x7 at four times the size
+}
+
+impl TextFont {
+    fn scale(&self) -> usize {
+        match self {
+            TextFont::Small | TextFont::Bold => 1,
+            TextFont::Large => 2,
+            TextFont::Huge => 4,
+        }
+    }
+
+    fn bold(&self) -> bool {
+        *self == TextFont::Bold
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSettings {
+    pub text: String,
+    pub font: TextFont,
+    pub fg: [u8; 3],
+    pub bg: [u8; 3],
+    pub width: u32,
+    pub height: u32,
+}
+
+// Wraps the text to fit and centers it. Whatever doesn't fit at the bottom gets cut off.
+pub fn render(settings: &TextSettings) -> RgbaImage {
+    let [r, g, b] = settings.bg;
+    let mut image = RgbaImage::from_pixel(settings.width, settings.height, Rgba([r, g, b, 255]));
+    let [r, g, b] = settings.fg;
+    let fg = Rgba([r, g, b, 255]);
+
+    let scale = settings.font.scale();
+    let bold = settings.font.bold() as usize;
+    let glyph_width = GLYPH_WIDTH + bold;
+    let char_advance = glyph_width + 1;
+    let line_advance = GLYPH_HEIGHT + 1;
+    // In font pixels
+    let (width, height) = ((settings.width as usize)/scale, (settings.height as usize)/scale);
+
+    let max_chars = (width + 1)/char_advance;
+    let max_lines = (height + 1)/line_advance;
+    if max_chars == 0 || max_lines == 0 {
+        return image;
+    }
+
+    // Keep the line breaks that were typed in
+    let mut lines: Vec<String> = settings.text.lines()
+        .flat_map(|line| {
+            let wrapped = banner::wrap(line, max_chars);
+            if wrapped.is_empty() { vec![String::new()] } else { wrapped }
+        })
+        .collect();
+    lines.truncate(max_lines);
+    if lines.is_empty() {
+        return image;
+    }
+
+    let text_height = lines.len()*line_advance - 1;
+    let top = (height - text_height)/2;
+    let mut plot = |x: usize, y: usize| {
+        for dy in 0..scale {
+            for dx in 0..scale {
+                image.put_pixel((x*scale + dx) as u32, (y*scale + dy) as u32, fg);
+            }
+        }
+    };
+
+    for (n, line) in lines.iter().enumerate() {
+        let line_chars = line.chars().count();
+        if line_chars == 0 {
+            continue;
+        }
+        let line_width = line_chars*char_advance - 1;
+        let left = (width - line_width)/2;
+        let line_top = top + n*line_advance;
+
+        for (i, c) in line.chars().enumerate() {
+            let glyph_left = left + i*char_advance;
+            for (gx, column) in banner::glyph(c).iter().enumerate() {
+                for gy in 0..GLYPH_HEIGHT {
+                    if (column >> gy) & 1 != 0 {
+                        plot(glyph_left + gx, line_top + gy);
+                        if bold == 1 {
+                            plot(glyph_left + gx + 1, line_top + gy);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    image
+}
+
+fn button_rgb(btn: &Button) -> [u8; 3] {
+    let (r, g, b) = btn.color().to_rgb();
+    [r, g, b]
+}
+
+// get_settings reads the processing settings from the main window, get_send_opts the send settings
+pub fn show_text_window<S, O>(
+    appmsg: &mpsc::Sender<AppMessage>,
+    bg: &mq::MessageQueueSender<BgMessage>,
+    get_settings: S,
+    get_send_opts: O,
+)
+where
+    S: Fn() -> Result<ImageSettings, String> + 'static,
+    O: Fn() -> Result<SendOSCOpts, String> + 'static,
+{
+    let mut win = i18n::labeled(Window::default().with_size(400, 330), "Text");
+    win.set_callback(|win| {
+        fltk::app::delete_widget(win.clone());
+    });
+    win.make_resizable(true);
+
+    let mut col = Flex::default_fill().column();
+    col.set_margin(10);
+
+    let text_input = MultilineInput::default_fill();
+
+    let mut font_choice = i18n::labeled(Choice::default(), "Font:");
+    font_choice.add_choice(&TextFont::VARIANTS.join("|"));
+    font_choice.set_value(0);
+    col.fixed(&font_choice, 30);
+
+    let color_row = Flex::default_fill().row();
+    let mut fg_btn = i18n::labeled(Button::default(), "Text color");
+    fg_btn.set_color(Color::White);
+    fg_btn.set_label_color(Color::Black);
+    let mut bg_btn = i18n::labeled(Button::default(), "Background");
+    bg_btn.set_color(Color::Black);
+    bg_btn.set_label_color(Color::White);
+    color_row.end();
+    col.fixed(&color_row, 30);
+
+    let mut info_frame = i18n::labeled(fltk::frame::Frame::default(), "Rendered at the size set in the main window");
+    info_frame.set_align(Align::Left | Align::Inside | Align::Wrap);
+    col.fixed(&info_frame, 30);
+
+    let button_row = Flex::default_fill().row();
+    let mut show_btn = i18n::labeled(Button::default(), "Show");
+    let mut send_btn = i18n::labeled(Button::default(), "Send");
+    button_row.end();
+    col.fixed(&button_row, 40);
+
+    for btn in [&mut fg_btn, &mut bg_btn] {
+        btn.set_callback(|b| {
+            let color = dialog::color_chooser_with_default(&b.label(), dialog::ColorMode::Byte, b.color().to_rgb());
+            b.set_color(Color::from_rgb(color.0, color.1, color.2));
+            b.redraw();
+        });
+    }
+
+    // Render it into the main window, and send it too if there are send options
+    let bg = bg.clone();
+    let render_and_send = move |options: Option<SendOSCOpts>| -> Result<(), String> {
+        let settings = get_settings()?;
+        let size = if settings.scaling { settings.scale } else { DEFAULT_SIZE };
+        let text_settings = TextSettings {
+            text: text_input.value(),
+            font: TextFont::from_str(&font_choice.choice().unwrap_or_default()).unwrap_or_default(),
+            fg: button_rgb(&fg_btn),
+            bg: button_rgb(&bg_btn),
+            width: size,
+            height: size,
+        };
+
+        // The BG thread handles these in order
+        let msgs = [BgMessage::RenderText(text_settings), BgMessage::UpdateImage(settings)].into_iter()
+            .chain(options.map(BgMessage::SendOSC));
+        for msg in msgs {
+            bg.send(msg).map_err(|err| format!("Couldn't send message to BG thread: {err}"))?;
+        }
+        Ok(())
+    };
+    let render_and_send = Rc::new(render_and_send);
+
+    show_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let render_and_send = Rc::clone(&render_and_send);
+        move |_| {
+            if let Err(err) = render_and_send(None) {
+                error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't show the text")));
+            }
+        }
+    });
+
+    send_btn.set_callback({
+        let appmsg = appmsg.clone();
+        move |_| {
+            match get_send_opts().and_then(|options| render_and_send(Some(options))) {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't send the text"))),
+            }
+        }
+    });
+
+    col.end();
+    win.end();
+    win.show();
+}
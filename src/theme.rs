@@ -0,0 +1,131 @@
+// FLTK scheme and color theme selection. The scheme is the look of the widgets, the theme the
+// colors (FLTK's defaults being the light one). Also the UI scale and font size, for when the
+// small_screen heuristic doesn't cut it (4K displays, tiny laptop screens).
+
+use fltk::{app, prelude::*};
+use std::sync::Mutex;
+
+pub const SCHEMES: [&str; 5] = ["Base", "Gtk", "Gleam", "Plastic", "Oxy"];
+pub const THEMES: [&str; 3] = ["Light", "Dark", "HighContrast"];
+
+pub const DEFAULT_SCHEME: &'static str = "Gleam";
+pub const DEFAULT_THEME: &'static str = "Light";
+
+pub const UI_SCALES: [f32; 6] = [0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+pub const FONT_SIZES: [i32; 6] = [10, 12, 14, 16, 18, 20];
+pub const DEFAULT_UI_SCALE: f32 = 1.0;
+pub const DEFAULT_FONT_SIZE: i32 = 14; // FLTK's own default
+
+// What's currently applied, as (scheme, theme)
+static CURRENT: Mutex<(String, String)> = Mutex::new((String::new(), String::new()));
+
+fn to_scheme(name: &str) -> Option<app::Scheme> {
+    Some(match name {
+        "Base" => app::Scheme::Base,
+        "Gtk" => app::Scheme::Gtk,
+        "Gleam" => app::Scheme::Gleam,
+        "Plastic" => app::Scheme::Plastic,
+        "Oxy" => app::Scheme::Oxy,
+        _ => return None,
+    })
+}
+
+pub fn set_scheme(name: &str) {
+    let name = if to_scheme(name).is_some() {
+        name
+    } else {
+        warn!("Unknown scheme {name:?}, using {DEFAULT_SCHEME}");
+        DEFAULT_SCHEME
+    };
+    app::set_scheme(to_scheme(name).unwrap_or(app::Scheme::Gleam));
+    if let Ok(mut current) = CURRENT.lock() {
+        current.0 = name.to_string();
+    }
+    redraw();
+}
+
+pub fn set_theme(name: &str) {
+    // (background, background2 i.e. input fields, foreground, selection)
+    let (bg, bg2, fg, sel) = match name {
+        "Light" => ((192, 192, 192), (255, 255, 255), (0, 0, 0), (15, 15, 150)),
+        "Dark" => ((50, 50, 54), (30, 30, 32), (225, 225, 225), (70, 110, 190)),
+        "HighContrast" => ((0, 0, 0), (0, 0, 0), (255, 255, 255), (255, 255, 0)),
+        _ => {
+            warn!("Unknown theme {name:?}, using {DEFAULT_THEME}");
+            return set_theme(DEFAULT_THEME);
+        },
+    };
+    app::background(bg.0, bg.1, bg.2);
+    app::background2(bg2.0, bg2.1, bg2.2);
+    app::foreground(fg.0, fg.1, fg.2);
+    app::set_selection_color(sel.0, sel.1, sel.2);
+    if let Ok(mut current) = CURRENT.lock() {
+        current.1 = name.to_string();
+    }
+    redraw();
+}
+
+// The scheme and theme currently in use
+pub fn current() -> (String, String) {
+    let current = CURRENT.lock().map(|c| c.clone()).unwrap_or_default();
+    (
+        if current.0.is_empty() { DEFAULT_SCHEME.to_string() } else { current.0 },
+        if current.1.is_empty() { DEFAULT_THEME.to_string() } else { current.1 },
+    )
+}
+
+// Widgets that set their own colors keep them, everything else follows the new defaults
+fn redraw() {
+    app::redraw();
+}
+
+static UI_SCALE: Mutex<f32> = Mutex::new(DEFAULT_UI_SCALE);
+static FONT_SIZE: Mutex<i32> = Mutex::new(DEFAULT_FONT_SIZE);
+
+pub fn ui_scale() -> f32 {
+    UI_SCALE.lock().map_or(DEFAULT_UI_SCALE, |s| *s)
+}
+
+pub fn font_size() -> i32 {
+    FONT_SIZE.lock().map_or(DEFAULT_FONT_SIZE, |s| *s)
+}
+
+// Scales everything, widgets and fonts alike, on all screens
+pub fn set_ui_scale(scale: f32) {
+    let scale = scale.clamp(0.5, 4.0);
+    for n in 0..app::screen_count() {
+        app::set_screen_scale(n, scale);
+    }
+    if let Ok(mut current) = UI_SCALE.lock() {
+        *current = scale;
+    }
+    redraw();
+}
+
+// Changes the size of the labels that are still at the old default size (the ones that set a size
+// of their own keep it), and the default for everything created from now on
+pub fn set_font_size(size: i32) {
+    let old = font_size();
+    app::set_font_size(size);
+
+    fn walk(widget: &mut fltk::widget::Widget, old: i32, size: i32) {
+        if widget.label_size() == old {
+            widget.set_label_size(size);
+        }
+        if let Some(group) = widget.as_group() {
+            for i in 0..group.children() {
+                if let Some(mut child) = group.child(i) {
+                    walk(&mut child, old, size);
+                }
+            }
+        }
+    }
+    for win in app::windows().unwrap_or_default() {
+        walk(&mut win.as_base_widget(), old, size);
+    }
+
+    if let Ok(mut current) = FONT_SIZE.lock() {
+        *current = size;
+    }
+    redraw();
+}
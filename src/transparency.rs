@@ -0,0 +1,72 @@
+// What happens to the transparent pixels of a source image. Pixels with an alpha below the
+// threshold count as transparent, everything else as fully opaque. By default they just get
+// composited onto the flatten background like before, but they can also be mapped to a palette
+// index of their own, or to the same index as the padding.
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{VariantNames, EnumString};
+
+#[derive(Debug, Clone, Default, PartialEq, VariantNames, EnumString, Serialize, Deserialize)]
+pub enum TransparentMode {
+    #[default]
+    Matte,        // Composite onto the flatten background
+    PaletteIndex, // A dedicated fully transparent palette entry
+    PadColor,     // Whatever index the padding gets
+}
+
+impl TransparentMode {
+    // Whether the transparent pixels get picked out into a mask after scaling, as opposed to just
+    // being composited before it
+    pub fn uses_mask(&self) -> bool {
+        *self != TransparentMode::Matte
+    }
+}
+
+// Snaps alpha to either 0 or 255. A threshold of 0 leaves the alpha channel alone.
+pub fn threshold_alpha(bytes: &mut [u8], threshold: u8) {
+    if threshold == 0 {
+        return;
+    }
+    for px in bytes.chunks_exact_mut(4) {
+        px[3] = if px[3] < threshold { 0 } else { 255 };
+    }
+}
+
+// Picks out the pixels with alpha below the threshold. They get made opaque with the average color
+// of the other pixels, so that they pull the palette around as little as possible before their
+// indexes get overwritten after quantization. None if there were no transparent pixels.
+pub fn take_transparent(bytes: &mut [u8], threshold: u8) -> Option<Vec<bool>> {
+    let mask: Vec<bool> = bytes.chunks_exact(4).map(|px| px[3] < threshold).collect();
+    if !mask.contains(&true) {
+        return None;
+    }
+
+    let mut sum = [0u64; 3];
+    let mut count = 0u64;
+    for (px, _) in bytes.chunks_exact(4).zip(&mask).filter(|(_, &transparent)| !transparent) {
+        for c in 0..3 {
+            sum[c] += px[c] as u64;
+        }
+        count += 1;
+    }
+    let average = sum.map(|s| (s / count.max(1)) as u8);
+
+    for (px, &transparent) in bytes.chunks_exact_mut(4).zip(&mask) {
+        if transparent {
+            px[..3].copy_from_slice(&average);
+            px[3] = 255;
+        }
+    }
+
+    Some(mask)
+}
+
+// Points the masked pixels at index
+pub fn apply_mask(indexes: &mut [u8], mask: &[bool], index: u8) {
+    assert!(indexes.len() == mask.len());
+    for (i, &transparent) in indexes.iter_mut().zip(mask) {
+        if transparent {
+            *i = index;
+        }
+    }
+}
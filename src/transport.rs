@@ -0,0 +1,140 @@
+// How the OSC messages get to the receiver: UDP like VRChat expects, or TCP with SLIP framing (as in
+// OSC 1.1) for relaying through bridges and across networks where UDP gets dropped. Picked per
+// destination, by writing the target as tcp://host:port instead of just host:port.
+
+use rosc::{encoder, OscMessage, OscPacket};
+use std::error::Error;
+use std::io::{ErrorKind, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::time::Duration;
+
+pub const TCP_SCHEME: &'static str = "tcp://";
+pub const UDP_SCHEME: &'static str = "udp://";
+
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Where sends on localhost come from
+pub const LOCAL_SEND_PORT: u16 = 9002;
+
+// How long to wait for the ICMP port unreachable to come back in probe
+pub const PROBE_TIMEOUT: Duration = Duration::from_millis(300);
+// Nothing listens to this, so it's harmless to send to VRChat
+const PROBE_ADDRESS: &'static str = "/oscpixelsender/probe";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Transport {
+    #[default]
+    Udp,
+    Tcp,
+}
+
+// Splits the scheme off an OSC target, no scheme meaning UDP
+pub fn parse_target(target: &str) -> (Transport, &str) {
+    let target = target.trim();
+    if let Some(rest) = target.strip_prefix(TCP_SCHEME) {
+        (Transport::Tcp, rest)
+    } else if let Some(rest) = target.strip_prefix(UDP_SCHEME) {
+        (Transport::Udp, rest)
+    } else {
+        (Transport::Udp, target)
+    }
+}
+
+const SLIP_END: u8 = 0xc0;
+const SLIP_ESC: u8 = 0xdb;
+const SLIP_ESC_END: u8 = 0xdc;
+const SLIP_ESC_ESC: u8 = 0xdd;
+
+// Double ended, so that any line noise before the packet gets thrown away as a packet of its own
+pub fn slip_encode(packet: &[u8]) -> Vec<u8> {
+    let mut result: Vec<u8> = Vec::with_capacity(packet.len() + 2);
+    result.push(SLIP_END);
+    for &b in packet {
+        match b {
+            SLIP_END => result.extend_from_slice(&[SLIP_ESC, SLIP_ESC_END]),
+            SLIP_ESC => result.extend_from_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+            _ => result.push(b),
+        }
+    }
+    result.push(SLIP_END);
+    result
+}
+
+pub enum Connection {
+    Udp { sock: UdpSocket, to_addr: SocketAddr },
+    Tcp(TcpStream),
+}
+
+impl Connection {
+    // local_port only matters for UDP to localhost, 0 = any
+    pub fn open(transport: Transport, to_addr: SocketAddr, local_port: u16) -> Result<Connection, Box<dyn Error>> {
+        match transport {
+            Transport::Udp => {
+                // Sending to another machine won't work from a socket bound to localhost
+                let host_addr = if to_addr.ip().is_loopback() {
+                    SocketAddr::from(([127, 0, 0, 1], local_port))
+                } else {
+                    SocketAddr::from(([0, 0, 0, 0], 0))
+                };
+                Ok(Connection::Udp { sock: UdpSocket::bind(host_addr)?, to_addr: to_addr })
+            },
+            Transport::Tcp => {
+                let stream = TcpStream::connect_timeout(&to_addr, CONNECT_TIMEOUT)
+                    .map_err(|err| format!("Couldn't connect to {to_addr}: {err}"))?;
+                // Every message is a chunk of its own already, no point holding them back
+                stream.set_nodelay(true)?;
+                info!("Connected to {to_addr} over TCP");
+                Ok(Connection::Tcp(stream))
+            },
+        }
+    }
+
+    // Returns the size of the OSC packet, not counting any framing
+    pub fn send(&self, packet: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Udp { sock, to_addr } => sock.send_to(packet, to_addr),
+            Connection::Tcp(stream) => {
+                let mut stream = stream;
+                stream.write_all(&slip_encode(packet))?;
+                Ok(packet.len())
+            },
+        }
+    }
+}
+
+// Whether anything seems to be listening on the target. For UDP there's no way to know for sure, but
+// when nothing has the port open the OS answers with ICMP port unreachable, which shows up as an
+// error on a connected socket. Reliable on localhost, while other machines (and firewalls) might
+// just drop it, in which case this says yes.
+pub fn probe(transport: Transport, to_addr: SocketAddr) -> Result<bool, Box<dyn Error>> {
+    let listening = |err: &std::io::Error| !matches!(err.kind(), ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset);
+    match transport {
+        Transport::Udp => {
+            let local_addr = if to_addr.ip().is_loopback() {
+                SocketAddr::from(([127, 0, 0, 1], 0))
+            } else {
+                SocketAddr::from(([0, 0, 0, 0], 0))
+            };
+            let sock = UdpSocket::bind(local_addr)?;
+            sock.connect(to_addr)?;
+            sock.set_read_timeout(Some(PROBE_TIMEOUT))?;
+            let packet = encoder::encode(&OscPacket::Message(OscMessage {
+                addr: PROBE_ADDRESS.to_string(),
+                args: vec![],
+            }))?;
+            if let Err(err) = sock.send(&packet) {
+                return if listening(&err) { Err(err.into()) } else { Ok(false) };
+            }
+            let mut buf = [0u8; 64];
+            match sock.recv(&mut buf) {
+                Ok(_) => Ok(true),
+                Err(err) => Ok(listening(&err)),
+            }
+        },
+        Transport::Tcp => match TcpStream::connect_timeout(&to_addr, CONNECT_TIMEOUT) {
+            Ok(_) => Ok(true),
+            Err(err) if !listening(&err) => Ok(false),
+            Err(err) => Err(err.into()),
+        },
+    }
+}
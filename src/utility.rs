@@ -1,27 +1,50 @@
 use crate::AppMessage;
+use crate::error::{AppError, ErrorReport};
 
 use std::sync::mpsc;
 use std::error::Error;
+use std::time::Duration;
 
 pub fn print_err<T, E: Error>(result: Result<T, E>) -> () {
     match result {
         Ok(_t) => (),
-        Err(err) => eprintln!("{}", err),
+        Err(err) => error!("{}", err),
     }
 }
 
 pub fn alert(appmsg: &mpsc::Sender<AppMessage>, message: String) -> () {
-    println!("{}", message);
+    info!("{}", message);
     print_err(appmsg.send(AppMessage::Alert(message)));
     fltk::app::awake();
 }
 
 pub fn error_alert(appmsg: &mpsc::Sender<AppMessage>, message: String) -> () {
-    eprintln!("{}", message);
+    error!("{}", message);
     print_err(appmsg.send(AppMessage::Alert(message)));
     fltk::app::awake();
 }
 
+pub fn report_error<E: AppError>(appmsg: &mpsc::Sender<AppMessage>, context: &str, err: &E) -> () {
+    let report = ErrorReport::new(context, err);
+    error!("{}", report.description());
+    print_err(appmsg.send(AppMessage::Error(report)));
+    fltk::app::awake();
+}
+
+pub fn duration_to_string(dur: Duration) -> String {
+    let total: u64 = dur.as_secs();
+    let mins: u64 = total/60;
+
+    if mins == 0 {
+        // Use the debug output for a very fine-grained output (two decimal places) when we are below a minute
+        format!("{:.2?}", dur)
+    } else {
+        // Above one minute we only care about whole seconds
+        let secs: u64 = total % 60;
+        format!("{mins} min {secs} s")
+    }
+}
+
 #[macro_export]
 macro_rules! static_assert {
     ($($tt:tt)*) => {
@@ -31,5 +54,5 @@ macro_rules! static_assert {
 
 #[allow(dead_code)]
 pub fn print_type_of<T>(_: &T) {
-    println!("{}", std::any::type_name::<T>());
+    debug!("{}", std::any::type_name::<T>());
 }
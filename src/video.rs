@@ -0,0 +1,300 @@
+// Video files as an image source, either a single frame as a still or a clip for the streaming
+// mode. The decoding is left to the ffmpeg and ffprobe programs, which need to be on the PATH.
+// That saves us from linking against the ffmpeg libraries, and handles whatever format ffmpeg does.
+
+use crate::{AppMessage, BgMessage, ImageSettings};
+use crate::i18n;
+use crate::mq;
+use crate::send_osc::SendOSCOpts;
+use crate::stream::{self, AnimationSource};
+use crate::utility::error_alert;
+
+use fltk::{prelude::*, button::{Button, CheckButton}, dialog, enums::Align, frame::Frame, group::Flex, valuator::HorValueSlider, window::Window};
+use image::RgbaImage;
+use std::cell::RefCell;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+// The clip gets scaled down to fit this while decoding, so that a few hundred frames don't take up
+// gigabytes. It gets scaled down a lot further than that on its way to the CRT anyway.
+const CLIP_MAX_SIZE: u32 = 512;
+const MAX_CLIP_FRAMES: usize = 600;
+pub const DEFAULT_CLIP_FPS: f64 = 10.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    pub duration: f64, // Seconds
+}
+
+// Runs one of the ffmpeg programs and returns what it wrote to stdout
+fn run(program: &str, args: &[&str]) -> Result<Vec<u8>, Box<dyn Error>> {
+    debug!("Running {program} {args:?}");
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|err| format!("Couldn't run {program} (is it installed and on the PATH?): {err}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{program} failed ({}): {}", output.status, stderr.trim()).into());
+    }
+    Ok(output.stdout)
+}
+
+pub fn probe(path: &Path) -> Result<VideoInfo, Box<dyn Error>> {
+    let path_str = path.to_str().ok_or(format!("Can't pass {path:?} on to ffprobe"))?;
+    let output = run("ffprobe", &[
+        "-v", "error",
+        "-select_streams", "v:0",
+        "-show_entries", "stream=width,height:format=duration",
+        "-of", "default=noprint_wrappers=1",
+        path_str,
+    ])?;
+
+    // key=value lines
+    let output = String::from_utf8_lossy(&output);
+    let value = |key: &str| output.lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v.trim().to_string())
+        .ok_or(format!("ffprobe didn't tell us the {key} of {path:?}"));
+
+    Ok(VideoInfo {
+        width: value("width")?.parse()?,
+        height: value("height")?.parse()?,
+        duration: value("duration")?.parse()?,
+    })
+}
+
+// The frame at time (in seconds)
+pub fn frame_at(path: &Path, time: f64) -> Result<RgbaImage, Box<dyn Error>> {
+    let path_str = path.to_str().ok_or(format!("Can't pass {path:?} on to ffmpeg"))?;
+    let time = format!("{:.3}", time.max(0.0));
+    let png = run("ffmpeg", &[
+        "-v", "error",
+        "-ss", &time,
+        "-i", path_str,
+        "-frames:v", "1",
+        "-f", "image2pipe",
+        "-c:v", "png",
+        "-",
+    ])?;
+    if png.is_empty() {
+        return Err(format!("No frame at {time} s in {path:?}").into());
+    }
+    Ok(image::load_from_memory(&png)?.to_rgba8())
+}
+
+// The frames from start to end (in seconds) at fps frames per second, scaled down to fit in
+// CLIP_MAX_SIZE
+pub fn clip_frames(path: &Path, info: &VideoInfo, start: f64, end: f64, fps: f64) -> Result<Vec<(RgbaImage, Duration)>, Box<dyn Error>> {
+    let path_str = path.to_str().ok_or(format!("Can't pass {path:?} on to ffmpeg"))?;
+    if end <= start || fps <= 0.0 {
+        return Err("The clip is empty".into());
+    }
+    if ((end - start)*fps).ceil() as usize > MAX_CLIP_FRAMES {
+        return Err(format!("That's more than {MAX_CLIP_FRAMES} frames, pick a shorter clip or a lower frame rate").into());
+    }
+
+    let factor = ((CLIP_MAX_SIZE as f64)/(info.width.max(info.height).max(1) as f64)).min(1.0);
+    let (width, height) = (((info.width as f64)*factor).round().max(1.0) as u32, ((info.height as f64)*factor).round().max(1.0) as u32);
+    let raw = run("ffmpeg", &[
+        "-v", "error",
+        "-ss", &format!("{start:.3}"),
+        "-t", &format!("{:.3}", end - start),
+        "-i", path_str,
+        "-vf", &format!("fps={fps},scale={width}:{height}"),
+        "-f", "rawvideo",
+        "-pix_fmt", "rgba",
+        "-",
+    ])?;
+
+    let delay = Duration::from_secs_f64(1.0/fps);
+    let frames: Vec<(RgbaImage, Duration)> = raw.chunks_exact((width*height*4) as usize)
+        .filter_map(|bytes| RgbaImage::from_raw(width, height, bytes.to_vec()))
+        .map(|image| (image, delay))
+        .collect();
+    info!("Decoded {} frames ({width}x{height}) from {path:?}", frames.len());
+    Ok(frames)
+}
+
+struct VideoState {
+    path: PathBuf,
+    info: VideoInfo,
+    clip: (f64, f64),
+}
+
+// get_settings reads the processing settings from the main window, get_send_opts the send settings
+pub fn show_video_window<S, O>(
+    appmsg: &mpsc::Sender<AppMessage>,
+    bg: &mq::MessageQueueSender<BgMessage>,
+    get_settings: S,
+    get_send_opts: O,
+)
+where
+    S: Fn() -> Result<ImageSettings, String> + 'static,
+    O: Fn() -> Result<SendOSCOpts, String> + 'static,
+{
+    let mut win = i18n::labeled(Window::default().with_size(450, 360), "Video");
+    win.set_callback(|win| {
+        fltk::app::delete_widget(win.clone());
+    });
+    win.make_resizable(true);
+
+    let mut col = Flex::default_fill().column();
+    col.set_margin(10);
+
+    let mut open_btn = i18n::labeled(Button::default(), "Open video...");
+    col.fixed(&open_btn, 30);
+    let mut info_frame = i18n::labeled(Frame::default(), "ffmpeg and ffprobe need to be installed");
+    info_frame.set_align(Align::Left | Align::Inside | Align::Wrap);
+    col.fixed(&info_frame, 40);
+
+    let mut time_slider = i18n::labeled(HorValueSlider::default(), "Time (s)");
+    time_slider.set_range(0.0, 0.0);
+    time_slider.set_step(0.1, 1);
+    // Only grab a frame once the slider is let go, it takes a while
+    time_slider.set_trigger(fltk::enums::CallbackTrigger::Release);
+    time_slider.deactivate();
+    col.fixed(&time_slider, 30);
+
+    let clip_row = Flex::default_fill().row();
+    let mut clip_start_btn = i18n::labeled(Button::default(), "Clip starts here");
+    let mut clip_end_btn = i18n::labeled(Button::default(), "Clip ends here");
+    clip_row.end();
+    col.fixed(&clip_row, 30);
+
+    let mut clip_frame = Frame::default();
+    clip_frame.set_align(Align::Left | Align::Inside);
+    col.fixed(&clip_frame, 30);
+
+    let mut fps_slider = i18n::labeled(HorValueSlider::default(), "Clip frames/second");
+    fps_slider.set_range(1.0, 30.0);
+    fps_slider.set_step(1.0, 1);
+    fps_slider.set_value(DEFAULT_CLIP_FPS);
+    col.fixed(&fps_slider, 30);
+
+    let same_palette_toggle = i18n::labeled(CheckButton::default(), "Keep the first frame's palette (smaller updates)");
+    same_palette_toggle.set_checked(true);
+    col.fixed(&same_palette_toggle, 30);
+    let global_palette_toggle = i18n::labeled(CheckButton::default(), "One palette made from all the frames");
+    global_palette_toggle.set_checked(true);
+    col.fixed(&global_palette_toggle, 30);
+
+    let mut stream_btn = i18n::labeled(Button::default(), "Stream clip");
+    col.fixed(&stream_btn, 40);
+
+    let state: Rc<RefCell<Option<VideoState>>> = Rc::new(RefCell::new(None));
+
+    let set_clip_label = {
+        let mut clip_frame = clip_frame.clone();
+        move |(start, end): (f64, f64)| {
+            clip_frame.set_label(&format!("{} {start:.1} s - {end:.1} s", i18n::tr("Clip:")));
+        }
+    };
+
+    open_btn.set_callback({
+        let state = Rc::clone(&state);
+        let mut info_frame = info_frame.clone();
+        let mut time_slider = time_slider.clone();
+        let mut set_clip_label = set_clip_label.clone();
+        move |_| {
+            let mut nfc = dialog::NativeFileChooser::new(dialog::FileDialogType::BrowseFile);
+            nfc.set_filter("Videos\t*.{mp4,webm,mkv,mov,avi}");
+            nfc.show();
+            let path = nfc.filename();
+            if path.as_os_str().is_empty() {
+                info!("No file selected/cancelled");
+                return;
+            }
+
+            match probe(&path) {
+                Ok(info) => {
+                    info_frame.set_label(&format!("{}\n{}x{}, {:.1} s", path.to_string_lossy(), info.width, info.height, info.duration));
+                    time_slider.set_range(0.0, info.duration);
+                    time_slider.set_value(0.0);
+                    time_slider.activate();
+                    let clip = (0.0, info.duration);
+                    set_clip_label(clip);
+                    *state.borrow_mut() = Some(VideoState { path: path, info: info, clip: clip });
+                },
+                Err(err) => dialog::alert_default(&format!("{} {path:?}:\n{err}", i18n::tr("Couldn't open"))),
+            }
+        }
+    });
+
+    // Load the frame under the slider into the main window as a still
+    time_slider.set_callback({
+        let state = Rc::clone(&state);
+        let appmsg = appmsg.clone();
+        let bg = bg.clone();
+        move |s| {
+            let Some(path) = state.borrow().as_ref().map(|state| state.path.clone()) else {
+                return;
+            };
+            // Only the latest position matters when scrubbing around
+            let msg = BgMessage::LoadVideoFrame(path, s.value());
+            if let Err(err) = bg.send_or_replace_if(|m| m.is_update() || matches!(m, BgMessage::LoadVideoFrame(..)), msg) {
+                error_alert(&appmsg, format!("{}: {err}", i18n::tr("Couldn't send message to BG thread")));
+            }
+        }
+    });
+
+    for (btn, is_start) in [(&mut clip_start_btn, true), (&mut clip_end_btn, false)] {
+        btn.set_callback({
+            let state = Rc::clone(&state);
+            let time_slider = time_slider.clone();
+            let mut set_clip_label = set_clip_label.clone();
+            move |_| {
+                if let Some(state) = state.borrow_mut().as_mut() {
+                    let t = time_slider.value();
+                    state.clip = if is_start { (t, state.clip.1.max(t)) } else { (state.clip.0.min(t), t) };
+                    set_clip_label(state.clip);
+                }
+            }
+        });
+    }
+
+    stream_btn.set_callback({
+        let appmsg = appmsg.clone();
+        let state = Rc::clone(&state);
+        move |_| {
+            match || -> Result<(), String> {
+                let state = state.borrow();
+                let state = state.as_ref().ok_or("Open a video first")?;
+                let (path, info, (start, end)) = (state.path.clone(), state.info.clone(), state.clip);
+                let fps = fps_slider.value();
+                let same_palette = same_palette_toggle.is_checked();
+                let global_palette = global_palette_toggle.is_checked();
+                let (settings, options) = (get_settings()?, get_send_opts()?);
+
+                // Decoding takes a while, so not on the main thread
+                let appmsg = appmsg.clone();
+                std::thread::spawn(move || {
+                    match || -> Result<(), String> {
+                        let frames = clip_frames(&path, &info, start, end, fps)
+                            .map_err(|err| format!("Couldn't decode the clip: {err}"))?;
+                        let source = AnimationSource::new(frames).map_err(|err| err.to_string())?;
+                        stream::start(&appmsg, Box::new(source), settings, options, same_palette, global_palette)
+                    }() {
+                        Ok(()) => (),
+                        Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't stream the clip"))),
+                    }
+                });
+                Ok(())
+            }() {
+                Ok(()) => (),
+                Err(err) => error_alert(&appmsg, format!("{}:\n{err}", i18n::tr("Couldn't stream the clip"))),
+            }
+        }
+    });
+
+    col.end();
+    win.end();
+    win.show();
+}
@@ -0,0 +1,204 @@
+// WebSocket bridge for watching and controlling sends from somewhere else (a phone, an OBS
+// overlay). Every connected client gets the send progress events as JSON:
+//
+//   {"event": "started", "number": 3}
+//   {"event": "progress", "message": "Sent pixel chunk 10/200 ...", "progress": 5.0}
+//   {"event": "finished", "number": 3, "cancelled": false, "elapsed_secs": 12.3}
+//   {"event": "failed", "error": "..."}
+//
+// and can send commands the same way:
+//
+//   {"cmd": "load", "path": "C:\\images\\foo.png"}
+//   {"cmd": "send"}
+//   {"cmd": "cancel"}
+//
+// Like the HTTP API this only listens on localhost, and turns away handshakes from web pages (any
+// page can open a WebSocket to localhost, and browsers always send an Origin header when they do).
+
+use crate::{AppMessage, BgMessage, mq, remote, send_osc};
+
+use std::error::Error;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 9121;
+
+// How often the threads check for things to do when nothing is coming in
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// One sender per connected client. Clients that have gone away get dropped on the next broadcast.
+static CLIENTS: Mutex<Vec<mpsc::Sender<String>>> = Mutex::new(Vec::new());
+
+// Send an event to every connected client. Cheap when nobody is connected.
+pub fn broadcast(event: serde_json::Value) {
+    let mut clients = match CLIENTS.lock() {
+        Ok(clients) => clients,
+        Err(err) => {
+            warn!("Couldn't lock WebSocket clients: {err}");
+            return;
+        },
+    };
+    if clients.is_empty() {
+        return;
+    }
+    let text = event.to_string();
+    clients.retain(|client| client.send(text.clone()).is_ok());
+}
+
+pub struct WsServer {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl WsServer {
+    pub fn start(
+        port: u16,
+        appmsg: &mpsc::Sender<AppMessage>,
+        bg: &mq::MessageQueueSender<BgMessage>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        // So we get to check the stop flag every now and then
+        listener.set_nonblocking(true)?;
+        info!("WebSocket bridge listening on 127.0.0.1:{port}");
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = Arc::clone(&stop);
+            let appmsg = appmsg.clone();
+            let bg = bg.clone();
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, addr)) => {
+                            info!("WebSocket client connected from {addr}");
+                            let stop = Arc::clone(&stop);
+                            let appmsg = appmsg.clone();
+                            let bg = bg.clone();
+                            thread::spawn(move || {
+                                if let Err(err) = serve_client(stream, &stop, &appmsg, &bg) {
+                                    warn!("WebSocket client {addr}: {err}");
+                                }
+                                info!("WebSocket client {addr} disconnected");
+                            });
+                        },
+                        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                        Err(err) => {
+                            error!("WebSocket accept failed: {err}");
+                            break;
+                        },
+                    }
+                }
+                info!("WebSocket bridge stopped");
+            }
+        });
+
+        Ok(WsServer { stop: stop, thread: Some(thread) })
+    }
+}
+
+impl Drop for WsServer {
+    fn drop(&mut self) {
+        // The client threads notice the flag as well and hang up
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn check_handshake(
+    request: &tungstenite::handshake::server::Request,
+    response: tungstenite::handshake::server::Response,
+) -> Result<tungstenite::handshake::server::Response, tungstenite::handshake::server::ErrorResponse> {
+    let headers = request.headers();
+    let host_ok = headers.get("Host")
+        .and_then(|host| host.to_str().ok())
+        .is_some_and(remote::is_local_host);
+    if headers.contains_key("Origin") || !host_ok {
+        warn!("Turned away a WebSocket connection from {:?}", headers.get("Origin"));
+        let mut error = tungstenite::handshake::server::ErrorResponse::new(Some("Connections from web pages aren't allowed".to_string()));
+        *error.status_mut() = tungstenite::http::StatusCode::FORBIDDEN;
+        return Err(error);
+    }
+    Ok(response)
+}
+
+fn serve_client(
+    stream: TcpStream,
+    stop: &AtomicBool,
+    appmsg: &mpsc::Sender<AppMessage>,
+    bg: &mq::MessageQueueSender<BgMessage>,
+) -> Result<(), Box<dyn Error>> {
+    // Accepted sockets inherit non-blocking from the listener on some platforms
+    stream.set_nonblocking(false)?;
+    let mut ws = tungstenite::accept_hdr(stream, check_handshake).map_err(|err| format!("Handshake failed: {err}"))?;
+    // Reading can't block forever, or we'd never get around to pushing out the events
+    ws.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let (tx, rx) = mpsc::channel::<String>();
+    CLIENTS.lock().map_err(|err| format!("Couldn't lock WebSocket clients: {err}"))?.push(tx);
+
+    while !stop.load(Ordering::Relaxed) {
+        match ws.read() {
+            Ok(msg) if msg.is_text() => {
+                let reply = match handle_command(msg.to_text()?, appmsg, bg) {
+                    Ok(()) => serde_json::json!({ "event": "ok" }),
+                    Err(err) => serde_json::json!({ "event": "error", "error": err }),
+                };
+                ws.send(tungstenite::Message::text(reply.to_string()))?;
+            },
+            Ok(msg) if msg.is_close() => return Ok(()),
+            Ok(_) => (), // Pings get answered by tungstenite itself
+            Err(tungstenite::Error::Io(err))
+                if matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => (),
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+
+        for event in rx.try_iter() {
+            ws.send(tungstenite::Message::text(event))?;
+        }
+    }
+
+    let _ = ws.close(None);
+    Ok(())
+}
+
+fn handle_command(
+    text: &str,
+    appmsg: &mpsc::Sender<AppMessage>,
+    bg: &mq::MessageQueueSender<BgMessage>,
+) -> Result<(), String> {
+    let cmd: serde_json::Value = serde_json::from_str(text)
+        .map_err(|err| format!("Couldn't parse command: {err}"))?;
+
+    match cmd["cmd"].as_str() {
+        Some("load") => {
+            let path = cmd["path"].as_str().ok_or("load needs a path")?;
+            let path = PathBuf::from(path);
+            if !path.is_file() {
+                return Err(format!("No such file: {path:?}"));
+            }
+            bg.send(BgMessage::LoadImage(path))
+                .map_err(|err| format!("Couldn't send message to BG thread: {err}"))?;
+        },
+        Some("send") => {
+            // The send settings live in the widgets, so this goes through the main thread
+            appmsg.send(AppMessage::RemoteSend)
+                .map_err(|err| format!("Couldn't send message to main thread: {err}"))?;
+        },
+        Some("cancel") => {
+            if !send_osc::cancel_current() {
+                return Err("Nothing is being sent".to_string());
+            }
+        },
+        Some(other) => return Err(format!("Unknown command {other:?}")),
+        None => return Err("Missing \"cmd\"".to_string()),
+    }
+    fltk::app::awake();
+    Ok(())
+}